@@ -0,0 +1,8 @@
+use clap::Parser;
+use cosmic_protocols_cli::capture;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args = capture::Args::parse();
+    cosmic_protocols_cli::main("cosmic-capture", |conn, out| capture::run(conn, &args, out))
+}
@@ -0,0 +1,8 @@
+use clap::Parser;
+use cosmic_protocols_cli::outputs;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args = outputs::Args::parse();
+    cosmic_protocols_cli::main("cosmic-outputs", |conn, out| outputs::run(conn, &args, out))
+}
@@ -0,0 +1,10 @@
+use clap::Parser;
+use cosmic_protocols_cli::toplevels;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args = toplevels::Args::parse();
+    cosmic_protocols_cli::main("cosmic-toplevels", |conn, out| {
+        toplevels::run(conn, &args, out)
+    })
+}
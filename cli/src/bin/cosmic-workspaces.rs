@@ -0,0 +1,10 @@
+use clap::Parser;
+use cosmic_protocols_cli::workspaces;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args = workspaces::Args::parse();
+    cosmic_protocols_cli::main("cosmic-workspaces", |conn, out| {
+        workspaces::run(conn, &args, out)
+    })
+}
@@ -0,0 +1,758 @@
+//! `cosmic-capture`: take screenshots of, and stream frames from, outputs, windows, workspaces
+//! and regions.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use cosmic_client_toolkit::{
+    screencopy::{
+        CaptureCursorSession, CaptureFrame, CaptureOptions, CaptureSession, CaptureSource,
+        FailureReason, Formats, Frame, Rect, RegionCapture, ScreencopyCursorSessionData,
+        ScreencopyFrameData, ScreencopyHandler, ScreencopySessionData, ScreencopyState,
+    },
+    sctk::{
+        self,
+        output::{OutputHandler, OutputState},
+        registry::{ProvidesRegistryState, RegistryState},
+        shm::{CreatePoolError, Shm, ShmHandler, raw::RawPool},
+    },
+    toplevel_info::{ToplevelInfoHandler, ToplevelInfoState},
+    wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1,
+    workspace::{WorkspaceHandler, WorkspaceState},
+};
+use std::{
+    fs,
+    io::{self, Write},
+};
+use wayland_client::{
+    Connection, EventQueue, QueueHandle, WEnum,
+    globals::registry_queue_init,
+    protocol::{wl_buffer, wl_output, wl_pointer, wl_seat, wl_shm},
+};
+
+use crate::{EXIT_CODES, Error, toplevels, workspaces};
+
+/// Take screenshots of, and stream frames from, outputs, windows, workspaces and regions
+#[derive(Debug, Parser)]
+#[command(name = "cosmic-capture", version, after_help = EXIT_CODES)]
+pub struct Args {
+    #[command(subcommand)]
+    pub source: Source,
+    /// Write images to this file instead of stdout
+    ///
+    /// `{}` in the path is replaced by the number of the frame, to write each frame to its own
+    /// file.
+    #[arg(short, long, global = true, value_name = "PATH")]
+    pub file: Option<String>,
+    /// Image format; defaults to PPM if the file has a `.ppm` extension, and PNG otherwise
+    #[arg(long, global = true)]
+    pub format: Option<Format>,
+    /// Ask the compositor to paint the cursor into the image
+    #[arg(long, global = true)]
+    pub paint_cursor: bool,
+    /// Number of frames to capture, or 0 to capture until the source goes away
+    ///
+    /// After the first frame, a frame is captured every time the source changes. Images are
+    /// written one after another, so PPM and raw output can be piped into an encoder.
+    #[arg(long, global = true, default_value_t = 1)]
+    pub frames: u64,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Source {
+    /// Capture an output
+    Output {
+        /// Name of the output; may be omitted if there is only one
+        name: Option<String>,
+        #[command(flatten)]
+        cursor: Cursor,
+    },
+    /// Capture a window
+    Toplevel {
+        #[command(flatten)]
+        select: toplevels::Selector,
+        #[command(flatten)]
+        cursor: Cursor,
+    },
+    /// Capture a workspace
+    Workspace {
+        #[command(flatten)]
+        select: workspaces::Selector,
+        #[command(flatten)]
+        cursor: Cursor,
+    },
+    /// Capture a region of the desktop, which may span multiple outputs
+    Region {
+        /// Region in logical coordinates, as `X,Y WIDTHxHEIGHT` like `slurp` prints it
+        #[arg(value_parser = parse_region)]
+        region: Rect,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, clap::Args)]
+pub struct Cursor {
+    /// Capture the image of the pointer cursor over the source, instead of the source
+    #[arg(long = "cursor", conflicts_with = "paint_cursor")]
+    pub enabled: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Png,
+    /// Binary PPM, without the alpha channel
+    Ppm,
+    /// RGBA pixels without a header, printing the size of frames to stderr
+    Raw,
+}
+
+fn parse_region(s: &str) -> Result<Rect, String> {
+    let parse = || {
+        let (position, size) = s.trim().split_once(' ')?;
+        let (x, y) = position.split_once(',')?;
+        let (width, height) = size.trim().split_once('x')?;
+        Some(Rect {
+            x: x.trim().parse().ok()?,
+            y: y.trim().parse().ok()?,
+            width: width.parse().ok().filter(|w| *w > 0)?,
+            height: height.parse().ok().filter(|h| *h > 0)?,
+        })
+    };
+    parse().ok_or_else(|| format!("expected `X,Y WIDTHxHEIGHT`, got {:?}", s))
+}
+
+// Supported shm formats, in order of preference
+const SHM_FORMATS: [wl_shm::Format; 4] = [
+    wl_shm::Format::Abgr8888,
+    wl_shm::Format::Xbgr8888,
+    wl_shm::Format::Argb8888,
+    wl_shm::Format::Xrgb8888,
+];
+
+struct Image {
+    width: u32,
+    height: u32,
+    /// RGBA8 pixel data
+    data: Vec<u8>,
+}
+
+impl Image {
+    fn write(&self, format: Format, out: &mut dyn Write) -> Result<(), Error> {
+        match format {
+            Format::Png => {
+                let mut encoder = png::Encoder::new(out, self.width, self.height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder
+                    .write_header()
+                    .and_then(|mut writer| writer.write_image_data(&self.data))
+                    .map_err(|err| match err {
+                        png::EncodingError::IoError(err) => Error::Io(err),
+                        err => Error::Io(io::Error::other(err)),
+                    })?;
+            }
+            Format::Ppm => {
+                write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+                let rgb = self
+                    .data
+                    .chunks_exact(4)
+                    .flat_map(|pixel| &pixel[..3])
+                    .copied()
+                    .collect::<Vec<_>>();
+                out.write_all(&rgb)?;
+            }
+            Format::Raw => out.write_all(&self.data)?,
+        }
+        Ok(())
+    }
+}
+
+struct Buffer {
+    pool: RawPool,
+    buffer: wl_buffer::WlBuffer,
+    size: (u32, u32),
+    format: wl_shm::Format,
+    // Buffer hasn't been captured into yet, so it must be fully damaged
+    needs_full_damage: bool,
+}
+
+impl Buffer {
+    fn new(
+        shm: &Shm,
+        size: (u32, u32),
+        format: wl_shm::Format,
+        qh: &QueueHandle<AppData>,
+    ) -> Result<Self, CreatePoolError> {
+        let (width, height) = size;
+        let mut pool = RawPool::new(width as usize * height as usize * 4, shm)?;
+        let buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            width as i32 * 4,
+            format,
+            (),
+            qh,
+        );
+        Ok(Self {
+            pool,
+            buffer,
+            size,
+            format,
+            needs_full_damage: true,
+        })
+    }
+
+    /// Copy buffer contents to an RGBA image, undoing the transform the compositor applied
+    fn read(&mut self, transform: WEnum<wl_output::Transform>) -> Image {
+        let (width, height) = self.size;
+        let mut data = self.pool.mmap()[..width as usize * height as usize * 4].to_vec();
+        for pixel in data.chunks_exact_mut(4) {
+            match self.format {
+                wl_shm::Format::Argb8888 => pixel.swap(0, 2),
+                wl_shm::Format::Xrgb8888 => {
+                    pixel.swap(0, 2);
+                    pixel[3] = 255;
+                }
+                wl_shm::Format::Xbgr8888 => pixel[3] = 255,
+                _ => {}
+            }
+        }
+        let transform = match transform {
+            WEnum::Value(transform) => transform,
+            WEnum::Unknown(_) => wl_output::Transform::Normal,
+        };
+        if transform == wl_output::Transform::Normal {
+            return Image {
+                width,
+                height,
+                data,
+            };
+        }
+
+        let (w, h) = match transform {
+            wl_output::Transform::_90
+            | wl_output::Transform::_270
+            | wl_output::Transform::Flipped90
+            | wl_output::Transform::Flipped270 => (height, width),
+            _ => (width, height),
+        };
+        let mut image = Vec::with_capacity(data.len());
+        for y in 0..h {
+            for x in 0..w {
+                let (bx, by) = match transform {
+                    wl_output::Transform::_90 => (y, w - 1 - x),
+                    wl_output::Transform::_180 => (w - 1 - x, h - 1 - y),
+                    wl_output::Transform::_270 => (h - 1 - y, x),
+                    wl_output::Transform::Flipped => (w - 1 - x, y),
+                    wl_output::Transform::Flipped90 => (y, x),
+                    wl_output::Transform::Flipped180 => (x, h - 1 - y),
+                    wl_output::Transform::Flipped270 => (h - 1 - y, w - 1 - x),
+                    _ => (x, y),
+                };
+                let idx = (by as usize * width as usize + bx as usize) * 4;
+                image.extend_from_slice(&data[idx..idx + 4]);
+            }
+        }
+        Image {
+            width: w,
+            height: h,
+            data: image,
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+    }
+}
+
+struct SourceCapture {
+    session: CaptureSession,
+    // Keeps the cursor session alive when capturing the cursor
+    _cursor_session: Option<CaptureCursorSession>,
+    buffer: Option<Buffer>,
+    frame: Option<CaptureFrame>,
+}
+
+impl SourceCapture {
+    fn capture(&mut self, qh: &QueueHandle<AppData>) {
+        let Some(buffer) = &mut self.buffer else {
+            return;
+        };
+        let damage = if buffer.needs_full_damage {
+            vec![Rect {
+                x: 0,
+                y: 0,
+                width: buffer.size.0 as i32,
+                height: buffer.size.1 as i32,
+            }]
+        } else {
+            Vec::new()
+        };
+        buffer.needs_full_damage = false;
+        self.frame =
+            Some(
+                self.session
+                    .capture(&buffer.buffer, &damage, qh, ScreencopyFrameData::default()),
+            );
+    }
+}
+
+enum Capture {
+    Source(Box<SourceCapture>),
+    Region(RegionCapture),
+}
+
+struct AppData {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    shm: Shm,
+    screencopy_state: ScreencopyState,
+    toplevel_info_state: Option<ToplevelInfoState>,
+    workspace_state: WorkspaceState,
+    seat: Option<wl_seat::WlSeat>,
+    capture: Option<Capture>,
+    options: CaptureOptions,
+    // Frames still to be captured; `None` to capture until the source goes away
+    remaining: Option<u64>,
+    images: Vec<Image>,
+    error: Option<Error>,
+    stopped: bool,
+}
+
+impl AppData {
+    fn frame_done(&mut self, image: Image) {
+        self.images.push(image);
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+        }
+    }
+
+    fn wants_frame(&self) -> bool {
+        self.remaining != Some(0) && self.error.is_none()
+    }
+
+    // Finish the region capture once every output is captured, and start the next one
+    fn region_progress(&mut self, qh: &QueueHandle<Self>) {
+        let Some(Capture::Region(region)) = &self.capture else {
+            return;
+        };
+        if !region.is_done() {
+            return;
+        }
+        let rect = region.region().clone();
+        let Some(Capture::Region(region)) = self.capture.take() else {
+            unreachable!()
+        };
+        let image = region.finish();
+        if let Some((_, err)) = image.failed.into_iter().next() {
+            self.error = Some(err.into());
+            return;
+        }
+        self.frame_done(Image {
+            width: image.width,
+            height: image.height,
+            data: image.data,
+        });
+        if self.wants_frame() {
+            let region = RegionCapture::new(
+                self.screencopy_state.capturer(),
+                &self.output_state,
+                rect,
+                self.options,
+                qh,
+            );
+            self.capture = Some(Capture::Region(region));
+            // Outputs whose session couldn't be created have already failed
+            self.region_progress(qh);
+        }
+    }
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(OutputState);
+}
+
+impl OutputHandler for AppData {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl ShmHandler for AppData {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+impl ToplevelInfoHandler for AppData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        // Only called for events of its objects, so it must exist
+        self.toplevel_info_state.as_mut().unwrap()
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+}
+
+impl WorkspaceHandler for AppData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+
+    fn done(&mut self) {}
+}
+
+impl ScreencopyHandler for AppData {
+    fn screencopy_state(&mut self) -> &mut ScreencopyState {
+        &mut self.screencopy_state
+    }
+
+    fn init_done(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        session: &CaptureSession,
+        formats: &Formats,
+    ) {
+        let capture = match &mut self.capture {
+            Some(Capture::Source(capture)) if capture.session == *session => capture,
+            Some(Capture::Region(region)) => {
+                region.init_done(&self.shm, qh, session, formats);
+                self.region_progress(qh);
+                return;
+            }
+            _ => return,
+        };
+        let Some(format) = SHM_FORMATS
+            .into_iter()
+            .find(|format| formats.shm_formats.contains(format))
+        else {
+            self.error = Some(Error::Capture(
+                "no supported shm format offered by compositor".to_string(),
+            ));
+            return;
+        };
+        if capture
+            .buffer
+            .as_ref()
+            .is_none_or(|buffer| buffer.size != formats.buffer_size || buffer.format != format)
+        {
+            match Buffer::new(&self.shm, formats.buffer_size, format, qh) {
+                Ok(buffer) => capture.buffer = Some(buffer),
+                Err(err) => {
+                    self.error = Some(Error::Capture(err.to_string()));
+                    return;
+                }
+            }
+        }
+        // Formats are sent again when buffer constraints change, while a frame may be pending
+        if capture.frame.is_none() {
+            capture.capture(qh);
+        }
+    }
+
+    fn stopped(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, session: &CaptureSession) {
+        match &mut self.capture {
+            Some(Capture::Source(capture)) if capture.session == *session => self.stopped = true,
+            Some(Capture::Region(region)) => {
+                region.stopped(session);
+                self.region_progress(qh);
+            }
+            _ => {}
+        }
+    }
+
+    fn ready(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        capture_frame: &CaptureFrame,
+        frame: Frame,
+    ) {
+        match &mut self.capture {
+            Some(Capture::Source(capture)) if capture.frame.as_ref() == Some(capture_frame) => {
+                capture.frame = None;
+                let image = capture.buffer.as_mut().unwrap().read(frame.transform);
+                self.frame_done(image);
+                if self.wants_frame()
+                    && let Some(Capture::Source(capture)) = &mut self.capture
+                {
+                    capture.capture(qh);
+                }
+            }
+            Some(Capture::Region(region)) => {
+                region.ready(capture_frame, &frame);
+                self.region_progress(qh);
+            }
+            _ => {}
+        }
+    }
+
+    fn failed(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        capture_frame: &CaptureFrame,
+        reason: WEnum<FailureReason>,
+    ) {
+        match &mut self.capture {
+            Some(Capture::Source(capture)) if capture.frame.as_ref() == Some(capture_frame) => {
+                capture.frame = None;
+                match reason {
+                    // Captured again once new constraints are received
+                    WEnum::Value(FailureReason::BufferConstraints) => {}
+                    WEnum::Value(FailureReason::Stopped) => self.stopped = true,
+                    _ => self.error = Some(Error::Capture(format!("{:?}", reason))),
+                }
+            }
+            Some(Capture::Region(region)) => {
+                region.failed(capture_frame, reason);
+                self.region_progress(qh);
+            }
+            _ => {}
+        }
+    }
+}
+
+sctk::delegate_registry!(AppData);
+sctk::delegate_output!(AppData);
+sctk::delegate_shm!(AppData);
+cosmic_client_toolkit::delegate_screencopy!(AppData);
+cosmic_client_toolkit::delegate_toplevel_info!(AppData);
+cosmic_client_toolkit::delegate_workspace!(AppData);
+wayland_client::delegate_noop!(AppData: ignore wl_buffer::WlBuffer);
+wayland_client::delegate_noop!(AppData: ignore wl_seat::WlSeat);
+wayland_client::delegate_noop!(AppData: ignore wl_pointer::WlPointer);
+
+fn connect(conn: &Connection, args: &Args) -> Result<(EventQueue<AppData>, AppData), Error> {
+    let (globals, mut event_queue) = registry_queue_init(conn)?;
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let mut app_data = AppData {
+        output_state: OutputState::new(&globals, &qh),
+        shm: Shm::bind(&globals, &qh).map_err(|_| Error::MissingGlobal("wl_shm"))?,
+        screencopy_state: ScreencopyState::try_new(&globals, &qh)
+            .map_err(|_| Error::MissingGlobal("ext_image_copy_capture_manager_v1"))?,
+        toplevel_info_state: ToplevelInfoState::try_new(&registry_state, &qh),
+        workspace_state: WorkspaceState::new(&registry_state, &qh),
+        registry_state,
+        seat: globals.bind(&qh, 1..=1, ()).ok(),
+        capture: None,
+        options: if args.paint_cursor {
+            CaptureOptions::PaintCursors
+        } else {
+            CaptureOptions::empty()
+        },
+        remaining: (args.frames > 0).then_some(args.frames),
+        images: Vec::new(),
+        error: None,
+        stopped: false,
+    };
+    // Extension objects are requested in response to events, so their initial state needs a
+    // second roundtrip
+    event_queue.roundtrip(&mut app_data)?;
+    event_queue.roundtrip(&mut app_data)?;
+    Ok((event_queue, app_data))
+}
+
+/// Output named `name`, or the only output
+fn output_source(app_data: &AppData, name: Option<&str>) -> Result<CaptureSource, Error> {
+    if let Some(name) = name {
+        return Ok(CaptureSource::Output(crate::find_output(
+            &app_data.output_state,
+            name,
+        )?));
+    }
+    let outputs = app_data.output_state.outputs().collect::<Vec<_>>();
+    match <[_; 1]>::try_from(outputs) {
+        Ok([output]) => Ok(CaptureSource::Output(output)),
+        Err(outputs) if outputs.is_empty() => Err(Error::NoMatch("output".to_string())),
+        Err(_) => Err(Error::Ambiguous("output; select one by name".to_string())),
+    }
+}
+
+fn toplevel_source(
+    app_data: &AppData,
+    select: &toplevels::Selector,
+) -> Result<CaptureSource, Error> {
+    let toplevel_info_state = app_data
+        .toplevel_info_state
+        .as_ref()
+        .ok_or(Error::MissingGlobal("ext_foreign_toplevel_list_v1"))?;
+    let toplevels = toplevel_info_state
+        .toplevels()
+        .filter(|info| select.matches(info))
+        .collect::<Vec<_>>();
+    match toplevels[..] {
+        [] => Err(Error::NoMatch("window".to_string())),
+        [info] => Ok(CaptureSource::Toplevel(info.foreign_toplevel.clone())),
+        _ => Err(Error::Ambiguous("window".to_string())),
+    }
+}
+
+fn workspace_source(
+    app_data: &AppData,
+    select: &workspaces::Selector,
+) -> Result<CaptureSource, Error> {
+    let workspace = select.select(&app_data.output_state, &app_data.workspace_state)?;
+    Ok(CaptureSource::Workspace(workspace.handle.clone()))
+}
+
+fn start(app_data: &mut AppData, args: &Args, qh: &QueueHandle<AppData>) -> Result<(), Error> {
+    let capturer = app_data.screencopy_state.capturer();
+    let (source, cursor) = match &args.source {
+        Source::Region { region } => {
+            let region = RegionCapture::new(
+                capturer,
+                &app_data.output_state,
+                region.clone(),
+                app_data.options,
+                qh,
+            );
+            if region.outputs().next().is_none() {
+                return Err(Error::NoMatch("output in region".to_string()));
+            }
+            app_data.capture = Some(Capture::Region(region));
+            app_data.region_progress(qh);
+            return app_data.error.take().map_or(Ok(()), Err);
+        }
+        Source::Output { name, cursor } => (output_source(app_data, name.as_deref())?, cursor),
+        Source::Toplevel { select, cursor } => (toplevel_source(app_data, select)?, cursor),
+        Source::Workspace { select, cursor } => (workspace_source(app_data, select)?, cursor),
+    };
+    let (session, cursor_session) = if cursor.enabled {
+        if !capturer.supports_cursor_sessions() {
+            return Err(Error::Unsupported("cursor capture"));
+        }
+        let seat = app_data
+            .seat
+            .as_ref()
+            .ok_or(Error::MissingGlobal("wl_seat"))?;
+        let pointer = seat.get_pointer(qh, ());
+        let cursor_session = capturer.create_cursor_session(
+            &source,
+            &pointer,
+            qh,
+            ScreencopyCursorSessionData::default(),
+        )?;
+        let session = cursor_session.capture_session(qh, ScreencopySessionData::default())?;
+        (session, Some(cursor_session))
+    } else {
+        let session = capturer.create_session(
+            &source,
+            app_data.options,
+            qh,
+            ScreencopySessionData::default(),
+        )?;
+        (session, None)
+    };
+    app_data.capture = Some(Capture::Source(Box::new(SourceCapture {
+        session,
+        _cursor_session: cursor_session,
+        buffer: None,
+        frame: None,
+    })));
+    Ok(())
+}
+
+pub fn run(conn: &Connection, args: &Args, out: &mut dyn Write) -> Result<(), Error> {
+    let (mut event_queue, mut app_data) = connect(conn, args)?;
+    start(&mut app_data, args, &event_queue.handle())?;
+
+    let format = args.format.unwrap_or_else(|| match &args.file {
+        Some(path) if path.ends_with(".ppm") => Format::Ppm,
+        _ => Format::Png,
+    });
+    let file_per_frame = args.file.as_ref().is_some_and(|path| path.contains("{}"));
+    let mut file = match &args.file {
+        Some(path) if !file_per_frame => Some(io::BufWriter::new(fs::File::create(path)?)),
+        _ => None,
+    };
+    let mut frames = 0;
+    let mut raw_size = None;
+    loop {
+        event_queue.blocking_dispatch(&mut app_data)?;
+        for image in app_data.images.drain(..) {
+            if format == Format::Raw && raw_size != Some((image.width, image.height)) {
+                raw_size = Some((image.width, image.height));
+                eprintln!("{}x{}", image.width, image.height);
+            }
+            match (&args.file, &mut file) {
+                (Some(path), None) => {
+                    let path = path.replace("{}", &frames.to_string());
+                    let mut file = io::BufWriter::new(fs::File::create(path)?);
+                    image.write(format, &mut file)?;
+                    file.flush()?;
+                }
+                (_, Some(file)) => image.write(format, file)?,
+                (None, None) => image.write(format, out)?,
+            }
+            frames += 1;
+        }
+        if let Some(file) = &mut file {
+            file.flush()?;
+        }
+        out.flush()?;
+        if let Some(err) = app_data.error.take() {
+            return Err(err);
+        }
+        if app_data.remaining == Some(0) {
+            return Ok(());
+        }
+        if app_data.stopped {
+            if frames == 0 {
+                return Err(Error::Capture("capture session stopped".to_string()));
+            }
+            return Ok(());
+        }
+    }
+}
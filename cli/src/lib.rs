@@ -0,0 +1,183 @@
+//! Command-line tools for scripting COSMIC, built on `cosmic-client-toolkit`.
+//!
+//! Each tool is a module with its clap arguments and a `run` function taking a
+//! [`Connection`], so it can be tested against `cosmic-mock-compositor`. The binaries in
+//! `src/bin` only parse arguments and connect with [`main`].
+//!
+//! Exit codes are shared by all tools:
+//!
+//! | Code | Meaning |
+//! |------|---------|
+//! | 0 | Success |
+//! | 1 | Connection or protocol error |
+//! | 2 | Invalid arguments |
+//! | 3 | Nothing matched the selection |
+//! | 4 | The selection matched more than one object |
+//! | 5 | The compositor doesn't support the request |
+//! | 6 | The compositor rejected the request |
+
+use cosmic_client_toolkit::{
+    screencopy::{CaptureSourceError, CaptureSourceKind, RegionCaptureError},
+    sctk::output::OutputState,
+};
+use std::{fmt, io, process::ExitCode};
+use wayland_client::{
+    ConnectError, Connection, DispatchError, globals::GlobalError, protocol::wl_output,
+};
+
+pub mod capture;
+pub mod outputs;
+pub mod toplevels;
+pub mod workspaces;
+
+/// Help text describing the exit codes
+pub const EXIT_CODES: &str = "Exit codes:
+  0  success
+  1  connection or protocol error
+  2  invalid arguments
+  3  nothing matched the selection
+  4  the selection matched more than one object
+  5  the compositor doesn't support the request
+  6  the compositor rejected the request";
+
+#[derive(Debug)]
+pub enum Error {
+    Connect(ConnectError),
+    Globals(GlobalError),
+    Dispatch(DispatchError),
+    Io(io::Error),
+    /// The compositor failed to capture a frame
+    Capture(String),
+    /// Nothing matched the selection of the given kind of object
+    NoMatch(String),
+    /// More than one object of the given kind matched, where only one was expected
+    Ambiguous(String),
+    /// A global needed by the command isn't advertised by the compositor
+    MissingGlobal(&'static str),
+    /// The compositor doesn't advertise the capability needed by the command
+    Unsupported(&'static str),
+    /// The compositor rejected the given request
+    Rejected(&'static str),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Connect(_)
+            | Self::Globals(_)
+            | Self::Dispatch(_)
+            | Self::Io(_)
+            | Self::Capture(_) => 1,
+            Self::NoMatch(_) => 3,
+            Self::Ambiguous(_) => 4,
+            Self::MissingGlobal(_) | Self::Unsupported(_) => 5,
+            Self::Rejected(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connect(err) => write!(f, "failed to connect to compositor: {}", err),
+            Self::Globals(err) => write!(f, "{}", err),
+            Self::Dispatch(err) => write!(f, "{}", err),
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Capture(err) => write!(f, "capture failed: {}", err),
+            Self::NoMatch(what) => write!(f, "no {} found", what),
+            Self::Ambiguous(what) => write!(f, "more than one {} found", what),
+            Self::MissingGlobal(interface) => {
+                write!(f, "{} unsupported by compositor", interface)
+            }
+            Self::Unsupported(capability) => {
+                write!(f, "{} unsupported by compositor", capability)
+            }
+            Self::Rejected(request) => write!(f, "{} rejected by compositor", request),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ConnectError> for Error {
+    fn from(err: ConnectError) -> Self {
+        Self::Connect(err)
+    }
+}
+
+impl From<GlobalError> for Error {
+    fn from(err: GlobalError) -> Self {
+        Self::Globals(err)
+    }
+}
+
+impl From<DispatchError> for Error {
+    fn from(err: DispatchError) -> Self {
+        Self::Dispatch(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<CaptureSourceError> for Error {
+    fn from(err: CaptureSourceError) -> Self {
+        Self::MissingGlobal(match err.kind() {
+            Some(CaptureSourceKind::Output) => "ext_output_image_capture_source_manager_v1",
+            Some(CaptureSourceKind::Toplevel) => {
+                "ext_foreign_toplevel_image_capture_source_manager_v1"
+            }
+            Some(CaptureSourceKind::Workspace) => {
+                "zcosmic_workspace_image_capture_source_manager_v1"
+            }
+            None => "ext_image_copy_capture_manager_v1",
+        })
+    }
+}
+
+impl From<RegionCaptureError> for Error {
+    fn from(err: RegionCaptureError) -> Self {
+        match err {
+            RegionCaptureError::Session(err) => err.into(),
+            err => Self::Capture(err.to_string()),
+        }
+    }
+}
+
+/// Output with the name `name`
+pub(crate) fn find_output(
+    output_state: &OutputState,
+    name: &str,
+) -> Result<wl_output::WlOutput, Error> {
+    output_state
+        .outputs()
+        .find(|output| {
+            output_state
+                .info(output)
+                .is_some_and(|info| info.name.as_deref() == Some(name))
+        })
+        .ok_or_else(|| Error::NoMatch(format!("output named {:?}", name)))
+}
+
+/// Connect to the compositor of the environment and run a tool, printing its output to
+/// stdout and errors to stderr.
+pub fn main(
+    name: &str,
+    run: impl FnOnce(&Connection, &mut dyn io::Write) -> Result<(), Error>,
+) -> ExitCode {
+    let result = Connection::connect_to_env()
+        .map_err(Error::from)
+        .and_then(|conn| run(&conn, &mut io::stdout().lock()));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        // Output piped to a command that exited early
+        Err(Error::Io(err)) if err.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}: {}", name, err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
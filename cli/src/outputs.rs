@@ -0,0 +1,890 @@
+//! `cosmic-outputs`: list and configure outputs, like `cosmic-randr`.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use cosmic_client_toolkit::cosmic_protocols::output_management::v1::client::{
+    zcosmic_output_configuration_head_v1, zcosmic_output_configuration_v1,
+    zcosmic_output_head_v1::{self, AdaptiveSyncAvailability, AdaptiveSyncStateExt},
+    zcosmic_output_manager_v1,
+};
+use serde_json::json;
+use std::{fmt, io::Write};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum, event_created_child,
+    globals::{GlobalListContents, registry_queue_init},
+    protocol::{wl_output::Transform, wl_registry},
+};
+use wayland_protocols_wlr::output_management::v1::client::{
+    zwlr_output_configuration_head_v1, zwlr_output_configuration_v1, zwlr_output_head_v1,
+    zwlr_output_manager_v1, zwlr_output_mode_v1,
+};
+
+use crate::{EXIT_CODES, Error};
+
+/// List and configure outputs
+#[derive(Debug, Parser)]
+#[command(name = "cosmic-outputs", version, after_help = EXIT_CODES)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List outputs, one per line as
+    /// `name<TAB>enabled|disabled<TAB>mode<TAB>position<TAB>scale<TAB>transform<TAB>flags`
+    ///
+    /// Flags are `xwayland-primary`, `adaptive-sync` or `adaptive-sync-always`, and
+    /// `mirroring=NAME`, separated by commas.
+    List {
+        /// Print a JSON array instead, including modes and output details
+        #[arg(long)]
+        json: bool,
+    },
+    /// Enable an output
+    Enable {
+        /// Name of the output
+        output: String,
+        #[command(flatten)]
+        apply: Apply,
+    },
+    /// Disable an output
+    Disable {
+        /// Name of the output
+        output: String,
+        #[command(flatten)]
+        apply: Apply,
+    },
+    /// Change the mode, position, scale, transform or adaptive sync of an output, enabling it
+    Configure {
+        /// Name of the output
+        output: String,
+        /// Mode as `WIDTHxHEIGHT` or `WIDTHxHEIGHT@HZ`; a custom mode if the output doesn't
+        /// advertise it
+        #[arg(long, value_parser = parse_mode)]
+        mode: Option<Mode>,
+        /// Position as `X,Y` in the global compositor space
+        #[arg(long, value_parser = parse_position)]
+        position: Option<(i32, i32)>,
+        #[arg(long)]
+        scale: Option<f64>,
+        #[arg(long)]
+        transform: Option<TransformArg>,
+        #[arg(long)]
+        adaptive_sync: Option<AdaptiveSync>,
+        #[command(flatten)]
+        apply: Apply,
+    },
+    /// Mirror another output onto an output
+    Mirror {
+        /// Name of the output to show the mirrored contents on
+        output: String,
+        /// Name of the output to mirror
+        source: String,
+        #[command(flatten)]
+        apply: Apply,
+    },
+    /// Set the output advertised to Xwayland as primary
+    SetXwaylandPrimary {
+        /// Name of the output; unsets the primary output if omitted
+        output: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, clap::Args)]
+pub struct Apply {
+    /// Only test whether the compositor accepts the configuration, without applying it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mode {
+    pub width: i32,
+    pub height: i32,
+    /// Refresh rate in mHz
+    pub refresh: Option<i32>,
+}
+
+fn parse_mode(s: &str) -> Result<Mode, String> {
+    let parse = || {
+        let (size, refresh) = match s.split_once('@') {
+            Some((size, refresh)) => (size, Some(refresh.parse::<f64>().ok()?)),
+            None => (s, None),
+        };
+        let (width, height) = size.split_once('x')?;
+        Some(Mode {
+            width: width.parse().ok().filter(|w| *w > 0)?,
+            height: height.parse().ok().filter(|h| *h > 0)?,
+            refresh: refresh.map(|hz| (hz * 1000.).round() as i32),
+        })
+    };
+    parse().ok_or_else(|| format!("expected `WIDTHxHEIGHT` or `WIDTHxHEIGHT@HZ`, got {:?}", s))
+}
+
+fn parse_position(s: &str) -> Result<(i32, i32), String> {
+    s.split_once(',')
+        .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+        .ok_or_else(|| format!("expected `X,Y`, got {:?}", s))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TransformArg {
+    Normal,
+    #[value(name = "90")]
+    Rotate90,
+    #[value(name = "180")]
+    Rotate180,
+    #[value(name = "270")]
+    Rotate270,
+    Flipped,
+    #[value(name = "flipped-90")]
+    Flipped90,
+    #[value(name = "flipped-180")]
+    Flipped180,
+    #[value(name = "flipped-270")]
+    Flipped270,
+}
+
+const TRANSFORMS: [(TransformArg, Transform, &str); 8] = [
+    (TransformArg::Normal, Transform::Normal, "normal"),
+    (TransformArg::Rotate90, Transform::_90, "90"),
+    (TransformArg::Rotate180, Transform::_180, "180"),
+    (TransformArg::Rotate270, Transform::_270, "270"),
+    (TransformArg::Flipped, Transform::Flipped, "flipped"),
+    (TransformArg::Flipped90, Transform::Flipped90, "flipped-90"),
+    (
+        TransformArg::Flipped180,
+        Transform::Flipped180,
+        "flipped-180",
+    ),
+    (
+        TransformArg::Flipped270,
+        Transform::Flipped270,
+        "flipped-270",
+    ),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum AdaptiveSync {
+    /// Disable adaptive sync
+    Off,
+    /// Activate adaptive sync automatically, for fullscreen windows that support it
+    Automatic,
+    /// Always activate adaptive sync; requires `zcosmic_output_manager_v1` version 2
+    Always,
+}
+
+fn adaptive_sync_name(state: AdaptiveSyncStateExt) -> &'static str {
+    match state {
+        AdaptiveSyncStateExt::Disabled => "disabled",
+        AdaptiveSyncStateExt::Automatic => "automatic",
+        AdaptiveSyncStateExt::Always => "always",
+        _ => "unknown",
+    }
+}
+
+fn adaptive_sync_available_name(available: AdaptiveSyncAvailability) -> &'static str {
+    match available {
+        AdaptiveSyncAvailability::Unsupported => "unsupported",
+        AdaptiveSyncAvailability::RequiresModeset => "requires_modeset",
+        AdaptiveSyncAvailability::Supported => "supported",
+        _ => "unknown",
+    }
+}
+
+#[derive(Debug)]
+struct HeadMode {
+    mode: zwlr_output_mode_v1::ZwlrOutputModeV1,
+    width: i32,
+    height: i32,
+    /// Refresh rate in mHz, or zero if unknown
+    refresh: i32,
+    preferred: bool,
+}
+
+impl fmt::Display for HeadMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)?;
+        if self.refresh > 0 {
+            write!(f, "@{}", self.refresh as f64 / 1000.)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Head {
+    head: zwlr_output_head_v1::ZwlrOutputHeadV1,
+    cosmic: Option<zcosmic_output_head_v1::ZcosmicOutputHeadV1>,
+    name: String,
+    description: String,
+    make: Option<String>,
+    model: Option<String>,
+    serial_number: Option<String>,
+    physical_size: (i32, i32),
+    modes: Vec<HeadMode>,
+    current_mode: Option<zwlr_output_mode_v1::ZwlrOutputModeV1>,
+    enabled: bool,
+    position: (i32, i32),
+    transform: Option<Transform>,
+    scale: f64,
+    // Properties sent by the cosmic extension
+    scale_1000: Option<i32>,
+    mirroring: Option<String>,
+    adaptive_sync: Option<AdaptiveSyncStateExt>,
+    adaptive_sync_available: Option<AdaptiveSyncAvailability>,
+    xwayland_primary: Option<bool>,
+    // Adaptive sync state of `zwlr_output_head_v1` version 4
+    wlr_adaptive_sync: Option<bool>,
+}
+
+impl Head {
+    fn new(head: zwlr_output_head_v1::ZwlrOutputHeadV1) -> Self {
+        Self {
+            head,
+            cosmic: None,
+            name: String::new(),
+            description: String::new(),
+            make: None,
+            model: None,
+            serial_number: None,
+            physical_size: (0, 0),
+            modes: Vec::new(),
+            current_mode: None,
+            enabled: false,
+            position: (0, 0),
+            transform: None,
+            scale: 1.,
+            scale_1000: None,
+            mirroring: None,
+            adaptive_sync: None,
+            adaptive_sync_available: None,
+            xwayland_primary: None,
+            wlr_adaptive_sync: None,
+        }
+    }
+
+    fn current_mode(&self) -> Option<&HeadMode> {
+        let current = self.current_mode.as_ref()?;
+        self.modes.iter().find(|mode| mode.mode == *current)
+    }
+
+    fn scale(&self) -> f64 {
+        self.scale_1000
+            .map_or(self.scale, |scale| scale as f64 / 1000.)
+    }
+
+    fn transform_name(&self) -> &'static str {
+        TRANSFORMS
+            .iter()
+            .find(|(_, transform, _)| Some(*transform) == self.transform)
+            .map_or("normal", |(_, _, name)| name)
+    }
+
+    fn flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if self.xwayland_primary == Some(true) {
+            flags.push("xwayland-primary".to_string());
+        }
+        match (self.adaptive_sync, self.wlr_adaptive_sync) {
+            (Some(AdaptiveSyncStateExt::Always), _) => {
+                flags.push("adaptive-sync-always".to_string())
+            }
+            (Some(AdaptiveSyncStateExt::Automatic), _) | (None, Some(true)) => {
+                flags.push("adaptive-sync".to_string())
+            }
+            _ => {}
+        }
+        if let Some(mirroring) = &self.mirroring {
+            flags.push(format!("mirroring={}", mirroring));
+        }
+        flags
+    }
+
+    fn to_line(&self) -> String {
+        if !self.enabled {
+            return format!("{}\tdisabled\t-\t-\t-\t-\t", self.name);
+        }
+        format!(
+            "{}\tenabled\t{}\t{},{}\t{}\t{}\t{}",
+            self.name,
+            self.current_mode()
+                .map_or_else(|| "-".to_string(), |mode| mode.to_string()),
+            self.position.0,
+            self.position.1,
+            self.scale(),
+            self.transform_name(),
+            self.flags().join(",")
+        )
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let modes = self
+            .modes
+            .iter()
+            .map(|mode| {
+                json!({
+                    "width": mode.width,
+                    "height": mode.height,
+                    "refresh": mode.refresh,
+                    "preferred": mode.preferred,
+                    "current": self.current_mode.as_ref() == Some(&mode.mode),
+                })
+            })
+            .collect::<Vec<_>>();
+        let adaptive_sync = self.adaptive_sync.map(adaptive_sync_name).or_else(|| {
+            self.wlr_adaptive_sync
+                .map(|enabled| if enabled { "automatic" } else { "disabled" })
+        });
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "make": self.make,
+            "model": self.model,
+            "serial_number": self.serial_number,
+            "physical_size": [self.physical_size.0, self.physical_size.1],
+            "enabled": self.enabled,
+            "modes": modes,
+            "position": [self.position.0, self.position.1],
+            "scale": self.scale(),
+            "transform": self.transform_name(),
+            "adaptive_sync": adaptive_sync,
+            "adaptive_sync_available": self.adaptive_sync_available.map(adaptive_sync_available_name),
+            "mirroring": self.mirroring,
+            "xwayland_primary": self.xwayland_primary,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ConfigurationResult {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+struct AppData {
+    manager: zwlr_output_manager_v1::ZwlrOutputManagerV1,
+    cosmic_manager: Option<zcosmic_output_manager_v1::ZcosmicOutputManagerV1>,
+    heads: Vec<Head>,
+    serial: u32,
+    result: Option<ConfigurationResult>,
+}
+
+impl AppData {
+    fn head(&self, name: &str) -> Result<&Head, Error> {
+        self.heads
+            .iter()
+            .find(|head| head.name == name)
+            .ok_or_else(|| Error::NoMatch(format!("output named {:?}", name)))
+    }
+
+    fn head_mut(&mut self, head: &zwlr_output_head_v1::ZwlrOutputHeadV1) -> Option<&mut Head> {
+        self.heads.iter_mut().find(|h| h.head == *head)
+    }
+
+    fn cosmic_manager(
+        &self,
+        since: u32,
+        name: &'static str,
+    ) -> Result<&zcosmic_output_manager_v1::ZcosmicOutputManagerV1, Error> {
+        let manager = self
+            .cosmic_manager
+            .as_ref()
+            .ok_or(Error::MissingGlobal("zcosmic_output_manager_v1"))?;
+        if manager.version() < since {
+            return Err(Error::Unsupported(name));
+        }
+        Ok(manager)
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for AppData {
+    fn event(
+        _state: &mut Self,
+        _registry: &wl_registry::WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<zwlr_output_manager_v1::ZwlrOutputManagerV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        _manager: &zwlr_output_manager_v1::ZwlrOutputManagerV1,
+        event: zwlr_output_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_output_manager_v1::Event::Head { head } => {
+                let mut info = Head::new(head.clone());
+                info.cosmic = state
+                    .cosmic_manager
+                    .as_ref()
+                    .map(|manager| manager.get_head(&head, qh, head.clone()));
+                state.heads.push(info);
+            }
+            zwlr_output_manager_v1::Event::Done { serial } => state.serial = serial,
+            zwlr_output_manager_v1::Event::Finished => {}
+            _ => {}
+        }
+    }
+
+    event_created_child!(AppData, zwlr_output_manager_v1::ZwlrOutputManagerV1, [
+        zwlr_output_manager_v1::EVT_HEAD_OPCODE => (zwlr_output_head_v1::ZwlrOutputHeadV1, ()),
+    ]);
+}
+
+impl Dispatch<zwlr_output_head_v1::ZwlrOutputHeadV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        head: &zwlr_output_head_v1::ZwlrOutputHeadV1,
+        event: zwlr_output_head_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_output_head_v1::Event::Finished = event {
+            if let Some(cosmic) = state.head_mut(head).and_then(|info| info.cosmic.take()) {
+                cosmic.release();
+            }
+            state.heads.retain(|h| h.head != *head);
+            head.release();
+            return;
+        }
+        let Some(info) = state.head_mut(head) else {
+            return;
+        };
+        match event {
+            zwlr_output_head_v1::Event::Name { name } => info.name = name,
+            zwlr_output_head_v1::Event::Description { description } => {
+                info.description = description
+            }
+            zwlr_output_head_v1::Event::PhysicalSize { width, height } => {
+                info.physical_size = (width, height)
+            }
+            zwlr_output_head_v1::Event::Mode { mode } => info.modes.push(HeadMode {
+                mode,
+                width: 0,
+                height: 0,
+                refresh: 0,
+                preferred: false,
+            }),
+            zwlr_output_head_v1::Event::Enabled { enabled } => info.enabled = enabled != 0,
+            zwlr_output_head_v1::Event::CurrentMode { mode } => info.current_mode = Some(mode),
+            zwlr_output_head_v1::Event::Position { x, y } => info.position = (x, y),
+            zwlr_output_head_v1::Event::Transform { transform } => {
+                info.transform = transform.into_result().ok()
+            }
+            zwlr_output_head_v1::Event::Scale { scale } => info.scale = scale,
+            zwlr_output_head_v1::Event::Make { make } => info.make = Some(make),
+            zwlr_output_head_v1::Event::Model { model } => info.model = Some(model),
+            zwlr_output_head_v1::Event::SerialNumber { serial_number } => {
+                info.serial_number = Some(serial_number)
+            }
+            zwlr_output_head_v1::Event::AdaptiveSync { state } => {
+                info.wlr_adaptive_sync =
+                    Some(state == WEnum::Value(zwlr_output_head_v1::AdaptiveSyncState::Enabled))
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(AppData, zwlr_output_head_v1::ZwlrOutputHeadV1, [
+        zwlr_output_head_v1::EVT_MODE_OPCODE => (zwlr_output_mode_v1::ZwlrOutputModeV1, ()),
+    ]);
+}
+
+impl Dispatch<zwlr_output_mode_v1::ZwlrOutputModeV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        mode: &zwlr_output_mode_v1::ZwlrOutputModeV1,
+        event: zwlr_output_mode_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        for head in &mut state.heads {
+            if let zwlr_output_mode_v1::Event::Finished = event {
+                head.modes.retain(|m| m.mode != *mode);
+                continue;
+            }
+            let Some(info) = head.modes.iter_mut().find(|m| m.mode == *mode) else {
+                continue;
+            };
+            match event {
+                zwlr_output_mode_v1::Event::Size { width, height } => {
+                    info.width = width;
+                    info.height = height;
+                }
+                zwlr_output_mode_v1::Event::Refresh { refresh } => info.refresh = refresh,
+                zwlr_output_mode_v1::Event::Preferred => info.preferred = true,
+                _ => {}
+            }
+            return;
+        }
+        if let zwlr_output_mode_v1::Event::Finished = event {
+            mode.release();
+        }
+    }
+}
+
+impl Dispatch<zcosmic_output_head_v1::ZcosmicOutputHeadV1, zwlr_output_head_v1::ZwlrOutputHeadV1>
+    for AppData
+{
+    fn event(
+        state: &mut Self,
+        _cosmic_head: &zcosmic_output_head_v1::ZcosmicOutputHeadV1,
+        event: zcosmic_output_head_v1::Event,
+        head: &zwlr_output_head_v1::ZwlrOutputHeadV1,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let Some(info) = state.head_mut(head) else {
+            return;
+        };
+        match event {
+            zcosmic_output_head_v1::Event::Scale1000 { scale_1000 } => {
+                info.scale_1000 = Some(scale_1000)
+            }
+            zcosmic_output_head_v1::Event::Mirroring { name } => info.mirroring = name,
+            zcosmic_output_head_v1::Event::AdaptiveSyncAvailable { available } => {
+                info.adaptive_sync_available = available.into_result().ok()
+            }
+            zcosmic_output_head_v1::Event::AdaptiveSyncExt { state } => {
+                info.adaptive_sync = state.into_result().ok()
+            }
+            zcosmic_output_head_v1::Event::XwaylandPrimary { state } => {
+                info.xwayland_primary = Some(state != 0)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<zwlr_output_configuration_v1::ZwlrOutputConfigurationV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        _config: &zwlr_output_configuration_v1::ZwlrOutputConfigurationV1,
+        event: zwlr_output_configuration_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        state.result = match event {
+            zwlr_output_configuration_v1::Event::Succeeded => Some(ConfigurationResult::Succeeded),
+            zwlr_output_configuration_v1::Event::Failed => Some(ConfigurationResult::Failed),
+            zwlr_output_configuration_v1::Event::Cancelled => Some(ConfigurationResult::Cancelled),
+            _ => return,
+        };
+    }
+}
+
+wayland_client::delegate_noop!(AppData: ignore zcosmic_output_manager_v1::ZcosmicOutputManagerV1);
+wayland_client::delegate_noop!(AppData: ignore zcosmic_output_configuration_v1::ZcosmicOutputConfigurationV1);
+wayland_client::delegate_noop!(AppData: ignore zcosmic_output_configuration_head_v1::ZcosmicOutputConfigurationHeadV1);
+wayland_client::delegate_noop!(AppData: ignore zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1);
+
+fn connect(conn: &Connection) -> Result<(EventQueue<AppData>, AppData), Error> {
+    let (globals, mut event_queue) = registry_queue_init(conn)?;
+    let qh = event_queue.handle();
+    // Bound first, so extension objects can be requested as heads are announced
+    let cosmic_manager = globals.bind(&qh, 1..=3, ()).ok();
+    let manager = globals
+        .bind(&qh, 1..=4, ())
+        .map_err(|_| Error::MissingGlobal("zwlr_output_manager_v1"))?;
+    let mut app_data = AppData {
+        manager,
+        cosmic_manager,
+        heads: Vec::new(),
+        serial: 0,
+        result: None,
+    };
+    // Extension objects are requested in response to events, so their initial state needs a
+    // second roundtrip
+    event_queue.roundtrip(&mut app_data)?;
+    event_queue.roundtrip(&mut app_data)?;
+    Ok((event_queue, app_data))
+}
+
+/// Advertised mode of `head` closest to `mode`, if any has its size
+fn find_mode<'a>(head: &'a Head, mode: &Mode) -> Option<&'a HeadMode> {
+    let modes = head
+        .modes
+        .iter()
+        .filter(|m| m.width == mode.width && m.height == mode.height);
+    match mode.refresh {
+        // Refresh rates given in Hz are rounded
+        Some(refresh) => modes
+            .filter(|m| (m.refresh - refresh).abs() < 500)
+            .min_by_key(|m| (m.refresh - refresh).abs()),
+        None => modes.max_by_key(|m| (m.preferred, m.refresh)),
+    }
+}
+
+// Settings of the `configure` command
+struct Settings<'a> {
+    mode: Option<&'a Mode>,
+    position: Option<(i32, i32)>,
+    scale: Option<f64>,
+    transform: Option<TransformArg>,
+    adaptive_sync: Option<AdaptiveSync>,
+}
+
+fn configure_head(
+    app_data: &AppData,
+    head: &Head,
+    config_head: &zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1,
+    settings: &Settings<'_>,
+    qh: &QueueHandle<AppData>,
+) -> Result<(), Error> {
+    if let Some(mode) = settings.mode {
+        match find_mode(head, mode) {
+            Some(mode) => config_head.set_mode(&mode.mode),
+            None => config_head.set_custom_mode(mode.width, mode.height, mode.refresh.unwrap_or(0)),
+        }
+    }
+    if let Some((x, y)) = settings.position {
+        config_head.set_position(x, y);
+    }
+    if let Some(transform) = settings.transform {
+        let (_, transform, _) = TRANSFORMS
+            .iter()
+            .find(|(arg, ..)| *arg == transform)
+            .unwrap();
+        config_head.set_transform(*transform);
+    }
+
+    let cosmic_config_head = || {
+        app_data
+            .cosmic_manager
+            .as_ref()
+            .map(|manager| manager.get_configuration_head(config_head, qh, ()))
+    };
+    let mut cosmic = None;
+    if let Some(scale) = settings.scale {
+        cosmic = cosmic_config_head();
+        match &cosmic {
+            Some(cosmic) => cosmic.set_scale_1000((scale * 1000.).round() as i32),
+            None => config_head.set_scale(scale),
+        }
+    }
+    if let Some(adaptive_sync) = settings.adaptive_sync {
+        if cosmic.is_none() {
+            cosmic = cosmic_config_head();
+        }
+        match cosmic.as_ref().filter(|cosmic| {
+            cosmic.version()
+                >= zcosmic_output_configuration_head_v1::REQ_SET_ADAPTIVE_SYNC_EXT_SINCE
+        }) {
+            Some(cosmic) => cosmic.set_adaptive_sync_ext(match adaptive_sync {
+                AdaptiveSync::Off => AdaptiveSyncStateExt::Disabled,
+                AdaptiveSync::Automatic => AdaptiveSyncStateExt::Automatic,
+                AdaptiveSync::Always => AdaptiveSyncStateExt::Always,
+            }),
+            None if config_head.version()
+                >= zwlr_output_configuration_head_v1::REQ_SET_ADAPTIVE_SYNC_SINCE =>
+            {
+                config_head.set_adaptive_sync(match adaptive_sync {
+                    AdaptiveSync::Off => zwlr_output_head_v1::AdaptiveSyncState::Disabled,
+                    AdaptiveSync::Automatic => zwlr_output_head_v1::AdaptiveSyncState::Enabled,
+                    AdaptiveSync::Always => return Err(Error::Unsupported("adaptive sync always")),
+                })
+            }
+            None => return Err(Error::Unsupported("adaptive sync")),
+        }
+    }
+    Ok(())
+}
+
+// Create a configuration with every head as it currently is, except `target`, which is set up
+// by `f`, then test or apply it.
+//
+// Heads mirroring `target` keep doing so only if it stays `mirrorable`, that is enabled and
+// not mirroring itself; otherwise they're enabled on their own.
+fn apply(
+    event_queue: &mut EventQueue<AppData>,
+    app_data: &mut AppData,
+    target: &str,
+    mirrorable: bool,
+    apply: &Apply,
+    mut f: impl FnMut(
+        &AppData,
+        &zwlr_output_configuration_v1::ZwlrOutputConfigurationV1,
+        Option<&zcosmic_output_configuration_v1::ZcosmicOutputConfigurationV1>,
+        &Head,
+    ) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let qh = event_queue.handle();
+    let target = app_data.head(target)?;
+    let config = app_data
+        .manager
+        .create_configuration(app_data.serial, &qh, ());
+    let cosmic_config = app_data
+        .cosmic_manager
+        .as_ref()
+        .map(|manager| manager.get_configuration(&config, &qh, ()));
+    let result = (|| {
+        for head in &app_data.heads {
+            if head.head == target.head {
+                f(app_data, &config, cosmic_config.as_ref(), head)?;
+            } else if !head.enabled {
+                config.disable_head(&head.head);
+            } else if let (Some(cosmic_config), Some(source)) = (
+                &cosmic_config,
+                head.mirroring
+                    .as_ref()
+                    .and_then(|name| app_data.heads.iter().find(|h| h.name == *name))
+                    .filter(|source| mirrorable || source.head != target.head),
+            ) {
+                cosmic_config.mirror_head(&head.head, &source.head, &qh, ());
+            } else {
+                config.enable_head(&head.head, &qh, ());
+            }
+        }
+        Ok(())
+    })();
+    if let Err(err) = result {
+        config.destroy();
+        return Err(err);
+    }
+    if apply.dry_run {
+        config.test();
+    } else {
+        config.apply();
+    }
+
+    app_data.result = None;
+    while app_data.result.is_none() {
+        event_queue.blocking_dispatch(app_data)?;
+    }
+    if let Some(cosmic_config) = cosmic_config {
+        cosmic_config.release();
+    }
+    config.destroy();
+    match app_data.result.unwrap() {
+        ConfigurationResult::Succeeded => Ok(()),
+        ConfigurationResult::Failed => Err(Error::Rejected("output configuration")),
+        ConfigurationResult::Cancelled => Err(Error::Rejected("outdated output configuration")),
+    }
+}
+
+pub fn run(conn: &Connection, args: &Args, out: &mut dyn Write) -> Result<(), Error> {
+    let (mut event_queue, mut app_data) = connect(conn)?;
+    let qh = event_queue.handle();
+    match &args.command {
+        Command::List { json } => {
+            if *json {
+                let list = app_data.heads.iter().map(Head::to_json).collect();
+                writeln!(out, "{:#}", serde_json::Value::Array(list))?;
+            } else {
+                for head in &app_data.heads {
+                    writeln!(out, "{}", head.to_line())?;
+                }
+            }
+            return Ok(());
+        }
+        Command::Enable { output, apply: a } => {
+            apply(
+                &mut event_queue,
+                &mut app_data,
+                output,
+                true,
+                a,
+                |_, config, _, head| {
+                    config.enable_head(&head.head, &qh, ());
+                    Ok(())
+                },
+            )?;
+        }
+        Command::Disable { output, apply: a } => {
+            apply(
+                &mut event_queue,
+                &mut app_data,
+                output,
+                false,
+                a,
+                |_, config, _, head| {
+                    config.disable_head(&head.head);
+                    Ok(())
+                },
+            )?;
+        }
+        Command::Configure {
+            output,
+            mode,
+            position,
+            scale,
+            transform,
+            adaptive_sync,
+            apply: a,
+        } => {
+            let settings = Settings {
+                mode: mode.as_ref(),
+                position: *position,
+                scale: *scale,
+                transform: *transform,
+                adaptive_sync: *adaptive_sync,
+            };
+            apply(
+                &mut event_queue,
+                &mut app_data,
+                output,
+                true,
+                a,
+                |app_data, config, _, head| {
+                    let config_head = config.enable_head(&head.head, &qh, ());
+                    configure_head(app_data, head, &config_head, &settings, &qh)
+                },
+            )?;
+        }
+        Command::Mirror {
+            output,
+            source,
+            apply: a,
+        } => {
+            let source = app_data.head(source)?.head.clone();
+            app_data.cosmic_manager(1, "mirroring")?;
+            apply(
+                &mut event_queue,
+                &mut app_data,
+                output,
+                false,
+                a,
+                |_, _, cosmic_config, head| {
+                    // Checked above
+                    cosmic_config
+                        .unwrap()
+                        .mirror_head(&head.head, &source, &qh, ());
+                    Ok(())
+                },
+            )?;
+        }
+        Command::SetXwaylandPrimary { output } => {
+            let manager = app_data.cosmic_manager(
+                zcosmic_output_manager_v1::REQ_SET_XWAYLAND_PRIMARY_SINCE,
+                "set_xwayland_primary",
+            )?;
+            let head = match output {
+                Some(output) => Some(
+                    app_data
+                        .head(output)?
+                        .cosmic
+                        .as_ref()
+                        // Created for every head when the manager exists
+                        .unwrap(),
+                ),
+                None => None,
+            };
+            manager.set_xwayland_primary(head);
+            // Wait for the compositor to process the request, so protocol errors are reported
+            event_queue.roundtrip(&mut app_data)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,639 @@
+//! `cosmic-toplevels`: list, watch and manage windows.
+
+use clap::{Parser, Subcommand};
+use cosmic_client_toolkit::{
+    cosmic_protocols::{
+        toplevel_info::v1::client::zcosmic_toplevel_handle_v1,
+        toplevel_management::v1::client::zcosmic_toplevel_manager_v1::{
+            self, ZcosmicToplelevelManagementCapabilitiesV1 as Capability,
+        },
+    },
+    sctk::{
+        self,
+        output::{OutputHandler, OutputState},
+        registry::{ProvidesRegistryState, RegistryState},
+    },
+    toplevel_info::{ToplevelInfo, ToplevelInfoHandler, ToplevelInfoState},
+    toplevel_management::{ToplevelManagerHandler, ToplevelManagerState},
+    wayland_protocols::ext::{
+        foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1,
+        workspace::v1::client::ext_workspace_handle_v1,
+    },
+    workspace::{WorkspaceHandler, WorkspaceState},
+};
+use regex::Regex;
+use serde_json::json;
+use std::io::Write;
+use wayland_client::{
+    Connection, EventQueue, QueueHandle, WEnum,
+    globals::registry_queue_init,
+    protocol::{wl_output, wl_seat},
+};
+
+use crate::{EXIT_CODES, Error};
+
+/// List, watch and manage windows
+#[derive(Debug, Parser)]
+#[command(name = "cosmic-toplevels", version, after_help = EXIT_CODES)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List windows, one per line as `identifier<TAB>app_id<TAB>title<TAB>states`
+    List {
+        /// Print a JSON array instead
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        select: Selector,
+    },
+    /// Print a line for every window opened, changed or closed
+    ///
+    /// Windows that are already open are printed as opened first.
+    Watch {
+        /// Print a JSON object per line instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Activate a window
+    Activate {
+        #[command(flatten)]
+        target: Target,
+    },
+    /// Ask a window to close
+    Close {
+        #[command(flatten)]
+        target: Target,
+    },
+    /// Minimize a window
+    Minimize {
+        #[command(flatten)]
+        target: Target,
+        /// Unminimize the window instead
+        #[arg(long)]
+        unset: bool,
+    },
+    /// Make a window fullscreen
+    Fullscreen {
+        #[command(flatten)]
+        target: Target,
+        /// Name of the output to make the window fullscreen on
+        #[arg(long)]
+        output: Option<String>,
+        /// Leave fullscreen instead
+        #[arg(long, conflicts_with = "output")]
+        unset: bool,
+    },
+    /// Move a window to a workspace
+    MoveToWorkspace {
+        #[command(flatten)]
+        target: Target,
+        /// Name of the workspace
+        workspace: String,
+        /// Name of the output of the workspace, if workspaces on several outputs have the name
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Show a window on all workspaces
+    Sticky {
+        #[command(flatten)]
+        target: Target,
+        /// Only show the window on its workspace instead
+        #[arg(long)]
+        unset: bool,
+    },
+}
+
+/// Selection of windows matching all the given criteria
+#[derive(Clone, Debug, Default, clap::Args)]
+pub struct Selector {
+    /// Select windows with this app ID
+    #[arg(long)]
+    pub app_id: Option<String>,
+    /// Select windows with a title matching this regular expression
+    #[arg(long)]
+    pub title: Option<Regex>,
+    /// Select the window with this identifier
+    #[arg(long)]
+    pub identifier: Option<String>,
+}
+
+impl Selector {
+    pub(crate) fn matches(&self, info: &ToplevelInfo) -> bool {
+        self.app_id
+            .as_ref()
+            .is_none_or(|app_id| info.app_id == *app_id)
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|title| title.is_match(&info.title))
+            && self
+                .identifier
+                .as_ref()
+                .is_none_or(|identifier| info.identifier == *identifier)
+    }
+}
+
+/// Windows a command applies to
+#[derive(Clone, Debug, Default, clap::Args)]
+pub struct Target {
+    #[command(flatten)]
+    pub select: Selector,
+    /// Apply the command to all selected windows, instead of requiring exactly one
+    #[arg(long)]
+    pub all: bool,
+}
+
+const STATES: [(zcosmic_toplevel_handle_v1::State, &str); 5] = [
+    (zcosmic_toplevel_handle_v1::State::Maximized, "maximized"),
+    (zcosmic_toplevel_handle_v1::State::Minimized, "minimized"),
+    (zcosmic_toplevel_handle_v1::State::Activated, "activated"),
+    (zcosmic_toplevel_handle_v1::State::Fullscreen, "fullscreen"),
+    (zcosmic_toplevel_handle_v1::State::Sticky, "sticky"),
+];
+
+struct Watch {
+    json: bool,
+    lines: Vec<String>,
+    finished: bool,
+}
+
+struct AppData {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    toplevel_info_state: ToplevelInfoState,
+    toplevel_manager_state: Option<ToplevelManagerState>,
+    workspace_state: WorkspaceState,
+    capabilities: Vec<WEnum<Capability>>,
+    seat: Option<wl_seat::WlSeat>,
+    watch: Option<Watch>,
+}
+
+impl AppData {
+    fn output_name(&self, output: &wl_output::WlOutput) -> Option<String> {
+        self.output_state.info(output)?.name
+    }
+
+    fn output(&self, name: &str) -> Result<wl_output::WlOutput, Error> {
+        crate::find_output(&self.output_state, name)
+    }
+
+    fn states(&self, info: &ToplevelInfo) -> Vec<&'static str> {
+        STATES
+            .iter()
+            .filter(|(state, _)| info.state.contains(state))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    fn to_line(&self, info: &ToplevelInfo) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            info.identifier,
+            info.app_id,
+            info.title,
+            self.states(info).join(",")
+        )
+    }
+
+    fn to_json(&self, info: &ToplevelInfo) -> serde_json::Value {
+        let mut outputs = info
+            .output
+            .iter()
+            .filter_map(|output| self.output_name(output))
+            .collect::<Vec<_>>();
+        outputs.sort();
+        let mut workspaces = info
+            .workspace
+            .iter()
+            .filter_map(|workspace| self.workspace_state.workspace_info(workspace))
+            .map(|workspace| workspace.name.as_str())
+            .collect::<Vec<_>>();
+        workspaces.sort();
+        let geometry = info
+            .geometry
+            .iter()
+            .filter_map(|(output, geometry)| {
+                let value = json!({
+                    "x": geometry.x,
+                    "y": geometry.y,
+                    "width": geometry.width,
+                    "height": geometry.height,
+                });
+                Some((self.output_name(output)?, value))
+            })
+            .collect::<serde_json::Map<_, _>>();
+        json!({
+            "identifier": info.identifier,
+            "app_id": info.app_id,
+            "title": info.title,
+            "state": self.states(info),
+            "outputs": outputs,
+            "workspaces": workspaces,
+            "geometry": geometry,
+        })
+    }
+
+    fn watch_event(
+        &mut self,
+        event: &str,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        let (Some(watch), Some(info)) = (&self.watch, self.toplevel_info_state.info(toplevel))
+        else {
+            return;
+        };
+        let line = if watch.json {
+            json!({ "event": event, "toplevel": self.to_json(info) }).to_string()
+        } else {
+            format!("{}\t{}", event, self.to_line(info))
+        };
+        self.watch.as_mut().unwrap().lines.push(line);
+    }
+
+    fn select(&self, target: &Target) -> Result<Vec<&ToplevelInfo>, Error> {
+        let toplevels = self
+            .toplevel_info_state
+            .toplevels()
+            .filter(|info| target.select.matches(info))
+            .collect::<Vec<_>>();
+        match toplevels.len() {
+            0 => Err(Error::NoMatch("window".to_string())),
+            1 => Ok(toplevels),
+            _ if target.all => Ok(toplevels),
+            _ => Err(Error::Ambiguous("window".to_string())),
+        }
+    }
+
+    fn manager(
+        &self,
+        capability: Capability,
+    ) -> Result<&zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, Error> {
+        let manager = self
+            .toplevel_manager_state
+            .as_ref()
+            .and_then(|state| state.manager())
+            .ok_or(Error::MissingGlobal("zcosmic_toplevel_manager_v1"))?;
+        if !self.capabilities.contains(&WEnum::Value(capability)) {
+            return Err(Error::Unsupported(match capability {
+                Capability::Close => "close",
+                Capability::Activate => "activate",
+                Capability::Maximize => "maximize",
+                Capability::Minimize => "minimize",
+                Capability::Fullscreen => "fullscreen",
+                Capability::MoveToWorkspace => "move_to_workspace",
+                Capability::Sticky => "sticky",
+                Capability::MoveToExtWorkspace => "move_to_ext_workspace",
+                _ => "capability",
+            }));
+        }
+        Ok(manager)
+    }
+
+    /// Send a request for each selected window, if the compositor has the capability
+    fn apply(
+        &self,
+        target: &Target,
+        capability: Capability,
+        request: impl Fn(
+            &zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1,
+            &zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1,
+        ),
+    ) -> Result<(), Error> {
+        self.apply_resolved(
+            target,
+            capability,
+            |_| Ok(()),
+            |manager, toplevel, ()| request(manager, toplevel),
+        )
+    }
+
+    /// Like [`Self::apply`], with arguments resolved for each window by `resolve`
+    ///
+    /// Every window is resolved before any request is sent, so nothing is changed if one of
+    /// them fails.
+    fn apply_resolved<T>(
+        &self,
+        target: &Target,
+        capability: Capability,
+        resolve: impl Fn(&ToplevelInfo) -> Result<T, Error>,
+        request: impl Fn(
+            &zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1,
+            &zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1,
+            T,
+        ),
+    ) -> Result<(), Error> {
+        let manager = self.manager(capability)?;
+        let resolved = self
+            .select(target)?
+            .into_iter()
+            .map(|info| {
+                let cosmic_toplevel = info
+                    .cosmic_toplevel
+                    .as_ref()
+                    .ok_or(Error::MissingGlobal("zcosmic_toplevel_info_v1"))?;
+                Ok((cosmic_toplevel, resolve(info)?))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        for (cosmic_toplevel, args) in resolved {
+            request(manager, cosmic_toplevel, args);
+        }
+        Ok(())
+    }
+
+    /// Find the workspace named `name` a window can move to, and the output to move it to
+    ///
+    /// If several workspaces have the name, the one on an output the window is on is used.
+    fn workspace(
+        &self,
+        info: &ToplevelInfo,
+        name: &str,
+        output: Option<&wl_output::WlOutput>,
+    ) -> Result<
+        (
+            ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+            wl_output::WlOutput,
+        ),
+        Error,
+    > {
+        let mut candidates = self
+            .workspace_state
+            .workspaces()
+            .filter(|workspace| workspace.name == name)
+            .map(|workspace| {
+                let outputs = self
+                    .workspace_state
+                    .workspace_groups()
+                    .find(|group| group.workspaces.contains(&workspace.handle))
+                    .map_or(&[][..], |group| &group.outputs[..]);
+                (&workspace.handle, outputs)
+            })
+            .filter(|(_, outputs)| output.is_none_or(|output| outputs.contains(output)))
+            .collect::<Vec<_>>();
+        if candidates.len() > 1 {
+            candidates.retain(|(_, outputs)| outputs.iter().any(|o| info.output.contains(o)));
+        }
+        let (workspace, outputs) = match candidates[..] {
+            [] => return Err(Error::NoMatch(format!("workspace named {:?}", name))),
+            [candidate] => candidate,
+            _ => return Err(Error::Ambiguous(format!("workspace named {:?}", name))),
+        };
+        let output = output
+            .or(outputs.first())
+            .or(info.output.iter().next())
+            .ok_or_else(|| Error::NoMatch(format!("output of workspace {:?}", name)))?;
+        Ok((workspace.clone(), output.clone()))
+    }
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(OutputState);
+}
+
+impl OutputHandler for AppData {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl ToplevelInfoHandler for AppData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        &mut self.toplevel_info_state
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.watch_event("opened", toplevel);
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.watch_event("changed", toplevel);
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.watch_event("closed", toplevel);
+    }
+
+    fn finished(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>) {
+        if let Some(watch) = &mut self.watch {
+            watch.finished = true;
+        }
+    }
+}
+
+impl ToplevelManagerHandler for AppData {
+    fn toplevel_manager_state(&mut self) -> &mut ToplevelManagerState {
+        self.toplevel_manager_state.as_mut().unwrap()
+    }
+
+    fn capabilities(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        capabilities: Vec<WEnum<Capability>>,
+    ) {
+        self.capabilities = capabilities;
+    }
+}
+
+impl WorkspaceHandler for AppData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+
+    fn done(&mut self) {}
+}
+
+sctk::delegate_registry!(AppData);
+sctk::delegate_output!(AppData);
+cosmic_client_toolkit::delegate_toplevel_info!(AppData);
+cosmic_client_toolkit::delegate_toplevel_manager!(AppData);
+cosmic_client_toolkit::delegate_workspace!(AppData);
+wayland_client::delegate_noop!(AppData: ignore wl_seat::WlSeat);
+
+fn connect(conn: &Connection) -> Result<(EventQueue<AppData>, AppData), Error> {
+    let (globals, mut event_queue) = registry_queue_init(conn)?;
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let toplevel_info_state = ToplevelInfoState::try_new(&registry_state, &qh)
+        .ok_or(Error::MissingGlobal("ext_foreign_toplevel_list_v1"))?;
+    let mut app_data = AppData {
+        output_state: OutputState::new(&globals, &qh),
+        toplevel_manager_state: ToplevelManagerState::try_new(&registry_state, &qh),
+        workspace_state: WorkspaceState::new(&registry_state, &qh),
+        toplevel_info_state,
+        registry_state,
+        capabilities: Vec::new(),
+        seat: globals.bind(&qh, 1..=1, ()).ok(),
+        watch: None,
+    };
+    // Extension objects are requested in response to events, so their initial state needs a
+    // second roundtrip
+    event_queue.roundtrip(&mut app_data)?;
+    event_queue.roundtrip(&mut app_data)?;
+    Ok((event_queue, app_data))
+}
+
+pub fn run(conn: &Connection, args: &Args, out: &mut dyn Write) -> Result<(), Error> {
+    let (mut event_queue, mut app_data) = connect(conn)?;
+    let app_data = &mut app_data;
+    match &args.command {
+        Command::List { json, select } => {
+            let toplevels = app_data
+                .toplevel_info_state
+                .toplevels()
+                .filter(|info| select.matches(info));
+            if *json {
+                let list = toplevels
+                    .map(|info| app_data.to_json(info))
+                    .collect::<Vec<_>>();
+                writeln!(out, "{:#}", serde_json::Value::Array(list))?;
+            } else {
+                for info in toplevels {
+                    writeln!(out, "{}", app_data.to_line(info))?;
+                }
+            }
+        }
+        Command::Watch { json } => {
+            app_data.watch = Some(Watch {
+                json: *json,
+                lines: Vec::new(),
+                finished: false,
+            });
+            let toplevels = app_data
+                .toplevel_info_state
+                .toplevels()
+                .map(|info| info.foreign_toplevel.clone())
+                .collect::<Vec<_>>();
+            for toplevel in &toplevels {
+                app_data.watch_event("opened", toplevel);
+            }
+            loop {
+                let watch = app_data.watch.as_mut().unwrap();
+                for line in watch.lines.drain(..) {
+                    writeln!(out, "{}", line)?;
+                }
+                out.flush()?;
+                if watch.finished {
+                    break;
+                }
+                event_queue.blocking_dispatch(app_data)?;
+            }
+        }
+        Command::Activate { target } => {
+            let seat = app_data
+                .seat
+                .as_ref()
+                .ok_or(Error::MissingGlobal("wl_seat"))?;
+            app_data.apply(target, Capability::Activate, |manager, toplevel| {
+                manager.activate(toplevel, seat);
+            })?;
+        }
+        Command::Close { target } => {
+            app_data.apply(target, Capability::Close, |manager, toplevel| {
+                manager.close(toplevel);
+            })?;
+        }
+        Command::Minimize { target, unset } => {
+            app_data.apply(target, Capability::Minimize, |manager, toplevel| {
+                if *unset {
+                    manager.unset_minimized(toplevel);
+                } else {
+                    manager.set_minimized(toplevel);
+                }
+            })?;
+        }
+        Command::Fullscreen {
+            target,
+            output,
+            unset,
+        } => {
+            let output = output
+                .as_deref()
+                .map(|name| app_data.output(name))
+                .transpose()?;
+            app_data.apply(target, Capability::Fullscreen, |manager, toplevel| {
+                if *unset {
+                    manager.unset_fullscreen(toplevel);
+                } else {
+                    manager.set_fullscreen(toplevel, output.as_ref());
+                }
+            })?;
+        }
+        Command::MoveToWorkspace {
+            target,
+            workspace,
+            output,
+        } => {
+            let output = output
+                .as_deref()
+                .map(|name| app_data.output(name))
+                .transpose()?;
+            app_data.apply_resolved(
+                target,
+                Capability::MoveToExtWorkspace,
+                |info| app_data.workspace(info, workspace, output.as_ref()),
+                |manager, toplevel, (workspace, output)| {
+                    manager.move_to_ext_workspace(toplevel, &workspace, &output);
+                },
+            )?;
+        }
+        Command::Sticky { target, unset } => {
+            app_data.apply(target, Capability::Sticky, |manager, toplevel| {
+                if *unset {
+                    manager.unset_sticky(toplevel);
+                } else {
+                    manager.set_sticky(toplevel);
+                }
+            })?;
+        }
+    }
+    // Wait for the compositor to process the requests, so protocol errors are reported
+    event_queue.roundtrip(app_data)?;
+    Ok(())
+}
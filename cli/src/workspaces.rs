@@ -0,0 +1,584 @@
+//! `cosmic-workspaces`: list, watch and manage workspaces.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use cosmic_client_toolkit::{
+    cosmic_protocols::workspace::v2::client::zcosmic_workspace_handle_v2,
+    sctk::{
+        self,
+        output::{OutputHandler, OutputState},
+        registry::{ProvidesRegistryState, RegistryState},
+    },
+    wayland_protocols::ext::workspace::v1::client::{
+        ext_workspace_group_handle_v1, ext_workspace_handle_v1, ext_workspace_manager_v1,
+    },
+    workspace::{Workspace, WorkspaceGroup, WorkspaceHandler, WorkspaceState},
+};
+use serde_json::json;
+use std::io::Write;
+use wayland_client::{
+    Connection, EventQueue, QueueHandle, WEnum, globals::registry_queue_init, protocol::wl_output,
+};
+
+use crate::{EXIT_CODES, Error};
+
+/// List, watch and manage workspaces
+#[derive(Debug, Parser)]
+#[command(name = "cosmic-workspaces", version, after_help = EXIT_CODES)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List workspaces, one per line as `outputs<TAB>name<TAB>coordinates<TAB>states`
+    List {
+        /// Print a JSON array instead
+        #[arg(long)]
+        json: bool,
+        /// Only list workspaces on this output
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Print the list of workspaces every time it changes
+    ///
+    /// Lists are separated by an empty line, or printed as a JSON array per line.
+    Watch {
+        /// Print a JSON array per line instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Activate a workspace
+    Activate {
+        #[command(flatten)]
+        select: Selector,
+    },
+    /// Create a workspace on an output
+    Create {
+        /// Name of the output
+        output: String,
+        /// Name of the new workspace
+        name: String,
+    },
+    /// Remove a workspace
+    Remove {
+        #[command(flatten)]
+        select: Selector,
+    },
+    /// Rename a workspace
+    Rename {
+        #[command(flatten)]
+        select: Selector,
+        /// New name of the workspace
+        name: String,
+    },
+    /// Pin a workspace, so it isn't removed when empty
+    Pin {
+        #[command(flatten)]
+        select: Selector,
+        /// Unpin the workspace instead
+        #[arg(long)]
+        unset: bool,
+    },
+    /// Move a workspace before another workspace
+    MoveBefore {
+        #[command(flatten)]
+        select: Selector,
+        /// Name or coordinates of the other workspace
+        other: String,
+        /// Axis of the coordinates to move along
+        #[arg(long, default_value_t = 0)]
+        axis: u32,
+    },
+    /// Move a workspace after another workspace
+    MoveAfter {
+        #[command(flatten)]
+        select: Selector,
+        /// Name or coordinates of the other workspace
+        other: String,
+        /// Axis of the coordinates to move along
+        #[arg(long, default_value_t = 0)]
+        axis: u32,
+    },
+    /// Enable or disable tiling on a workspace
+    Tiling {
+        #[command(flatten)]
+        select: Selector,
+        state: Toggle,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Toggle {
+    On,
+    Off,
+}
+
+/// Selection of a single workspace
+#[derive(Clone, Debug, clap::Args)]
+pub struct Selector {
+    /// Name of the workspace, or its coordinates separated by commas if no workspace has the
+    /// name
+    pub workspace: String,
+    /// Select the workspace on this output, if workspaces on several outputs match
+    #[arg(long)]
+    pub output: Option<String>,
+}
+
+fn parse_coordinates(s: &str) -> Option<Vec<u32>> {
+    s.split(',').map(|c| c.trim().parse().ok()).collect()
+}
+
+impl Selector {
+    pub(crate) fn select<'a>(
+        &self,
+        output_state: &OutputState,
+        workspace_state: &'a WorkspaceState,
+    ) -> Result<&'a Workspace, Error> {
+        let output = self
+            .output
+            .as_deref()
+            .map(|name| crate::find_output(output_state, name))
+            .transpose()?;
+        find(workspace_state, &self.workspace, output.as_ref())
+    }
+}
+
+fn group<'a>(
+    workspace_state: &'a WorkspaceState,
+    workspace: &Workspace,
+) -> Option<&'a WorkspaceGroup> {
+    workspace_state
+        .workspace_groups()
+        .find(|group| group.workspaces.contains(&workspace.handle))
+}
+
+/// Find the workspace named `name`, or at the coordinates it describes
+fn find<'a>(
+    workspace_state: &'a WorkspaceState,
+    name: &str,
+    output: Option<&wl_output::WlOutput>,
+) -> Result<&'a Workspace, Error> {
+    let on_output = |workspace: &&Workspace| {
+        output.is_none_or(|output| {
+            group(workspace_state, workspace).is_some_and(|group| group.outputs.contains(output))
+        })
+    };
+    let mut workspaces = workspace_state
+        .workspaces()
+        .filter(|workspace| workspace.name == name)
+        .filter(on_output)
+        .collect::<Vec<_>>();
+    if let (true, Some(coordinates)) = (workspaces.is_empty(), parse_coordinates(name)) {
+        workspaces = workspace_state
+            .workspaces()
+            .filter(|workspace| workspace.coordinates == coordinates)
+            .filter(on_output)
+            .collect();
+    }
+    match workspaces[..] {
+        [] => Err(Error::NoMatch(format!("workspace {:?}", name))),
+        [workspace] => Ok(workspace),
+        _ => Err(Error::Ambiguous(format!(
+            "workspace {:?}; select one with --output",
+            name
+        ))),
+    }
+}
+
+struct AppData {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    workspace_state: WorkspaceState,
+    // Lists printed by `watch`
+    watch: Option<(bool, Vec<String>)>,
+}
+
+impl AppData {
+    fn output_name(&self, output: &wl_output::WlOutput) -> Option<String> {
+        self.output_state.info(output)?.name
+    }
+
+    fn output(&self, name: &str) -> Result<wl_output::WlOutput, Error> {
+        crate::find_output(&self.output_state, name)
+    }
+
+    fn group(&self, workspace: &Workspace) -> Option<&WorkspaceGroup> {
+        group(&self.workspace_state, workspace)
+    }
+
+    fn outputs(&self, workspace: &Workspace) -> Vec<String> {
+        let mut outputs = self
+            .group(workspace)
+            .into_iter()
+            .flat_map(|group| &group.outputs)
+            .filter_map(|output| self.output_name(output))
+            .collect::<Vec<_>>();
+        outputs.sort();
+        outputs
+    }
+
+    /// Workspaces ordered by group, then by coordinates
+    fn workspaces(&self) -> Vec<&Workspace> {
+        let groups = self.workspace_state.workspace_groups().collect::<Vec<_>>();
+        let mut workspaces = self.workspace_state.workspaces().collect::<Vec<_>>();
+        workspaces.sort_by_key(|workspace| {
+            let group = groups
+                .iter()
+                .position(|group| group.workspaces.contains(&workspace.handle));
+            (group.unwrap_or(groups.len()), workspace.coordinates.clone())
+        });
+        workspaces
+    }
+
+    fn states(&self, workspace: &Workspace) -> Vec<&'static str> {
+        let mut states = Vec::new();
+        for (state, name) in [
+            (ext_workspace_handle_v1::State::Active, "active"),
+            (ext_workspace_handle_v1::State::Urgent, "urgent"),
+            (ext_workspace_handle_v1::State::Hidden, "hidden"),
+        ] {
+            if workspace.state.contains(state) {
+                states.push(name);
+            }
+        }
+        if workspace
+            .cosmic_state
+            .contains(zcosmic_workspace_handle_v2::State::Pinned)
+        {
+            states.push("pinned");
+        }
+        if workspace.tiling
+            == Some(WEnum::Value(
+                zcosmic_workspace_handle_v2::TilingState::TilingEnabled,
+            ))
+        {
+            states.push("tiling");
+        }
+        states
+    }
+
+    fn to_line(&self, workspace: &Workspace) -> String {
+        let coordinates = workspace
+            .coordinates
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.outputs(workspace).join(","),
+            workspace.name,
+            coordinates.join(","),
+            self.states(workspace).join(",")
+        )
+    }
+
+    fn to_json(&self, workspace: &Workspace) -> serde_json::Value {
+        let tiling = workspace.tiling.map(|tiling| {
+            tiling == WEnum::Value(zcosmic_workspace_handle_v2::TilingState::TilingEnabled)
+        });
+        json!({
+            "id": workspace.id,
+            "name": workspace.name,
+            "outputs": self.outputs(workspace),
+            "coordinates": workspace.coordinates,
+            "state": self.states(workspace),
+            "tiling": tiling,
+        })
+    }
+
+    fn print(
+        &self,
+        json: bool,
+        workspaces: &[&Workspace],
+        out: &mut dyn Write,
+    ) -> Result<(), Error> {
+        if json {
+            let list = workspaces
+                .iter()
+                .map(|workspace| self.to_json(workspace))
+                .collect::<Vec<_>>();
+            writeln!(out, "{:#}", serde_json::Value::Array(list))?;
+        } else {
+            for workspace in workspaces {
+                writeln!(out, "{}", self.to_line(workspace))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn find(&self, name: &str, output: Option<&wl_output::WlOutput>) -> Result<&Workspace, Error> {
+        find(&self.workspace_state, name, output)
+    }
+
+    fn select(&self, select: &Selector) -> Result<&Workspace, Error> {
+        select.select(&self.output_state, &self.workspace_state)
+    }
+
+    /// Workspace named or at `other`, preferably in the group of `workspace`
+    fn other(&self, workspace: &Workspace, other: &str) -> Result<&Workspace, Error> {
+        let output = self
+            .group(workspace)
+            .and_then(|group| group.outputs.first());
+        self.find(other, output).or_else(|_| self.find(other, None))
+    }
+
+    fn manager(&self) -> &ext_workspace_manager_v1::ExtWorkspaceManagerV1 {
+        // Checked on connect
+        self.workspace_state.workspace_manager().get().unwrap()
+    }
+}
+
+fn require(
+    workspace: &Workspace,
+    capability: ext_workspace_handle_v1::WorkspaceCapabilities,
+    name: &'static str,
+) -> Result<(), Error> {
+    if workspace.capabilities.contains(capability) {
+        Ok(())
+    } else {
+        Err(Error::Unsupported(name))
+    }
+}
+
+/// `zcosmic_workspace_handle_v2` of a workspace, if the compositor has the capability
+fn cosmic_handle<'a>(
+    workspace: &'a Workspace,
+    capability: zcosmic_workspace_handle_v2::WorkspaceCapabilities,
+    name: &'static str,
+) -> Result<&'a zcosmic_workspace_handle_v2::ZcosmicWorkspaceHandleV2, Error> {
+    let handle = workspace
+        .cosmic_handle
+        .as_ref()
+        .ok_or(Error::MissingGlobal("zcosmic_workspace_manager_v2"))?;
+    if !workspace.has_cosmic_capability(capability) {
+        return Err(Error::Unsupported(name));
+    }
+    Ok(handle)
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(OutputState);
+}
+
+impl OutputHandler for AppData {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl WorkspaceHandler for AppData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+
+    fn done(&mut self) {
+        let Some((json, _)) = self.watch else {
+            return;
+        };
+        let mut list = Vec::new();
+        // Writing to a `Vec` can't fail
+        self.print(json, &self.workspaces(), &mut list).unwrap();
+        self.watch
+            .as_mut()
+            .unwrap()
+            .1
+            .push(String::from_utf8(list).unwrap());
+    }
+}
+
+sctk::delegate_registry!(AppData);
+sctk::delegate_output!(AppData);
+cosmic_client_toolkit::delegate_workspace!(AppData);
+
+fn connect(conn: &Connection) -> Result<(EventQueue<AppData>, AppData), Error> {
+    let (globals, mut event_queue) = registry_queue_init(conn)?;
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let mut app_data = AppData {
+        output_state: OutputState::new(&globals, &qh),
+        workspace_state: WorkspaceState::new(&registry_state, &qh),
+        registry_state,
+        watch: None,
+    };
+    if app_data.workspace_state.workspace_manager().get().is_err() {
+        return Err(Error::MissingGlobal("ext_workspace_manager_v1"));
+    }
+    // Extension objects are requested in response to events, so their initial state needs a
+    // second roundtrip
+    event_queue.roundtrip(&mut app_data)?;
+    event_queue.roundtrip(&mut app_data)?;
+    Ok((event_queue, app_data))
+}
+
+pub fn run(conn: &Connection, args: &Args, out: &mut dyn Write) -> Result<(), Error> {
+    let (mut event_queue, mut app_data) = connect(conn)?;
+    let app_data = &mut app_data;
+    match &args.command {
+        Command::List { json, output } => {
+            let output = output
+                .as_deref()
+                .map(|name| app_data.output(name))
+                .transpose()?;
+            let workspaces = app_data
+                .workspaces()
+                .into_iter()
+                .filter(|workspace| {
+                    output.as_ref().is_none_or(|output| {
+                        app_data
+                            .group(workspace)
+                            .is_some_and(|group| group.outputs.contains(output))
+                    })
+                })
+                .collect::<Vec<_>>();
+            app_data.print(*json, &workspaces, out)?;
+            return Ok(());
+        }
+        Command::Watch { json } => {
+            let workspaces = app_data.workspaces();
+            app_data.print(*json, &workspaces, out)?;
+            app_data.watch = Some((*json, Vec::new()));
+            // Until the compositor sends `finished`
+            while app_data.workspace_state.workspace_manager().get().is_ok() {
+                out.flush()?;
+                event_queue.blocking_dispatch(app_data)?;
+                for list in app_data.watch.as_mut().unwrap().1.drain(..) {
+                    if !*json {
+                        writeln!(out)?;
+                    }
+                    write!(out, "{}", list)?;
+                }
+            }
+            out.flush()?;
+            return Ok(());
+        }
+        Command::Activate { select } => {
+            let workspace = app_data.select(select)?;
+            require(
+                workspace,
+                ext_workspace_handle_v1::WorkspaceCapabilities::Activate,
+                "activate",
+            )?;
+            workspace.handle.activate();
+        }
+        Command::Create { output, name } => {
+            let output = app_data.output(output)?;
+            let group = app_data
+                .workspace_state
+                .workspace_groups()
+                .find(|group| group.outputs.contains(&output))
+                .ok_or_else(|| Error::NoMatch("workspace group of output".to_string()))?;
+            if !group
+                .capabilities
+                .contains(ext_workspace_group_handle_v1::GroupCapabilities::CreateWorkspace)
+            {
+                return Err(Error::Unsupported("create_workspace"));
+            }
+            group.handle.create_workspace(name.clone());
+        }
+        Command::Remove { select } => {
+            let workspace = app_data.select(select)?;
+            require(
+                workspace,
+                ext_workspace_handle_v1::WorkspaceCapabilities::Remove,
+                "remove",
+            )?;
+            workspace.handle.remove();
+        }
+        Command::Rename { select, name } => {
+            let workspace = app_data.select(select)?;
+            cosmic_handle(
+                workspace,
+                zcosmic_workspace_handle_v2::WorkspaceCapabilities::Rename,
+                "rename",
+            )?
+            .rename(name.clone());
+        }
+        Command::Pin { select, unset } => {
+            let workspace = app_data.select(select)?;
+            let handle = cosmic_handle(
+                workspace,
+                zcosmic_workspace_handle_v2::WorkspaceCapabilities::Pin,
+                "pin",
+            )?;
+            if *unset {
+                handle.unpin();
+            } else {
+                handle.pin();
+            }
+        }
+        Command::MoveBefore {
+            select,
+            other,
+            axis,
+        } => {
+            let workspace = app_data.select(select)?;
+            let other = app_data.other(workspace, other)?;
+            cosmic_handle(
+                workspace,
+                zcosmic_workspace_handle_v2::WorkspaceCapabilities::Move,
+                "move",
+            )?
+            .move_before(&other.handle, *axis);
+        }
+        Command::MoveAfter {
+            select,
+            other,
+            axis,
+        } => {
+            let workspace = app_data.select(select)?;
+            let other = app_data.other(workspace, other)?;
+            cosmic_handle(
+                workspace,
+                zcosmic_workspace_handle_v2::WorkspaceCapabilities::Move,
+                "move",
+            )?
+            .move_after(&other.handle, *axis);
+        }
+        Command::Tiling { select, state } => {
+            let workspace = app_data.select(select)?;
+            let state = match state {
+                Toggle::On => zcosmic_workspace_handle_v2::TilingState::TilingEnabled,
+                Toggle::Off => zcosmic_workspace_handle_v2::TilingState::FloatingOnly,
+            };
+            cosmic_handle(
+                workspace,
+                zcosmic_workspace_handle_v2::WorkspaceCapabilities::SetTilingState,
+                "set_tiling_state",
+            )?
+            .set_tiling_state(state);
+        }
+    }
+    app_data.manager().commit();
+    // Wait for the compositor to process the requests, so protocol errors are reported
+    event_queue.roundtrip(app_data)?;
+    Ok(())
+}
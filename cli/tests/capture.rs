@@ -0,0 +1,151 @@
+use clap::Parser;
+use cosmic_mock_compositor::{FrameContent, Global, MockCompositor};
+use cosmic_protocols_cli::{Error, capture};
+use std::{thread, time::Duration};
+
+// Run `cosmic-capture`, completing the frames it captures with the given colors in order
+fn run(mock: &MockCompositor, args: &[&str], colors: &[[u8; 4]]) -> Result<Vec<u8>, Error> {
+    let args = capture::Args::try_parse_from(["cosmic-capture"].iter().chain(args)).unwrap();
+    let conn = mock.connect();
+    let thread = thread::spawn(move || {
+        let mut out = Vec::new();
+        capture::run(&conn, &args, &mut out).map(|()| out)
+    });
+    for color in colors {
+        let frame = loop {
+            if let Some(frame) = mock.with(|c| c.pending_frames().next()) {
+                break frame;
+            }
+            assert!(!thread.is_finished(), "capture ended before all frames");
+            thread::sleep(Duration::from_millis(5));
+        };
+        mock.with(|c| {
+            c.ready_frame(
+                frame,
+                &FrameContent {
+                    color: *color,
+                    ..Default::default()
+                },
+            )
+        });
+    }
+    thread.join().unwrap()
+}
+
+fn decode_png(data: &[u8]) -> (u32, u32, Vec<u8>) {
+    let mut reader = png::Decoder::new(std::io::Cursor::new(data))
+        .read_info()
+        .unwrap();
+    let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    (info.width, info.height, buf)
+}
+
+#[test]
+fn output() {
+    let mock = MockCompositor::new();
+    mock.with(|c| c.add_output("DP-1", (0, 0), (64, 48), 1));
+
+    let png = run(&mock, &["output"], &[[255, 0, 0, 255]]).unwrap();
+    let (width, height, data) = decode_png(&png);
+    assert_eq!((width, height), (64, 48));
+    assert!(data.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]));
+
+    // Each frame is written as its own image
+    let ppm = run(
+        &mock,
+        &["output", "DP-1", "--format", "ppm", "--frames", "2"],
+        &[[0, 0, 255, 255], [0, 255, 0, 255]],
+    )
+    .unwrap();
+    let header = b"P6\n64 48\n255\n";
+    let size = header.len() + 64 * 48 * 3;
+    assert_eq!(ppm.len(), 2 * size);
+    assert!(ppm.starts_with(header));
+    assert_eq!(&ppm[header.len()..header.len() + 3], [0, 0, 255]);
+    assert_eq!(
+        &ppm[size + header.len()..size + header.len() + 3],
+        [0, 255, 0]
+    );
+
+    assert_eq!(
+        run(&mock, &["output", "DP-2"], &[])
+            .unwrap_err()
+            .exit_code(),
+        3
+    );
+}
+
+#[test]
+fn cursor() {
+    let mock = MockCompositor::new();
+    mock.with(|c| c.add_output("DP-1", (0, 0), (64, 48), 1));
+
+    let raw = run(
+        &mock,
+        &["output", "--cursor", "--format", "raw"],
+        &[[1, 2, 3, 4]],
+    )
+    .unwrap();
+    assert_eq!(raw.len(), 24 * 24 * 4);
+    assert_eq!(raw[..4], [1, 2, 3, 4]);
+
+    assert!(
+        capture::Args::try_parse_from(["cosmic-capture", "output", "--cursor", "--paint-cursor"])
+            .is_err()
+    );
+}
+
+#[test]
+fn region() {
+    let mock = MockCompositor::new();
+    mock.with(|c| {
+        c.add_output("DP-1", (0, 0), (64, 48), 1);
+        c.add_output("DP-2", (64, 0), (64, 48), 1);
+    });
+
+    let png = run(
+        &mock,
+        &["region", "32,0 64x16"],
+        &[[255, 255, 255, 255], [255, 255, 255, 255]],
+    )
+    .unwrap();
+    let (width, height, data) = decode_png(&png);
+    assert_eq!((width, height), (64, 16));
+    assert!(data.chunks_exact(4).all(|pixel| pixel == [255; 4]));
+
+    assert_eq!(
+        run(&mock, &["region", "200,200 10x10"], &[])
+            .unwrap_err()
+            .exit_code(),
+        3
+    );
+
+    // Outputs can't be captured without their capture source manager
+    mock.with(|c| c.remove_global(Global::OutputImageCaptureSourceManager));
+    assert_eq!(
+        run(&mock, &["region", "32,0 64x16"], &[])
+            .unwrap_err()
+            .exit_code(),
+        5
+    );
+}
+
+#[test]
+fn toplevel() {
+    let mock = MockCompositor::new();
+    mock.with(|c| {
+        c.new_toplevel("org.example.App", "Example");
+        c.new_toplevel("org.example.Other", "Other");
+    });
+
+    let png = run(
+        &mock,
+        &["toplevel", "--app-id", "org.example.App"],
+        &[[0, 0, 0, 255]],
+    )
+    .unwrap();
+    assert!(png.starts_with(b"\x89PNG"));
+
+    assert_eq!(run(&mock, &["toplevel"], &[]).unwrap_err().exit_code(), 4);
+}
@@ -0,0 +1,22 @@
+use std::{
+    io::{self, Write},
+    sync::mpsc,
+};
+
+/// Output of a command running on another thread, sent line by line
+pub struct Lines(pub mpsc::Sender<String>, pub Vec<u8>);
+
+impl Write for Lines {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.1.extend_from_slice(buf);
+        while let Some(end) = self.1.iter().position(|b| *b == b'\n') {
+            let line = self.1.drain(..=end).collect::<Vec<_>>();
+            let _ = self.0.send(String::from_utf8(line).unwrap());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
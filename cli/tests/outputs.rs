@@ -0,0 +1,231 @@
+use clap::Parser;
+use cosmic_mock_compositor::{
+    Global, MockCompositor, OutputId, OutputSnapshot, Request,
+    cosmic_protocols::{
+        output_management::v1::server::zcosmic_output_head_v1::AdaptiveSyncStateExt,
+        server::output_management::{
+            HeadConfiguration, ModeConfiguration, OutputConfigurationRequest, OutputMode,
+        },
+    },
+};
+use cosmic_protocols_cli::{Error, outputs};
+
+fn run(mock: &MockCompositor, args: &[&str]) -> Result<String, Error> {
+    let args = outputs::Args::try_parse_from(["cosmic-outputs"].iter().chain(args)).unwrap();
+    let mut out = Vec::new();
+    outputs::run(&mock.connect(), &args, &mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+fn exit_code(result: Result<String, Error>) -> u8 {
+    result.unwrap_err().exit_code()
+}
+
+const MODES: [OutputMode; 2] = [
+    OutputMode {
+        width: 1920,
+        height: 1080,
+        refresh: 60000,
+        preferred: true,
+    },
+    OutputMode {
+        width: 1280,
+        height: 720,
+        refresh: 59940,
+        preferred: false,
+    },
+];
+
+// Enabled "DP-1" at 1920x1080, mirrored by "DP-2", and disabled "HDMI-A-1"
+fn heads(mock: &MockCompositor) -> [OutputId; 3] {
+    mock.with(|c| {
+        let ids = [
+            c.add_output("DP-1", (0, 0), (1920, 1080), 1),
+            c.add_output("DP-2", (0, 0), (1920, 1080), 1),
+            c.add_output("HDMI-A-1", (0, 0), (1920, 1080), 1),
+        ];
+        c.set_output_heads([
+            (
+                ids[0],
+                OutputSnapshot {
+                    name: "DP-1".to_string(),
+                    modes: MODES.to_vec(),
+                    enabled: true,
+                    current_mode: Some(0),
+                    scale: 1.5,
+                    adaptive_sync: AdaptiveSyncStateExt::Automatic,
+                    xwayland_primary: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                ids[1],
+                OutputSnapshot {
+                    name: "DP-2".to_string(),
+                    modes: MODES.to_vec(),
+                    enabled: true,
+                    current_mode: Some(0),
+                    mirroring: Some("DP-1".to_string()),
+                    ..Default::default()
+                },
+            ),
+            (
+                ids[2],
+                OutputSnapshot {
+                    name: "HDMI-A-1".to_string(),
+                    modes: MODES.to_vec(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        ids
+    })
+}
+
+#[test]
+fn list() {
+    let mock = MockCompositor::new();
+    heads(&mock);
+
+    let lines = run(&mock, &["list"]).unwrap();
+    assert_eq!(
+        lines.lines().collect::<Vec<_>>(),
+        [
+            "DP-1\tenabled\t1920x1080@60\t0,0\t1.5\tnormal\txwayland-primary,adaptive-sync",
+            "DP-2\tenabled\t1920x1080@60\t0,0\t1\tnormal\tmirroring=DP-1",
+            "HDMI-A-1\tdisabled\t-\t-\t-\t-\t",
+        ]
+    );
+
+    let json = run(&mock, &["list", "--json"]).unwrap();
+    let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 3);
+    assert_eq!(
+        json[0],
+        serde_json::json!({
+            "name": "DP-1",
+            "description": "",
+            "make": null,
+            "model": null,
+            "serial_number": null,
+            "physical_size": [0, 0],
+            "enabled": true,
+            "modes": [
+                {"width": 1920, "height": 1080, "refresh": 60000, "preferred": true, "current": true},
+                {"width": 1280, "height": 720, "refresh": 59940, "preferred": false, "current": false},
+            ],
+            "position": [0, 0],
+            "scale": 1.5,
+            "transform": "normal",
+            "adaptive_sync": "automatic",
+            "adaptive_sync_available": "unsupported",
+            "mirroring": null,
+            "xwayland_primary": true,
+        })
+    );
+}
+
+#[test]
+fn configure() {
+    let mock = MockCompositor::new();
+    let [dp1, dp2, hdmi] = heads(&mock);
+
+    run(
+        &mock,
+        &[
+            "configure",
+            "DP-1",
+            "--mode",
+            "1280x720@59.94",
+            "--position",
+            "1920,0",
+            "--scale",
+            "1.25",
+            "--adaptive-sync",
+            "always",
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [Request::ConfigureOutputs(OutputConfigurationRequest {
+            test_only: false,
+            heads: vec![
+                (
+                    dp1,
+                    Some(HeadConfiguration {
+                        mode: Some(ModeConfiguration::Mode(MODES[1])),
+                        position: Some((1920, 0)),
+                        transform: None,
+                        scale: Some(1.25),
+                        adaptive_sync: Some(AdaptiveSyncStateExt::Always),
+                        mirroring: None,
+                    }),
+                ),
+                (
+                    dp2,
+                    Some(HeadConfiguration {
+                        mode: None,
+                        position: None,
+                        transform: None,
+                        scale: None,
+                        adaptive_sync: None,
+                        mirroring: Some(dp1),
+                    }),
+                ),
+                (hdmi, None),
+            ],
+        })]
+    );
+
+    run(&mock, &["disable", "DP-1", "--dry-run"]).unwrap();
+    let requests = mock.with(|c| c.take_requests());
+    let [Request::ConfigureOutputs(request)] = &requests[..] else {
+        panic!("unexpected requests {:?}", requests);
+    };
+    assert!(request.test_only);
+    assert_eq!(request.heads[0], (dp1, None));
+    // Stops mirroring the disabled output
+    assert_eq!(
+        request.heads[1].1.as_ref().map(|head| head.mirroring),
+        Some(None)
+    );
+
+    run(&mock, &["mirror", "HDMI-A-1", "DP-1"]).unwrap();
+    let requests = mock.with(|c| c.take_requests());
+    let [Request::ConfigureOutputs(request)] = &requests[..] else {
+        panic!("unexpected requests {:?}", requests);
+    };
+    assert_eq!(
+        request.heads[2].1.as_ref().and_then(|head| head.mirroring),
+        Some(dp1)
+    );
+
+    mock.with(|c| c.set_output_configuration_result(false));
+    assert_eq!(exit_code(run(&mock, &["enable", "HDMI-A-1"])), 6);
+    assert_eq!(exit_code(run(&mock, &["enable", "HDMI-A-2"])), 3);
+}
+
+#[test]
+fn xwayland_primary() {
+    let mock = MockCompositor::new();
+    let [_, dp2, _] = heads(&mock);
+
+    run(&mock, &["set-xwayland-primary", "DP-2"]).unwrap();
+    run(&mock, &["set-xwayland-primary"]).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [
+            Request::SetXwaylandPrimary(Some(dp2)),
+            Request::SetXwaylandPrimary(None),
+        ]
+    );
+
+    let mock = MockCompositor::with_globals(Global::ALL.iter().map(|global| match global {
+        Global::OutputManager => (*global, 2),
+        _ => (*global, global.max_version()),
+    }));
+    heads(&mock);
+    assert_eq!(exit_code(run(&mock, &["set-xwayland-primary", "DP-1"])), 5);
+    assert_eq!(run(&mock, &["list"]).unwrap().lines().count(), 3);
+}
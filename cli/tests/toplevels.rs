@@ -0,0 +1,213 @@
+use clap::Parser;
+use cosmic_mock_compositor::{
+    Global, MockCompositor, Request,
+    cosmic_protocols::toplevel_info::v1::server::zcosmic_toplevel_handle_v1::State,
+    wayland_protocols::ext::workspace::v1::server::ext_workspace_group_handle_v1::GroupCapabilities,
+};
+use cosmic_protocols_cli::{Error, toplevels};
+use std::{sync::mpsc, thread};
+
+use common::Lines;
+
+mod common;
+
+fn run(mock: &MockCompositor, args: &[&str]) -> Result<String, Error> {
+    let args = toplevels::Args::try_parse_from(["cosmic-toplevels"].iter().chain(args)).unwrap();
+    let mut out = Vec::new();
+    toplevels::run(&mock.connect(), &args, &mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+fn exit_code(result: Result<String, Error>) -> u8 {
+    result.unwrap_err().exit_code()
+}
+
+#[test]
+fn list() {
+    let mock = MockCompositor::new();
+    let identifier = mock.with(|c| {
+        let output = c.add_output("DP-1", (0, 0), (1920, 1080), 1);
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        c.workspace_group_enter_output(group, output);
+        let workspace = c.new_workspace(Some(group), "1");
+        let toplevel = c.new_toplevel("org.example.App", "Example");
+        c.set_toplevel_state(toplevel, &[State::Activated, State::Maximized]);
+        c.toplevel_enter_output(toplevel, output);
+        c.toplevel_enter_workspace(toplevel, workspace);
+        c.new_toplevel("org.example.Other", "Other");
+        c.toplevel_identifier(toplevel).unwrap().to_string()
+    });
+
+    let lines = run(&mock, &["list"]).unwrap();
+    let lines = lines.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines.contains(&&*format!(
+        "{}\torg.example.App\tExample\tmaximized,activated",
+        identifier
+    )));
+
+    let json = run(&mock, &["list", "--json", "--title", "^Ex"]).unwrap();
+    let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([{
+            "identifier": identifier,
+            "app_id": "org.example.App",
+            "title": "Example",
+            "state": ["maximized", "activated"],
+            "outputs": ["DP-1"],
+            "workspaces": ["1"],
+            "geometry": {},
+        }])
+    );
+}
+
+#[test]
+fn watch() {
+    let mock = MockCompositor::new();
+    let toplevel = mock.with(|c| c.new_toplevel("org.example.App", "Example"));
+    let identifier = mock.with(|c| c.toplevel_identifier(toplevel).unwrap().to_string());
+
+    let (sender, receiver) = mpsc::channel();
+    let conn = mock.connect();
+    let watch = thread::spawn(move || {
+        let args = toplevels::Args::try_parse_from(["cosmic-toplevels", "watch"]).unwrap();
+        toplevels::run(&conn, &args, &mut Lines(sender, Vec::new()))
+    });
+    let next_line = || receiver.recv().unwrap();
+    assert_eq!(
+        next_line(),
+        format!("opened\t{}\torg.example.App\tExample\t\n", identifier)
+    );
+
+    mock.with(|c| {
+        c.set_toplevel_title(toplevel, "Renamed");
+        c.toplevel_done(toplevel);
+    });
+    assert_eq!(
+        next_line(),
+        format!("changed\t{}\torg.example.App\tRenamed\t\n", identifier)
+    );
+
+    mock.with(|c| c.close_toplevel(toplevel));
+    assert_eq!(
+        next_line(),
+        format!("closed\t{}\torg.example.App\tRenamed\t\n", identifier)
+    );
+
+    // The command exits once the compositor stops sending events
+    mock.with(|c| c.toplevel_list_finished());
+    watch.join().unwrap().unwrap();
+}
+
+#[test]
+fn selection() {
+    let mock = MockCompositor::new();
+    let (first, second) = mock.with(|c| {
+        (
+            c.new_toplevel("org.example.App", "First"),
+            c.new_toplevel("org.example.App", "Second"),
+        )
+    });
+
+    run(&mock, &["close", "--title", "Sec"]).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [Request::CloseToplevel(second)]
+    );
+
+    let identifier = mock.with(|c| c.toplevel_identifier(first).unwrap().to_string());
+    run(&mock, &["minimize", "--identifier", &identifier]).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [Request::SetMinimized(first)]
+    );
+
+    assert_eq!(
+        exit_code(run(&mock, &["close", "--app-id", "org.example.App"])),
+        4
+    );
+    assert_eq!(
+        exit_code(run(&mock, &["close", "--app-id", "org.example.None"])),
+        3
+    );
+    assert!(mock.with(|c| c.take_requests()).is_empty());
+
+    run(&mock, &["sticky", "--app-id", "org.example.App", "--all"]).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [Request::SetSticky(first), Request::SetSticky(second)]
+    );
+}
+
+#[test]
+fn move_to_workspace() {
+    let mock = MockCompositor::new();
+    let (toplevel, workspace, output) = mock.with(|c| {
+        // Each output has a workspace named "2"
+        let mut workspaces = Vec::new();
+        let mut outputs = Vec::new();
+        for (i, name) in ["DP-1", "DP-2"].into_iter().enumerate() {
+            let output = c.add_output(name, (i as i32 * 1920, 0), (1920, 1080), 1);
+            let group = c.new_workspace_group(GroupCapabilities::empty());
+            c.workspace_group_enter_output(group, output);
+            c.new_workspace(Some(group), "1");
+            workspaces.push(c.new_workspace(Some(group), "2"));
+            outputs.push(output);
+        }
+        let toplevel = c.new_toplevel("org.example.App", "Example");
+        c.toplevel_enter_output(toplevel, outputs[1]);
+        (toplevel, workspaces, outputs)
+    });
+
+    run(&mock, &["move-to-workspace", "2"]).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [Request::MoveToExtWorkspace {
+            toplevel,
+            workspace: workspace[1],
+            output: output[1],
+        }]
+    );
+
+    run(&mock, &["move-to-workspace", "2", "--output", "DP-1"]).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [Request::MoveToExtWorkspace {
+            toplevel,
+            workspace: workspace[0],
+            output: output[0],
+        }]
+    );
+
+    assert_eq!(exit_code(run(&mock, &["move-to-workspace", "3"])), 3);
+
+    // Nothing is moved if a workspace can't be found for one of the windows
+    mock.with(|c| c.new_toplevel("org.example.App", "Nowhere"));
+    assert_eq!(
+        exit_code(run(&mock, &["move-to-workspace", "2", "--all"])),
+        3
+    );
+    assert!(mock.with(|c| c.take_requests()).is_empty());
+}
+
+#[test]
+fn unsupported() {
+    let mock = MockCompositor::with_globals(Global::ALL.iter().map(|global| match global {
+        Global::ToplevelManager => (*global, 2),
+        _ => (*global, global.max_version()),
+    }));
+    mock.with(|c| c.new_toplevel("org.example.App", "Example"));
+    assert_eq!(exit_code(run(&mock, &["sticky"])), 5);
+    run(&mock, &["fullscreen"]).unwrap();
+
+    let mock = MockCompositor::with_globals(
+        Global::ALL
+            .into_iter()
+            .filter(|global| *global != Global::ToplevelManager)
+            .map(|global| (global, global.max_version())),
+    );
+    mock.with(|c| c.new_toplevel("org.example.App", "Example"));
+    assert_eq!(exit_code(run(&mock, &["close"])), 5);
+    assert_eq!(run(&mock, &["list"]).unwrap().lines().count(), 1);
+}
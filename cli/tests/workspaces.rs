@@ -0,0 +1,205 @@
+use clap::Parser;
+use cosmic_mock_compositor::wayland_server::WEnum;
+use cosmic_mock_compositor::{
+    MockCompositor, Request,
+    cosmic_protocols::workspace::v2::server::zcosmic_workspace_handle_v2::{self, TilingState},
+    wayland_protocols::ext::workspace::v1::server::{
+        ext_workspace_group_handle_v1::GroupCapabilities, ext_workspace_handle_v1,
+    },
+};
+use cosmic_protocols_cli::{Error, workspaces};
+use std::{sync::mpsc, thread};
+
+use common::Lines;
+
+mod common;
+
+fn run(mock: &MockCompositor, args: &[&str]) -> Result<String, Error> {
+    let args = workspaces::Args::try_parse_from(["cosmic-workspaces"].iter().chain(args)).unwrap();
+    let mut out = Vec::new();
+    workspaces::run(&mock.connect(), &args, &mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+fn exit_code(result: Result<String, Error>) -> u8 {
+    result.unwrap_err().exit_code()
+}
+
+#[test]
+fn list() {
+    let mock = MockCompositor::new();
+    mock.with(|c| {
+        let output = c.add_output("DP-1", (0, 0), (1920, 1080), 1);
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        c.workspace_group_enter_output(group, output);
+        let second = c.new_workspace(Some(group), "2");
+        c.set_workspace_coordinates(second, &[1]);
+        c.set_workspace_tiling_state(second, TilingState::TilingEnabled);
+        let first = c.new_workspace(Some(group), "1");
+        c.set_workspace_coordinates(first, &[0]);
+        c.set_workspace_state(first, ext_workspace_handle_v1::State::Active);
+        c.set_workspace_cosmic_state(first, zcosmic_workspace_handle_v2::State::Pinned);
+        c.workspace_done();
+    });
+
+    assert_eq!(
+        run(&mock, &["list"]).unwrap(),
+        "DP-1\t1\t0\tactive,pinned\nDP-1\t2\t1\ttiling\n"
+    );
+
+    let json = run(&mock, &["list", "--json", "--output", "DP-1"]).unwrap();
+    let mut json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+    assert!(json[1]["id"].is_string());
+    json[1]["id"].take();
+    assert_eq!(
+        json[1],
+        serde_json::json!({
+            "id": null,
+            "name": "2",
+            "outputs": ["DP-1"],
+            "coordinates": [1],
+            "state": ["tiling"],
+            "tiling": true,
+        })
+    );
+
+    assert_eq!(exit_code(run(&mock, &["list", "--output", "DP-2"])), 3);
+}
+
+#[test]
+fn watch() {
+    let mock = MockCompositor::new();
+    let workspace = mock.with(|c| {
+        let workspace = c.new_workspace(None, "1");
+        c.workspace_done();
+        workspace
+    });
+
+    let (sender, receiver) = mpsc::channel();
+    let conn = mock.connect();
+    let watch = thread::spawn(move || {
+        let args = workspaces::Args::try_parse_from(["cosmic-workspaces", "watch"]).unwrap();
+        workspaces::run(&conn, &args, &mut Lines(sender, Vec::new()))
+    });
+    assert_eq!(receiver.recv().unwrap(), "\t1\t\t\n");
+
+    mock.with(|c| {
+        c.set_workspace_name(workspace, "Web");
+        c.workspace_done();
+    });
+    assert_eq!(receiver.recv().unwrap(), "\n");
+    assert_eq!(receiver.recv().unwrap(), "\tWeb\t\t\n");
+
+    // The command exits once the compositor stops sending events
+    mock.with(|c| c.workspace_finished());
+    watch.join().unwrap().unwrap();
+}
+
+#[test]
+fn manage() {
+    let mock = MockCompositor::new();
+    let (groups, workspaces) = mock.with(|c| {
+        // Each output has workspaces named "1" and "2"
+        let mut groups = Vec::new();
+        let mut workspaces = Vec::new();
+        for (i, name) in ["DP-1", "DP-2"].into_iter().enumerate() {
+            let output = c.add_output(name, (i as i32 * 1920, 0), (1920, 1080), 1);
+            let group = c.new_workspace_group(GroupCapabilities::CreateWorkspace);
+            c.workspace_group_enter_output(group, output);
+            for j in 0..2 {
+                let workspace = c.new_workspace(Some(group), &(j + 1).to_string());
+                c.set_workspace_coordinates(workspace, &[i as u32, j]);
+                workspaces.push(workspace);
+            }
+            groups.push(group);
+        }
+        c.workspace_done();
+        (groups, workspaces)
+    });
+
+    assert_eq!(exit_code(run(&mock, &["activate", "2"])), 4);
+    assert_eq!(exit_code(run(&mock, &["activate", "3"])), 3);
+    assert!(mock.with(|c| c.take_requests()).is_empty());
+
+    run(&mock, &["activate", "2", "--output", "DP-2"]).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [
+            Request::ActivateWorkspace(workspaces[3]),
+            Request::CommitWorkspaces
+        ]
+    );
+
+    run(&mock, &["remove", "0,1"]).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [
+            Request::RemoveWorkspace(workspaces[1]),
+            Request::CommitWorkspaces
+        ]
+    );
+
+    run(&mock, &["create", "DP-2", "3"]).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [
+            Request::CreateWorkspace {
+                group: groups[1],
+                name: "3".to_string()
+            },
+            Request::CommitWorkspaces
+        ]
+    );
+
+    run(&mock, &["rename", "1", "--output", "DP-1", "Web"]).unwrap();
+    run(&mock, &["pin", "0,0"]).unwrap();
+    run(&mock, &["tiling", "1,0", "on"]).unwrap();
+    // The other workspace is looked up in the same group
+    run(&mock, &["move-after", "1,0", "2"]).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [
+            Request::RenameWorkspace {
+                workspace: workspaces[0],
+                name: "Web".to_string()
+            },
+            Request::CommitWorkspaces,
+            Request::PinWorkspace(workspaces[0]),
+            Request::CommitWorkspaces,
+            Request::SetTilingState {
+                workspace: workspaces[2],
+                state: WEnum::Value(TilingState::TilingEnabled)
+            },
+            Request::CommitWorkspaces,
+            Request::MoveWorkspaceAfter {
+                workspace: workspaces[2],
+                other: workspaces[3],
+                axis: 0
+            },
+            Request::CommitWorkspaces,
+        ]
+    );
+}
+
+#[test]
+fn unsupported() {
+    let mock = MockCompositor::new();
+    mock.with(|c| {
+        let workspace = c.new_workspace(None, "1");
+        c.set_workspace_capabilities(
+            workspace,
+            ext_workspace_handle_v1::WorkspaceCapabilities::Activate,
+        );
+        c.set_workspace_cosmic_capabilities(
+            workspace,
+            zcosmic_workspace_handle_v2::WorkspaceCapabilities::Rename,
+        );
+        c.workspace_done();
+    });
+    run(&mock, &["activate", "1"]).unwrap();
+    run(&mock, &["rename", "1", "Web"]).unwrap();
+    assert_eq!(exit_code(run(&mock, &["remove", "1"])), 5);
+    assert_eq!(exit_code(run(&mock, &["tiling", "1", "off"])), 5);
+    assert_eq!(exit_code(run(&mock, &["pin", "1"])), 5);
+    assert_eq!(exit_code(run(&mock, &["create", "DP-1", "2"])), 3);
+}
@@ -0,0 +1,560 @@
+//! [calloop] event sources delivering toolkit events, for applications that don't want to
+//! implement the handler traits on their own state.
+//!
+//! Each source dispatches its own event queue on the connection, with an internal state
+//! implementing the handler traits. That state sends events over a calloop channel, which
+//! the source passes on to the callback it was inserted with, along with the toolkit state:
+//!
+//! ```ignore
+//! let source = ToplevelSource::new(&conn)?;
+//! loop_handle.insert_source(source, |event, toplevel_info_state, app_data| match event {
+//!     ToplevelEvent::Added(info) => { /* ... */ }
+//!     ToplevelEvent::Changed(info) => { /* ... */ }
+//!     ToplevelEvent::Closed(handle) => { /* ... */ }
+//! })?;
+//! ```
+//!
+//! The states follow their globals being removed and advertised again, like they do as
+//! registry handlers. Sources poll their own duplicate of the connection's socket, so several
+//! can be inserted in the same loop, next to a `WaylandSource` of the connection.
+
+use std::{
+    io,
+    os::fd::{AsFd, OwnedFd},
+};
+
+use sctk::{
+    reexports::calloop::{
+        self, EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory,
+        channel::{self, Channel, Sender},
+        generic::Generic,
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, QueueHandle, WEnum,
+    backend::WaylandError,
+    globals::{GlobalError, GlobalList, GlobalListContents, registry_queue_init},
+    protocol::{wl_buffer, wl_registry},
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1;
+
+use crate::{
+    screencopy::{
+        CaptureFrame, CaptureOptions, CaptureSession, CaptureSource, CaptureSourceError, Capturer,
+        FailureReason, Formats, Frame, Rect, ScreencopyFrameData, ScreencopyHandler,
+        ScreencopySessionData, ScreencopyState,
+    },
+    toplevel_info::{ToplevelInfo, ToplevelInfoHandler, ToplevelInfoState},
+    workspace::{Workspace, WorkspaceGroup, WorkspaceHandler, WorkspaceState},
+};
+
+#[derive(Clone, Debug)]
+pub enum ToplevelEvent {
+    /// A toplevel was announced, with its initial state
+    Added(ToplevelInfo),
+    /// The state of a toplevel changed
+    Changed(ToplevelInfo),
+    Closed(ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1),
+}
+
+/// The workspace groups and workspaces after a `done` event
+#[derive(Clone, Debug)]
+pub struct Workspaces {
+    pub groups: Vec<WorkspaceGroup>,
+    pub workspaces: Vec<Workspace>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ScreencopyEvent {
+    /// The session is ready, and frames can be captured into buffers matching `formats`
+    InitDone {
+        session: CaptureSession,
+        formats: Formats,
+    },
+    /// A frame was captured into its buffer
+    Ready {
+        frame: CaptureFrame,
+        info: Frame,
+    },
+    Failed {
+        frame: CaptureFrame,
+        reason: WEnum<FailureReason>,
+    },
+    Stopped(CaptureSession),
+}
+
+// State dispatching the event queue of a `ChannelSource`
+trait SourceData: Sized + 'static {
+    type Event;
+    type Metadata;
+
+    fn metadata(&mut self) -> &mut Self::Metadata;
+}
+
+// Reads and dispatches an event queue, and passes on the events its state sends over
+// `channel`.
+//
+// Unlike `calloop_wayland_source::WaylandSource`, no read guard is held while polling, since
+// reading with a guard blocks until every other guard of the thread is released. Events are
+// read when processing instead, after the `WaylandSource`s of the loop released theirs. Events
+// other readers put in the queue are dispatched before sleeping, waking the channel.
+#[derive(Debug)]
+struct ChannelSource<D: SourceData> {
+    socket: Generic<OwnedFd>,
+    event_queue: EventQueue<D>,
+    data: D,
+    channel: Channel<D::Event>,
+}
+
+impl<D> ChannelSource<D>
+where
+    D: SourceData + Dispatch<wl_registry::WlRegistry, GlobalListContents>,
+{
+    fn new(
+        conn: &Connection,
+        init: impl FnOnce(&GlobalList, &QueueHandle<D>, Sender<D::Event>) -> D,
+    ) -> Result<Self, GlobalError> {
+        // Registering the same fd twice with the poller fails
+        let socket = conn
+            .as_fd()
+            .try_clone_to_owned()
+            .map_err(|err| GlobalError::Backend(WaylandError::Io(err)))?;
+        let (globals, event_queue) = registry_queue_init(conn)?;
+        let (sender, channel) = channel::channel();
+        let data = init(&globals, &event_queue.handle(), sender);
+        Ok(Self {
+            socket: Generic::new(socket, Interest::READ, Mode::Level),
+            event_queue,
+            data,
+            channel,
+        })
+    }
+}
+
+impl<D: SourceData> ChannelSource<D> {
+    fn dispatch(&mut self) -> calloop::Result<()> {
+        self.event_queue
+            .dispatch_pending(&mut self.data)
+            .map_err(|err| calloop::Error::OtherError(Box::new(err)))?;
+        match self.event_queue.flush() {
+            // Retried before the next sleep
+            Err(WaylandError::Io(err)) if err.kind() != io::ErrorKind::WouldBlock => {
+                Err(err.into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<D: SourceData> EventSource for ChannelSource<D> {
+    type Event = D::Event;
+    type Metadata = D::Metadata;
+    type Ret = ();
+    type Error = calloop::Error;
+
+    const NEEDS_EXTRA_LIFECYCLE_EVENTS: bool = true;
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> Result<PostAction, Self::Error>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        if let Some(guard) = self.event_queue.prepare_read()
+            && let Err(WaylandError::Io(err)) = guard.read()
+            // Events were already read, by another source or thread
+            && err.kind() != io::ErrorKind::WouldBlock
+        {
+            return Err(err.into());
+        }
+        self.dispatch()?;
+        // Events sent while dispatching wake the channel on the next iteration
+        let data = &mut self.data;
+        self.channel
+            .process_events(readiness, token, |event, &mut ()| {
+                if let channel::Event::Msg(event) = event {
+                    callback(event, data.metadata());
+                }
+            })
+            .map_err(|err| calloop::Error::OtherError(Box::new(err)))?;
+        Ok(PostAction::Continue)
+    }
+
+    fn register(
+        &mut self,
+        poll: &mut Poll,
+        token_factory: &mut TokenFactory,
+    ) -> calloop::Result<()> {
+        self.socket.register(poll, token_factory)?;
+        self.channel.register(poll, token_factory)
+    }
+
+    fn reregister(
+        &mut self,
+        poll: &mut Poll,
+        token_factory: &mut TokenFactory,
+    ) -> calloop::Result<()> {
+        self.socket.reregister(poll, token_factory)?;
+        self.channel.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.socket.unregister(poll)?;
+        self.channel.unregister(poll)
+    }
+
+    fn before_sleep(&mut self) -> calloop::Result<Option<(Readiness, Token)>> {
+        self.dispatch()?;
+        Ok(None)
+    }
+}
+
+// `EventSource` of a public source, forwarded to its `ChannelSource`
+macro_rules! forward_event_source {
+    ($source:ty, $event:ty, $metadata:ty) => {
+        impl EventSource for $source {
+            type Event = $event;
+            type Metadata = $metadata;
+            type Ret = ();
+            type Error = calloop::Error;
+
+            const NEEDS_EXTRA_LIFECYCLE_EVENTS: bool = true;
+
+            fn process_events<F>(
+                &mut self,
+                readiness: Readiness,
+                token: Token,
+                callback: F,
+            ) -> Result<PostAction, Self::Error>
+            where
+                F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+            {
+                self.0.process_events(readiness, token, callback)
+            }
+
+            fn register(
+                &mut self,
+                poll: &mut Poll,
+                token_factory: &mut TokenFactory,
+            ) -> calloop::Result<()> {
+                self.0.register(poll, token_factory)
+            }
+
+            fn reregister(
+                &mut self,
+                poll: &mut Poll,
+                token_factory: &mut TokenFactory,
+            ) -> calloop::Result<()> {
+                self.0.reregister(poll, token_factory)
+            }
+
+            fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+                self.0.unregister(poll)
+            }
+
+            fn before_sleep(&mut self) -> calloop::Result<Option<(Readiness, Token)>> {
+                self.0.before_sleep()
+            }
+
+            fn before_handle_events(&mut self, events: calloop::EventIterator<'_>) {
+                self.0.before_handle_events(events)
+            }
+        }
+    };
+}
+
+/// Source of [`ToplevelEvent`]s, passing the [`ToplevelInfoState`] to its callback.
+#[derive(Debug)]
+pub struct ToplevelSource(ChannelSource<ToplevelData>);
+
+impl ToplevelSource {
+    pub fn new(conn: &Connection) -> Result<Self, GlobalError> {
+        ChannelSource::new(conn, |globals, qh, sender| {
+            let registry_state = RegistryState::new(globals);
+            ToplevelData {
+                toplevel_info_state: ToplevelInfoState::new(&registry_state, qh),
+                registry_state,
+                sender,
+            }
+        })
+        .map(Self)
+    }
+
+    pub fn state(&self) -> &ToplevelInfoState {
+        &self.0.data.toplevel_info_state
+    }
+}
+
+forward_event_source!(ToplevelSource, ToplevelEvent, ToplevelInfoState);
+
+#[derive(Debug)]
+struct ToplevelData {
+    registry_state: RegistryState,
+    toplevel_info_state: ToplevelInfoState,
+    sender: Sender<ToplevelEvent>,
+}
+
+impl ToplevelData {
+    fn send_info(
+        &self,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+        event: fn(ToplevelInfo) -> ToplevelEvent,
+    ) {
+        if let Some(info) = self.toplevel_info_state.info(toplevel) {
+            let _ = self.sender.send(event(info.clone()));
+        }
+    }
+}
+
+impl SourceData for ToplevelData {
+    type Event = ToplevelEvent;
+    type Metadata = ToplevelInfoState;
+
+    fn metadata(&mut self) -> &mut ToplevelInfoState {
+        &mut self.toplevel_info_state
+    }
+}
+
+impl ProvidesRegistryState for ToplevelData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(ToplevelInfoState);
+}
+
+impl ToplevelInfoHandler for ToplevelData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        &mut self.toplevel_info_state
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.send_info(toplevel, ToplevelEvent::Added);
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.send_info(toplevel, ToplevelEvent::Changed);
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        let _ = self.sender.send(ToplevelEvent::Closed(toplevel.clone()));
+    }
+}
+
+sctk::delegate_registry!(ToplevelData);
+crate::delegate_toplevel_info!(ToplevelData);
+
+/// Source of [`Workspaces`], sent after each `done` event, passing the [`WorkspaceState`] to
+/// its callback.
+#[derive(Debug)]
+pub struct WorkspaceSource(ChannelSource<WorkspaceData>);
+
+impl WorkspaceSource {
+    pub fn new(conn: &Connection) -> Result<Self, GlobalError> {
+        ChannelSource::new(conn, |globals, qh, sender| {
+            let registry_state = RegistryState::new(globals);
+            WorkspaceData {
+                workspace_state: WorkspaceState::new(&registry_state, qh),
+                registry_state,
+                sender,
+            }
+        })
+        .map(Self)
+    }
+
+    pub fn state(&self) -> &WorkspaceState {
+        &self.0.data.workspace_state
+    }
+}
+
+forward_event_source!(WorkspaceSource, Workspaces, WorkspaceState);
+
+#[derive(Debug)]
+struct WorkspaceData {
+    registry_state: RegistryState,
+    workspace_state: WorkspaceState,
+    sender: Sender<Workspaces>,
+}
+
+impl SourceData for WorkspaceData {
+    type Event = Workspaces;
+    type Metadata = WorkspaceState;
+
+    fn metadata(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+}
+
+impl ProvidesRegistryState for WorkspaceData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(WorkspaceState);
+}
+
+impl WorkspaceHandler for WorkspaceData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+
+    fn done(&mut self) {
+        let _ = self.sender.send(Workspaces {
+            groups: self.workspace_state.workspace_groups().cloned().collect(),
+            workspaces: self.workspace_state.workspaces().cloned().collect(),
+        });
+    }
+}
+
+sctk::delegate_registry!(WorkspaceData);
+crate::delegate_workspace!(WorkspaceData);
+
+/// Source of [`ScreencopyEvent`]s for sessions created with its [`ScreencopyData`], which
+/// is passed to its callback.
+#[derive(Debug)]
+pub struct ScreencopySource(ChannelSource<ScreencopyData>);
+
+impl ScreencopySource {
+    pub fn new(conn: &Connection) -> Result<Self, GlobalError> {
+        ChannelSource::new(conn, |globals, qh, sender| ScreencopyData {
+            registry_state: RegistryState::new(globals),
+            screencopy_state: ScreencopyState::new(globals, qh),
+            qh: qh.clone(),
+            sender,
+        })
+        .map(Self)
+    }
+
+    pub fn data(&self) -> &ScreencopyData {
+        &self.0.data
+    }
+}
+
+forward_event_source!(ScreencopySource, ScreencopyEvent, ScreencopyData);
+
+/// Creates capture sessions and frames dispatched by a [`ScreencopySource`]
+#[derive(Debug)]
+pub struct ScreencopyData {
+    registry_state: RegistryState,
+    screencopy_state: ScreencopyState,
+    qh: QueueHandle<Self>,
+    sender: Sender<ScreencopyEvent>,
+}
+
+impl ScreencopyData {
+    pub fn capturer(&self) -> &Capturer {
+        self.screencopy_state.capturer()
+    }
+
+    pub fn create_session(
+        &self,
+        source: &CaptureSource,
+        options: CaptureOptions,
+    ) -> Result<CaptureSession, CaptureSourceError> {
+        self.capturer()
+            .create_session(source, options, &self.qh, ScreencopySessionData::default())
+    }
+
+    /// Capture a frame of `session` into `buffer`, which may belong to any event queue
+    pub fn capture(
+        &self,
+        session: &CaptureSession,
+        buffer: &wl_buffer::WlBuffer,
+        buffer_damage: &[Rect],
+    ) -> CaptureFrame {
+        session.capture(
+            buffer,
+            buffer_damage,
+            &self.qh,
+            ScreencopyFrameData::default(),
+        )
+    }
+}
+
+impl SourceData for ScreencopyData {
+    type Event = ScreencopyEvent;
+    type Metadata = Self;
+
+    fn metadata(&mut self) -> &mut Self {
+        self
+    }
+}
+
+impl ProvidesRegistryState for ScreencopyData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(ScreencopyState);
+}
+
+impl ScreencopyHandler for ScreencopyData {
+    fn screencopy_state(&mut self) -> &mut ScreencopyState {
+        &mut self.screencopy_state
+    }
+
+    fn init_done(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        session: &CaptureSession,
+        formats: &Formats,
+    ) {
+        let _ = self.sender.send(ScreencopyEvent::InitDone {
+            session: session.clone(),
+            formats: formats.clone(),
+        });
+    }
+
+    fn stopped(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, session: &CaptureSession) {
+        let _ = self.sender.send(ScreencopyEvent::Stopped(session.clone()));
+    }
+
+    fn ready(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        screencopy_frame: &CaptureFrame,
+        frame: Frame,
+    ) {
+        let _ = self.sender.send(ScreencopyEvent::Ready {
+            frame: screencopy_frame.clone(),
+            info: frame,
+        });
+    }
+
+    fn failed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        screencopy_frame: &CaptureFrame,
+        reason: WEnum<FailureReason>,
+    ) {
+        let _ = self.sender.send(ScreencopyEvent::Failed {
+            frame: screencopy_frame.clone(),
+            reason,
+        });
+    }
+}
+
+sctk::delegate_registry!(ScreencopyData);
+crate::delegate_screencopy!(ScreencopyData);
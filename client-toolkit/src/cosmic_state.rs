@@ -0,0 +1,350 @@
+//! All toolkit states in one, bound from whichever COSMIC globals the compositor advertises.
+//!
+//! ```ignore
+//! impl ProvidesRegistryState for AppData {
+//!     fn registry(&mut self) -> &mut RegistryState {
+//!         &mut self.registry_state
+//!     }
+//!
+//!     sctk::registry_handlers!(CosmicState);
+//! }
+//!
+//! impl CosmicHandler for AppData {
+//!     fn cosmic_state(&mut self) -> &mut CosmicState {
+//!         &mut self.cosmic_state
+//!     }
+//! }
+//!
+//! impl ToplevelInfoHandler for AppData {
+//!     fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+//!         self.cosmic_state.toplevel_info.as_mut().unwrap()
+//!     }
+//!     // ...
+//! }
+//!
+//! cosmic_client_toolkit::delegate_cosmic!(AppData);
+//! ```
+//!
+//! Events and global changes are only passed on to a state while its field is `Some`, so the
+//! state accessors of the handlers can unwrap it. Screencopy sessions and frames are still
+//! dispatched after [`CosmicState::screencopy`] is dropped, since they keep working with the
+//! globals they were created from; the toolkit never calls `screencopy_state` for them.
+
+use std::sync::Arc;
+
+use cosmic_protocols::{
+    image_capture_source::v1::client::zcosmic_workspace_image_capture_source_manager_v1,
+    toplevel_info::v1::client::{zcosmic_toplevel_handle_v1, zcosmic_toplevel_info_v1},
+    toplevel_management::v1::client::zcosmic_toplevel_manager_v1,
+    workspace::v2::client::{zcosmic_workspace_handle_v2, zcosmic_workspace_manager_v2},
+};
+use sctk::registry::{ProvidesRegistryState, RegistryHandler, RegistryState};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, backend::ObjectData};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::client::{
+        ext_foreign_toplevel_handle_v1, ext_foreign_toplevel_list_v1,
+    },
+    image_capture_source::v1::client::{
+        ext_foreign_toplevel_image_capture_source_manager_v1,
+        ext_output_image_capture_source_manager_v1,
+    },
+    image_copy_capture::v1::client::ext_image_copy_capture_manager_v1,
+    workspace::v1::client::{
+        ext_workspace_group_handle_v1, ext_workspace_handle_v1, ext_workspace_manager_v1,
+    },
+};
+
+use crate::{
+    GlobalData,
+    query::Query,
+    screencopy::{ScreencopyHandler, ScreencopyState, is_screencopy_global},
+    toplevel_info::{ToplevelInfoHandler, ToplevelInfoState, ToplevelUserData},
+    toplevel_management::{ToplevelManagerHandler, ToplevelManagerState},
+    workspace::{WorkspaceHandler, WorkspaceState},
+};
+
+/// The toolkit states, each `None` while the compositor doesn't advertise its global.
+///
+/// Add `CosmicState` to `sctk::registry_handlers!` to bind states when their global appears
+/// and drop them when it is removed. States that stay bound follow changes of their other
+/// globals, like [`ToplevelInfoState`] does as a registry handler of its own.
+#[derive(Debug)]
+pub struct CosmicState {
+    /// Requires ext_foreign_toplevel_list_v1
+    pub toplevel_info: Option<ToplevelInfoState>,
+    /// Requires zcosmic_toplevel_manager_v1
+    pub toplevel_manager: Option<ToplevelManagerState>,
+    /// Requires ext_workspace_manager_v1
+    pub workspace: Option<WorkspaceState>,
+    /// Requires ext_image_copy_capture_manager_v1 and an image capture source manager
+    pub screencopy: Option<ScreencopyState>,
+}
+
+impl CosmicState {
+    pub fn new<D>(registry: &RegistryState, qh: &QueueHandle<D>) -> Self
+    where
+        D: Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, GlobalData>
+            + Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, GlobalData>
+            + Dispatch<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, GlobalData>
+            + Dispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, GlobalData>
+            + Dispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, GlobalData>
+            + Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, GlobalData>
+            + Dispatch<
+                ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+                GlobalData,
+            > + Dispatch<
+                ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+                GlobalData,
+            > + Dispatch<
+                zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+                GlobalData,
+            > + 'static,
+    {
+        Self {
+            toplevel_info: ToplevelInfoState::try_new(registry, qh),
+            toplevel_manager: ToplevelManagerState::try_new(registry, qh),
+            workspace: bind_workspace(registry, qh),
+            screencopy: ScreencopyState::try_from_registry(registry, qh).ok(),
+        }
+    }
+
+    /// Queries across toplevels and workspaces, while both states are bound
+    pub fn query(&self) -> Option<Query<'_>> {
+        Some(Query::new(
+            self.toplevel_info.as_ref()?,
+            self.workspace.as_ref()?,
+        ))
+    }
+}
+
+fn bind_workspace<D>(registry: &RegistryState, qh: &QueueHandle<D>) -> Option<WorkspaceState>
+where
+    D: Dispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, GlobalData>
+        + Dispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, GlobalData>
+        + 'static,
+{
+    // `WorkspaceState::new` also succeeds without the global
+    has_global::<ext_workspace_manager_v1::ExtWorkspaceManagerV1>(registry)
+        .then(|| WorkspaceState::new(registry, qh))
+}
+
+fn has_global<I: Proxy>(registry: &RegistryState) -> bool {
+    registry
+        .globals_by_interface(I::interface().name)
+        .next()
+        .is_some()
+}
+
+pub trait CosmicHandler {
+    fn cosmic_state(&mut self) -> &mut CosmicState;
+}
+
+impl<D> RegistryHandler<D> for CosmicState
+where
+    D: ProvidesRegistryState
+        + CosmicHandler
+        + ToplevelInfoHandler
+        + ToplevelManagerHandler
+        + WorkspaceHandler
+        + ScreencopyHandler
+        + Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, GlobalData>
+        + Dispatch<zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1, GlobalData>
+        + Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, GlobalData>
+        + Dispatch<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, GlobalData>
+        + Dispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, GlobalData>
+        + Dispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, GlobalData>
+        + Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, GlobalData>
+        + Dispatch<
+            ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+            GlobalData,
+        > + Dispatch<
+            ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+            GlobalData,
+        > + Dispatch<
+            zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+            GlobalData,
+        > + 'static,
+{
+    fn new_global(
+        data: &mut D,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+        name: u32,
+        interface: &str,
+        version: u32,
+    ) {
+        if data.cosmic_state().toplevel_info.is_some() {
+            ToplevelInfoState::new_global(data, conn, qh, name, interface, version);
+        } else if interface
+            == ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1::interface().name
+        {
+            let toplevel_info = ToplevelInfoState::try_new(data.registry(), qh);
+            data.cosmic_state().toplevel_info = toplevel_info;
+        }
+
+        if data.cosmic_state().toplevel_manager.is_some() {
+            ToplevelManagerState::new_global(data, conn, qh, name, interface, version);
+        } else if interface
+            == zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1::interface().name
+        {
+            let toplevel_manager = ToplevelManagerState::try_new(data.registry(), qh);
+            data.cosmic_state().toplevel_manager = toplevel_manager;
+        }
+
+        if data.cosmic_state().workspace.is_some() {
+            WorkspaceState::new_global(data, conn, qh, name, interface, version);
+        } else if interface == ext_workspace_manager_v1::ExtWorkspaceManagerV1::interface().name {
+            let workspace = bind_workspace(data.registry(), qh);
+            data.cosmic_state().workspace = workspace;
+        }
+
+        if data.cosmic_state().screencopy.is_some() {
+            ScreencopyState::new_global(data, conn, qh, name, interface, version);
+        } else if is_screencopy_global(interface) {
+            let screencopy = ScreencopyState::try_from_registry(data.registry(), qh).ok();
+            data.cosmic_state().screencopy = screencopy;
+        }
+    }
+
+    fn remove_global(
+        data: &mut D,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+        name: u32,
+        interface: &str,
+    ) {
+        // The states rebind to another instance of the global if there is one, and otherwise
+        // are dropped
+        if data.cosmic_state().toplevel_info.is_some() {
+            ToplevelInfoState::remove_global(data, conn, qh, name, interface);
+            let cosmic_state = data.cosmic_state();
+            if let Some(toplevel_info) = &cosmic_state.toplevel_info
+                && toplevel_info.foreign_toplevel_list().is_none()
+            {
+                cosmic_state.toplevel_info = None;
+            }
+        }
+
+        if data.cosmic_state().toplevel_manager.is_some() {
+            ToplevelManagerState::remove_global(data, conn, qh, name, interface);
+            let cosmic_state = data.cosmic_state();
+            if let Some(toplevel_manager) = &cosmic_state.toplevel_manager
+                && toplevel_manager.manager().is_none()
+            {
+                cosmic_state.toplevel_manager = None;
+            }
+        }
+
+        if data.cosmic_state().workspace.is_some() {
+            WorkspaceState::remove_global(data, conn, qh, name, interface);
+            let cosmic_state = data.cosmic_state();
+            if let Some(workspace) = &cosmic_state.workspace
+                && workspace.workspace_manager().get().is_err()
+            {
+                cosmic_state.workspace = None;
+            }
+        }
+
+        if data.cosmic_state().screencopy.is_some() {
+            ScreencopyState::remove_global(data, conn, qh, name, interface);
+            let cosmic_state = data.cosmic_state();
+            if let Some(screencopy) = &cosmic_state.screencopy
+                && screencopy.capturer().supported_sources().is_empty()
+            {
+                cosmic_state.screencopy = None;
+            }
+        }
+    }
+}
+
+// Forwards events to `$state`, unless the global it was bound from has been removed
+macro_rules! forward_dispatch {
+    ($field:ident: $state:ty => [$($interface:ty: $udata:ty),* $(,)?]) => {
+        $(
+            impl<D> Dispatch<$interface, $udata, D> for CosmicState
+            where
+                D: Dispatch<$interface, $udata> + CosmicHandler + 'static,
+                $state: Dispatch<$interface, $udata, D>,
+            {
+                fn event(
+                    state: &mut D,
+                    proxy: &$interface,
+                    event: <$interface as Proxy>::Event,
+                    data: &$udata,
+                    conn: &Connection,
+                    qh: &QueueHandle<D>,
+                ) {
+                    if state.cosmic_state().$field.is_some() {
+                        <$state as Dispatch<$interface, $udata, D>>::event(
+                            state, proxy, event, data, conn, qh,
+                        );
+                    }
+                }
+
+                fn event_created_child(opcode: u16, qh: &QueueHandle<D>) -> Arc<dyn ObjectData> {
+                    <$state as Dispatch<$interface, $udata, D>>::event_created_child(opcode, qh)
+                }
+            }
+        )*
+    };
+}
+
+forward_dispatch!(toplevel_info: ToplevelInfoState => [
+    zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1: GlobalData,
+    zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1: GlobalData,
+    ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1: GlobalData,
+    ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1: ToplevelUserData,
+]);
+
+forward_dispatch!(workspace: WorkspaceState => [
+    ext_workspace_manager_v1::ExtWorkspaceManagerV1: GlobalData,
+    ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1: GlobalData,
+    ext_workspace_handle_v1::ExtWorkspaceHandleV1: GlobalData,
+    zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2: GlobalData,
+    zcosmic_workspace_handle_v2::ZcosmicWorkspaceHandleV2: GlobalData,
+]);
+
+forward_dispatch!(toplevel_manager: ToplevelManagerState => [
+    zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1: GlobalData,
+]);
+
+#[macro_export]
+macro_rules! delegate_cosmic {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::wayland_client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1: $crate::GlobalData
+        ] => $crate::cosmic_state::CosmicState);
+        $crate::wayland_client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1: $crate::GlobalData
+        ] => $crate::cosmic_state::CosmicState);
+        $crate::wayland_client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1: $crate::GlobalData
+        ] => $crate::cosmic_state::CosmicState);
+        $crate::wayland_client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1: $crate::toplevel_info::ToplevelUserData
+        ] => $crate::cosmic_state::CosmicState);
+
+        $crate::wayland_client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland_protocols::ext::workspace::v1::client::ext_workspace_manager_v1::ExtWorkspaceManagerV1: $crate::GlobalData
+        ] => $crate::cosmic_state::CosmicState);
+        $crate::wayland_client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland_protocols::ext::workspace::v1::client::ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1: $crate::GlobalData
+        ] => $crate::cosmic_state::CosmicState);
+        $crate::wayland_client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::ExtWorkspaceHandleV1: $crate::GlobalData
+        ] => $crate::cosmic_state::CosmicState);
+        $crate::wayland_client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::cosmic_protocols::workspace::v2::client::zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2: $crate::GlobalData
+        ] => $crate::cosmic_state::CosmicState);
+        $crate::wayland_client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::cosmic_protocols::workspace::v2::client::zcosmic_workspace_handle_v2::ZcosmicWorkspaceHandleV2: $crate::GlobalData
+        ] => $crate::cosmic_state::CosmicState);
+
+        $crate::wayland_client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::cosmic_protocols::toplevel_management::v1::client::zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1: $crate::GlobalData
+        ] => $crate::cosmic_state::CosmicState);
+
+        // Sessions and frames outlive the state, so screencopy is delegated to directly
+        $crate::delegate_screencopy!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty);
+    };
+}
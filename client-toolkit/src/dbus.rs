@@ -0,0 +1,499 @@
+//! Bridge publishing toplevels and workspaces on D-Bus, for tools that don't speak Wayland.
+//!
+//! [`DbusBridge`] serves two objects on a D-Bus connection:
+//!
+//! - [`TOPLEVELS_PATH`] with the [`TOPLEVELS_INTERFACE`] interface: a `Toplevels` property
+//!   listing [`Toplevel`]s, and methods `Activate`, `Close`, `SetMaximized`, `SetMinimized`,
+//!   `SetFullscreen` and `SetSticky` forwarded to `zcosmic_toplevel_manager_v1`.
+//! - [`WORKSPACES_PATH`] with the [`WORKSPACES_INTERFACE`] interface: a `Workspaces` property
+//!   listing [`Workspace`]s, and methods `Activate`, `Remove` and `Rename` forwarded to
+//!   `ext_workspace_manager_v1` and `zcosmic_workspace_manager_v2`.
+//!
+//! Methods fail with `org.freedesktop.DBus.Error.NotSupported` if the compositor doesn't
+//! advertise the capability they need, like the command-line tools.
+//!
+//! Toplevels and workspaces are identified by the protocol id of their handle, as in
+//! [`snapshot`](crate::snapshot). The properties are updated by calling
+//! [`update_toplevels`](DbusBridge::update_toplevels) and
+//! [`update_workspaces`](DbusBridge::update_workspaces) from the handlers of the toolkit
+//! states, which emits `PropertiesChanged` if anything changed. The
+//! `zcosmic_toplevel_manager_v1` global may be rebound, so call
+//! [`update_toplevel_manager`](DbusBridge::update_toplevel_manager) whenever it sends its
+//! capabilities, to forward requests to the current manager.
+//!
+//! Method calls are handled on the thread of the D-Bus connection, sending requests directly
+//! on the Wayland connection, so they don't wait for the event loop of the application.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use cosmic_protocols::{
+    toplevel_info::v1::client::zcosmic_toplevel_handle_v1,
+    toplevel_management::v1::client::zcosmic_toplevel_manager_v1,
+    workspace::v2::client::zcosmic_workspace_handle_v2,
+};
+use sctk::output::OutputState;
+use serde::{Deserialize, Serialize};
+use wayland_client::{Connection, WEnum, protocol::wl_seat};
+use wayland_protocols::ext::workspace::v1::client::{
+    ext_workspace_handle_v1, ext_workspace_manager_v1,
+};
+use zbus::{
+    fdo,
+    zvariant::{OwnedValue, Type, Value},
+};
+
+use crate::{
+    snapshot::{ToplevelSnapshot, WorkspaceGroupSnapshot, WorkspaceSnapshot},
+    toplevel_info::ToplevelInfoState,
+    toplevel_management::ToplevelManagerState,
+    workspace::{self, WorkspaceState},
+};
+
+pub const TOPLEVELS_PATH: &str = "/com/system76/Cosmic/Toplevels";
+pub const TOPLEVELS_INTERFACE: &str = "com.system76.Cosmic.Toplevels";
+pub const WORKSPACES_PATH: &str = "/com/system76/Cosmic/Workspaces";
+pub const WORKSPACES_INTERFACE: &str = "com.system76.Cosmic.Workspaces";
+
+/// Toplevel as published in the `Toplevels` property, with the signature `(usssasasau)`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Type, Value, OwnedValue)]
+pub struct Toplevel {
+    pub object_id: u32,
+    pub identifier: String,
+    pub app_id: String,
+    pub title: String,
+    pub state: Vec<String>,
+    pub outputs: Vec<String>,
+    /// Object ids of the workspaces
+    pub workspaces: Vec<u32>,
+}
+
+impl From<ToplevelSnapshot> for Toplevel {
+    fn from(snapshot: ToplevelSnapshot) -> Self {
+        Self {
+            object_id: snapshot.object_id,
+            identifier: snapshot.identifier,
+            app_id: snapshot.app_id,
+            title: snapshot.title,
+            state: snapshot.state,
+            outputs: snapshot.outputs,
+            workspaces: snapshot.workspaces,
+        }
+    }
+}
+
+/// Workspace as published in the `Workspaces` property, with the signature `(ussauasasas)`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Type, Value, OwnedValue)]
+pub struct Workspace {
+    pub object_id: u32,
+    /// Id assigned by the compositor, or empty if it didn't send one
+    pub id: String,
+    pub name: String,
+    pub coordinates: Vec<u32>,
+    /// States, with `tiling` if tiling is enabled
+    pub state: Vec<String>,
+    pub capabilities: Vec<String>,
+    /// Outputs of the workspace group
+    pub outputs: Vec<String>,
+}
+
+impl Workspace {
+    fn new(snapshot: WorkspaceSnapshot, groups: &[WorkspaceGroupSnapshot]) -> Self {
+        let outputs = groups
+            .iter()
+            .find(|group| group.workspaces.contains(&snapshot.object_id))
+            .map_or_else(Vec::new, |group| group.outputs.clone());
+        let mut state = snapshot.state;
+        if snapshot.tiling == Some(true) {
+            state.push("tiling".to_string());
+            state.sort();
+        }
+        Self {
+            object_id: snapshot.object_id,
+            id: snapshot.id.unwrap_or_default(),
+            name: snapshot.name,
+            coordinates: snapshot.coordinates,
+            state,
+            capabilities: snapshot.capabilities,
+            outputs,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ToplevelData {
+    toplevel: Toplevel,
+    cosmic_toplevel: Option<zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1>,
+}
+
+#[derive(Debug)]
+struct WorkspaceData {
+    workspace: Workspace,
+    info: workspace::Workspace,
+}
+
+type ToplevelCapability = zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1;
+
+// State shared with the interfaces, which are served on the thread of the D-Bus connection
+#[derive(Debug)]
+struct Shared {
+    conn: Connection,
+    toplevel_manager: Option<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1>,
+    toplevel_capabilities: Vec<WEnum<ToplevelCapability>>,
+    workspace_manager: Option<ext_workspace_manager_v1::ExtWorkspaceManagerV1>,
+    seat: Option<wl_seat::WlSeat>,
+    toplevels: Vec<ToplevelData>,
+    workspaces: Vec<WorkspaceData>,
+}
+
+impl Shared {
+    /// Manager and handle of a toplevel, if the manager advertises `capability`
+    fn toplevel(
+        &self,
+        object_id: u32,
+        capability: ToplevelCapability,
+        name: &str,
+    ) -> fdo::Result<(
+        &zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1,
+        &zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1,
+    )> {
+        let manager = self.toplevel_manager.as_ref().ok_or_else(|| {
+            fdo::Error::NotSupported("zcosmic_toplevel_manager_v1 unsupported".to_string())
+        })?;
+        if !self
+            .toplevel_capabilities
+            .contains(&WEnum::Value(capability))
+        {
+            return Err(unsupported(name));
+        }
+        let handle = self
+            .toplevels
+            .iter()
+            .find(|data| data.toplevel.object_id == object_id)
+            .ok_or_else(|| fdo::Error::UnknownObject(format!("no toplevel {}", object_id)))?
+            .cosmic_toplevel
+            .as_ref()
+            .ok_or_else(|| {
+                fdo::Error::NotSupported("zcosmic_toplevel_info_v1 unsupported".to_string())
+            })?;
+        Ok((manager, handle))
+    }
+
+    /// Workspace, if the compositor advertises `capability` for it
+    fn workspace(
+        &self,
+        object_id: u32,
+        capability: ext_workspace_handle_v1::WorkspaceCapabilities,
+        name: &str,
+    ) -> fdo::Result<&workspace::Workspace> {
+        let info = self.workspace_info(object_id)?;
+        if !info.capabilities.contains(capability) {
+            return Err(unsupported(name));
+        }
+        Ok(info)
+    }
+
+    fn workspace_info(&self, object_id: u32) -> fdo::Result<&workspace::Workspace> {
+        self.workspaces
+            .iter()
+            .find(|data| data.workspace.object_id == object_id)
+            .map(|data| &data.info)
+            .ok_or_else(|| fdo::Error::UnknownObject(format!("no workspace {}", object_id)))
+    }
+
+    fn commit_workspaces(&self) -> fdo::Result<()> {
+        if let Some(manager) = &self.workspace_manager {
+            manager.commit();
+        }
+        self.flush()
+    }
+
+    fn flush(&self) -> fdo::Result<()> {
+        self.conn
+            .flush()
+            .map_err(|err| fdo::Error::Failed(err.to_string()))
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap()
+}
+
+fn unsupported(name: &str) -> fdo::Error {
+    fdo::Error::NotSupported(format!("{} unsupported by compositor", name))
+}
+
+struct ToplevelsInterface(Arc<Mutex<Shared>>);
+
+#[zbus::interface(name = "com.system76.Cosmic.Toplevels")]
+impl ToplevelsInterface {
+    #[zbus(property)]
+    fn toplevels(&self) -> Vec<Toplevel> {
+        let shared = lock(&self.0);
+        shared
+            .toplevels
+            .iter()
+            .map(|data| data.toplevel.clone())
+            .collect()
+    }
+
+    /// Requires a seat set with [`DbusBridge::set_seat`]
+    fn activate(&self, object_id: u32) -> fdo::Result<()> {
+        let shared = lock(&self.0);
+        let (manager, handle) =
+            shared.toplevel(object_id, ToplevelCapability::Activate, "activate")?;
+        let seat = shared
+            .seat
+            .as_ref()
+            .ok_or_else(|| fdo::Error::NotSupported("no seat".to_string()))?;
+        manager.activate(handle, seat);
+        shared.flush()
+    }
+
+    fn close(&self, object_id: u32) -> fdo::Result<()> {
+        let shared = lock(&self.0);
+        let (manager, handle) = shared.toplevel(object_id, ToplevelCapability::Close, "close")?;
+        manager.close(handle);
+        shared.flush()
+    }
+
+    fn set_maximized(&self, object_id: u32, maximized: bool) -> fdo::Result<()> {
+        let shared = lock(&self.0);
+        let (manager, handle) =
+            shared.toplevel(object_id, ToplevelCapability::Maximize, "maximize")?;
+        if maximized {
+            manager.set_maximized(handle);
+        } else {
+            manager.unset_maximized(handle);
+        }
+        shared.flush()
+    }
+
+    fn set_minimized(&self, object_id: u32, minimized: bool) -> fdo::Result<()> {
+        let shared = lock(&self.0);
+        let (manager, handle) =
+            shared.toplevel(object_id, ToplevelCapability::Minimize, "minimize")?;
+        if minimized {
+            manager.set_minimized(handle);
+        } else {
+            manager.unset_minimized(handle);
+        }
+        shared.flush()
+    }
+
+    fn set_fullscreen(&self, object_id: u32, fullscreen: bool) -> fdo::Result<()> {
+        let shared = lock(&self.0);
+        let (manager, handle) =
+            shared.toplevel(object_id, ToplevelCapability::Fullscreen, "fullscreen")?;
+        if fullscreen {
+            manager.set_fullscreen(handle, None);
+        } else {
+            manager.unset_fullscreen(handle);
+        }
+        shared.flush()
+    }
+
+    fn set_sticky(&self, object_id: u32, sticky: bool) -> fdo::Result<()> {
+        let shared = lock(&self.0);
+        let (manager, handle) = shared.toplevel(object_id, ToplevelCapability::Sticky, "sticky")?;
+        if sticky {
+            manager.set_sticky(handle);
+        } else {
+            manager.unset_sticky(handle);
+        }
+        shared.flush()
+    }
+}
+
+struct WorkspacesInterface(Arc<Mutex<Shared>>);
+
+#[zbus::interface(name = "com.system76.Cosmic.Workspaces")]
+impl WorkspacesInterface {
+    #[zbus(property)]
+    fn workspaces(&self) -> Vec<Workspace> {
+        let shared = lock(&self.0);
+        shared
+            .workspaces
+            .iter()
+            .map(|data| data.workspace.clone())
+            .collect()
+    }
+
+    fn activate(&self, object_id: u32) -> fdo::Result<()> {
+        let shared = lock(&self.0);
+        shared
+            .workspace(
+                object_id,
+                ext_workspace_handle_v1::WorkspaceCapabilities::Activate,
+                "activate",
+            )?
+            .handle
+            .activate();
+        shared.commit_workspaces()
+    }
+
+    fn remove(&self, object_id: u32) -> fdo::Result<()> {
+        let shared = lock(&self.0);
+        shared
+            .workspace(
+                object_id,
+                ext_workspace_handle_v1::WorkspaceCapabilities::Remove,
+                "remove",
+            )?
+            .handle
+            .remove();
+        shared.commit_workspaces()
+    }
+
+    fn rename(&self, object_id: u32, name: &str) -> fdo::Result<()> {
+        let shared = lock(&self.0);
+        let info = shared.workspace_info(object_id)?;
+        let handle = info
+            .cosmic_handle
+            .as_ref()
+            .filter(|_| {
+                info.has_cosmic_capability(
+                    zcosmic_workspace_handle_v2::WorkspaceCapabilities::Rename,
+                )
+            })
+            .ok_or_else(|| unsupported("rename"))?;
+        handle.rename(name.to_string());
+        shared.commit_workspaces()
+    }
+}
+
+/// Publishes toplevels and workspaces on a D-Bus connection; see the [module](self)
+/// documentation.
+#[derive(Debug)]
+pub struct DbusBridge {
+    dbus: zbus::blocking::Connection,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl DbusBridge {
+    /// Serve the bridge objects on `dbus`, forwarding toplevel requests to `toplevel_manager`
+    /// if given. The name of the connection is left to the caller.
+    pub fn new(
+        dbus: &zbus::blocking::Connection,
+        conn: &Connection,
+        toplevel_manager: Option<&ToplevelManagerState>,
+    ) -> zbus::Result<Self> {
+        let shared = Arc::new(Mutex::new(Shared {
+            conn: conn.clone(),
+            toplevel_manager: toplevel_manager.and_then(|state| state.manager().cloned()),
+            toplevel_capabilities: toplevel_manager
+                .map_or_else(Vec::new, |state| state.capabilities().to_vec()),
+            workspace_manager: None,
+            seat: None,
+            toplevels: Vec::new(),
+            workspaces: Vec::new(),
+        }));
+        dbus.object_server()
+            .at(TOPLEVELS_PATH, ToplevelsInterface(shared.clone()))?;
+        dbus.object_server()
+            .at(WORKSPACES_PATH, WorkspacesInterface(shared.clone()))?;
+        Ok(Self {
+            dbus: dbus.clone(),
+            shared,
+        })
+    }
+
+    /// Forward toplevel requests to the manager of `toplevel_manager`, if the capabilities it
+    /// last sent allow them, typically from
+    /// [`ToplevelManagerHandler::capabilities`](crate::toplevel_management::ToplevelManagerHandler::capabilities),
+    /// which is sent whenever the global is bound.
+    pub fn update_toplevel_manager(&self, toplevel_manager: Option<&ToplevelManagerState>) {
+        let mut shared = lock(&self.shared);
+        shared.toplevel_manager = toplevel_manager.and_then(|state| state.manager().cloned());
+        shared.toplevel_capabilities =
+            toplevel_manager.map_or_else(Vec::new, |state| state.capabilities().to_vec());
+    }
+
+    /// Seat used to activate toplevels
+    pub fn set_seat(&self, seat: Option<wl_seat::WlSeat>) {
+        lock(&self.shared).seat = seat;
+    }
+
+    /// Publish the toplevels of `toplevel_info_state`, typically from
+    /// [`ToplevelInfoHandler::info_done`](crate::toplevel_info::ToplevelInfoHandler::info_done).
+    pub fn update_toplevels(
+        &self,
+        toplevel_info_state: &ToplevelInfoState,
+        output_state: &OutputState,
+    ) -> zbus::Result<()> {
+        let toplevels = toplevel_info_state
+            .toplevels()
+            .map(|info| ToplevelData {
+                toplevel: ToplevelSnapshot::new(info, output_state).into(),
+                cosmic_toplevel: info.cosmic_toplevel.clone(),
+            })
+            .collect::<Vec<_>>();
+        {
+            let mut shared = lock(&self.shared);
+            let unchanged = shared
+                .toplevels
+                .iter()
+                .map(|data| &data.toplevel)
+                .eq(toplevels.iter().map(|data| &data.toplevel));
+            shared.toplevels = toplevels;
+            if unchanged {
+                return Ok(());
+            }
+        }
+        let iface = self
+            .dbus
+            .object_server()
+            .interface::<_, ToplevelsInterface>(TOPLEVELS_PATH)?;
+        zbus::block_on(iface.get().toplevels_changed(iface.signal_emitter()))
+    }
+
+    /// Publish the workspaces of `workspace_state`, typically from
+    /// [`WorkspaceHandler::done`](crate::workspace::WorkspaceHandler::done).
+    pub fn update_workspaces(
+        &self,
+        workspace_state: &WorkspaceState,
+        output_state: &OutputState,
+    ) -> zbus::Result<()> {
+        let groups = workspace_state
+            .workspace_groups()
+            .map(|group| WorkspaceGroupSnapshot::new(group, output_state))
+            .collect::<Vec<_>>();
+        let workspaces = workspace_state
+            .workspaces()
+            .map(|workspace| WorkspaceData {
+                workspace: Workspace::new(workspace.into(), &groups),
+                info: workspace.clone(),
+            })
+            .collect::<Vec<_>>();
+        {
+            let mut shared = lock(&self.shared);
+            shared.workspace_manager = workspace_state.workspace_manager().get().ok().cloned();
+            let unchanged = shared
+                .workspaces
+                .iter()
+                .map(|data| &data.workspace)
+                .eq(workspaces.iter().map(|data| &data.workspace));
+            shared.workspaces = workspaces;
+            if unchanged {
+                return Ok(());
+            }
+        }
+        let iface = self
+            .dbus
+            .object_server()
+            .interface::<_, WorkspacesInterface>(WORKSPACES_PATH)?;
+        zbus::block_on(iface.get().workspaces_changed(iface.signal_emitter()))
+    }
+}
+
+impl Drop for DbusBridge {
+    fn drop(&mut self) {
+        let _ = self
+            .dbus
+            .object_server()
+            .remove::<ToplevelsInterface, _>(TOPLEVELS_PATH);
+        let _ = self
+            .dbus
+            .object_server()
+            .remove::<WorkspacesInterface, _>(WORKSPACES_PATH);
+    }
+}
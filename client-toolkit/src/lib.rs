@@ -3,7 +3,15 @@ pub use sctk;
 pub use wayland_client;
 pub use wayland_protocols;
 
+pub mod calloop;
+pub mod cosmic_state;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+pub mod query;
+pub mod recording;
 pub mod screencopy;
+#[cfg(feature = "serde")]
+pub mod snapshot;
 pub mod toplevel_info;
 pub mod toplevel_management;
 pub mod workspace;
@@ -0,0 +1,101 @@
+//! Queries joining toplevels with the workspaces they are on.
+//!
+//! [`ToplevelInfo::workspace`] holds the same `ext_workspace_handle_v1` objects as
+//! [`WorkspaceState`]. A [`Query`] borrows both states and looks one up in the other.
+//!
+//! Both states only expose what was applied by the last `done` of their protocol, so results
+//! are consistent as long as the query is made after those events were dispatched. Workspaces
+//! of a toplevel that aren't known to the [`WorkspaceState`] (yet) are ignored.
+
+use wayland_client::protocol::wl_output;
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1,
+    workspace::v1::client::ext_workspace_handle_v1,
+};
+
+use crate::{
+    toplevel_info::{ToplevelInfo, ToplevelInfoState},
+    workspace::{Workspace, WorkspaceState},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Query<'a> {
+    toplevel_info: &'a ToplevelInfoState,
+    workspace: &'a WorkspaceState,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(toplevel_info: &'a ToplevelInfoState, workspace: &'a WorkspaceState) -> Self {
+        Self {
+            toplevel_info,
+            workspace,
+        }
+    }
+
+    /// Toplevels on `workspace`, in the order they were announced
+    pub fn toplevels_in_workspace(
+        &self,
+        workspace: &ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+    ) -> impl Iterator<Item = &'a ToplevelInfo> {
+        let known = self.workspace.workspace_info(workspace).is_some();
+        let workspace = workspace.clone();
+        self.toplevel_info
+            .toplevels()
+            .filter(move |info| known && info.workspace.contains(&workspace))
+    }
+
+    /// Active workspace of a workspace group on `output`
+    ///
+    /// If several are active, the first announced is returned.
+    pub fn active_workspace_for_output(
+        &self,
+        output: &wl_output::WlOutput,
+    ) -> Option<&'a Workspace> {
+        let workspace = self.workspace;
+        workspace
+            .workspace_groups()
+            .filter(|group| group.outputs.contains(output))
+            .flat_map(|group| &group.workspaces)
+            .filter_map(|handle| workspace.workspace_info(handle))
+            .filter(|info| is_active(info))
+            .min_by_key(|info| announced(workspace, info))
+    }
+
+    /// Toplevels on at least one active workspace, in the order they were announced
+    pub fn toplevels_on_active_workspaces(&self) -> impl Iterator<Item = &'a ToplevelInfo> {
+        let workspace = self.workspace;
+        self.toplevel_info.toplevels().filter(move |info| {
+            info.workspace
+                .iter()
+                .any(|handle| workspace.workspace_info(handle).is_some_and(is_active))
+        })
+    }
+
+    /// Workspace `toplevel` is on
+    ///
+    /// For a toplevel on several workspaces, an active one is preferred, and otherwise the
+    /// first announced is returned.
+    pub fn workspace_of_toplevel(
+        &self,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) -> Option<&'a Workspace> {
+        let workspace = self.workspace;
+        self.toplevel_info
+            .info(toplevel)?
+            .workspace
+            .iter()
+            .filter_map(|handle| workspace.workspace_info(handle))
+            .min_by_key(|info| (!is_active(info), announced(workspace, info)))
+    }
+}
+
+fn is_active(info: &Workspace) -> bool {
+    info.state.contains(ext_workspace_handle_v1::State::Active)
+}
+
+// Position in `WorkspaceState::workspaces`, so results don't depend on `HashSet` order
+fn announced(workspace: &WorkspaceState, info: &Workspace) -> Option<usize> {
+    workspace
+        .workspaces()
+        .position(|other| other.handle == info.handle)
+}
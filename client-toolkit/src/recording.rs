@@ -0,0 +1,206 @@
+use sctk::registry::RegistryState;
+use std::{
+    any::Any,
+    fmt::{self, Write},
+    ops::RangeInclusive,
+    os::fd::OwnedFd,
+    sync::{Arc, Mutex},
+};
+use wayland_client::{
+    Dispatch, Proxy, QueueHandle,
+    backend::{
+        Backend, ObjectData, ObjectId,
+        protocol::{Argument, Message},
+    },
+    globals::{BindError, GlobalList},
+    protocol::wl_registry,
+};
+
+/// Recording of the events received by a client, which can be replayed with
+/// `cosmic_mock_compositor::MockCompositor::replaying`.
+///
+/// Events are recorded for the objects created by states constructed with `new_recorded`:
+/// [`ToplevelInfoState`](crate::toplevel_info::ToplevelInfoState),
+/// [`WorkspaceState`](crate::workspace::WorkspaceState) and
+/// [`ScreencopyState`](crate::screencopy::ScreencopyState). Objects created by events of
+/// recorded objects are recorded as well.
+///
+/// The recording is text with one entry per line, written by its `Display` implementation:
+///
+/// ```text
+/// global <interface> <version>
+/// create <id> <interface> <version>
+/// event <id> <interface>.<event> <args>...
+/// ```
+///
+/// `global` lists the globals advertised when the recorder was created, and `create` an object
+/// created by the client. Event arguments are numbers, strings quoted with Rust escapes,
+/// `@<id>` for objects, `new@<id>` for objects created by the event, `[<hex>]` for arrays,
+/// `null` for null strings and objects, and `fd` for file descriptors, whose content isn't
+/// recorded. Lines starting with `#` are comments.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Recorder {
+    pub fn new(globals: &GlobalList) -> Self {
+        let recorder = Self::default();
+        globals.contents().with_list(|list| {
+            let mut lines = recorder.0.lock().unwrap();
+            for global in list {
+                lines.push(format!("global {} {}", global.interface, global.version));
+            }
+        });
+        recorder
+    }
+
+    fn record_event(&self, msg: &Message<ObjectId, OwnedFd>) {
+        let interface = msg.sender_id.interface();
+        let mut line = format!(
+            "event {} {}.{}",
+            msg.sender_id.protocol_id(),
+            interface.name,
+            interface.events[msg.opcode as usize].name
+        );
+        for arg in &msg.args {
+            line.push(' ');
+            match arg {
+                Argument::Int(value) | Argument::Fixed(value) => write!(line, "{}", value).unwrap(),
+                Argument::Uint(value) => write!(line, "{}", value).unwrap(),
+                Argument::Str(Some(value)) => {
+                    write!(line, "{:?}", String::from_utf8_lossy(value.as_bytes())).unwrap()
+                }
+                Argument::Object(id) if !id.is_null() => {
+                    write!(line, "@{}", id.protocol_id()).unwrap()
+                }
+                Argument::Str(None) | Argument::Object(_) => line.push_str("null"),
+                Argument::NewId(id) => write!(line, "new@{}", id.protocol_id()).unwrap(),
+                Argument::Array(bytes) => {
+                    line.push('[');
+                    for byte in bytes.iter() {
+                        write!(line, "{:02x}", byte).unwrap();
+                    }
+                    line.push(']');
+                }
+                Argument::Fd(_) => line.push_str("fd"),
+            }
+        }
+        self.0.lock().unwrap().push(line);
+    }
+}
+
+impl fmt::Display for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.0.lock().unwrap().iter() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+// Object data of a recorded object, recording events before passing them on to the original
+// object data
+struct RecordingData {
+    inner: Arc<dyn ObjectData>,
+    recorder: Recorder,
+}
+
+impl ObjectData for RecordingData {
+    fn event(
+        self: Arc<Self>,
+        backend: &Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        self.recorder.record_event(&msg);
+        let child = self.inner.clone().event(backend, msg)?;
+        Some(Arc::new(RecordingData {
+            inner: child,
+            recorder: self.recorder.clone(),
+        }))
+    }
+
+    fn destroyed(&self, object_id: ObjectId) {
+        self.inner.destroyed(object_id);
+    }
+
+    fn debug(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.debug(f)
+    }
+
+    fn data_as_any(&self) -> &dyn Any {
+        self.inner.data_as_any()
+    }
+}
+
+// Object data is wrapped when objects are created, since it can't be replaced safely later with
+// all wayland-backend versions.
+
+/// Send a request creating an object, recording its events if `recorder` is set
+pub(crate) fn create<P, I, D, U>(
+    recorder: Option<&Recorder>,
+    parent: &P,
+    request: P::Request<'_>,
+    qh: &QueueHandle<D>,
+    udata: U,
+) -> I
+where
+    P: Proxy,
+    I: Proxy + 'static,
+    D: Dispatch<I, U> + 'static,
+    U: Send + Sync + 'static,
+{
+    let mut data = qh.make_data::<I, U>(udata);
+    if let Some(recorder) = recorder {
+        data = Arc::new(RecordingData {
+            inner: data,
+            recorder: recorder.clone(),
+        });
+    }
+    let proxy = parent
+        .send_constructor::<I>(request, data)
+        .unwrap_or_else(|_| I::inert(parent.backend().clone()));
+    if let Some(recorder) = recorder {
+        recorder.0.lock().unwrap().push(format!(
+            "create {} {} {}",
+            proxy.id().protocol_id(),
+            I::interface().name,
+            proxy.version()
+        ));
+    }
+    proxy
+}
+
+/// Like `RegistryState::bind_one`, recording events of the global if `recorder` is set
+pub(crate) fn bind_one<I, D, U>(
+    recorder: Option<&Recorder>,
+    registry: &RegistryState,
+    qh: &QueueHandle<D>,
+    version: RangeInclusive<u32>,
+    udata: U,
+) -> Result<I, BindError>
+where
+    I: Proxy + 'static,
+    D: Dispatch<I, U> + 'static,
+    U: Send + Sync + 'static,
+{
+    let Some(recorder) = recorder else {
+        return registry.bind_one(qh, version, udata);
+    };
+    let global = registry
+        .globals_by_interface(I::interface().name)
+        .next()
+        .ok_or(BindError::NotPresent)?;
+    if global.version < *version.start() {
+        return Err(BindError::UnsupportedVersion);
+    }
+    let request = wl_registry::Request::Bind {
+        name: global.name,
+        id: (I::interface(), global.version.min(*version.end())),
+    };
+    Ok(create(
+        Some(recorder),
+        registry.registry(),
+        request,
+        qh,
+        udata,
+    ))
+}
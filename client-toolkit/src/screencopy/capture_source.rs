@@ -10,11 +10,46 @@ use super::Capturer;
 use crate::GlobalData;
 
 #[derive(Debug)]
-pub struct CaptureSourceError(CaptureSourceKind);
+pub struct CaptureSourceError(Reason);
+
+#[derive(Debug)]
+enum Reason {
+    /// No source manager global for this kind of capture source
+    Unsupported(CaptureSourceKind),
+    /// No `ext_image_copy_capture_manager_v1` global to create sessions with
+    NoCopyManager,
+}
+
+impl CaptureSourceError {
+    pub(crate) fn unsupported(kind: CaptureSourceKind) -> Self {
+        Self(Reason::Unsupported(kind))
+    }
+
+    pub(crate) fn no_copy_manager() -> Self {
+        Self(Reason::NoCopyManager)
+    }
+
+    /// The kind of capture source the compositor doesn't support, or `None` if it doesn't
+    /// support `ext_image_copy_capture_manager_v1`
+    pub fn kind(&self) -> Option<CaptureSourceKind> {
+        match self.0 {
+            Reason::Unsupported(kind) => Some(kind),
+            Reason::NoCopyManager => None,
+        }
+    }
+}
 
 impl fmt::Display for CaptureSourceError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(f, "capture kind '{:?}' unsupported by compositor", self.0)
+        match self.0 {
+            Reason::Unsupported(kind) => {
+                write!(f, "capture kind '{:?}' unsupported by compositor", kind)
+            }
+            Reason::NoCopyManager => write!(
+                f,
+                "ext_image_copy_capture_manager_v1 unsupported by compositor"
+            ),
+        }
     }
 }
 
@@ -75,7 +110,7 @@ impl CaptureSource {
                 }
             }
         }
-        Err(CaptureSourceError(self.kind()))
+        Err(CaptureSourceError::unsupported(self.kind()))
     }
 }
 
@@ -0,0 +1,189 @@
+// Shm buffers for screencopy helpers, and conversion of their contents to RGBA
+
+use sctk::shm::{CreatePoolError, Shm, raw::RawPool};
+use wayland_client::{
+    Dispatch, QueueHandle, WEnum,
+    protocol::{wl_buffer, wl_output, wl_shm},
+};
+
+use super::{Formats, Rect};
+
+// Supported shm formats, in order of preference
+const SHM_FORMATS: [wl_shm::Format; 4] = [
+    wl_shm::Format::Abgr8888,
+    wl_shm::Format::Xbgr8888,
+    wl_shm::Format::Argb8888,
+    wl_shm::Format::Xrgb8888,
+];
+
+pub(crate) fn shm_format(formats: &Formats) -> Option<wl_shm::Format> {
+    SHM_FORMATS
+        .into_iter()
+        .find(|format| formats.shm_formats.contains(format))
+}
+
+#[derive(Debug)]
+pub(crate) struct ShmBuffer {
+    pool: RawPool,
+    pub buffer: wl_buffer::WlBuffer,
+    pub size: (u32, u32),
+    format: wl_shm::Format,
+}
+
+impl ShmBuffer {
+    pub fn new<D>(
+        shm: &Shm,
+        size: (u32, u32),
+        format: wl_shm::Format,
+        qh: &QueueHandle<D>,
+    ) -> Result<Self, CreatePoolError>
+    where
+        D: Dispatch<wl_buffer::WlBuffer, ()> + 'static,
+    {
+        let (width, height) = size;
+        let mut pool = RawPool::new(width as usize * height as usize * 4, shm)?;
+        let buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            width as i32 * 4,
+            format,
+            (),
+            qh,
+        );
+        Ok(Self {
+            pool,
+            buffer,
+            size,
+            format,
+        })
+    }
+
+    pub fn format(&self) -> wl_shm::Format {
+        self.format
+    }
+
+    pub fn full_damage(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: self.size.0 as i32,
+            height: self.size.1 as i32,
+        }
+    }
+
+    /// Copy buffer contents, converting them to RGBA
+    pub fn read(&mut self, transform: WEnum<wl_output::Transform>) -> BufferImage {
+        let (width, height) = self.size;
+        let mut data = self.pool.mmap()[..width as usize * height as usize * 4].to_vec();
+        for pixel in data.chunks_exact_mut(4) {
+            match self.format {
+                wl_shm::Format::Argb8888 => pixel.swap(0, 2),
+                wl_shm::Format::Xrgb8888 => {
+                    pixel.swap(0, 2);
+                    pixel[3] = 255;
+                }
+                wl_shm::Format::Xbgr8888 => pixel[3] = 255,
+                _ => {}
+            }
+        }
+        let transform = match transform {
+            WEnum::Value(transform) => transform,
+            WEnum::Unknown(_) => wl_output::Transform::Normal,
+        };
+        BufferImage {
+            width,
+            height,
+            transform,
+            data,
+        }
+    }
+}
+
+impl Drop for ShmBuffer {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+    }
+}
+
+// Captured buffer, converted to RGBA, but still in buffer coordinates
+#[derive(Debug)]
+pub(crate) struct BufferImage {
+    width: u32,
+    height: u32,
+    transform: wl_output::Transform,
+    data: Vec<u8>,
+}
+
+impl BufferImage {
+    // Size in the orientation of the source's logical coordinate space
+    pub fn logical_size(&self) -> (u32, u32) {
+        if is_rotated(self.transform) {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+
+    // RGBA value at `x`, `y` in the source's logical orientation, undoing the transform the
+    // compositor applied to the buffer contents
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let (w, h) = self.logical_size();
+        let (bx, by) = match self.transform {
+            wl_output::Transform::_90 => (y, w - 1 - x),
+            wl_output::Transform::_180 => (w - 1 - x, h - 1 - y),
+            wl_output::Transform::_270 => (h - 1 - y, x),
+            wl_output::Transform::Flipped => (w - 1 - x, y),
+            wl_output::Transform::Flipped90 => (y, x),
+            wl_output::Transform::Flipped180 => (x, h - 1 - y),
+            wl_output::Transform::Flipped270 => (h - 1 - y, w - 1 - x),
+            _ => (x, y),
+        };
+        let idx = (by as usize * self.width as usize + bx as usize) * 4;
+        self.data[idx..idx + 4].try_into().unwrap()
+    }
+
+    /// Downscale to fit within `max_size`, preserving aspect ratio, by averaging pixels
+    pub fn scale_to_fit(&self, max_size: (u32, u32)) -> (u32, u32, Vec<u8>) {
+        let (width, height) = self.logical_size();
+        if width == 0 || height == 0 {
+            return (0, 0, Vec::new());
+        }
+        let factor = (max_size.0 as f64 / width as f64)
+            .min(max_size.1 as f64 / height as f64)
+            .min(1.);
+        let out_width = ((width as f64 * factor).round() as u32).max(1);
+        let out_height = ((height as f64 * factor).round() as u32).max(1);
+
+        let mut data = Vec::with_capacity(out_width as usize * out_height as usize * 4);
+        for y in 0..out_height {
+            let y0 = y * height / out_height;
+            let y1 = ((y + 1) * height / out_height).max(y0 + 1);
+            for x in 0..out_width {
+                let x0 = x * width / out_width;
+                let x1 = ((x + 1) * width / out_width).max(x0 + 1);
+                let mut sum = [0u32; 4];
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        for (sum, value) in sum.iter_mut().zip(self.pixel(sx, sy)) {
+                            *sum += u32::from(value);
+                        }
+                    }
+                }
+                let count = (x1 - x0) * (y1 - y0);
+                data.extend(sum.map(|sum| (sum / count) as u8));
+            }
+        }
+        (out_width, out_height, data)
+    }
+}
+
+fn is_rotated(transform: wl_output::Transform) -> bool {
+    matches!(
+        transform,
+        wl_output::Transform::_90
+            | wl_output::Transform::_270
+            | wl_output::Transform::Flipped90
+            | wl_output::Transform::Flipped270
+    )
+}
@@ -1,5 +1,10 @@
 use cosmic_protocols::image_capture_source::v1::client::zcosmic_workspace_image_capture_source_manager_v1;
+use sctk::registry::{ProvidesRegistryState, RegistryHandler, RegistryState};
 use std::{
+    collections::HashSet,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
     sync::{Arc, Mutex, OnceLock, Weak},
     time::Duration,
 };
@@ -22,11 +27,25 @@ use wayland_protocols::ext::{
 pub use ext_image_copy_capture_frame_v1::FailureReason;
 pub use ext_image_copy_capture_manager_v1::Options as CaptureOptions;
 
-use crate::GlobalData;
+use crate::{
+    GlobalData,
+    recording::{self, Recorder},
+};
 
 mod capture_source;
 pub use capture_source::{CaptureSource, CaptureSourceError, CaptureSourceKind};
 mod dispatch;
+mod image;
+mod region;
+pub use region::{RegionCapture, RegionCaptureError, RegionImage};
+mod stats;
+pub use stats::{FrameStats, clock_monotonic};
+mod thumbnail;
+pub use thumbnail::Thumbnail;
+mod toplevel_previews;
+pub use toplevel_previews::ToplevelPreviews;
+mod workspace_thumbnails;
+pub use workspace_thumbnails::WorkspaceThumbnails;
 
 #[derive(Clone, Debug)]
 pub struct Rect {
@@ -40,7 +59,7 @@ pub struct Rect {
 pub struct Frame {
     pub transform: WEnum<Transform>,
     pub damage: Vec<Rect>,
-    // XXX monotonic? Is this used elsewhere in wayland?
+    /// Time the frame was presented, in `CLOCK_MONOTONIC` (see [`clock_monotonic`])
     pub present_time: Option<Duration>,
 }
 
@@ -68,6 +87,7 @@ struct CapturerInner {
     output_source_manager: Option<ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1>,
     foreign_toplevel_source_manager: Option<ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1>,
     workspace_source_manager: Option<zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1>,
+    recorder: Option<Recorder>,
 }
 
 impl Drop for CapturerInner {
@@ -91,7 +111,53 @@ impl Drop for CapturerInner {
 pub struct Capturer(Arc<CapturerInner>);
 
 impl Capturer {
-    // TODO check supported capture types
+    /// Capture source kinds that can be used to create sessions with this capturer
+    pub fn supported_sources(&self) -> HashSet<CaptureSourceKind> {
+        let mut kinds = HashSet::new();
+        if self.0.image_copy_capture_manager.is_none() {
+            return kinds;
+        }
+        if self.0.output_source_manager.is_some() {
+            kinds.insert(CaptureSourceKind::Output);
+        }
+        if self.0.foreign_toplevel_source_manager.is_some() {
+            kinds.insert(CaptureSourceKind::Toplevel);
+        }
+        if self.0.workspace_source_manager.is_some() {
+            kinds.insert(CaptureSourceKind::Workspace);
+        }
+        kinds
+    }
+
+    /// Whether sessions can be created for capture sources of `kind`
+    pub fn supports_source(&self, kind: CaptureSourceKind) -> bool {
+        self.supported_sources().contains(&kind)
+    }
+
+    /// Whether `create_cursor_session` is available
+    pub fn supports_cursor_sessions(&self) -> bool {
+        self.0.image_copy_capture_manager.is_some()
+    }
+
+    /// Which of the screencopy globals were bound
+    pub fn globals(&self) -> ScreencopyGlobals {
+        ScreencopyGlobals {
+            image_copy_capture_manager: self.0.image_copy_capture_manager.is_some(),
+            output_source_manager: self.0.output_source_manager.is_some(),
+            foreign_toplevel_source_manager: self.0.foreign_toplevel_source_manager.is_some(),
+            workspace_source_manager: self.0.workspace_source_manager.is_some(),
+        }
+    }
+
+    fn image_copy_capture_manager(
+        &self,
+    ) -> Result<&ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, CaptureSourceError>
+    {
+        self.0
+            .image_copy_capture_manager
+            .as_ref()
+            .ok_or_else(CaptureSourceError::no_copy_manager)
+    }
 
     pub fn create_session<D, U>(
         &self,
@@ -106,21 +172,26 @@ impl Capturer {
         D: Dispatch<ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1, U>,
         U: ScreencopySessionDataExt + Send + Sync + 'static,
     {
+        let manager = self.image_copy_capture_manager()?;
         let source = source.create_source(self, qh)?;
+        let recorder = self.0.recorder.clone();
         Ok(CaptureSession(Arc::new_cyclic(|weak_session| {
             udata
                 .screencopy_session_data()
                 .session
                 .set(weak_session.clone())
                 .unwrap();
-            CaptureSessionInner {
-                session: self
-                    .0
-                    .image_copy_capture_manager
-                    .as_ref()
-                    .expect("ext capture source with no image capture copy manager")
-                    .create_session(&source.0, options, qh, udata),
-            }
+            let session = recording::create(
+                recorder.as_ref(),
+                manager,
+                ext_image_copy_capture_manager_v1::Request::CreateSession {
+                    source: source.0.clone(),
+                    options: WEnum::Value(options),
+                },
+                qh,
+                udata,
+            );
+            CaptureSessionInner { session, recorder }
         })))
     }
 
@@ -140,28 +211,48 @@ impl Capturer {
             >,
         U: ScreencopyCursorSessionDataExt + Send + Sync + 'static,
     {
+        let manager = self.image_copy_capture_manager()?;
         let source = source.create_source(self, qh)?;
+        let recorder = self.0.recorder.clone();
         Ok(CaptureCursorSession(Arc::new_cyclic(|weak_session| {
             udata
                 .screencopy_cursor_session_data()
                 .session
                 .set(weak_session.clone())
                 .unwrap();
-            CaptureCursorSessionInner {
-                session: self
-                    .0
-                    .image_copy_capture_manager
-                    .as_ref()
-                    .expect("ext capture source with no image capture copy manager")
-                    .create_pointer_cursor_session(&source.0, pointer, qh, udata),
-            }
+            let session = recording::create(
+                recorder.as_ref(),
+                manager,
+                ext_image_copy_capture_manager_v1::Request::CreatePointerCursorSession {
+                    source: source.0.clone(),
+                    pointer: pointer.clone(),
+                },
+                qh,
+                udata,
+            );
+            CaptureCursorSessionInner { session, recorder }
         })))
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug)]
 struct CaptureSessionInner {
     session: ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+    recorder: Option<Recorder>,
+}
+
+impl PartialEq for CaptureSessionInner {
+    fn eq(&self, other: &Self) -> bool {
+        self.session == other.session
+    }
+}
+
+impl Eq for CaptureSessionInner {}
+
+impl Hash for CaptureSessionInner {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.session.hash(state);
+    }
 }
 
 impl Drop for CaptureSessionInner {
@@ -191,7 +282,13 @@ impl CaptureSession {
             .session
             .set(Arc::downgrade(&self.0))
             .unwrap();
-        let frame = self.0.session.create_frame(qh, udata);
+        let frame = recording::create(
+            self.0.recorder.as_ref(),
+            &self.0.session,
+            ext_image_copy_capture_session_v1::Request::CreateFrame {},
+            qh,
+            udata,
+        );
         frame.attach_buffer(buffer);
         for Rect {
             x,
@@ -235,9 +332,24 @@ impl CaptureFrame {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug)]
 struct CaptureCursorSessionInner {
     session: ext_image_copy_capture_cursor_session_v1::ExtImageCopyCaptureCursorSessionV1,
+    recorder: Option<Recorder>,
+}
+
+impl PartialEq for CaptureCursorSessionInner {
+    fn eq(&self, other: &Self) -> bool {
+        self.session == other.session
+    }
+}
+
+impl Eq for CaptureCursorSessionInner {}
+
+impl Hash for CaptureCursorSessionInner {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.session.hash(state);
+    }
 }
 
 impl Drop for CaptureCursorSessionInner {
@@ -267,19 +379,85 @@ impl CaptureCursorSession {
                 .session
                 .set(weak_session.clone())
                 .unwrap();
+            let session = recording::create(
+                self.0.recorder.as_ref(),
+                &self.0.session,
+                ext_image_copy_capture_cursor_session_v1::Request::GetCaptureSession {},
+                qh,
+                udata,
+            );
             CaptureSessionInner {
-                session: self.0.session.get_capture_session(qh, udata),
+                session,
+                recorder: self.0.recorder.clone(),
             }
         })))
     }
 }
 
+/// Which screencopy related globals the compositor advertised
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScreencopyGlobals {
+    pub image_copy_capture_manager: bool,
+    pub output_source_manager: bool,
+    pub foreign_toplevel_source_manager: bool,
+    pub workspace_source_manager: bool,
+}
+
+#[derive(Debug)]
+pub struct ScreencopyInitError {
+    pub globals: ScreencopyGlobals,
+}
+
+impl fmt::Display for ScreencopyInitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        if !self.globals.image_copy_capture_manager {
+            write!(
+                f,
+                "ext_image_copy_capture_manager_v1 unsupported by compositor"
+            )
+        } else {
+            write!(f, "no image capture source manager supported by compositor")
+        }
+    }
+}
+
+impl Error for ScreencopyInitError {}
+
 #[derive(Debug)]
 pub struct ScreencopyState {
     capturer: Capturer,
 }
 
 impl ScreencopyState {
+    /// Bind screencopy globals, failing if no capture session could be created with them.
+    ///
+    /// The returned error reports which of the globals were found.
+    pub fn try_new<D>(globals: &GlobalList, qh: &QueueHandle<D>) -> Result<Self, ScreencopyInitError>
+    where
+        D: 'static,
+        D: Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, GlobalData>,
+        D: Dispatch<ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1, GlobalData>,
+        D: Dispatch<ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1, GlobalData>,
+        D: Dispatch<zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalData>,
+    {
+        Self::new(globals, qh).supported()
+    }
+
+    /// Like [`try_new`](Self::try_new), binding the globals through a [`RegistryState`].
+    pub fn try_from_registry<D>(
+        registry: &RegistryState,
+        qh: &QueueHandle<D>,
+    ) -> Result<Self, ScreencopyInitError>
+    where
+        D: 'static,
+        D: Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, GlobalData>,
+        D: Dispatch<ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1, GlobalData>,
+        D: Dispatch<ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1, GlobalData>,
+        D: Dispatch<zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalData>,
+    {
+        Self::bind_registry(registry, qh, None).supported()
+    }
+
     pub fn new<D>(globals: &GlobalList, qh: &QueueHandle<D>) -> Self
     where
         D: 'static,
@@ -288,26 +466,161 @@ impl ScreencopyState {
         D: Dispatch<ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1, GlobalData>,
         D: Dispatch<zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalData>,
     {
-        let image_copy_capture_manager = globals.bind(qh, 1..=1, GlobalData).ok();
-        let output_source_manager = globals.bind(qh, 1..=1, GlobalData).ok();
-        let foreign_toplevel_source_manager = globals.bind(qh, 1..=1, GlobalData).ok();
-        let workspace_source_manager = globals.bind(qh, 1..=1, GlobalData).ok();
+        Self::bind(globals, qh, None)
+    }
+
+    /// Like [`new`](Self::new), recording the events of capture sessions and frames.
+    pub fn new_recorded<D>(globals: &GlobalList, qh: &QueueHandle<D>, recorder: &Recorder) -> Self
+    where
+        D: 'static,
+        D: Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, GlobalData>,
+        D: Dispatch<ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1, GlobalData>,
+        D: Dispatch<ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1, GlobalData>,
+        D: Dispatch<zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalData>,
+    {
+        Self::bind(globals, qh, Some(recorder.clone()))
+    }
+
+    fn bind<D>(globals: &GlobalList, qh: &QueueHandle<D>, recorder: Option<Recorder>) -> Self
+    where
+        D: 'static,
+        D: Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, GlobalData>,
+        D: Dispatch<ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1, GlobalData>,
+        D: Dispatch<ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1, GlobalData>,
+        D: Dispatch<zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalData>,
+    {
+        Self::from_managers(
+            globals.bind(qh, 1..=1, GlobalData).ok(),
+            globals.bind(qh, 1..=1, GlobalData).ok(),
+            globals.bind(qh, 1..=1, GlobalData).ok(),
+            globals.bind(qh, 1..=1, GlobalData).ok(),
+            recorder,
+        )
+    }
+
+    fn bind_registry<D>(
+        registry: &RegistryState,
+        qh: &QueueHandle<D>,
+        recorder: Option<Recorder>,
+    ) -> Self
+    where
+        D: 'static,
+        D: Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, GlobalData>,
+        D: Dispatch<ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1, GlobalData>,
+        D: Dispatch<ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1, GlobalData>,
+        D: Dispatch<zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalData>,
+    {
+        Self::from_managers(
+            registry.bind_one(qh, 1..=1, GlobalData).ok(),
+            registry.bind_one(qh, 1..=1, GlobalData).ok(),
+            registry.bind_one(qh, 1..=1, GlobalData).ok(),
+            registry.bind_one(qh, 1..=1, GlobalData).ok(),
+            recorder,
+        )
+    }
 
+    fn from_managers(
+        image_copy_capture_manager: Option<
+            ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1,
+        >,
+        output_source_manager: Option<
+            ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+        >,
+        foreign_toplevel_source_manager: Option<
+            ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+        >,
+        workspace_source_manager: Option<
+            zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+        >,
+        recorder: Option<Recorder>,
+    ) -> Self {
         let capturer = Capturer(Arc::new(CapturerInner {
             image_copy_capture_manager,
             output_source_manager,
             foreign_toplevel_source_manager,
             workspace_source_manager,
+            recorder,
         }));
 
         Self { capturer }
     }
 
+    fn supported(self) -> Result<Self, ScreencopyInitError> {
+        if self.capturer.supported_sources().is_empty() {
+            return Err(ScreencopyInitError {
+                globals: self.capturer.globals(),
+            });
+        }
+        Ok(self)
+    }
+
     pub fn capturer(&self) -> &Capturer {
         &self.capturer
     }
 }
 
+/// Rebinds the globals when one of them is added or removed, calling
+/// [`ScreencopyHandler::globals_changed`]. Sessions created before keep using the previous
+/// globals.
+impl<D> RegistryHandler<D> for ScreencopyState
+where
+    D: ProvidesRegistryState + ScreencopyHandler + 'static,
+    D: Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, GlobalData>,
+    D: Dispatch<ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1, GlobalData>,
+    D: Dispatch<ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1, GlobalData>,
+    D: Dispatch<zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalData>,
+{
+    fn new_global(
+        data: &mut D,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+        _name: u32,
+        interface: &str,
+        _version: u32,
+    ) {
+        if is_screencopy_global(interface) {
+            rebind(data, conn, qh);
+        }
+    }
+
+    fn remove_global(
+        data: &mut D,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+        _name: u32,
+        interface: &str,
+    ) {
+        if is_screencopy_global(interface) {
+            rebind(data, conn, qh);
+        }
+    }
+}
+
+pub(crate) fn is_screencopy_global(interface: &str) -> bool {
+    [
+        ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1::interface().name,
+        ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1::interface().name,
+        ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1::interface().name,
+        zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1::interface().name,
+    ]
+    .contains(&interface)
+}
+
+fn rebind<D>(data: &mut D, conn: &Connection, qh: &QueueHandle<D>)
+where
+    D: ProvidesRegistryState + ScreencopyHandler + 'static,
+    D: Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, GlobalData>,
+    D: Dispatch<ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1, GlobalData>,
+    D: Dispatch<ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1, GlobalData>,
+    D: Dispatch<zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalData>,
+{
+    let recorder = data.screencopy_state().capturer.0.recorder.clone();
+    let state = ScreencopyState::bind_registry(data.registry(), qh, recorder);
+    let globals = state.capturer.globals();
+    *data.screencopy_state() = state;
+    data.globals_changed(conn, qh, globals);
+}
+
 pub trait ScreencopyHandler: Sized {
     fn screencopy_state(&mut self) -> &mut ScreencopyState;
 
@@ -372,6 +685,15 @@ pub trait ScreencopyHandler: Sized {
         _y: i32,
     ) {
     }
+
+    /// The screencopy globals were added or removed, and [`ScreencopyState`] rebound
+    fn globals_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _globals: ScreencopyGlobals,
+    ) {
+    }
 }
 
 pub trait ScreencopySessionDataExt {
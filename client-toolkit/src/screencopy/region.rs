@@ -0,0 +1,362 @@
+//! Capture a rectangle in the global logical coordinate space, which may span multiple outputs.
+
+use sctk::{
+    output::OutputState,
+    shm::{CreatePoolError, Shm},
+};
+use std::{error::Error, fmt};
+use wayland_client::{
+    Dispatch, QueueHandle, WEnum,
+    protocol::{wl_buffer, wl_output},
+};
+use wayland_protocols::ext::{
+    image_capture_source::v1::client::ext_image_capture_source_v1,
+    image_copy_capture::v1::client::{
+        ext_image_copy_capture_frame_v1, ext_image_copy_capture_session_v1,
+    },
+};
+
+use super::{
+    CaptureFrame, CaptureOptions, CaptureSession, CaptureSource, CaptureSourceError, Capturer,
+    FailureReason, Formats, Frame, Rect, ScreencopyFrameData, ScreencopySessionData,
+    image::{BufferImage, ShmBuffer, shm_format},
+};
+use crate::GlobalData;
+
+#[derive(Debug)]
+pub enum RegionCaptureError {
+    /// Compositor didn't offer any shm format supported by `RegionCapture`
+    NoSupportedFormat,
+    Shm(CreatePoolError),
+    /// No session could be created for the output
+    Session(CaptureSourceError),
+    Failed(WEnum<FailureReason>),
+    /// Session was stopped before a frame was captured
+    Stopped,
+}
+
+impl fmt::Display for RegionCaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::NoSupportedFormat => write!(f, "no supported shm format offered by compositor"),
+            Self::Shm(err) => write!(f, "failed to create shm pool: {}", err),
+            Self::Session(err) => write!(f, "failed to create capture session: {}", err),
+            Self::Failed(reason) => write!(f, "capture failed: {:?}", reason),
+            Self::Stopped => write!(f, "capture session stopped"),
+        }
+    }
+}
+
+impl Error for RegionCaptureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Shm(err) => Some(err),
+            Self::Session(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum OutputCaptureState {
+    WaitingForFormats,
+    Capturing {
+        frame: CaptureFrame,
+        buffer: ShmBuffer,
+    },
+    Ready(BufferImage),
+    Failed(RegionCaptureError),
+}
+
+#[derive(Debug)]
+struct OutputCapture {
+    output: wl_output::WlOutput,
+    geometry: Rect,
+    session: Option<CaptureSession>,
+    state: OutputCaptureState,
+}
+
+impl OutputCapture {
+    fn finish(&mut self, state: OutputCaptureState) {
+        self.state = state;
+        self.session = None;
+    }
+}
+
+/// Result of a [`RegionCapture`]
+#[derive(Debug)]
+pub struct RegionImage {
+    pub width: u32,
+    pub height: u32,
+    /// Buffer pixels per logical pixel; the highest scale of the captured outputs
+    pub scale: f64,
+    /// RGBA8 pixel data. Areas not covered by a successfully captured output are transparent.
+    pub data: Vec<u8>,
+    /// Outputs intersecting the region that could not be captured
+    pub failed: Vec<(wl_output::WlOutput, RegionCaptureError)>,
+}
+
+/// Captures every output intersecting a region and stitches the result into one image.
+///
+/// Sessions for all outputs are created at once, so they are captured in parallel. The
+/// `ScreencopyHandler` implementation has to forward `init_done`, `ready`, `failed` and
+/// `stopped` to the methods of the same name, which return `false` for sessions and frames
+/// that don't belong to this capture.
+#[derive(Debug)]
+pub struct RegionCapture {
+    region: Rect,
+    outputs: Vec<OutputCapture>,
+}
+
+impl RegionCapture {
+    pub fn new<D>(
+        capturer: &Capturer,
+        output_state: &OutputState,
+        region: Rect,
+        options: CaptureOptions,
+        qh: &QueueHandle<D>,
+    ) -> Self
+    where
+        D: 'static,
+        D: Dispatch<ext_image_capture_source_v1::ExtImageCaptureSourceV1, GlobalData>,
+        D: Dispatch<
+                ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+                ScreencopySessionData,
+            >,
+    {
+        let mut outputs = Vec::new();
+        for output in output_state.outputs() {
+            let Some(info) = output_state.info(&output) else {
+                continue;
+            };
+            let (Some((x, y)), Some((width, height))) = (info.logical_position, info.logical_size)
+            else {
+                continue;
+            };
+            let geometry = Rect {
+                x,
+                y,
+                width,
+                height,
+            };
+            if intersection(&region, &geometry).is_none() {
+                continue;
+            }
+            let (session, state) = match capturer.create_session(
+                &CaptureSource::Output(output.clone()),
+                options,
+                qh,
+                ScreencopySessionData::default(),
+            ) {
+                Ok(session) => (Some(session), OutputCaptureState::WaitingForFormats),
+                Err(err) => (
+                    None,
+                    OutputCaptureState::Failed(RegionCaptureError::Session(err)),
+                ),
+            };
+            outputs.push(OutputCapture {
+                output,
+                geometry,
+                session,
+                state,
+            });
+        }
+        Self { region, outputs }
+    }
+
+    pub fn region(&self) -> &Rect {
+        &self.region
+    }
+
+    /// Outputs intersecting the region
+    pub fn outputs(&self) -> impl Iterator<Item = &wl_output::WlOutput> {
+        self.outputs.iter().map(|o| &o.output)
+    }
+
+    /// Whether every output has either been captured or failed
+    pub fn is_done(&self) -> bool {
+        self.outputs.iter().all(|o| {
+            matches!(
+                o.state,
+                OutputCaptureState::Ready(_) | OutputCaptureState::Failed(_)
+            )
+        })
+    }
+
+    pub fn init_done<D>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+        session: &CaptureSession,
+        formats: &Formats,
+    ) -> bool
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        let Some(capture) = self
+            .outputs
+            .iter_mut()
+            .find(|o| o.session.as_ref() == Some(session))
+        else {
+            return false;
+        };
+        // Formats may be sent again; only capture once
+        if !matches!(capture.state, OutputCaptureState::WaitingForFormats) {
+            return true;
+        }
+
+        let Some(format) = shm_format(formats) else {
+            capture.finish(OutputCaptureState::Failed(
+                RegionCaptureError::NoSupportedFormat,
+            ));
+            return true;
+        };
+        let buffer = match ShmBuffer::new(shm, formats.buffer_size, format, qh) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                capture.finish(OutputCaptureState::Failed(RegionCaptureError::Shm(err)));
+                return true;
+            }
+        };
+        let frame = session.capture(
+            &buffer.buffer,
+            &[buffer.full_damage()],
+            qh,
+            ScreencopyFrameData::default(),
+        );
+        capture.state = OutputCaptureState::Capturing { frame, buffer };
+        true
+    }
+
+    pub fn ready(&mut self, capture_frame: &CaptureFrame, frame: &Frame) -> bool {
+        let Some(capture) = self.output_for_frame(capture_frame) else {
+            return false;
+        };
+        let OutputCaptureState::Capturing { buffer, .. } = &mut capture.state else {
+            return false;
+        };
+        let image = buffer.read(frame.transform);
+        capture.finish(OutputCaptureState::Ready(image));
+        true
+    }
+
+    pub fn failed(&mut self, capture_frame: &CaptureFrame, reason: WEnum<FailureReason>) -> bool {
+        let Some(capture) = self.output_for_frame(capture_frame) else {
+            return false;
+        };
+        capture.finish(OutputCaptureState::Failed(RegionCaptureError::Failed(
+            reason,
+        )));
+        true
+    }
+
+    pub fn stopped(&mut self, session: &CaptureSession) -> bool {
+        let Some(capture) = self
+            .outputs
+            .iter_mut()
+            .find(|o| o.session.as_ref() == Some(session))
+        else {
+            return false;
+        };
+        capture.finish(OutputCaptureState::Failed(RegionCaptureError::Stopped));
+        true
+    }
+
+    fn output_for_frame(&mut self, capture_frame: &CaptureFrame) -> Option<&mut OutputCapture> {
+        self.outputs.iter_mut().find(|o| {
+            matches!(&o.state, OutputCaptureState::Capturing { frame, .. } if frame == capture_frame)
+        })
+    }
+
+    /// Stitch captured outputs into a single image.
+    ///
+    /// Outputs that haven't finished capturing yet are reported as failed with
+    /// [`RegionCaptureError::Stopped`].
+    pub fn finish(mut self) -> RegionImage {
+        let mut failed = Vec::new();
+        let mut images = Vec::new();
+        for capture in self.outputs.drain(..) {
+            match capture.state {
+                OutputCaptureState::Ready(image) => images.push((capture.geometry, image)),
+                OutputCaptureState::Failed(err) => failed.push((capture.output, err)),
+                _ => failed.push((capture.output, RegionCaptureError::Stopped)),
+            }
+        }
+
+        let scale = images
+            .iter()
+            .map(|(geometry, image)| image.logical_size().0 as f64 / geometry.width as f64)
+            .reduce(f64::max)
+            .unwrap_or(1.);
+        let width = (self.region.width.max(0) as f64 * scale).round() as u32;
+        let height = (self.region.height.max(0) as f64 * scale).round() as u32;
+
+        let mut data = vec![0; width as usize * height as usize * 4];
+        for (geometry, image) in &images {
+            let (image_width, image_height) = image.logical_size();
+            if image_width == 0 || image_height == 0 {
+                continue;
+            }
+            let output_scale_x = image_width as f64 / geometry.width as f64;
+            let output_scale_y = image_height as f64 / geometry.height as f64;
+            let Some(area) = intersection(&self.region, geometry) else {
+                continue;
+            };
+            // Destination pixels covered by this output
+            let x_start = ((area.x - self.region.x) as f64 * scale).floor() as u32;
+            let y_start = ((area.y - self.region.y) as f64 * scale).floor() as u32;
+            let x_end =
+                (((area.x + area.width - self.region.x) as f64 * scale).ceil() as u32).min(width);
+            let y_end =
+                (((area.y + area.height - self.region.y) as f64 * scale).ceil() as u32).min(height);
+            for y in y_start..y_end {
+                // Sample at pixel center, in logical coordinates relative to output
+                let logical_y = self.region.y as f64 + (y as f64 + 0.5) / scale - geometry.y as f64;
+                if logical_y < 0. || logical_y >= geometry.height as f64 {
+                    continue;
+                }
+                let src_y = ((logical_y * output_scale_y) as u32).min(image_height - 1);
+                for x in x_start..x_end {
+                    let logical_x =
+                        self.region.x as f64 + (x as f64 + 0.5) / scale - geometry.x as f64;
+                    if logical_x < 0. || logical_x >= geometry.width as f64 {
+                        continue;
+                    }
+                    let src_x = ((logical_x * output_scale_x) as u32).min(image_width - 1);
+                    let idx = (y as usize * width as usize + x as usize) * 4;
+                    data[idx..idx + 4].copy_from_slice(&image.pixel(src_x, src_y));
+                }
+            }
+        }
+
+        RegionImage {
+            width,
+            height,
+            scale,
+            data,
+            failed,
+        }
+    }
+}
+
+fn intersection(a: &Rect, b: &Rect) -> Option<Rect> {
+    let x = a.x.max(b.x);
+    let y = a.y.max(b.y);
+    let width = (a.x + a.width).min(b.x + b.width) - x;
+    let height = (a.y + a.height).min(b.y + b.height) - y;
+    if width > 0 && height > 0 {
+        Some(Rect {
+            x,
+            y,
+            width,
+            height,
+        })
+    } else {
+        None
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use super::Frame;
+
+/// Current time of `CLOCK_MONOTONIC`, the clock [`Frame::present_time`] is in
+pub fn clock_monotonic() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid pointer, and `CLOCK_MONOTONIC` is always supported
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Timing statistics for the frames of a capture session.
+///
+/// Feed it every frame from `ScreencopyHandler::ready` and `failed`. Latency is the time from
+/// the frame being presented to `ready` being received, and frame intervals are the
+/// differences between consecutive presentation times. Frames without a presentation time
+/// are counted, but don't contribute to timings.
+#[derive(Clone, Debug, Default)]
+pub struct FrameStats {
+    expected_interval: Option<Duration>,
+    frames: u64,
+    failed: u64,
+    dropped: u64,
+    last_present_time: Option<Duration>,
+    latency_count: u32,
+    latency_sum: Duration,
+    latency_min: Option<Duration>,
+    latency_max: Option<Duration>,
+    interval_count: u32,
+    interval_min: Option<Duration>,
+    interval_max: Option<Duration>,
+    // Running mean and sum of squared differences, in seconds
+    interval_mean: f64,
+    interval_m2: f64,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count dropped frames, assuming a frame is presented every `interval`.
+    ///
+    /// This is usually the refresh interval of the output being captured.
+    pub fn with_expected_interval(interval: Duration) -> Self {
+        Self {
+            expected_interval: Some(interval),
+            ..Self::default()
+        }
+    }
+
+    /// Record a frame `ready` was received for now
+    pub fn record_ready(&mut self, frame: &Frame) {
+        self.record_ready_at(frame, clock_monotonic());
+    }
+
+    /// Record a frame `ready` was received for at `received`, in `CLOCK_MONOTONIC`
+    pub fn record_ready_at(&mut self, frame: &Frame, received: Duration) {
+        self.frames += 1;
+        let Some(present_time) = frame.present_time else {
+            return;
+        };
+
+        if let Some(latency) = received.checked_sub(present_time) {
+            self.latency_count += 1;
+            self.latency_sum += latency;
+            self.latency_min = Some(self.latency_min.map_or(latency, |min| min.min(latency)));
+            self.latency_max = Some(self.latency_max.map_or(latency, |max| max.max(latency)));
+        }
+
+        if let Some(interval) = self
+            .last_present_time
+            .and_then(|last| present_time.checked_sub(last))
+        {
+            self.interval_count += 1;
+            self.interval_min = Some(self.interval_min.map_or(interval, |min| min.min(interval)));
+            self.interval_max = Some(self.interval_max.map_or(interval, |max| max.max(interval)));
+            let secs = interval.as_secs_f64();
+            let delta = secs - self.interval_mean;
+            self.interval_mean += delta / f64::from(self.interval_count);
+            self.interval_m2 += delta * (secs - self.interval_mean);
+
+            if let Some(expected) = self.expected_interval.filter(|e| !e.is_zero()) {
+                let presented = (secs / expected.as_secs_f64()).round() as u64;
+                self.dropped += presented.saturating_sub(1);
+            }
+        }
+        self.last_present_time = Some(present_time);
+    }
+
+    pub fn record_failed(&mut self) {
+        self.failed += 1;
+    }
+
+    /// Frames that were captured successfully
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn failed_frames(&self) -> u64 {
+        self.failed
+    }
+
+    /// Frames presented between captured frames, that weren't captured.
+    ///
+    /// `None` unless created with [`with_expected_interval`](Self::with_expected_interval).
+    pub fn dropped_frames(&self) -> Option<u64> {
+        self.expected_interval.map(|_| self.dropped)
+    }
+
+    pub fn mean_latency(&self) -> Option<Duration> {
+        (self.latency_count > 0).then(|| self.latency_sum / self.latency_count)
+    }
+
+    pub fn min_latency(&self) -> Option<Duration> {
+        self.latency_min
+    }
+
+    pub fn max_latency(&self) -> Option<Duration> {
+        self.latency_max
+    }
+
+    pub fn mean_interval(&self) -> Option<Duration> {
+        (self.interval_count > 0).then(|| Duration::from_secs_f64(self.interval_mean))
+    }
+
+    pub fn min_interval(&self) -> Option<Duration> {
+        self.interval_min
+    }
+
+    pub fn max_interval(&self) -> Option<Duration> {
+        self.interval_max
+    }
+
+    /// Standard deviation of frame intervals
+    pub fn jitter(&self) -> Option<Duration> {
+        (self.interval_count > 0).then(|| {
+            Duration::from_secs_f64((self.interval_m2 / f64::from(self.interval_count)).sqrt())
+        })
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self {
+            expected_interval: self.expected_interval,
+            ..Self::default()
+        };
+    }
+}
@@ -0,0 +1,327 @@
+// Shared implementation of helpers keeping downscaled captures of sources up to date
+
+use sctk::shm::Shm;
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    time::{Duration, Instant},
+};
+use wayland_client::{
+    Dispatch, QueueHandle, WEnum,
+    protocol::{wl_buffer, wl_shm},
+};
+use wayland_protocols::ext::{
+    image_capture_source::v1::client::ext_image_capture_source_v1,
+    image_copy_capture::v1::client::{
+        ext_image_copy_capture_frame_v1, ext_image_copy_capture_session_v1,
+    },
+};
+
+use super::{
+    CaptureFrame, CaptureOptions, CaptureSession, CaptureSource, CaptureSourceError, Capturer,
+    FailureReason, Formats, Frame, ScreencopyFrameData, ScreencopySessionData,
+    image::{ShmBuffer, shm_format},
+};
+use crate::GlobalData;
+
+/// Downscaled capture of a source
+#[derive(Clone, Debug)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA8 pixel data
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct SourceCapture {
+    // `None` once the compositor stopped the session
+    session: Option<CaptureSession>,
+    buffer_spec: Option<((u32, u32), wl_shm::Format)>,
+    buffer: Option<ShmBuffer>,
+    frame: Option<CaptureFrame>,
+    // Buffer hasn't been captured into yet, so it must be fully damaged
+    needs_full_damage: bool,
+    last_capture: Option<Instant>,
+    wants_capture: bool,
+    thumbnail: Option<Thumbnail>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ThumbnailCaptures<K> {
+    max_size: (u32, u32),
+    frame_interval: Duration,
+    captures: HashMap<K, SourceCapture>,
+}
+
+impl<K: Clone + Eq + Hash> ThumbnailCaptures<K> {
+    pub fn new(max_size: (u32, u32), frame_interval: Duration) -> Self {
+        Self {
+            max_size,
+            frame_interval,
+            captures: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.captures.contains_key(key)
+    }
+
+    pub fn insert<D>(
+        &mut self,
+        key: K,
+        capturer: &Capturer,
+        source: &CaptureSource,
+        qh: &QueueHandle<D>,
+    ) -> Result<(), CaptureSourceError>
+    where
+        D: 'static,
+        D: Dispatch<ext_image_capture_source_v1::ExtImageCaptureSourceV1, GlobalData>,
+        D: Dispatch<
+                ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+                ScreencopySessionData,
+            >,
+    {
+        let session = capturer.create_session(
+            source,
+            CaptureOptions::empty(),
+            qh,
+            ScreencopySessionData::default(),
+        )?;
+        self.captures.insert(
+            key,
+            SourceCapture {
+                session: Some(session),
+                buffer_spec: None,
+                buffer: None,
+                frame: None,
+                needs_full_damage: true,
+                last_capture: None,
+                wants_capture: false,
+                thumbnail: None,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        self.captures.remove(key);
+    }
+
+    pub fn key_for_session(&self, session: &CaptureSession) -> Option<&K> {
+        self.captures
+            .iter()
+            .find(|(_, c)| c.session.as_ref() == Some(session))
+            .map(|(key, _)| key)
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&K) -> bool) {
+        self.captures.retain(|key, _| f(key));
+    }
+
+    pub fn thumbnail(&self, key: &K) -> Option<&Thumbnail> {
+        self.captures.get(key)?.thumbnail.as_ref()
+    }
+
+    pub fn thumbnails(&self) -> impl Iterator<Item = (&K, &Thumbnail)> {
+        self.captures
+            .iter()
+            .filter_map(|(key, capture)| Some((key, capture.thumbnail.as_ref()?)))
+    }
+
+    pub fn init_done<D>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+        session: &CaptureSession,
+        formats: &Formats,
+    ) -> bool
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        let frame_interval = self.frame_interval;
+        let Some(capture) = self
+            .captures
+            .values_mut()
+            .find(|c| c.session.as_ref() == Some(session))
+        else {
+            return false;
+        };
+        capture.buffer_spec = shm_format(formats).map(|format| (formats.buffer_size, format));
+        capture.schedule(shm, qh, frame_interval);
+        true
+    }
+
+    pub fn ready<D>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+        capture_frame: &CaptureFrame,
+        frame: &Frame,
+    ) -> bool
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        let (max_size, frame_interval) = (self.max_size, self.frame_interval);
+        let Some(capture) = self.capture_for_frame(capture_frame) else {
+            return false;
+        };
+        capture.frame = None;
+        // Only update the thumbnail if the content changed
+        if (capture.thumbnail.is_none() || !frame.damage.is_empty())
+            && let Some(buffer) = &mut capture.buffer
+        {
+            let (width, height, data) = buffer.read(frame.transform).scale_to_fit(max_size);
+            capture.thumbnail = Some(Thumbnail {
+                width,
+                height,
+                data,
+            });
+        }
+        capture.schedule(shm, qh, frame_interval);
+        true
+    }
+
+    pub fn failed<D>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+        capture_frame: &CaptureFrame,
+        reason: WEnum<FailureReason>,
+    ) -> bool
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        let frame_interval = self.frame_interval;
+        let Some(capture) = self.capture_for_frame(capture_frame) else {
+            return false;
+        };
+        capture.frame = None;
+        match reason {
+            // Session is about to be stopped
+            WEnum::Value(FailureReason::Stopped) => {}
+            // Wait for `init_done` with new constraints
+            WEnum::Value(FailureReason::BufferConstraints) => {}
+            _ => capture.schedule(shm, qh, frame_interval),
+        }
+        true
+    }
+
+    pub fn stopped(&mut self, session: &CaptureSession) -> bool {
+        let Some(capture) = self
+            .captures
+            .values_mut()
+            .find(|c| c.session.as_ref() == Some(session))
+        else {
+            return false;
+        };
+        // Keep the entry, so the source isn't captured again until it is removed
+        capture.session = None;
+        capture.frame = None;
+        capture.buffer = None;
+        capture.wants_capture = false;
+        true
+    }
+
+    pub fn refresh<D>(&mut self, shm: &Shm, qh: &QueueHandle<D>)
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        for capture in self.captures.values_mut() {
+            if capture.wants_capture {
+                capture.schedule(shm, qh, self.frame_interval);
+            }
+        }
+    }
+
+    pub fn next_refresh(&self) -> Option<Instant> {
+        self.captures
+            .values()
+            .filter(|c| c.wants_capture)
+            .map(|c| {
+                c.last_capture
+                    .map_or_else(Instant::now, |t| t + self.frame_interval)
+            })
+            .min()
+    }
+
+    fn capture_for_frame(&mut self, capture_frame: &CaptureFrame) -> Option<&mut SourceCapture> {
+        self.captures
+            .values_mut()
+            .find(|c| c.frame.as_ref() == Some(capture_frame))
+    }
+}
+
+impl SourceCapture {
+    // Capture now if the frame interval elapsed, or mark the source to be captured on `refresh`
+    fn schedule<D>(&mut self, shm: &Shm, qh: &QueueHandle<D>, frame_interval: Duration)
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        let Some(session) = &self.session else {
+            return;
+        };
+        let Some((size, format)) = self.buffer_spec else {
+            return;
+        };
+        if self.frame.is_some() {
+            return;
+        }
+        if self
+            .last_capture
+            .is_some_and(|t| t.elapsed() < frame_interval)
+        {
+            self.wants_capture = true;
+            return;
+        }
+
+        if self
+            .buffer
+            .as_ref()
+            .is_none_or(|b| b.size != size || b.format() != format)
+        {
+            // Retried on next `refresh` if allocation fails
+            self.buffer = ShmBuffer::new(shm, size, format, qh).ok();
+            self.needs_full_damage = true;
+        }
+        let Some(buffer) = &self.buffer else {
+            self.wants_capture = true;
+            return;
+        };
+        let damage = if self.needs_full_damage {
+            vec![buffer.full_damage()]
+        } else {
+            Vec::new()
+        };
+        self.frame =
+            Some(session.capture(&buffer.buffer, &damage, qh, ScreencopyFrameData::default()));
+        self.needs_full_damage = false;
+        self.last_capture = Some(Instant::now());
+        self.wants_capture = false;
+    }
+}
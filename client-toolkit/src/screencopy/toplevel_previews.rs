@@ -0,0 +1,176 @@
+use sctk::shm::Shm;
+use std::time::{Duration, Instant};
+use wayland_client::{Dispatch, QueueHandle, WEnum, protocol::wl_buffer};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1,
+    image_capture_source::v1::client::ext_image_capture_source_v1,
+    image_copy_capture::v1::client::{
+        ext_image_copy_capture_frame_v1, ext_image_copy_capture_session_v1,
+    },
+};
+
+use super::{
+    CaptureFrame, CaptureSession, CaptureSource, CaptureSourceError, Capturer, FailureReason,
+    Formats, Frame, ScreencopyFrameData, ScreencopySessionData,
+    thumbnail::{Thumbnail, ThumbnailCaptures},
+};
+use crate::GlobalData;
+
+/// Live previews of toplevels, captured only while requested.
+///
+/// [`request`](Self::request) starts capturing a toplevel, and [`release`](Self::release)
+/// stops it again. `ToplevelPreviews` isn't notified of toplevels closing by itself, so
+/// `ToplevelInfoHandler::toplevel_closed` has to call `release` for the closed toplevel.
+/// The `ScreencopyHandler` implementation has to forward `init_done`, `ready`, `failed` and
+/// `stopped` to the methods of the same name, which return `false` for sessions and frames
+/// that don't belong to a preview. A session stopped by the compositor is dropped, so
+/// the toplevel can be requested again.
+///
+/// Each toplevel is captured at most once per `frame_interval`; throttled captures are
+/// issued by [`refresh`](Self::refresh), which should be called at
+/// [`next_refresh`](Self::next_refresh).
+#[derive(Debug)]
+pub struct ToplevelPreviews {
+    captures: ThumbnailCaptures<ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1>,
+}
+
+impl ToplevelPreviews {
+    /// `max_size` is the size previews are downscaled to fit in, preserving aspect ratio.
+    pub fn new(max_size: (u32, u32), frame_interval: Duration) -> Self {
+        Self {
+            captures: ThumbnailCaptures::new(max_size, frame_interval),
+        }
+    }
+
+    /// Start capturing `toplevel`, if it isn't captured already
+    pub fn request<D>(
+        &mut self,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+        capturer: &Capturer,
+        qh: &QueueHandle<D>,
+    ) -> Result<(), CaptureSourceError>
+    where
+        D: 'static,
+        D: Dispatch<ext_image_capture_source_v1::ExtImageCaptureSourceV1, GlobalData>,
+        D: Dispatch<
+                ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+                ScreencopySessionData,
+            >,
+    {
+        if self.captures.contains(toplevel) {
+            return Ok(());
+        }
+        self.captures.insert(
+            toplevel.clone(),
+            capturer,
+            &CaptureSource::Toplevel(toplevel.clone()),
+            qh,
+        )
+    }
+
+    /// Stop capturing `toplevel`, dropping its preview
+    pub fn release(
+        &mut self,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.captures.remove(toplevel);
+    }
+
+    /// Latest preview of `toplevel`, if it is requested and a frame was captured
+    pub fn preview(
+        &self,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) -> Option<&Thumbnail> {
+        self.captures.thumbnail(toplevel)
+    }
+
+    pub fn previews(
+        &self,
+    ) -> impl Iterator<
+        Item = (
+            &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+            &Thumbnail,
+        ),
+    > {
+        self.captures.thumbnails()
+    }
+
+    pub fn init_done<D>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+        session: &CaptureSession,
+        formats: &Formats,
+    ) -> bool
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        self.captures.init_done(shm, qh, session, formats)
+    }
+
+    pub fn ready<D>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+        capture_frame: &CaptureFrame,
+        frame: &Frame,
+    ) -> bool
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        self.captures.ready(shm, qh, capture_frame, frame)
+    }
+
+    pub fn failed<D>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+        capture_frame: &CaptureFrame,
+        reason: WEnum<FailureReason>,
+    ) -> bool
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        self.captures.failed(shm, qh, capture_frame, reason)
+    }
+
+    pub fn stopped(&mut self, session: &CaptureSession) -> bool {
+        let Some(toplevel) = self.captures.key_for_session(session).cloned() else {
+            return false;
+        };
+        self.captures.remove(&toplevel);
+        true
+    }
+
+    pub fn refresh<D>(&mut self, shm: &Shm, qh: &QueueHandle<D>)
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        self.captures.refresh(shm, qh);
+    }
+
+    /// When [`refresh`](Self::refresh) has throttled captures to issue
+    pub fn next_refresh(&self) -> Option<Instant> {
+        self.captures.next_refresh()
+    }
+}
@@ -0,0 +1,165 @@
+use sctk::shm::Shm;
+use std::time::{Duration, Instant};
+use wayland_client::{Dispatch, QueueHandle, WEnum, protocol::wl_buffer};
+use wayland_protocols::ext::{
+    image_capture_source::v1::client::ext_image_capture_source_v1,
+    image_copy_capture::v1::client::{
+        ext_image_copy_capture_frame_v1, ext_image_copy_capture_session_v1,
+    },
+    workspace::v1::client::ext_workspace_handle_v1,
+};
+
+use super::{
+    CaptureFrame, CaptureSession, CaptureSource, CaptureSourceError, CaptureSourceKind, Capturer,
+    FailureReason, Formats, Frame, ScreencopyFrameData, ScreencopySessionData,
+    thumbnail::{Thumbnail, ThumbnailCaptures},
+};
+use crate::{GlobalData, workspace::WorkspaceState};
+
+/// Live thumbnails of every workspace in a [`WorkspaceState`].
+///
+/// Call [`update`](Self::update) from `WorkspaceHandler::done` to create sessions for new
+/// workspaces and drop the ones of removed workspaces. The `ScreencopyHandler` implementation
+/// has to forward `init_done`, `ready`, `failed` and `stopped` to the methods of the same
+/// name, which return `false` for sessions and frames that don't belong to a thumbnail.
+///
+/// A workspace is captured at most once per `frame_interval`. Captures that are throttled
+/// are issued by [`refresh`](Self::refresh), which should be called at
+/// [`next_refresh`](Self::next_refresh). Since the compositor only completes a capture once
+/// the workspace changed, and thumbnails are only updated for damaged frames, idle
+/// workspaces cost nothing.
+#[derive(Debug)]
+pub struct WorkspaceThumbnails {
+    captures: ThumbnailCaptures<ext_workspace_handle_v1::ExtWorkspaceHandleV1>,
+}
+
+impl WorkspaceThumbnails {
+    /// `max_size` is the size thumbnails are downscaled to fit in, preserving aspect ratio.
+    pub fn new(max_size: (u32, u32), frame_interval: Duration) -> Self {
+        Self {
+            captures: ThumbnailCaptures::new(max_size, frame_interval),
+        }
+    }
+
+    pub fn update<D>(
+        &mut self,
+        workspace_state: &WorkspaceState,
+        capturer: &Capturer,
+        qh: &QueueHandle<D>,
+    ) -> Result<(), CaptureSourceError>
+    where
+        D: 'static,
+        D: Dispatch<ext_image_capture_source_v1::ExtImageCaptureSourceV1, GlobalData>,
+        D: Dispatch<
+                ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+                ScreencopySessionData,
+            >,
+    {
+        if !capturer.supports_source(CaptureSourceKind::Workspace) {
+            return Err(CaptureSourceError::unsupported(
+                CaptureSourceKind::Workspace,
+            ));
+        }
+        self.captures
+            .retain(|handle| workspace_state.workspace_info(handle).is_some());
+        for workspace in workspace_state.workspaces() {
+            if !self.captures.contains(&workspace.handle) {
+                self.captures.insert(
+                    workspace.handle.clone(),
+                    capturer,
+                    &CaptureSource::Workspace(workspace.handle.clone()),
+                    qh,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn thumbnail(
+        &self,
+        workspace: &ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+    ) -> Option<&Thumbnail> {
+        self.captures.thumbnail(workspace)
+    }
+
+    pub fn thumbnails(
+        &self,
+    ) -> impl Iterator<Item = (&ext_workspace_handle_v1::ExtWorkspaceHandleV1, &Thumbnail)> {
+        self.captures.thumbnails()
+    }
+
+    pub fn init_done<D>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+        session: &CaptureSession,
+        formats: &Formats,
+    ) -> bool
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        self.captures.init_done(shm, qh, session, formats)
+    }
+
+    pub fn ready<D>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+        capture_frame: &CaptureFrame,
+        frame: &Frame,
+    ) -> bool
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        self.captures.ready(shm, qh, capture_frame, frame)
+    }
+
+    pub fn failed<D>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+        capture_frame: &CaptureFrame,
+        reason: WEnum<FailureReason>,
+    ) -> bool
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        self.captures.failed(shm, qh, capture_frame, reason)
+    }
+
+    pub fn stopped(&mut self, session: &CaptureSession) -> bool {
+        self.captures.stopped(session)
+    }
+
+    pub fn refresh<D>(&mut self, shm: &Shm, qh: &QueueHandle<D>)
+    where
+        D: 'static,
+        D: Dispatch<
+                ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+                ScreencopyFrameData,
+            >,
+        D: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        self.captures.refresh(shm, qh);
+    }
+
+    /// When [`refresh`](Self::refresh) has throttled captures to issue
+    pub fn next_refresh(&self) -> Option<Instant> {
+        self.captures.next_refresh()
+    }
+}
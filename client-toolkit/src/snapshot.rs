@@ -0,0 +1,239 @@
+//! Serializable snapshots of toplevels, workspaces and capture formats.
+//!
+//! [`ToplevelInfo`], [`Workspace`], [`WorkspaceGroup`] and [`Formats`] refer to other
+//! objects through proxies, which only mean something on the connection they belong to.
+//! The types here replace them with plain values, so state can be sent to other processes:
+//!
+//! - Toplevels, workspaces and workspace groups are identified by the protocol id of their
+//!   handle (`object_id`), which other snapshots from the same connection refer to.
+//! - Outputs are referred to by name. Outputs without one (`wl_output` before version 4) are
+//!   left out.
+//! - States and capabilities are lists of the `snake_case` names of their protocol enum
+//!   entries, in alphabetical order.
+
+use std::collections::BTreeMap;
+
+use sctk::output::OutputState;
+use serde::{Deserialize, Serialize};
+use wayland_client::{Proxy, WEnum, protocol::wl_output};
+
+use crate::{
+    cosmic_protocols::workspace::v2::client::zcosmic_workspace_handle_v2,
+    screencopy::Formats,
+    toplevel_info::{ToplevelGeometry, ToplevelInfo},
+    workspace::{Workspace, WorkspaceGroup},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToplevelSnapshot {
+    /// Protocol id of the `ext_foreign_toplevel_handle_v1`
+    pub object_id: u32,
+    pub identifier: String,
+    pub app_id: String,
+    pub title: String,
+    pub state: Vec<String>,
+    pub outputs: Vec<String>,
+    /// Geometry relative to each output, by output name
+    pub geometry: BTreeMap<String, ToplevelGeometry>,
+    /// Object ids of the workspaces
+    pub workspaces: Vec<u32>,
+}
+
+impl ToplevelSnapshot {
+    pub fn new(info: &ToplevelInfo, output_state: &OutputState) -> Self {
+        let mut outputs = info
+            .output
+            .iter()
+            .filter_map(|output| output_name(output_state, output))
+            .collect::<Vec<_>>();
+        outputs.sort();
+        let geometry = info
+            .geometry
+            .iter()
+            .filter_map(|(output, geometry)| {
+                Some((output_name(output_state, output)?, geometry.clone()))
+            })
+            .collect();
+        let mut workspaces = info
+            .workspace
+            .iter()
+            .map(|workspace| workspace.id().protocol_id())
+            .collect::<Vec<_>>();
+        workspaces.sort();
+        Self {
+            object_id: info.foreign_toplevel.id().protocol_id(),
+            identifier: info.identifier.clone(),
+            app_id: info.app_id.clone(),
+            title: info.title.clone(),
+            state: sorted(
+                info.state
+                    .iter()
+                    .map(|state| snake_case(&format!("{:?}", state))),
+            ),
+            outputs,
+            geometry,
+            workspaces,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceSnapshot {
+    /// Protocol id of the `ext_workspace_handle_v1`
+    pub object_id: u32,
+    /// Id assigned by the compositor, which stays the same across connections
+    pub id: Option<String>,
+    pub name: String,
+    pub coordinates: Vec<u32>,
+    /// States of `ext_workspace_handle_v1` and `zcosmic_workspace_handle_v2`
+    pub state: Vec<String>,
+    /// Capabilities of `ext_workspace_handle_v1` and `zcosmic_workspace_handle_v2`
+    pub capabilities: Vec<String>,
+    /// Requires zcosmic_workspace_handle_v2
+    pub tiling: Option<bool>,
+}
+
+impl From<&Workspace> for WorkspaceSnapshot {
+    fn from(workspace: &Workspace) -> Self {
+        let tiling = workspace.tiling.map(|tiling| {
+            tiling == WEnum::Value(zcosmic_workspace_handle_v2::TilingState::TilingEnabled)
+        });
+        Self {
+            object_id: workspace.handle.id().protocol_id(),
+            id: workspace.id.clone(),
+            name: workspace.name.clone(),
+            coordinates: workspace.coordinates.clone(),
+            state: sorted(
+                flag_names(workspace.state.iter_names())
+                    .chain(flag_names(workspace.cosmic_state.iter_names())),
+            ),
+            capabilities: sorted(
+                flag_names(workspace.capabilities.iter_names())
+                    .chain(cosmic_capabilities(workspace)),
+            ),
+            tiling,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceGroupSnapshot {
+    /// Protocol id of the `ext_workspace_group_handle_v1`
+    pub object_id: u32,
+    pub capabilities: Vec<String>,
+    pub outputs: Vec<String>,
+    /// Object ids of the workspaces
+    pub workspaces: Vec<u32>,
+}
+
+impl WorkspaceGroupSnapshot {
+    pub fn new(group: &WorkspaceGroup, output_state: &OutputState) -> Self {
+        let mut workspaces = group
+            .workspaces
+            .iter()
+            .map(|workspace| workspace.id().protocol_id())
+            .collect::<Vec<_>>();
+        workspaces.sort();
+        Self {
+            object_id: group.handle.id().protocol_id(),
+            capabilities: sorted(flag_names(group.capabilities.iter_names())),
+            outputs: group
+                .outputs
+                .iter()
+                .filter_map(|output| output_name(output_state, output))
+                .collect(),
+            workspaces,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormatsSnapshot {
+    pub buffer_size: (u32, u32),
+    /// Names of the `wl_shm` formats
+    pub shm_formats: Vec<String>,
+    pub dmabuf_device: Option<libc::dev_t>,
+    pub dmabuf_formats: Vec<DmabufFormat>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DmabufFormat {
+    /// DRM fourcc code
+    pub format: u32,
+    pub modifiers: Vec<u64>,
+}
+
+impl From<&Formats> for FormatsSnapshot {
+    fn from(formats: &Formats) -> Self {
+        Self {
+            buffer_size: formats.buffer_size,
+            shm_formats: formats
+                .shm_formats
+                .iter()
+                .map(|format| snake_case(&format!("{:?}", format)))
+                .collect(),
+            dmabuf_device: formats.dmabuf_device,
+            dmabuf_formats: formats
+                .dmabuf_formats
+                .iter()
+                .map(|(format, modifiers)| DmabufFormat {
+                    format: *format,
+                    modifiers: modifiers.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn output_name(output_state: &OutputState, output: &wl_output::WlOutput) -> Option<String> {
+    output_state.info(output)?.name
+}
+
+fn flag_names<'a>(
+    names: impl Iterator<Item = (&'a str, impl Sized)> + 'a,
+) -> impl Iterator<Item = String> + 'a {
+    names.map(|(name, _)| snake_case(name))
+}
+
+fn cosmic_capabilities(workspace: &Workspace) -> impl Iterator<Item = String> + '_ {
+    // Not `iter_names`, which skips `pin` since its value isn't a single bit
+    [
+        (
+            zcosmic_workspace_handle_v2::WorkspaceCapabilities::Rename,
+            "rename",
+        ),
+        (
+            zcosmic_workspace_handle_v2::WorkspaceCapabilities::SetTilingState,
+            "set_tiling_state",
+        ),
+        (
+            zcosmic_workspace_handle_v2::WorkspaceCapabilities::Pin,
+            "pin",
+        ),
+        (
+            zcosmic_workspace_handle_v2::WorkspaceCapabilities::Move,
+            "move",
+        ),
+    ]
+    .into_iter()
+    .filter(move |(capability, _)| workspace.has_cosmic_capability(*capability))
+    .map(|(_, name)| name.to_string())
+}
+
+fn sorted(names: impl Iterator<Item = String>) -> Vec<String> {
+    let mut names = names.collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+// Enum entries are converted to `CamelCase` by `wayland-scanner`
+fn snake_case(name: &str) -> String {
+    let mut snake_case = String::with_capacity(name.len() + 2);
+    for (i, c) in name.char_indices() {
+        if c.is_ascii_uppercase() && i != 0 {
+            snake_case.push('_');
+        }
+        snake_case.push(c.to_ascii_lowercase());
+    }
+    snake_case
+}
@@ -1,12 +1,12 @@
 use std::{
     collections::{HashMap, HashSet},
-    sync::OnceLock,
+    sync::Mutex,
 };
 
 use cosmic_protocols::toplevel_info::v1::client::{
     zcosmic_toplevel_handle_v1, zcosmic_toplevel_info_v1,
 };
-use sctk::registry::RegistryState;
+use sctk::registry::{GlobalProxy, ProvidesRegistryState, RegistryHandler, RegistryState};
 use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, Weak, protocol::wl_output};
 use wayland_protocols::ext::{
     foreign_toplevel_list::v1::client::{
@@ -15,9 +15,13 @@ use wayland_protocols::ext::{
     workspace::v1::client::ext_workspace_handle_v1,
 };
 
-use crate::GlobalData;
+use crate::{
+    GlobalData,
+    recording::{self, Recorder},
+};
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ToplevelGeometry {
     pub x: i32,
     pub y: i32,
@@ -82,16 +86,22 @@ impl ToplevelData {
 #[doc(hidden)]
 #[derive(Default)]
 pub struct ToplevelUserData {
-    cosmic_toplevel: OnceLock<Option<Weak<zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1>>>,
+    cosmic_toplevel: Mutex<Option<Weak<zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1>>>,
 }
 
 /// Handler for `ext-foreign-toplevel-list-v1`, and optionally
 /// `cosmic-toplevel-info-unstable-v1` which extends it with additional information.
+///
+/// Add the state to `sctk::registry_handlers!` to follow the globals being removed and
+/// advertised again. If `ext_foreign_toplevel_list_v1` is removed, the toplevels are closed,
+/// and announced again once bound. If only `zcosmic_toplevel_info_v1` is added or removed, the
+/// toplevels are updated with or without its information.
 #[derive(Debug)]
 pub struct ToplevelInfoState {
-    pub foreign_toplevel_list: ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1,
+    foreign_toplevel_list: GlobalProxy<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1>,
     pub cosmic_toplevel_info: Option<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1>,
     toplevels: Vec<ToplevelData>,
+    recorder: Option<Recorder>,
 }
 
 impl ToplevelInfoState {
@@ -101,35 +111,56 @@ impl ToplevelInfoState {
             + Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, GlobalData>
             + 'static,
     {
-        let foreign_toplevel_list = registry
-            .bind_one::<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, _, _>(
-                qh,
-                1..=1,
-                GlobalData,
-            )
-            .ok()?;
-        let cosmic_toplevel_info = registry
-            .bind_one::<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, _, _>(
-                qh,
-                2..=3,
-                GlobalData,
-            )
-            .ok();
-
-        Some(Self {
-            foreign_toplevel_list,
-            cosmic_toplevel_info,
-            toplevels: Vec::new(),
-        })
+        let state = Self::new(registry, qh);
+        state.foreign_toplevel_list.get().is_ok().then_some(state)
     }
 
+    /// Unlike [`try_new`](Self::try_new), succeeds without `ext_foreign_toplevel_list_v1`,
+    /// which is then bound once advertised.
     pub fn new<D>(registry: &RegistryState, qh: &QueueHandle<D>) -> Self
     where
         D: Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, GlobalData>
             + Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, GlobalData>
             + 'static,
     {
-        Self::try_new(registry, qh).unwrap()
+        Self::bind(registry, qh, None)
+    }
+
+    /// Like [`new`](Self::new), recording the events of the toplevel list and its toplevels.
+    pub fn new_recorded<D>(
+        registry: &RegistryState,
+        qh: &QueueHandle<D>,
+        recorder: &Recorder,
+    ) -> Self
+    where
+        D: Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, GlobalData>
+            + Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, GlobalData>
+            + 'static,
+    {
+        Self::bind(registry, qh, Some(recorder.clone()))
+    }
+
+    fn bind<D>(registry: &RegistryState, qh: &QueueHandle<D>, recorder: Option<Recorder>) -> Self
+    where
+        D: Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, GlobalData>
+            + Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, GlobalData>
+            + 'static,
+    {
+        let (foreign_toplevel_list, cosmic_toplevel_info) =
+            bind_globals(registry, qh, recorder.as_ref());
+        Self {
+            foreign_toplevel_list,
+            cosmic_toplevel_info,
+            toplevels: Vec::new(),
+            recorder,
+        }
+    }
+
+    /// `ext_foreign_toplevel_list_v1`, if bound
+    pub fn foreign_toplevel_list(
+        &self,
+    ) -> Option<&ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1> {
+        self.foreign_toplevel_list.get().ok()
     }
 
     pub fn info(
@@ -150,6 +181,229 @@ impl ToplevelInfoState {
     }
 }
 
+fn bind_globals<D>(
+    registry: &RegistryState,
+    qh: &QueueHandle<D>,
+    recorder: Option<&Recorder>,
+) -> (
+    GlobalProxy<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1>,
+    Option<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1>,
+)
+where
+    D: Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, GlobalData>
+        + Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, GlobalData>
+        + 'static,
+{
+    let foreign_toplevel_list = GlobalProxy::from(recording::bind_one::<
+        ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1,
+        _,
+        _,
+    >(recorder, registry, qh, 1..=1, GlobalData));
+    // Only used along with the toplevel list
+    let cosmic_toplevel_info = foreign_toplevel_list.get().ok().and_then(|_| {
+        recording::bind_one::<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, _, _>(
+            recorder,
+            registry,
+            qh,
+            2..=3,
+            GlobalData,
+        )
+        .ok()
+    });
+    (foreign_toplevel_list, cosmic_toplevel_info)
+}
+
+/// Create the `zcosmic_toplevel_handle_v1` extending a toplevel
+fn get_cosmic_toplevel<D>(
+    recorder: Option<&Recorder>,
+    cosmic_toplevel_info: &zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1,
+    toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    qh: &QueueHandle<D>,
+) -> zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1
+where
+    D: Dispatch<zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1, GlobalData> + 'static,
+{
+    let cosmic_toplevel = recording::create(
+        recorder,
+        cosmic_toplevel_info,
+        zcosmic_toplevel_info_v1::Request::GetCosmicToplevel {
+            foreign_toplevel: toplevel.clone(),
+        },
+        qh,
+        GlobalData,
+    );
+    if let Some(data) = toplevel.data::<ToplevelUserData>() {
+        *data.cosmic_toplevel.lock().unwrap() = Some(cosmic_toplevel.downgrade());
+    }
+    cosmic_toplevel
+}
+
+/// Bind `zcosmic_toplevel_info_v1` if it's advertised, and extend the existing toplevels with
+/// it. They're updated once the compositor sent their information.
+fn attach_cosmic_toplevels<D>(data: &mut D, qh: &QueueHandle<D>)
+where
+    D: ProvidesRegistryState
+        + ToplevelInfoHandler
+        + Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, GlobalData>
+        + Dispatch<zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1, GlobalData>
+        + 'static,
+{
+    let recorder = data.toplevel_info_state().recorder.clone();
+    let Ok(cosmic_toplevel_info) = recording::bind_one::<
+        zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1,
+        _,
+        _,
+    >(recorder.as_ref(), data.registry(), qh, 2..=3, GlobalData) else {
+        return;
+    };
+    let state = data.toplevel_info_state();
+    for toplevel in &mut state.toplevels {
+        let cosmic_toplevel = get_cosmic_toplevel(
+            recorder.as_ref(),
+            &cosmic_toplevel_info,
+            toplevel.foreign_toplevel(),
+            qh,
+        );
+        toplevel.pending_info.cosmic_toplevel = Some(cosmic_toplevel);
+    }
+    state.cosmic_toplevel_info = Some(cosmic_toplevel_info);
+}
+
+/// Destroy the `zcosmic_toplevel_handle_v1`s, and update the toplevels without their
+/// information.
+fn detach_cosmic_toplevels<D>(data: &mut D, conn: &Connection, qh: &QueueHandle<D>)
+where
+    D: ToplevelInfoHandler,
+{
+    let state = data.toplevel_info_state();
+    state.cosmic_toplevel_info = None;
+    let mut updated = Vec::new();
+    for toplevel in &mut state.toplevels {
+        let Some(cosmic_toplevel) = toplevel.pending_info.cosmic_toplevel.take() else {
+            continue;
+        };
+        cosmic_toplevel.destroy();
+        if let Some(data) = toplevel.foreign_toplevel().data::<ToplevelUserData>() {
+            *data.cosmic_toplevel.lock().unwrap() = None;
+        }
+        let info = &mut toplevel.pending_info;
+        info.state.clear();
+        info.output.clear();
+        info.geometry.clear();
+        info.workspace.clear();
+        toplevel.has_cosmic_info = false;
+        if toplevel.current_info.is_some() {
+            toplevel.current_info = Some(toplevel.pending_info.clone());
+            updated.push(toplevel.foreign_toplevel().clone());
+        }
+    }
+    for toplevel in &updated {
+        data.update_toplevel(conn, qh, toplevel);
+    }
+}
+
+/// Close all toplevels and destroy the objects bound from the globals, so no more events
+/// are received for them.
+pub(crate) fn unbind<D>(data: &mut D, conn: &Connection, qh: &QueueHandle<D>)
+where
+    D: ToplevelInfoHandler,
+{
+    let closed = data
+        .toplevel_info_state()
+        .toplevels()
+        .map(|info| info.foreign_toplevel.clone())
+        .collect::<Vec<_>>();
+    for toplevel in &closed {
+        data.toplevel_closed(conn, qh, toplevel);
+    }
+
+    let state = data.toplevel_info_state();
+    for toplevel in state.toplevels.drain(..) {
+        if let Some(cosmic_toplevel) = toplevel.cosmic_toplevel() {
+            cosmic_toplevel.destroy();
+        }
+        toplevel.foreign_toplevel().destroy();
+    }
+    state.cosmic_toplevel_info = None;
+    let foreign_toplevel_list =
+        std::mem::replace(&mut state.foreign_toplevel_list, GlobalProxy::NotPresent);
+    // Already destroyed if the compositor sent `finished`
+    if let GlobalProxy::Bound(foreign_toplevel_list) = foreign_toplevel_list
+        && foreign_toplevel_list.is_alive()
+    {
+        foreign_toplevel_list.destroy();
+        data.finished(conn, qh);
+    }
+}
+
+/// Bind the globals again, after `ext_foreign_toplevel_list_v1` was added or removed.
+fn rebind<D>(data: &mut D, conn: &Connection, qh: &QueueHandle<D>)
+where
+    D: ProvidesRegistryState
+        + ToplevelInfoHandler
+        + Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, GlobalData>
+        + Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, GlobalData>
+        + 'static,
+{
+    unbind(data, conn, qh);
+    let recorder = data.toplevel_info_state().recorder.clone();
+    let (foreign_toplevel_list, cosmic_toplevel_info) =
+        bind_globals(data.registry(), qh, recorder.as_ref());
+    let state = data.toplevel_info_state();
+    state.foreign_toplevel_list = foreign_toplevel_list;
+    state.cosmic_toplevel_info = cosmic_toplevel_info;
+}
+
+impl<D> RegistryHandler<D> for ToplevelInfoState
+where
+    D: ProvidesRegistryState
+        + ToplevelInfoHandler
+        + Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, GlobalData>
+        + Dispatch<zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1, GlobalData>
+        + Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, GlobalData>
+        + 'static,
+{
+    fn new_global(
+        data: &mut D,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+        _name: u32,
+        interface: &str,
+        _version: u32,
+    ) {
+        let state = data.toplevel_info_state();
+        if interface == ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1::interface().name {
+            if state.foreign_toplevel_list.get().is_err() {
+                rebind(data, conn, qh);
+            }
+        } else if interface == zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1::interface().name
+            // Otherwise bound along with the toplevel list
+            && state.cosmic_toplevel_info.is_none()
+            && state.foreign_toplevel_list.get().is_ok()
+        {
+            attach_cosmic_toplevels(data, qh);
+        }
+    }
+
+    fn remove_global(
+        data: &mut D,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+        _name: u32,
+        interface: &str,
+    ) {
+        if interface == ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1::interface().name {
+            rebind(data, conn, qh);
+        } else if interface == zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1::interface().name
+            && data.toplevel_info_state().cosmic_toplevel_info.is_some()
+        {
+            // Another instance of the global may still be advertised
+            detach_cosmic_toplevels(data, conn, qh);
+            attach_cosmic_toplevels(data, qh);
+        }
+    }
+}
+
 pub trait ToplevelInfoHandler: Sized {
     fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState;
 
@@ -308,20 +562,17 @@ where
             ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } => {
                 let info_state = state.toplevel_info_state();
                 let mut toplevel_data = ToplevelData::new(toplevel.clone());
-                let cosmic_toplevel =
-                    info_state
-                        .cosmic_toplevel_info
-                        .as_ref()
-                        .map(|cosmic_toplevel_info| {
-                            cosmic_toplevel_info.get_cosmic_toplevel(&toplevel, qh, GlobalData)
-                        });
-                toplevel
-                    .data::<ToplevelUserData>()
-                    .unwrap()
-                    .cosmic_toplevel
-                    .set(cosmic_toplevel.as_ref().map(|t| t.downgrade()))
-                    .unwrap();
-                toplevel_data.pending_info.cosmic_toplevel = cosmic_toplevel;
+                toplevel_data.pending_info.cosmic_toplevel = info_state
+                    .cosmic_toplevel_info
+                    .as_ref()
+                    .map(|cosmic_toplevel_info| {
+                        get_cosmic_toplevel(
+                            info_state.recorder.as_ref(),
+                            cosmic_toplevel_info,
+                            &toplevel,
+                            qh,
+                        )
+                    });
                 info_state.toplevels.push(toplevel_data);
             }
             ext_foreign_toplevel_list_v1::Event::Finished => {
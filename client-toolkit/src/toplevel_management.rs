@@ -1,11 +1,17 @@
 use cosmic_protocols::toplevel_management::v1::client::zcosmic_toplevel_manager_v1;
-use sctk::registry::RegistryState;
-use wayland_client::{Connection, Dispatch, QueueHandle, WEnum};
+use sctk::registry::{GlobalProxy, ProvidesRegistryState, RegistryHandler, RegistryState};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, WEnum};
 
 use crate::GlobalData;
 
+/// Add the state to `sctk::registry_handlers!` to follow the global being removed and
+/// advertised again. The handler then gets empty capabilities, until they are sent by the
+/// new global.
+#[derive(Debug)]
 pub struct ToplevelManagerState {
-    pub manager: zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1,
+    manager: GlobalProxy<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1>,
+    capabilities:
+        Vec<WEnum<zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1>>,
 }
 
 impl ToplevelManagerState {
@@ -13,22 +19,81 @@ impl ToplevelManagerState {
     where
         D: Dispatch<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, GlobalData> + 'static,
     {
-        let manager = registry
-            .bind_one::<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, _, _>(
-                qh,
-                1..=4,
-                GlobalData,
-            )
-            .ok()?;
-
-        Some(Self { manager })
+        let state = Self::new(registry, qh);
+        state.manager.get().is_ok().then_some(state)
     }
 
+    /// Unlike [`try_new`](Self::try_new), succeeds without `zcosmic_toplevel_manager_v1`,
+    /// which is then bound once advertised. It no longer panics if the global is missing.
     pub fn new<D>(registry: &RegistryState, qh: &QueueHandle<D>) -> Self
     where
         D: Dispatch<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, GlobalData> + 'static,
     {
-        Self::try_new(registry, qh).unwrap()
+        Self {
+            manager: GlobalProxy::from(registry.bind_one(qh, 1..=4, GlobalData)),
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// `zcosmic_toplevel_manager_v1`, if bound
+    pub fn manager(&self) -> Option<&zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1> {
+        self.manager.get().ok()
+    }
+
+    /// Capabilities last sent by the manager, as passed to
+    /// [`ToplevelManagerHandler::capabilities`]
+    pub fn capabilities(
+        &self,
+    ) -> &[WEnum<zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1>] {
+        &self.capabilities
+    }
+}
+
+impl<D> RegistryHandler<D> for ToplevelManagerState
+where
+    D: ProvidesRegistryState
+        + ToplevelManagerHandler
+        + Dispatch<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, GlobalData>
+        + 'static,
+{
+    fn new_global(
+        data: &mut D,
+        _conn: &Connection,
+        qh: &QueueHandle<D>,
+        _name: u32,
+        interface: &str,
+        _version: u32,
+    ) {
+        if interface == zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1::interface().name
+            && data.toplevel_manager_state().manager.get().is_err()
+        {
+            let manager = GlobalProxy::from(data.registry().bind_one(qh, 1..=4, GlobalData));
+            data.toplevel_manager_state().manager = manager;
+        }
+    }
+
+    fn remove_global(
+        data: &mut D,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+        _name: u32,
+        interface: &str,
+    ) {
+        if interface != zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1::interface().name {
+            return;
+        }
+        let manager = std::mem::replace(
+            &mut data.toplevel_manager_state().manager,
+            GlobalProxy::NotPresent,
+        );
+        if let GlobalProxy::Bound(manager) = manager {
+            manager.destroy();
+            data.toplevel_manager_state().capabilities.clear();
+            data.capabilities(conn, qh, Vec::new());
+        }
+        // Another instance of the global may still be advertised
+        let manager = GlobalProxy::from(data.registry().bind_one(qh, 1..=4, GlobalData));
+        data.toplevel_manager_state().manager = manager;
     }
 }
 
@@ -53,7 +118,8 @@ where
                 let capabilities = capabilities
                     .chunks(4)
                     .map(|chunk| WEnum::from(u32::from_ne_bytes(chunk.try_into().unwrap())))
-                    .collect();
+                    .collect::<Vec<_>>();
+                state.toplevel_manager_state().capabilities = capabilities.clone();
                 state.capabilities(conn, qhandle, capabilities)
             }
             _ => unimplemented!(),
@@ -1,14 +1,17 @@
 use cosmic_protocols::workspace::v2::client::{
     zcosmic_workspace_handle_v2, zcosmic_workspace_manager_v2,
 };
-use sctk::registry::{GlobalProxy, RegistryState};
+use sctk::registry::{GlobalProxy, ProvidesRegistryState, RegistryHandler, RegistryState};
 use std::collections::HashSet;
-use wayland_client::{Connection, Dispatch, QueueHandle, WEnum, protocol::wl_output};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, WEnum, protocol::wl_output};
 use wayland_protocols::ext::workspace::v1::client::{
     ext_workspace_group_handle_v1, ext_workspace_handle_v1, ext_workspace_manager_v1,
 };
 
-use crate::GlobalData;
+use crate::{
+    GlobalData,
+    recording::{self, Recorder},
+};
 
 #[derive(Clone, Debug)]
 pub struct WorkspaceGroup {
@@ -59,6 +62,30 @@ pub struct Workspace {
     pub id: Option<String>,
 }
 
+impl Workspace {
+    /// Whether the compositor advertises a capability of `zcosmic_workspace_handle_v2`, at the
+    /// version of [`Self::cosmic_handle`]
+    ///
+    /// The protocol gives `pin` the value 3 instead of a bit of its own, so it can't be told
+    /// apart from `rename` and `set_tiling_state` together: it's reported if both bits are set,
+    /// and the handle has version 2. `move` is 4, and is reported like the others.
+    pub fn has_cosmic_capability(
+        &self,
+        capability: zcosmic_workspace_handle_v2::WorkspaceCapabilities,
+    ) -> bool {
+        let since = match capability {
+            zcosmic_workspace_handle_v2::WorkspaceCapabilities::Pin
+            | zcosmic_workspace_handle_v2::WorkspaceCapabilities::Move => 2,
+            _ => 1,
+        };
+        let bits = capability.bits();
+        self.cosmic_handle
+            .as_ref()
+            .is_some_and(|handle| handle.version() >= since)
+            && self.cosmic_capabilities.bits() & bits == bits
+    }
+}
+
 #[derive(Debug)]
 struct WorkspaceData {
     handle: ext_workspace_handle_v1::ExtWorkspaceHandleV1,
@@ -94,12 +121,15 @@ impl WorkspaceData {
     }
 }
 
+/// Add the state to `sctk::registry_handlers!` to follow the globals being removed and
+/// advertised again. The workspaces are then removed, and announced again once bound.
 #[derive(Debug)]
 pub struct WorkspaceState {
     workspace_groups: Vec<WorkspaceGroupData>,
     workspaces: Vec<WorkspaceData>,
     manager: GlobalProxy<ext_workspace_manager_v1::ExtWorkspaceManagerV1>,
     cosmic_manager: GlobalProxy<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2>,
+    recorder: Option<Recorder>,
 }
 
 impl WorkspaceState {
@@ -109,11 +139,37 @@ impl WorkspaceState {
             + Dispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, GlobalData>
             + 'static,
     {
+        Self::bind(registry, qh, None)
+    }
+
+    /// Like [`new`](Self::new), recording the events of the workspace manager and its
+    /// workspaces.
+    pub fn new_recorded<D>(
+        registry: &RegistryState,
+        qh: &QueueHandle<D>,
+        recorder: &Recorder,
+    ) -> Self
+    where
+        D: Dispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, GlobalData>
+            + Dispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, GlobalData>
+            + 'static,
+    {
+        Self::bind(registry, qh, Some(recorder.clone()))
+    }
+
+    fn bind<D>(registry: &RegistryState, qh: &QueueHandle<D>, recorder: Option<Recorder>) -> Self
+    where
+        D: Dispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, GlobalData>
+            + Dispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, GlobalData>
+            + 'static,
+    {
+        let (manager, cosmic_manager) = bind_globals(registry, qh, recorder.as_ref());
         Self {
             workspace_groups: Vec::new(),
             workspaces: Vec::new(),
-            manager: GlobalProxy::from(registry.bind_one(qh, 1..=1, GlobalData)),
-            cosmic_manager: GlobalProxy::from(registry.bind_one(qh, 1..=2, GlobalData)),
+            manager,
+            cosmic_manager,
+            recorder,
         }
     }
 
@@ -158,6 +214,127 @@ impl WorkspaceState {
     }
 }
 
+fn bind_globals<D>(
+    registry: &RegistryState,
+    qh: &QueueHandle<D>,
+    recorder: Option<&Recorder>,
+) -> (
+    GlobalProxy<ext_workspace_manager_v1::ExtWorkspaceManagerV1>,
+    GlobalProxy<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2>,
+)
+where
+    D: Dispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, GlobalData>
+        + Dispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, GlobalData>
+        + 'static,
+{
+    let manager = recording::bind_one(recorder, registry, qh, 1..=1, GlobalData);
+    let cosmic_manager = recording::bind_one(recorder, registry, qh, 1..=2, GlobalData);
+    (
+        GlobalProxy::from(manager),
+        GlobalProxy::from(cosmic_manager),
+    )
+}
+
+/// Remove all workspaces and groups, and destroy the objects bound from the globals, so no
+/// more events are received for them.
+pub(crate) fn unbind<D>(data: &mut D)
+where
+    D: WorkspaceHandler,
+{
+    let state = data.workspace_state();
+    let bound = state.manager.get().is_ok();
+    for workspace in state.workspaces.drain(..) {
+        if let Some(cosmic_handle) = &workspace.cosmic_handle {
+            cosmic_handle.destroy();
+        }
+        workspace.handle.destroy();
+    }
+    for group in state.workspace_groups.drain(..) {
+        group.handle.destroy();
+    }
+    if let GlobalProxy::Bound(cosmic_manager) =
+        std::mem::replace(&mut state.cosmic_manager, GlobalProxy::NotPresent)
+    {
+        cosmic_manager.destroy();
+    }
+    // The manager has no destructor, it is destroyed once the compositor sends `finished`
+    if let GlobalProxy::Bound(manager) =
+        std::mem::replace(&mut state.manager, GlobalProxy::NotPresent)
+    {
+        manager.stop();
+    }
+    if bound {
+        data.done();
+    }
+}
+
+/// Bind the globals again, if they were removed or `zcosmic_workspace_manager_v2` was added.
+fn rebind<D>(data: &mut D, qh: &QueueHandle<D>)
+where
+    D: ProvidesRegistryState
+        + WorkspaceHandler
+        + Dispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, GlobalData>
+        + Dispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, GlobalData>
+        + 'static,
+{
+    unbind(data);
+    let recorder = data.workspace_state().recorder.clone();
+    let (manager, cosmic_manager) = bind_globals(data.registry(), qh, recorder.as_ref());
+    let state = data.workspace_state();
+    state.manager = manager;
+    state.cosmic_manager = cosmic_manager;
+}
+
+impl<D> RegistryHandler<D> for WorkspaceState
+where
+    D: ProvidesRegistryState
+        + WorkspaceHandler
+        + Dispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, GlobalData>
+        + Dispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, GlobalData>
+        + 'static,
+{
+    fn new_global(
+        data: &mut D,
+        _conn: &Connection,
+        qh: &QueueHandle<D>,
+        _name: u32,
+        interface: &str,
+        _version: u32,
+    ) {
+        let state = data.workspace_state();
+        // `zcosmic_workspace_handle_v2`s are only created along with the workspaces, so
+        // workspaces are announced again to add them
+        let bind = if interface == ext_workspace_manager_v1::ExtWorkspaceManagerV1::interface().name
+        {
+            state.manager.get().is_err()
+        } else if interface
+            == zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2::interface().name
+        {
+            state.cosmic_manager.get().is_err()
+        } else {
+            false
+        };
+        if bind {
+            rebind(data, qh);
+        }
+    }
+
+    fn remove_global(
+        data: &mut D,
+        _conn: &Connection,
+        qh: &QueueHandle<D>,
+        _name: u32,
+        interface: &str,
+    ) {
+        if interface == ext_workspace_manager_v1::ExtWorkspaceManagerV1::interface().name
+            || interface
+                == zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2::interface().name
+        {
+            rebind(data, qh);
+        }
+    }
+}
+
 pub trait WorkspaceHandler {
     fn workspace_state(&mut self) -> &mut WorkspaceState;
 
@@ -176,12 +353,24 @@ where
 {
     fn event(
         state: &mut D,
-        _: &ext_workspace_manager_v1::ExtWorkspaceManagerV1,
+        proxy: &ext_workspace_manager_v1::ExtWorkspaceManagerV1,
         event: ext_workspace_manager_v1::Event,
         _: &GlobalData,
         _: &Connection,
         qh: &QueueHandle<D>,
     ) {
+        // Events a manager replaced by `rebind` sends until `finished`, with objects that
+        // were never announced to the handler
+        if state.workspace_state().manager.get().ok() != Some(proxy) {
+            match event {
+                ext_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
+                    workspace_group.destroy();
+                }
+                ext_workspace_manager_v1::Event::Workspace { workspace } => workspace.destroy(),
+                _ => {}
+            }
+            return;
+        }
         match event {
             ext_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
                 state
@@ -194,14 +383,22 @@ where
                     });
             }
             ext_workspace_manager_v1::Event::Workspace { workspace } => {
+                let workspace_state = state.workspace_state();
                 let cosmic_handle =
-                    state
-                        .workspace_state()
+                    workspace_state
                         .cosmic_manager
                         .get()
                         .ok()
                         .map(|cosmic_manager| {
-                            cosmic_manager.get_cosmic_workspace(&workspace, qh, GlobalData)
+                            recording::create(
+                                workspace_state.recorder.as_ref(),
+                                cosmic_manager,
+                                zcosmic_workspace_manager_v2::Request::GetCosmicWorkspace {
+                                    workspace: workspace.clone(),
+                                },
+                                qh,
+                                GlobalData,
+                            )
                         });
                 state.workspace_state().workspaces.push(WorkspaceData {
                     handle: workspace,
@@ -232,7 +429,17 @@ where
                 }
                 state.done();
             }
-            ext_workspace_manager_v1::Event::Finished => {}
+            // The compositor stopped sending events on its own
+            ext_workspace_manager_v1::Event::Finished => {
+                let workspace_state = state.workspace_state();
+                workspace_state.manager = GlobalProxy::NotPresent;
+                let bound = !workspace_state.workspaces.is_empty()
+                    || !workspace_state.workspace_groups.is_empty();
+                unbind(state);
+                if bound {
+                    state.done();
+                }
+            }
             _ => unreachable!(),
         }
     }
@@ -259,12 +466,15 @@ where
         _: &Connection,
         _: &QueueHandle<D>,
     ) {
-        let group = &mut state
+        // Groups of a replaced manager aren't tracked
+        let Some(group) = state
             .workspace_state()
             .workspace_groups
             .iter_mut()
             .find(|group| &group.handle == handle)
-            .unwrap();
+        else {
+            return;
+        };
         match event {
             ext_workspace_group_handle_v1::Event::Capabilities { capabilities } => {
                 group.pending().capabilities = bitflags_retained(capabilities);
@@ -311,12 +521,15 @@ where
         _: &Connection,
         _: &QueueHandle<D>,
     ) {
-        let workspace = state
+        // Workspaces of a replaced manager aren't tracked
+        let Some(workspace) = state
             .workspace_state()
             .workspaces
             .iter_mut()
             .find(|w| &w.handle == handle)
-            .unwrap();
+        else {
+            return;
+        };
         match event {
             ext_workspace_handle_v1::Event::Name { name } => {
                 workspace.pending().name = name;
@@ -388,12 +601,15 @@ where
         _: &Connection,
         _: &QueueHandle<D>,
     ) {
-        let workspace = state
+        // Handles destroyed by `unbind` may still have events queued
+        let Some(workspace) = state
             .workspace_state()
             .workspaces
             .iter_mut()
             .find(|w| w.cosmic_handle.as_ref() == Some(&handle))
-            .unwrap();
+        else {
+            return;
+        };
         match event {
             zcosmic_workspace_handle_v2::Event::Capabilities { capabilities } => {
                 workspace.pending().cosmic_capabilities = bitflags_retained(capabilities);
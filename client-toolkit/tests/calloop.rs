@@ -0,0 +1,235 @@
+use cosmic_client_toolkit::{
+    calloop::{ScreencopyEvent, ScreencopySource, ToplevelEvent, ToplevelSource, WorkspaceSource},
+    screencopy::{CaptureOptions, CaptureSource},
+};
+use cosmic_mock_compositor::{
+    FrameContent, MockCompositor,
+    wayland_protocols::ext::workspace::v1::server::ext_workspace_group_handle_v1::GroupCapabilities,
+};
+use sctk::{
+    reexports::calloop::{Dispatcher, EventLoop},
+    registry::{ProvidesRegistryState, RegistryState},
+    shm::{Shm, ShmHandler, raw::RawPool},
+};
+use std::time::Duration;
+use wayland_client::{
+    QueueHandle,
+    globals::registry_queue_init,
+    protocol::{wl_buffer, wl_shm},
+};
+
+// Dispatch the loop until `done` returns true, failing after a second
+fn dispatch_until<T>(event_loop: &mut EventLoop<T>, data: &mut T, done: impl Fn(&T) -> bool) {
+    for _ in 0..100 {
+        if done(data) {
+            return;
+        }
+        event_loop
+            .dispatch(Duration::from_millis(10), data)
+            .unwrap();
+    }
+    panic!("timed out waiting for events");
+}
+
+#[test]
+fn toplevel_events() {
+    let mock = MockCompositor::new();
+    let toplevel = mock.with(|c| c.new_toplevel("org.example.App", "Example"));
+    let conn = mock.connect();
+    let mut event_loop = EventLoop::<Vec<ToplevelEvent>>::try_new().unwrap();
+    event_loop
+        .handle()
+        .insert_source(
+            ToplevelSource::new(&conn).unwrap(),
+            |event, _state, events| events.push(event),
+        )
+        .unwrap();
+
+    let mut events = Vec::new();
+    dispatch_until(&mut event_loop, &mut events, |events| !events.is_empty());
+    let [ToplevelEvent::Added(info)] = &events[..] else {
+        panic!("unexpected events: {events:?}");
+    };
+    assert_eq!(info.title, "Example");
+    let handle = info.foreign_toplevel.clone();
+
+    events.clear();
+    mock.with(|c| {
+        c.set_toplevel_title(toplevel, "Renamed");
+        c.toplevel_done(toplevel);
+    });
+    dispatch_until(&mut event_loop, &mut events, |events| !events.is_empty());
+    let [ToplevelEvent::Changed(info)] = &events[..] else {
+        panic!("unexpected events: {events:?}");
+    };
+    assert_eq!(info.title, "Renamed");
+
+    events.clear();
+    mock.with(|c| c.close_toplevel(toplevel));
+    dispatch_until(&mut event_loop, &mut events, |events| !events.is_empty());
+    assert!(matches!(&events[..], [ToplevelEvent::Closed(closed)] if *closed == handle));
+}
+
+#[test]
+fn workspace_snapshots() {
+    let mock = MockCompositor::new();
+    let group = mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        c.new_workspace(Some(group), "1");
+        c.workspace_done();
+        group
+    });
+    let conn = mock.connect();
+    let mut event_loop = EventLoop::try_new().unwrap();
+    event_loop
+        .handle()
+        .insert_source(
+            WorkspaceSource::new(&conn).unwrap(),
+            |workspaces, _state, snapshots: &mut Vec<_>| snapshots.push(workspaces),
+        )
+        .unwrap();
+
+    let mut snapshots = Vec::new();
+    dispatch_until(&mut event_loop, &mut snapshots, |snapshots| {
+        !snapshots.is_empty()
+    });
+    assert_eq!(snapshots[0].groups.len(), 1);
+    assert_eq!(snapshots[0].workspaces.len(), 1);
+    assert_eq!(snapshots[0].workspaces[0].name, "1");
+
+    snapshots.clear();
+    mock.with(|c| {
+        c.new_workspace(Some(group), "2");
+        c.workspace_done();
+    });
+    dispatch_until(&mut event_loop, &mut snapshots, |snapshots| {
+        !snapshots.is_empty()
+    });
+    let names = snapshots[0]
+        .workspaces
+        .iter()
+        .map(|workspace| workspace.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["1", "2"]);
+    assert_eq!(snapshots[0].groups[0].workspaces.len(), 2);
+}
+
+// Buffers for captured frames, on a queue of the test's own
+struct AppData {
+    registry_state: RegistryState,
+    shm: Shm,
+    qh: QueueHandle<AppData>,
+    pools: Vec<RawPool>,
+    events: Vec<ScreencopyEvent>,
+}
+
+impl AppData {
+    fn create_buffer(&mut self, size: (u32, u32)) -> wl_buffer::WlBuffer {
+        let (width, height) = (size.0 as i32, size.1 as i32);
+        let mut pool = RawPool::new((width * height * 4) as usize, &self.shm).unwrap();
+        let buffer = pool.create_buffer(
+            0,
+            width,
+            height,
+            width * 4,
+            wl_shm::Format::Argb8888,
+            (),
+            &self.qh,
+        );
+        self.pools.push(pool);
+        buffer
+    }
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!();
+}
+
+impl ShmHandler for AppData {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+sctk::delegate_registry!(AppData);
+sctk::delegate_shm!(AppData);
+wayland_client::delegate_noop!(AppData: ignore wl_buffer::WlBuffer);
+
+#[test]
+fn capture_frame() {
+    let mock = MockCompositor::new();
+    mock.with(|c| {
+        let toplevel = c.new_toplevel("org.example.App", "Example");
+        c.set_toplevel_capture_size(toplevel, (64, 32));
+    });
+    let conn = mock.connect();
+    let (globals, event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let mut app_data = AppData {
+        registry_state: RegistryState::new(&globals),
+        shm: Shm::bind(&globals, &qh).unwrap(),
+        qh,
+        pools: Vec::new(),
+        events: Vec::new(),
+    };
+
+    let mut event_loop = EventLoop::<AppData>::try_new().unwrap();
+    let toplevels = ToplevelSource::new(&conn).unwrap();
+    let toplevels = Dispatcher::new(toplevels, |_event, _state, _app_data| {});
+    event_loop
+        .handle()
+        .register_dispatcher(toplevels.clone())
+        .unwrap();
+    // Capture into a new buffer once the session is ready
+    let screencopy = Dispatcher::new(
+        ScreencopySource::new(&conn).unwrap(),
+        |event, screencopy, app_data: &mut AppData| {
+            if let ScreencopyEvent::InitDone { session, formats } = &event {
+                let buffer = app_data.create_buffer(formats.buffer_size);
+                screencopy.capture(session, &buffer, &[]);
+            }
+            app_data.events.push(event);
+        },
+    );
+    event_loop
+        .handle()
+        .register_dispatcher(screencopy.clone())
+        .unwrap();
+
+    dispatch_until(&mut event_loop, &mut app_data, |_| {
+        toplevels.as_source_ref().state().toplevels().count() == 1
+    });
+    let toplevel = toplevels
+        .as_source_ref()
+        .state()
+        .toplevels()
+        .next()
+        .unwrap()
+        .foreign_toplevel
+        .clone();
+    let _session = screencopy
+        .as_source_ref()
+        .data()
+        .create_session(&CaptureSource::Toplevel(toplevel), CaptureOptions::empty())
+        .unwrap();
+    dispatch_until(&mut event_loop, &mut app_data, |_| {
+        mock.with(|c| c.pending_frames().count()) == 1
+    });
+    let [ScreencopyEvent::InitDone { formats, .. }] = &app_data.events[..] else {
+        panic!("unexpected events: {:?}", app_data.events);
+    };
+    assert_eq!(formats.buffer_size, (64, 32));
+
+    mock.with(|c| {
+        let frame = c.pending_frames().next().unwrap();
+        c.ready_frame(frame, &FrameContent::default());
+    });
+    dispatch_until(&mut event_loop, &mut app_data, |app_data| {
+        app_data.events.len() == 2
+    });
+    assert!(matches!(app_data.events[1], ScreencopyEvent::Ready { .. }));
+}
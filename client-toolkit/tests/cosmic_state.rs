@@ -0,0 +1,236 @@
+use cosmic_client_toolkit::{
+    cosmic_protocols::toplevel_management::v1::client::zcosmic_toplevel_manager_v1,
+    cosmic_state::{CosmicHandler, CosmicState},
+    screencopy::{
+        CaptureFrame, CaptureSession, FailureReason, Formats, Frame, ScreencopyHandler,
+        ScreencopyState,
+    },
+    toplevel_info::{ToplevelInfoHandler, ToplevelInfoState},
+    toplevel_management::{ToplevelManagerHandler, ToplevelManagerState},
+    workspace::{WorkspaceHandler, WorkspaceState},
+};
+use cosmic_mock_compositor::{
+    Global, MockCompositor,
+    wayland_protocols::ext::workspace::v1::server::ext_workspace_group_handle_v1::GroupCapabilities,
+};
+use sctk::registry::{ProvidesRegistryState, RegistryState};
+use wayland_client::{Connection, EventQueue, QueueHandle, WEnum, globals::registry_queue_init};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1;
+
+struct AppData {
+    registry_state: RegistryState,
+    cosmic_state: CosmicState,
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(CosmicState);
+}
+
+impl CosmicHandler for AppData {
+    fn cosmic_state(&mut self) -> &mut CosmicState {
+        &mut self.cosmic_state
+    }
+}
+
+impl ToplevelInfoHandler for AppData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        self.cosmic_state.toplevel_info.as_mut().unwrap()
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+}
+
+impl ToplevelManagerHandler for AppData {
+    fn toplevel_manager_state(&mut self) -> &mut ToplevelManagerState {
+        self.cosmic_state.toplevel_manager.as_mut().unwrap()
+    }
+
+    fn capabilities(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _capabilities: Vec<
+            WEnum<zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1>,
+        >,
+    ) {
+    }
+}
+
+impl WorkspaceHandler for AppData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        self.cosmic_state.workspace.as_mut().unwrap()
+    }
+
+    fn done(&mut self) {}
+}
+
+impl ScreencopyHandler for AppData {
+    fn screencopy_state(&mut self) -> &mut ScreencopyState {
+        self.cosmic_state.screencopy.as_mut().unwrap()
+    }
+
+    fn init_done(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _session: &CaptureSession,
+        _formats: &Formats,
+    ) {
+    }
+
+    fn stopped(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _session: &CaptureSession) {}
+
+    fn ready(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _screencopy_frame: &CaptureFrame,
+        _frame: Frame,
+    ) {
+    }
+
+    fn failed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _screencopy_frame: &CaptureFrame,
+        _reason: WEnum<FailureReason>,
+    ) {
+    }
+}
+
+sctk::delegate_registry!(AppData);
+cosmic_client_toolkit::delegate_cosmic!(AppData);
+
+fn connect(mock: &MockCompositor) -> (EventQueue<AppData>, AppData) {
+    let conn = mock.connect();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let mut app_data = AppData {
+        cosmic_state: CosmicState::new(&registry_state, &qh),
+        registry_state,
+    };
+    event_queue.roundtrip(&mut app_data).unwrap();
+    event_queue.roundtrip(&mut app_data).unwrap();
+    (event_queue, app_data)
+}
+
+#[test]
+fn binds_advertised_globals() {
+    let mock = MockCompositor::with_globals(
+        Global::ALL
+            .iter()
+            .filter(|global| {
+                !matches!(
+                    global,
+                    Global::WorkspaceManager | Global::ImageCopyCaptureManager
+                )
+            })
+            .map(|global| (*global, global.max_version())),
+    );
+    mock.with(|c| c.new_toplevel("org.example.App", "Example"));
+    let (_event_queue, app_data) = connect(&mock);
+
+    let cosmic_state = &app_data.cosmic_state;
+    assert_eq!(
+        cosmic_state
+            .toplevel_info
+            .as_ref()
+            .unwrap()
+            .toplevels()
+            .count(),
+        1
+    );
+    assert!(cosmic_state.toplevel_manager.is_some());
+    assert!(cosmic_state.workspace.is_none());
+    assert!(cosmic_state.screencopy.is_none());
+}
+
+#[test]
+fn globals_added_and_removed() {
+    let mock = MockCompositor::new();
+    let toplevel = mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        c.new_workspace(Some(group), "1");
+        c.workspace_done();
+        c.new_toplevel("org.example.App", "Example")
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+    assert!(app_data.cosmic_state.toplevel_info.is_some());
+    assert!(app_data.cosmic_state.workspace.is_some());
+    assert!(app_data.cosmic_state.screencopy.is_some());
+
+    mock.with(|c| {
+        c.remove_global(Global::ForeignToplevelList);
+        c.remove_global(Global::WorkspaceManager);
+        c.remove_global(Global::ImageCopyCaptureManager);
+        c.remove_global(Global::ToplevelManager);
+    });
+    event_queue.roundtrip(&mut app_data).unwrap();
+    assert!(app_data.cosmic_state.toplevel_info.is_none());
+    assert!(app_data.cosmic_state.workspace.is_none());
+    assert!(app_data.cosmic_state.screencopy.is_none());
+    assert!(app_data.cosmic_state.toplevel_manager.is_none());
+
+    mock.with(|c| {
+        c.create_global(Global::ForeignToplevelList, 1);
+        c.create_global(Global::WorkspaceManager, 1);
+        c.create_global(Global::ImageCopyCaptureManager, 1);
+    });
+    event_queue.roundtrip(&mut app_data).unwrap();
+    event_queue.roundtrip(&mut app_data).unwrap();
+    // Objects of the removed globals were destroyed, so only the new ones get these events
+    mock.with(|c| {
+        c.set_toplevel_title(toplevel, "Renamed");
+        c.toplevel_done(toplevel);
+    });
+    event_queue.roundtrip(&mut app_data).unwrap();
+
+    let cosmic_state = &app_data.cosmic_state;
+    let toplevels = cosmic_state
+        .toplevel_info
+        .as_ref()
+        .unwrap()
+        .toplevels()
+        .collect::<Vec<_>>();
+    assert_eq!(toplevels.len(), 1);
+    assert_eq!(toplevels[0].title, "Renamed");
+    assert_eq!(
+        cosmic_state
+            .workspace
+            .as_ref()
+            .unwrap()
+            .workspaces()
+            .count(),
+        1
+    );
+    assert!(cosmic_state.screencopy.is_some());
+    assert!(cosmic_state.toplevel_manager.is_none());
+}
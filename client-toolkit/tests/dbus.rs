@@ -0,0 +1,368 @@
+use cosmic_client_toolkit::{
+    cosmic_protocols::toplevel_management::v1::client::zcosmic_toplevel_manager_v1,
+    dbus::{
+        DbusBridge, TOPLEVELS_INTERFACE, TOPLEVELS_PATH, Toplevel, WORKSPACES_INTERFACE,
+        WORKSPACES_PATH, Workspace,
+    },
+    toplevel_info::{ToplevelInfoHandler, ToplevelInfoState},
+    toplevel_management::{ToplevelManagerHandler, ToplevelManagerState},
+    workspace::{WorkspaceHandler, WorkspaceState},
+};
+use cosmic_mock_compositor::{
+    Global, MockCompositor, Request,
+    wayland_protocols::ext::workspace::v1::server::{
+        ext_workspace_group_handle_v1::GroupCapabilities, ext_workspace_handle_v1,
+    },
+};
+use sctk::{
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+};
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+};
+use wayland_client::{
+    Connection, EventQueue, QueueHandle, WEnum, globals::registry_queue_init, protocol::wl_output,
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1;
+
+struct AppData {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    toplevel_info_state: ToplevelInfoState,
+    toplevel_manager_state: ToplevelManagerState,
+    workspace_state: WorkspaceState,
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(OutputState);
+}
+
+impl OutputHandler for AppData {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+}
+
+impl ToplevelInfoHandler for AppData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        &mut self.toplevel_info_state
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+}
+
+impl ToplevelManagerHandler for AppData {
+    fn toplevel_manager_state(&mut self) -> &mut ToplevelManagerState {
+        &mut self.toplevel_manager_state
+    }
+
+    fn capabilities(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _capabilities: Vec<
+            WEnum<zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1>,
+        >,
+    ) {
+    }
+}
+
+impl WorkspaceHandler for AppData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+
+    fn done(&mut self) {}
+}
+
+sctk::delegate_registry!(AppData);
+sctk::delegate_output!(AppData);
+cosmic_client_toolkit::delegate_toplevel_info!(AppData);
+cosmic_client_toolkit::delegate_toplevel_manager!(AppData);
+cosmic_client_toolkit::delegate_workspace!(AppData);
+
+// Private bus, killed on drop
+struct DbusDaemon {
+    child: Child,
+    address: String,
+}
+
+impl DbusDaemon {
+    fn new() -> Self {
+        let mut child = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to run dbus-daemon");
+        let mut address = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        Self {
+            child,
+            address: address.trim().to_string(),
+        }
+    }
+
+    fn connect(&self) -> zbus::blocking::Connection {
+        zbus::blocking::connection::Builder::address(&*self.address)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+}
+
+impl Drop for DbusDaemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn connect(mock: &MockCompositor) -> (Connection, EventQueue<AppData>, AppData) {
+    let conn = mock.connect();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let mut app_data = AppData {
+        output_state: OutputState::new(&globals, &qh),
+        toplevel_info_state: ToplevelInfoState::new(&registry_state, &qh),
+        toplevel_manager_state: ToplevelManagerState::new(&registry_state, &qh),
+        workspace_state: WorkspaceState::new(&registry_state, &qh),
+        registry_state,
+    };
+    event_queue.roundtrip(&mut app_data).unwrap();
+    event_queue.roundtrip(&mut app_data).unwrap();
+    (conn, event_queue, app_data)
+}
+
+#[test]
+fn bridge() {
+    let mock = MockCompositor::new();
+    let (toplevel, workspace) = mock.with(|c| {
+        let output = c.add_output("DP-1", (0, 0), (1920, 1080), 1);
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        c.workspace_group_enter_output(group, output);
+        let workspace = c.new_workspace(Some(group), "1");
+        c.workspace_done();
+        let toplevel = c.new_toplevel("org.example.App", "Example");
+        c.toplevel_enter_output(toplevel, output);
+        (toplevel, workspace)
+    });
+    let (conn, mut event_queue, mut app_data) = connect(&mock);
+
+    let daemon = DbusDaemon::new();
+    let server = daemon.connect();
+    let bridge = DbusBridge::new(&server, &conn, Some(&app_data.toplevel_manager_state)).unwrap();
+    bridge
+        .update_toplevels(&app_data.toplevel_info_state, &app_data.output_state)
+        .unwrap();
+    bridge
+        .update_workspaces(&app_data.workspace_state, &app_data.output_state)
+        .unwrap();
+
+    let client = daemon.connect();
+    let toplevels = zbus::blocking::proxy::Builder::<zbus::blocking::Proxy>::new(&client)
+        .destination(server.unique_name().unwrap().to_owned())
+        .unwrap()
+        .path(TOPLEVELS_PATH)
+        .unwrap()
+        .interface(TOPLEVELS_INTERFACE)
+        .unwrap()
+        .cache_properties(zbus::proxy::CacheProperties::No)
+        .build()
+        .unwrap();
+    let list = toplevels
+        .get_property::<Vec<Toplevel>>("Toplevels")
+        .unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].app_id, "org.example.App");
+    assert_eq!(list[0].outputs, ["DP-1"]);
+    let object_id = list[0].object_id;
+
+    // Changes are announced with `PropertiesChanged`
+    let properties = zbus::blocking::fdo::PropertiesProxy::builder(&client)
+        .destination(server.unique_name().unwrap().to_owned())
+        .unwrap()
+        .path(TOPLEVELS_PATH)
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut changes = properties.receive_properties_changed().unwrap();
+    mock.with(|c| {
+        c.set_toplevel_title(toplevel, "Renamed");
+        c.toplevel_done(toplevel);
+    });
+    event_queue.roundtrip(&mut app_data).unwrap();
+    bridge
+        .update_toplevels(&app_data.toplevel_info_state, &app_data.output_state)
+        .unwrap();
+    let change = changes.next().unwrap();
+    let args = change.args().unwrap();
+    assert!(args.changed_properties().contains_key("Toplevels"));
+    let list = toplevels
+        .get_property::<Vec<Toplevel>>("Toplevels")
+        .unwrap();
+    assert_eq!(list[0].title, "Renamed");
+
+    // Method calls are forwarded to the compositor
+    toplevels
+        .call_method("SetMaximized", &(object_id, true))
+        .unwrap();
+    toplevels.call_method("Close", &(object_id,)).unwrap();
+    assert!(toplevels.call_method("Close", &(object_id + 1,)).is_err());
+    let workspaces = zbus::blocking::proxy::Builder::<zbus::blocking::Proxy>::new(&client)
+        .destination(server.unique_name().unwrap().to_owned())
+        .unwrap()
+        .path(WORKSPACES_PATH)
+        .unwrap()
+        .interface(WORKSPACES_INTERFACE)
+        .unwrap()
+        .cache_properties(zbus::proxy::CacheProperties::No)
+        .build()
+        .unwrap();
+    let list = workspaces
+        .get_property::<Vec<Workspace>>("Workspaces")
+        .unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].name, "1");
+    assert_eq!(list[0].outputs, ["DP-1"]);
+    workspaces
+        .call_method("Rename", &(list[0].object_id, "renamed"))
+        .unwrap();
+    event_queue.roundtrip(&mut app_data).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [
+            Request::SetMaximized(toplevel),
+            Request::CloseToplevel(toplevel),
+            Request::RenameWorkspace {
+                workspace,
+                name: "renamed".to_string(),
+            },
+            Request::CommitWorkspaces,
+        ]
+    );
+
+    // Requests go to the toplevel manager the bridge was last given
+    bridge.update_toplevel_manager(None);
+    assert!(
+        toplevels
+            .call_method("SetMinimized", &(object_id, true))
+            .is_err()
+    );
+    bridge.update_toplevel_manager(Some(&app_data.toplevel_manager_state));
+    toplevels
+        .call_method("SetMinimized", &(object_id, true))
+        .unwrap();
+    event_queue.roundtrip(&mut app_data).unwrap();
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [Request::SetMinimized(toplevel)]
+    );
+}
+
+#[test]
+fn unsupported() {
+    // Version 2 of the toplevel manager has no sticky capability
+    let mock = MockCompositor::with_globals(Global::ALL.iter().map(|global| match global {
+        Global::ToplevelManager => (*global, 2),
+        _ => (*global, global.max_version()),
+    }));
+    mock.with(|c| {
+        let workspace = c.new_workspace(None, "1");
+        c.set_workspace_capabilities(
+            workspace,
+            ext_workspace_handle_v1::WorkspaceCapabilities::Activate,
+        );
+        c.workspace_done();
+        c.new_toplevel("org.example.App", "Example");
+    });
+    let (conn, _event_queue, app_data) = connect(&mock);
+
+    let daemon = DbusDaemon::new();
+    let server = daemon.connect();
+    let bridge = DbusBridge::new(&server, &conn, Some(&app_data.toplevel_manager_state)).unwrap();
+    bridge
+        .update_toplevels(&app_data.toplevel_info_state, &app_data.output_state)
+        .unwrap();
+    bridge
+        .update_workspaces(&app_data.workspace_state, &app_data.output_state)
+        .unwrap();
+
+    let client = daemon.connect();
+    let proxy = |path, interface| {
+        zbus::blocking::proxy::Builder::<zbus::blocking::Proxy>::new(&client)
+            .destination(server.unique_name().unwrap().to_owned())
+            .unwrap()
+            .path(path)
+            .unwrap()
+            .interface(interface)
+            .unwrap()
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .unwrap()
+    };
+    let not_supported = |result: zbus::Result<_>| match result {
+        Err(zbus::Error::MethodError(name, ..)) => {
+            name.as_str() == "org.freedesktop.DBus.Error.NotSupported"
+        }
+        _ => false,
+    };
+
+    let toplevels = proxy(TOPLEVELS_PATH, TOPLEVELS_INTERFACE);
+    let object_id = toplevels
+        .get_property::<Vec<Toplevel>>("Toplevels")
+        .unwrap()[0]
+        .object_id;
+    assert!(not_supported(
+        toplevels.call_method("SetSticky", &(object_id, true))
+    ));
+    toplevels
+        .call_method("SetFullscreen", &(object_id, true))
+        .unwrap();
+
+    let workspaces = proxy(WORKSPACES_PATH, WORKSPACES_INTERFACE);
+    let object_id = workspaces
+        .get_property::<Vec<Workspace>>("Workspaces")
+        .unwrap()[0]
+        .object_id;
+    assert!(not_supported(
+        workspaces.call_method("Remove", &(object_id,))
+    ));
+    workspaces.call_method("Activate", &(object_id,)).unwrap();
+}
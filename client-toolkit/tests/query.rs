@@ -0,0 +1,239 @@
+use cosmic_client_toolkit::{
+    query::Query,
+    toplevel_info::{ToplevelInfo, ToplevelInfoHandler, ToplevelInfoState},
+    workspace::{WorkspaceHandler, WorkspaceState},
+};
+use cosmic_mock_compositor::{
+    MockCompositor,
+    wayland_protocols::ext::workspace::v1::server::{
+        ext_workspace_group_handle_v1::GroupCapabilities, ext_workspace_handle_v1::State,
+    },
+};
+use sctk::{
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+};
+use wayland_client::{Connection, QueueHandle, globals::registry_queue_init, protocol::wl_output};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1,
+    workspace::v1::client::ext_workspace_handle_v1,
+};
+
+struct AppData {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    toplevel_info_state: ToplevelInfoState,
+    workspace_state: WorkspaceState,
+}
+
+impl AppData {
+    fn query(&self) -> Query<'_> {
+        Query::new(&self.toplevel_info_state, &self.workspace_state)
+    }
+
+    fn output(&self, name: &str) -> wl_output::WlOutput {
+        self.output_state
+            .outputs()
+            .find(|output| {
+                self.output_state
+                    .info(output)
+                    .is_some_and(|info| info.name.as_deref() == Some(name))
+            })
+            .unwrap()
+    }
+
+    fn workspace(&self, name: &str) -> ext_workspace_handle_v1::ExtWorkspaceHandleV1 {
+        self.workspace_state
+            .workspaces()
+            .find(|workspace| workspace.name == name)
+            .unwrap()
+            .handle
+            .clone()
+    }
+
+    fn workspace_of_toplevel(&self, title: &str) -> Option<&str> {
+        self.query()
+            .workspace_of_toplevel(&self.toplevel(title))
+            .map(|workspace| workspace.name.as_str())
+    }
+
+    fn toplevel(&self, title: &str) -> ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1 {
+        self.toplevel_info_state
+            .toplevels()
+            .find(|info| info.title == title)
+            .unwrap()
+            .foreign_toplevel
+            .clone()
+    }
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(OutputState);
+}
+
+impl OutputHandler for AppData {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+}
+
+impl ToplevelInfoHandler for AppData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        &mut self.toplevel_info_state
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+}
+
+impl WorkspaceHandler for AppData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+
+    fn done(&mut self) {}
+}
+
+sctk::delegate_registry!(AppData);
+sctk::delegate_output!(AppData);
+cosmic_client_toolkit::delegate_toplevel_info!(AppData);
+cosmic_client_toolkit::delegate_workspace!(AppData);
+
+fn titles<'a>(toplevels: impl Iterator<Item = &'a ToplevelInfo>) -> Vec<&'a str> {
+    toplevels.map(|info| info.title.as_str()).collect()
+}
+
+#[test]
+fn queries() {
+    let mock = MockCompositor::new();
+    let (workspaces, toplevels) = mock.with(|c| {
+        let left = c.add_output("DP-1", (0, 0), (1920, 1080), 1);
+        let right = c.add_output("DP-2", (1920, 0), (1920, 1080), 1);
+        let left_group = c.new_workspace_group(GroupCapabilities::empty());
+        c.workspace_group_enter_output(left_group, left);
+        let right_group = c.new_workspace_group(GroupCapabilities::empty());
+        c.workspace_group_enter_output(right_group, right);
+        let workspaces = [
+            c.new_workspace(Some(left_group), "1"),
+            c.new_workspace(Some(left_group), "2"),
+            c.new_workspace(Some(right_group), "3"),
+        ];
+        c.set_workspace_state(workspaces[0], State::Active);
+        c.set_workspace_state(workspaces[2], State::Active);
+        c.workspace_done();
+        let toplevels = [
+            c.new_toplevel("org.example.Editor", "Editor"),
+            c.new_toplevel("org.example.Terminal", "Terminal"),
+            c.new_toplevel("org.example.Player", "Player"),
+        ];
+        c.toplevel_enter_workspace(toplevels[0], workspaces[0]);
+        c.toplevel_enter_workspace(toplevels[1], workspaces[1]);
+        // Sticky toplevel
+        c.toplevel_enter_workspace(toplevels[2], workspaces[1]);
+        c.toplevel_enter_workspace(toplevels[2], workspaces[2]);
+        (workspaces, toplevels)
+    });
+
+    let conn = mock.connect();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let mut app_data = AppData {
+        output_state: OutputState::new(&globals, &qh),
+        toplevel_info_state: ToplevelInfoState::new(&registry_state, &qh),
+        workspace_state: WorkspaceState::new(&registry_state, &qh),
+        registry_state,
+    };
+    event_queue.roundtrip(&mut app_data).unwrap();
+    event_queue.roundtrip(&mut app_data).unwrap();
+
+    let query = app_data.query();
+    assert_eq!(
+        titles(query.toplevels_in_workspace(&app_data.workspace("2"))),
+        ["Terminal", "Player"]
+    );
+    assert_eq!(
+        query
+            .active_workspace_for_output(&app_data.output("DP-1"))
+            .unwrap()
+            .name,
+        "1"
+    );
+    assert_eq!(
+        query
+            .active_workspace_for_output(&app_data.output("DP-2"))
+            .unwrap()
+            .name,
+        "3"
+    );
+    assert_eq!(
+        titles(query.toplevels_on_active_workspaces()),
+        ["Editor", "Player"]
+    );
+    assert_eq!(app_data.workspace_of_toplevel("Editor"), Some("1"));
+    // The active workspace is preferred
+    assert_eq!(app_data.workspace_of_toplevel("Player"), Some("3"));
+
+    // Results follow the next `done`
+    mock.with(|c| {
+        c.set_workspace_state(workspaces[0], State::empty());
+        c.set_workspace_state(workspaces[1], State::Active);
+        c.set_workspace_state(workspaces[2], State::empty());
+        c.workspace_done();
+        c.toplevel_leave_workspace(toplevels[1], workspaces[1]);
+        c.toplevel_done(toplevels[1]);
+    });
+    event_queue.roundtrip(&mut app_data).unwrap();
+
+    let query = app_data.query();
+    assert_eq!(
+        query
+            .active_workspace_for_output(&app_data.output("DP-1"))
+            .unwrap()
+            .name,
+        "2"
+    );
+    assert!(
+        query
+            .active_workspace_for_output(&app_data.output("DP-2"))
+            .is_none()
+    );
+    assert_eq!(titles(query.toplevels_on_active_workspaces()), ["Player"]);
+    assert_eq!(
+        titles(query.toplevels_in_workspace(&app_data.workspace("2"))),
+        ["Player"]
+    );
+    assert_eq!(app_data.workspace_of_toplevel("Terminal"), None);
+    assert_eq!(app_data.workspace_of_toplevel("Player"), Some("2"));
+}
@@ -0,0 +1,273 @@
+use cosmic_client_toolkit::{
+    recording::Recorder,
+    screencopy::{
+        CaptureFrame, CaptureSession, FailureReason, Formats, Frame, ScreencopyHandler,
+        ScreencopyState, ToplevelPreviews,
+    },
+    toplevel_info::{ToplevelInfoHandler, ToplevelInfoState},
+    workspace::{WorkspaceHandler, WorkspaceState},
+};
+use cosmic_mock_compositor::{
+    FrameContent, MockCompositor, Recording, ReplayStatus,
+    wayland_protocols::ext::workspace::v1::server::ext_workspace_group_handle_v1::GroupCapabilities,
+};
+use sctk::{
+    registry::{ProvidesRegistryState, RegistryState},
+    shm::{Shm, ShmHandler},
+};
+use std::time::Duration;
+use wayland_client::{
+    Connection, EventQueue, QueueHandle, WEnum, globals::registry_queue_init, protocol::wl_buffer,
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1;
+
+struct AppData {
+    registry_state: RegistryState,
+    shm: Shm,
+    toplevel_info_state: ToplevelInfoState,
+    workspace_state: WorkspaceState,
+    screencopy_state: ScreencopyState,
+    previews: ToplevelPreviews,
+    // What the handlers were called with, compared between the session and its replay
+    events: Vec<String>,
+}
+
+impl AppData {
+    fn title(
+        &self,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) -> String {
+        self.toplevel_info_state
+            .info(toplevel)
+            .unwrap()
+            .title
+            .clone()
+    }
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!();
+}
+
+impl ShmHandler for AppData {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+impl ToplevelInfoHandler for AppData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        &mut self.toplevel_info_state
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.events
+            .push(format!("new toplevel {}", self.title(toplevel)));
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.events
+            .push(format!("update toplevel {}", self.title(toplevel)));
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.events
+            .push(format!("closed toplevel {}", self.title(toplevel)));
+        self.previews.release(toplevel);
+    }
+}
+
+impl WorkspaceHandler for AppData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+
+    fn done(&mut self) {
+        let names = self
+            .workspace_state
+            .workspaces()
+            .map(|w| w.name.as_str())
+            .collect::<Vec<_>>();
+        self.events.push(format!("workspaces {:?}", names));
+    }
+}
+
+impl ScreencopyHandler for AppData {
+    fn screencopy_state(&mut self) -> &mut ScreencopyState {
+        &mut self.screencopy_state
+    }
+
+    fn init_done(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        session: &CaptureSession,
+        formats: &Formats,
+    ) {
+        self.events
+            .push(format!("init done {:?}", formats.buffer_size));
+        self.previews.init_done(&self.shm, qh, session, formats);
+    }
+
+    fn stopped(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, session: &CaptureSession) {
+        self.events.push("stopped".to_string());
+        self.previews.stopped(session);
+    }
+
+    fn ready(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        screencopy_frame: &CaptureFrame,
+        frame: Frame,
+    ) {
+        self.events.push(format!("ready {:?}", frame.present_time));
+        self.previews.ready(&self.shm, qh, screencopy_frame, &frame);
+    }
+
+    fn failed(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        screencopy_frame: &CaptureFrame,
+        reason: WEnum<FailureReason>,
+    ) {
+        self.events.push(format!("failed {:?}", reason));
+        self.previews
+            .failed(&self.shm, qh, screencopy_frame, reason);
+    }
+}
+
+sctk::delegate_registry!(AppData);
+sctk::delegate_shm!(AppData);
+cosmic_client_toolkit::delegate_toplevel_info!(AppData);
+cosmic_client_toolkit::delegate_workspace!(AppData);
+cosmic_client_toolkit::delegate_screencopy!(AppData);
+wayland_client::delegate_noop!(AppData: ignore wl_buffer::WlBuffer);
+
+fn connect(mock: &MockCompositor) -> (EventQueue<AppData>, AppData, Recorder) {
+    let conn = mock.connect();
+    let (globals, event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let recorder = Recorder::new(&globals);
+    let app_data = AppData {
+        shm: Shm::bind(&globals, &qh).unwrap(),
+        toplevel_info_state: ToplevelInfoState::new_recorded(&registry_state, &qh, &recorder),
+        workspace_state: WorkspaceState::new_recorded(&registry_state, &qh, &recorder),
+        screencopy_state: ScreencopyState::new_recorded(&globals, &qh, &recorder),
+        registry_state,
+        previews: ToplevelPreviews::new((16, 16), Duration::ZERO),
+        events: Vec::new(),
+    };
+    (event_queue, app_data, recorder)
+}
+
+// The client sends requests in response to events, so the replies need a second roundtrip
+fn roundtrip(event_queue: &mut EventQueue<AppData>, app_data: &mut AppData) {
+    event_queue.roundtrip(app_data).unwrap();
+    event_queue.roundtrip(app_data).unwrap();
+}
+
+// Replay until the recording is done, or needs something the client doesn't do by itself
+fn replay(
+    mock: &MockCompositor,
+    event_queue: &mut EventQueue<AppData>,
+    app_data: &mut AppData,
+) -> ReplayStatus {
+    let mut status = mock.with(|c| c.replay());
+    loop {
+        roundtrip(event_queue, app_data);
+        let next = mock.with(|c| c.replay());
+        if next == status {
+            return status;
+        }
+        status = next;
+    }
+}
+
+fn request_preview(event_queue: &EventQueue<AppData>, app_data: &mut AppData) {
+    let toplevel = app_data
+        .toplevel_info_state
+        .toplevels()
+        .next()
+        .unwrap()
+        .foreign_toplevel
+        .clone();
+    let capturer = app_data.screencopy_state.capturer().clone();
+    app_data
+        .previews
+        .request(&toplevel, &capturer, &event_queue.handle())
+        .unwrap();
+}
+
+#[test]
+fn replay_matches_recorded_session() {
+    let mock = MockCompositor::new();
+    let toplevel = mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::CreateWorkspace);
+        c.new_workspace(Some(group), "1");
+        c.new_workspace(Some(group), "2");
+        let toplevel = c.new_toplevel("org.example.App", "Example");
+        c.set_toplevel_capture_size(toplevel, (64, 32));
+        toplevel
+    });
+    let (mut event_queue, mut app_data, recorder) = connect(&mock);
+    roundtrip(&mut event_queue, &mut app_data);
+
+    mock.with(|c| {
+        c.set_toplevel_title(toplevel, "Renamed");
+        c.toplevel_done(toplevel);
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+
+    request_preview(&event_queue, &mut app_data);
+    roundtrip(&mut event_queue, &mut app_data);
+    mock.with(|c| {
+        let frame = c.pending_frames().next().unwrap();
+        c.ready_frame(frame, &FrameContent::default());
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+
+    mock.with(|c| c.close_toplevel(toplevel));
+    roundtrip(&mut event_queue, &mut app_data);
+    let recorded_events = app_data.events;
+    assert!(recorded_events.contains(&"ready None".to_string()));
+
+    let recording = recorder.to_string().parse::<Recording>().unwrap();
+    let mock = MockCompositor::replaying(recording);
+    let (mut event_queue, mut app_data, _) = connect(&mock);
+    // The compositor waits for the capture session the client requested
+    assert!(matches!(
+        replay(&mock, &mut event_queue, &mut app_data),
+        ReplayStatus::Blocked { .. }
+    ));
+    assert_eq!(app_data.events, recorded_events[..app_data.events.len()]);
+
+    request_preview(&event_queue, &mut app_data);
+    assert_eq!(
+        replay(&mock, &mut event_queue, &mut app_data),
+        ReplayStatus::Done
+    );
+    assert_eq!(app_data.events, recorded_events);
+    assert!(app_data.toplevel_info_state.toplevels().next().is_none());
+}
@@ -0,0 +1,615 @@
+use cosmic_client_toolkit::{
+    screencopy::{
+        CaptureFrame, CaptureOptions, CaptureSession, CaptureSourceKind, FailureReason, Formats,
+        Frame, FrameStats, Rect, RegionCapture, RegionImage, ScreencopyHandler, ScreencopyState,
+        ToplevelPreviews, WorkspaceThumbnails,
+    },
+    toplevel_info::{ToplevelInfoHandler, ToplevelInfoState},
+    workspace::{WorkspaceHandler, WorkspaceState},
+};
+use cosmic_mock_compositor::{
+    CaptureSource, FrameContent, Global, MockCompositor,
+    wayland_protocols::ext::workspace::v1::server::ext_workspace_group_handle_v1::GroupCapabilities,
+    wayland_server::protocol::wl_output::Transform,
+};
+use sctk::{
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+    shm::{Shm, ShmHandler},
+};
+use std::time::{Duration, Instant};
+use wayland_client::{
+    Connection, EventQueue, QueueHandle, WEnum,
+    globals::registry_queue_init,
+    protocol::{wl_buffer, wl_output},
+};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1,
+    workspace::v1::client::ext_workspace_handle_v1,
+};
+
+struct AppData {
+    registry_state: RegistryState,
+    shm: Shm,
+    output_state: OutputState,
+    toplevel_info_state: ToplevelInfoState,
+    workspace_state: WorkspaceState,
+    screencopy_state: ScreencopyState,
+    previews: ToplevelPreviews,
+    thumbnails: WorkspaceThumbnails,
+    region: Option<RegionCapture>,
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(OutputState);
+}
+
+impl OutputHandler for AppData {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+}
+
+impl ShmHandler for AppData {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+impl ToplevelInfoHandler for AppData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        &mut self.toplevel_info_state
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.previews.release(toplevel);
+    }
+}
+
+impl WorkspaceHandler for AppData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+
+    fn done(&mut self) {}
+}
+
+impl ScreencopyHandler for AppData {
+    fn screencopy_state(&mut self) -> &mut ScreencopyState {
+        &mut self.screencopy_state
+    }
+
+    fn init_done(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        session: &CaptureSession,
+        formats: &Formats,
+    ) {
+        if let Some(region) = &mut self.region
+            && region.init_done(&self.shm, qh, session, formats)
+        {
+            return;
+        }
+        if self.thumbnails.init_done(&self.shm, qh, session, formats) {
+            return;
+        }
+        self.previews.init_done(&self.shm, qh, session, formats);
+    }
+
+    fn stopped(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, session: &CaptureSession) {
+        if let Some(region) = &mut self.region
+            && region.stopped(session)
+        {
+            return;
+        }
+        if self.thumbnails.stopped(session) {
+            return;
+        }
+        self.previews.stopped(session);
+    }
+
+    fn ready(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        screencopy_frame: &CaptureFrame,
+        frame: Frame,
+    ) {
+        if let Some(region) = &mut self.region
+            && region.ready(screencopy_frame, &frame)
+        {
+            return;
+        }
+        if self
+            .thumbnails
+            .ready(&self.shm, qh, screencopy_frame, &frame)
+        {
+            return;
+        }
+        self.previews.ready(&self.shm, qh, screencopy_frame, &frame);
+    }
+
+    fn failed(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        screencopy_frame: &CaptureFrame,
+        reason: WEnum<FailureReason>,
+    ) {
+        if let Some(region) = &mut self.region
+            && region.failed(screencopy_frame, reason)
+        {
+            return;
+        }
+        if self
+            .thumbnails
+            .failed(&self.shm, qh, screencopy_frame, reason)
+        {
+            return;
+        }
+        self.previews
+            .failed(&self.shm, qh, screencopy_frame, reason);
+    }
+}
+
+sctk::delegate_registry!(AppData);
+sctk::delegate_output!(AppData);
+sctk::delegate_shm!(AppData);
+cosmic_client_toolkit::delegate_toplevel_info!(AppData);
+cosmic_client_toolkit::delegate_workspace!(AppData);
+cosmic_client_toolkit::delegate_screencopy!(AppData);
+wayland_client::delegate_noop!(AppData: ignore wl_buffer::WlBuffer);
+
+fn connect(mock: &MockCompositor) -> (EventQueue<AppData>, AppData) {
+    let conn = mock.connect();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let mut app_data = AppData {
+        shm: Shm::bind(&globals, &qh).unwrap(),
+        output_state: OutputState::new(&globals, &qh),
+        toplevel_info_state: ToplevelInfoState::new(&registry_state, &qh),
+        workspace_state: WorkspaceState::new(&registry_state, &qh),
+        screencopy_state: ScreencopyState::new(&globals, &qh),
+        registry_state,
+        previews: ToplevelPreviews::new((16, 16), Duration::ZERO),
+        thumbnails: WorkspaceThumbnails::new((16, 16), Duration::ZERO),
+        region: None,
+    };
+    roundtrip(&mut event_queue, &mut app_data);
+    (event_queue, app_data)
+}
+
+// The client sends requests in response to events, so the replies need a second roundtrip
+fn roundtrip(event_queue: &mut EventQueue<AppData>, app_data: &mut AppData) {
+    event_queue.roundtrip(app_data).unwrap();
+    event_queue.roundtrip(app_data).unwrap();
+}
+
+fn preview_pixel(
+    app_data: &AppData,
+    toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+) -> Option<[u8; 4]> {
+    let preview = app_data.previews.preview(toplevel)?;
+    Some(preview.data[..4].try_into().unwrap())
+}
+
+fn workspace(app_data: &AppData, name: &str) -> ext_workspace_handle_v1::ExtWorkspaceHandleV1 {
+    app_data
+        .workspace_state
+        .workspaces()
+        .find(|workspace| workspace.name == name)
+        .unwrap()
+        .handle
+        .clone()
+}
+
+fn update_thumbnails(event_queue: &mut EventQueue<AppData>, app_data: &mut AppData) {
+    let capturer = app_data.screencopy_state.capturer().clone();
+    app_data
+        .thumbnails
+        .update(&app_data.workspace_state, &capturer, &event_queue.handle())
+        .unwrap();
+    roundtrip(event_queue, app_data);
+}
+
+fn thumbnail_pixel(app_data: &AppData, name: &str) -> Option<[u8; 4]> {
+    let thumbnail = app_data.thumbnails.thumbnail(&workspace(app_data, name))?;
+    Some(thumbnail.data[..4].try_into().unwrap())
+}
+
+#[test]
+fn toplevel_preview() {
+    let mock = MockCompositor::new();
+    mock.with(|c| {
+        let toplevel = c.new_toplevel("org.example.App", "Example");
+        c.set_toplevel_capture_size(toplevel, (64, 32));
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+    let toplevel = app_data
+        .toplevel_info_state
+        .toplevels()
+        .next()
+        .unwrap()
+        .foreign_toplevel
+        .clone();
+
+    let capturer = app_data.screencopy_state.capturer().clone();
+    app_data
+        .previews
+        .request(&toplevel, &capturer, &event_queue.handle())
+        .unwrap();
+    roundtrip(&mut event_queue, &mut app_data);
+
+    let color = [255, 128, 0, 255];
+    mock.with(|c| {
+        let frame = c.pending_frames().next().unwrap();
+        c.ready_frame(
+            frame,
+            &FrameContent {
+                color,
+                ..Default::default()
+            },
+        );
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    let preview = app_data.previews.preview(&toplevel).unwrap();
+    assert_eq!((preview.width, preview.height), (16, 8));
+    assert_eq!(preview_pixel(&app_data, &toplevel), Some(color));
+
+    // A frame without damage leaves the preview unchanged
+    mock.with(|c| {
+        let frame = c.pending_frames().next().unwrap();
+        c.ready_frame(
+            frame,
+            &FrameContent {
+                color: [0, 0, 255, 255],
+                damage: Some(Vec::new()),
+                ..Default::default()
+            },
+        );
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(preview_pixel(&app_data, &toplevel), Some(color));
+
+    mock.with(|c| {
+        let frame = c.pending_frames().next().unwrap();
+        c.ready_frame(
+            frame,
+            &FrameContent {
+                color: [0, 0, 255, 255],
+                ..Default::default()
+            },
+        );
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(preview_pixel(&app_data, &toplevel), Some([0, 0, 255, 255]));
+}
+
+#[test]
+fn workspace_thumbnail_size() {
+    let mock = MockCompositor::new();
+    mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        for (name, size) in [("wide", (64, 32)), ("tall", (10, 40)), ("small", (8, 4))] {
+            let workspace = c.new_workspace(Some(group), name);
+            c.set_workspace_capture_size(workspace, size);
+        }
+        c.workspace_done();
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+    update_thumbnails(&mut event_queue, &mut app_data);
+
+    mock.with(|c| {
+        for frame in c.pending_frames().collect::<Vec<_>>() {
+            c.ready_frame(frame, &FrameContent::default());
+        }
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    let size = |name| {
+        let thumbnail = app_data
+            .thumbnails
+            .thumbnail(&workspace(&app_data, name))
+            .unwrap();
+        (thumbnail.width, thumbnail.height)
+    };
+    // Downscaled to fit in 16x16, preserving aspect ratio
+    assert_eq!(size("wide"), (16, 8));
+    assert_eq!(size("tall"), (4, 16));
+    // Never upscaled
+    assert_eq!(size("small"), (8, 4));
+}
+
+#[test]
+fn workspace_thumbnail_throttling() {
+    let mock = MockCompositor::new();
+    mock.with(|c| {
+        c.new_workspace(None, "1");
+        c.workspace_done();
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+    let frame_interval = Duration::from_millis(100);
+    app_data.thumbnails = WorkspaceThumbnails::new((16, 16), frame_interval);
+    update_thumbnails(&mut event_queue, &mut app_data);
+
+    let red = [255, 0, 0, 255];
+    let blue = [0, 0, 255, 255];
+    let ready_frame = |content: FrameContent| {
+        mock.with(|c| {
+            let frame = c.pending_frames().next().unwrap();
+            c.ready_frame(frame, &content);
+        });
+    };
+    ready_frame(FrameContent {
+        color: red,
+        ..Default::default()
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(thumbnail_pixel(&app_data, "1"), Some(red));
+
+    // The next capture waits for the frame interval
+    assert_eq!(mock.with(|c| c.pending_frames().count()), 0);
+    let next_refresh = app_data.thumbnails.next_refresh().unwrap();
+    app_data
+        .thumbnails
+        .refresh(&app_data.shm, &event_queue.handle());
+    roundtrip(&mut event_queue, &mut app_data);
+    if Instant::now() < next_refresh {
+        assert_eq!(mock.with(|c| c.pending_frames().count()), 0);
+    }
+    let refresh = |event_queue: &mut EventQueue<AppData>, app_data: &mut AppData| {
+        std::thread::sleep(
+            app_data
+                .thumbnails
+                .next_refresh()
+                .unwrap()
+                .saturating_duration_since(Instant::now()),
+        );
+        app_data
+            .thumbnails
+            .refresh(&app_data.shm, &event_queue.handle());
+        roundtrip(event_queue, app_data);
+        assert_eq!(mock.with(|c| c.pending_frames().count()), 1);
+        assert!(app_data.thumbnails.next_refresh().is_none());
+    };
+    refresh(&mut event_queue, &mut app_data);
+
+    // A frame without damage leaves the thumbnail unchanged
+    ready_frame(FrameContent {
+        color: blue,
+        damage: Some(Vec::new()),
+        ..Default::default()
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(thumbnail_pixel(&app_data, "1"), Some(red));
+
+    refresh(&mut event_queue, &mut app_data);
+    ready_frame(FrameContent {
+        color: blue,
+        ..Default::default()
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(thumbnail_pixel(&app_data, "1"), Some(blue));
+}
+
+#[test]
+fn closed_toplevel_stops_preview() {
+    let mock = MockCompositor::new();
+    let mock_toplevel = mock.with(|c| c.new_toplevel("org.example.App", "Example"));
+    let (mut event_queue, mut app_data) = connect(&mock);
+    let toplevel = app_data
+        .toplevel_info_state
+        .toplevels()
+        .next()
+        .unwrap()
+        .foreign_toplevel
+        .clone();
+
+    let capturer = app_data.screencopy_state.capturer().clone();
+    app_data
+        .previews
+        .request(&toplevel, &capturer, &event_queue.handle())
+        .unwrap();
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(mock.with(|c| c.sessions().count()), 1);
+
+    mock.with(|c| c.close_toplevel(mock_toplevel));
+    roundtrip(&mut event_queue, &mut app_data);
+    assert!(app_data.previews.preview(&toplevel).is_none());
+    assert_eq!(mock.with(|c| c.pending_frames().count()), 0);
+}
+
+#[test]
+fn supported_sources() {
+    let mock = MockCompositor::new();
+    let (_event_queue, app_data) = connect(&mock);
+    let capturer = app_data.screencopy_state.capturer();
+    assert!(capturer.supports_source(CaptureSourceKind::Workspace));
+    assert!(capturer.supports_cursor_sessions());
+
+    let mock = MockCompositor::with_globals(
+        Global::ALL
+            .into_iter()
+            .filter(|global| *global != Global::WorkspaceImageCaptureSourceManager)
+            .map(|global| (global, global.max_version())),
+    );
+    let (_event_queue, app_data) = connect(&mock);
+    let capturer = app_data.screencopy_state.capturer();
+    assert_eq!(
+        capturer.supported_sources(),
+        [CaptureSourceKind::Output, CaptureSourceKind::Toplevel].into()
+    );
+}
+
+fn region_pixel(image: &RegionImage, x: u32, y: u32) -> [u8; 4] {
+    let idx = (y * image.width + x) as usize * 4;
+    image.data[idx..idx + 4].try_into().unwrap()
+}
+
+#[test]
+fn region_capture() {
+    let mock = MockCompositor::new();
+    let (left, right) = mock.with(|c| {
+        let left = c.add_output("DP-1", (0, 0), (40, 20), 1);
+        // The 80x40 mode is 20x40 in logical coordinates, once scaled and rotated
+        let right = c.add_output("DP-2", (40, 0), (80, 40), 2);
+        c.set_output_transform(right, Transform::_90);
+        (left, right)
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+
+    let capturer = app_data.screencopy_state.capturer().clone();
+    let region = Rect {
+        x: 20,
+        y: 0,
+        width: 40,
+        height: 30,
+    };
+    let region = RegionCapture::new(
+        &capturer,
+        &app_data.output_state,
+        region,
+        CaptureOptions::empty(),
+        &event_queue.handle(),
+    );
+    assert_eq!(region.outputs().count(), 2);
+    app_data.region = Some(region);
+    roundtrip(&mut event_queue, &mut app_data);
+
+    let red = [255, 0, 0, 255];
+    let blue = [0, 0, 255, 255];
+    mock.with(|c| {
+        let frames = c.pending_frames().collect::<Vec<_>>();
+        assert_eq!(frames.len(), 2);
+        for frame in frames {
+            let session = c.frame_session(frame).unwrap();
+            let content = match c.session_source(session) {
+                Some(CaptureSource::Output(output)) if output == left => FrameContent {
+                    color: red,
+                    ..Default::default()
+                },
+                Some(CaptureSource::Output(output)) if output == right => FrameContent {
+                    color: blue,
+                    transform: Transform::_90,
+                    ..Default::default()
+                },
+                source => panic!("unexpected source {source:?}"),
+            };
+            c.ready_frame(frame, &content);
+        }
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+
+    let region = app_data.region.take().unwrap();
+    assert!(region.is_done());
+    let image = region.finish();
+    assert!(image.failed.is_empty());
+    // The scale of the right output is used for the whole image
+    assert_eq!(image.scale, 2.);
+    assert_eq!((image.width, image.height), (80, 60));
+    // The left output covers logical x 20..40 and y 0..20 of the region
+    assert_eq!(region_pixel(&image, 0, 0), red);
+    assert_eq!(region_pixel(&image, 39, 39), red);
+    assert_eq!(region_pixel(&image, 0, 40), [0; 4]);
+    // The right output covers logical x 40..60 and y 0..30
+    assert_eq!(region_pixel(&image, 40, 0), blue);
+    assert_eq!(region_pixel(&image, 79, 59), blue);
+}
+
+fn presented_at(millis: Option<u64>) -> Frame {
+    Frame {
+        present_time: millis.map(Duration::from_millis),
+        ..Default::default()
+    }
+}
+
+fn assert_millis(duration: Option<Duration>, millis: f64) {
+    let duration = duration.unwrap().as_secs_f64() * 1000.;
+    assert!(
+        (duration - millis).abs() < 1e-6,
+        "{duration}ms != {millis}ms"
+    );
+}
+
+#[test]
+fn frame_stats() {
+    let mut stats = FrameStats::with_expected_interval(Duration::from_millis(10));
+    // The frame presented at 20ms wasn't captured
+    for (present, latency) in [(0, 2), (10, 4), (30, 2), (40, 4)] {
+        stats.record_ready_at(
+            &presented_at(Some(present)),
+            Duration::from_millis(present + latency),
+        );
+    }
+    stats.record_failed();
+
+    assert_eq!(stats.frames(), 4);
+    assert_eq!(stats.failed_frames(), 1);
+    assert_eq!(stats.dropped_frames(), Some(1));
+    assert_millis(stats.mean_latency(), 3.);
+    assert_eq!(stats.min_latency(), Some(Duration::from_millis(2)));
+    assert_eq!(stats.max_latency(), Some(Duration::from_millis(4)));
+    // Intervals of 10, 20 and 10ms
+    assert_millis(stats.mean_interval(), 40. / 3.);
+    assert_eq!(stats.min_interval(), Some(Duration::from_millis(10)));
+    assert_eq!(stats.max_interval(), Some(Duration::from_millis(20)));
+    assert_millis(stats.jitter(), (200f64 / 9.).sqrt());
+
+    stats.reset();
+    assert_eq!(stats.frames(), 0);
+    assert_eq!(stats.dropped_frames(), Some(0));
+    assert_eq!(stats.mean_interval(), None);
+}
+
+#[test]
+fn frame_stats_without_present_time() {
+    let mut stats = FrameStats::new();
+    stats.record_ready_at(&presented_at(None), Duration::from_millis(5));
+    stats.record_ready_at(&presented_at(None), Duration::from_millis(15));
+    assert_eq!(stats.frames(), 2);
+    assert_eq!(stats.dropped_frames(), None);
+    assert_eq!(stats.mean_latency(), None);
+    assert_eq!(stats.mean_interval(), None);
+    assert_eq!(stats.jitter(), None);
+
+    // Frames without a presentation time don't split intervals
+    stats.record_ready_at(&presented_at(Some(20)), Duration::from_millis(21));
+    stats.record_ready_at(&presented_at(None), Duration::from_millis(30));
+    stats.record_ready_at(&presented_at(Some(36)), Duration::from_millis(37));
+    assert_eq!(stats.frames(), 5);
+    assert_millis(stats.mean_latency(), 1.);
+    assert_millis(stats.mean_interval(), 16.);
+    assert_millis(stats.jitter(), 0.);
+}
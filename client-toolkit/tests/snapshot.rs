@@ -0,0 +1,187 @@
+use cosmic_client_toolkit::{
+    snapshot::{ToplevelSnapshot, WorkspaceGroupSnapshot, WorkspaceSnapshot},
+    toplevel_info::{ToplevelInfoHandler, ToplevelInfoState},
+    workspace::{WorkspaceHandler, WorkspaceState},
+};
+use cosmic_mock_compositor::{
+    MockCompositor, Rect,
+    cosmic_protocols::toplevel_info::v1::server::zcosmic_toplevel_handle_v1::State,
+    wayland_protocols::ext::workspace::v1::server::{
+        ext_workspace_group_handle_v1::GroupCapabilities, ext_workspace_handle_v1,
+    },
+};
+use sctk::{
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+};
+use serde_json::json;
+use wayland_client::{
+    Connection, Proxy, QueueHandle, globals::registry_queue_init, protocol::wl_output,
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1;
+
+struct AppData {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    toplevel_info_state: ToplevelInfoState,
+    workspace_state: WorkspaceState,
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(OutputState);
+}
+
+impl OutputHandler for AppData {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+}
+
+impl ToplevelInfoHandler for AppData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        &mut self.toplevel_info_state
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+}
+
+impl WorkspaceHandler for AppData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+
+    fn done(&mut self) {}
+}
+
+sctk::delegate_registry!(AppData);
+sctk::delegate_output!(AppData);
+cosmic_client_toolkit::delegate_toplevel_info!(AppData);
+cosmic_client_toolkit::delegate_workspace!(AppData);
+
+#[test]
+fn snapshots() {
+    let mock = MockCompositor::new();
+    let (toplevel, output) = mock.with(|c| {
+        let output = c.add_output("DP-1", (0, 0), (1920, 1080), 1);
+        let group = c.new_workspace_group(GroupCapabilities::CreateWorkspace);
+        c.workspace_group_enter_output(group, output);
+        let workspace = c.new_workspace(Some(group), "1");
+        c.set_workspace_state(workspace, ext_workspace_handle_v1::State::Active);
+        c.workspace_done();
+        let toplevel = c.new_toplevel("org.example.App", "Example");
+        c.set_toplevel_state(toplevel, &[State::Activated, State::Maximized]);
+        c.toplevel_enter_output(toplevel, output);
+        c.toplevel_enter_workspace(toplevel, workspace);
+        (toplevel, output)
+    });
+
+    let conn = mock.connect();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let mut app_data = AppData {
+        output_state: OutputState::new(&globals, &qh),
+        toplevel_info_state: ToplevelInfoState::new(&registry_state, &qh),
+        workspace_state: WorkspaceState::new(&registry_state, &qh),
+        registry_state,
+    };
+    event_queue.roundtrip(&mut app_data).unwrap();
+    event_queue.roundtrip(&mut app_data).unwrap();
+    // Geometry is only sent to existing toplevel handles
+    mock.with(|c| {
+        c.set_toplevel_geometry(
+            toplevel,
+            output,
+            Rect {
+                x: 10,
+                y: 20,
+                width: 640,
+                height: 480,
+            },
+        );
+        c.toplevel_done(toplevel);
+    });
+    event_queue.roundtrip(&mut app_data).unwrap();
+
+    let group = app_data.workspace_state.workspace_groups().next().unwrap();
+    let workspace = app_data.workspace_state.workspaces().next().unwrap();
+    let toplevel = app_data.toplevel_info_state.toplevels().next().unwrap();
+    let workspace_object = workspace.handle.id().protocol_id();
+
+    let snapshot = ToplevelSnapshot::new(toplevel, &app_data.output_state);
+    assert_eq!(
+        serde_json::to_value(&snapshot).unwrap(),
+        json!({
+            "object_id": toplevel.foreign_toplevel.id().protocol_id(),
+            "identifier": toplevel.identifier,
+            "app_id": "org.example.App",
+            "title": "Example",
+            "state": ["activated", "maximized"],
+            "outputs": ["DP-1"],
+            "geometry": {"DP-1": {"x": 10, "y": 20, "width": 640, "height": 480}},
+            "workspaces": [workspace_object],
+        })
+    );
+    let json = serde_json::to_string(&snapshot).unwrap();
+    assert_eq!(
+        serde_json::from_str::<ToplevelSnapshot>(&json).unwrap(),
+        snapshot
+    );
+
+    let snapshot = WorkspaceSnapshot::from(workspace);
+    assert_eq!(snapshot.object_id, workspace_object);
+    assert!(snapshot.id.unwrap().starts_with("mock-workspace-"));
+    assert_eq!(snapshot.state, ["active"]);
+    // The mock compositor advertises every capability
+    assert_eq!(
+        snapshot.capabilities,
+        [
+            "activate",
+            "assign",
+            "deactivate",
+            "move",
+            "pin",
+            "remove",
+            "rename",
+            "set_tiling_state"
+        ]
+    );
+    assert_eq!(snapshot.tiling, Some(false));
+
+    let snapshot = WorkspaceGroupSnapshot::new(group, &app_data.output_state);
+    assert_eq!(snapshot.capabilities, ["create_workspace"]);
+    assert_eq!(snapshot.outputs, ["DP-1"]);
+    assert_eq!(snapshot.workspaces, [workspace_object]);
+}
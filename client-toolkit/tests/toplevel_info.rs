@@ -0,0 +1,290 @@
+use cosmic_client_toolkit::{
+    cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1,
+    toplevel_info::{ToplevelInfoHandler, ToplevelInfoState},
+};
+use cosmic_mock_compositor::{
+    Global, MockCompositor,
+    cosmic_protocols::toplevel_info::v1::server::zcosmic_toplevel_handle_v1 as server_handle,
+};
+use sctk::registry::{ProvidesRegistryState, RegistryState};
+use wayland_client::{Connection, EventQueue, QueueHandle, globals::registry_queue_init};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1;
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    New(String),
+    Update(String),
+    Closed(String),
+    InfoDone,
+    Finished,
+}
+
+struct AppData {
+    registry_state: RegistryState,
+    toplevel_info_state: ToplevelInfoState,
+    events: Vec<Event>,
+}
+
+impl AppData {
+    fn title(
+        &self,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) -> String {
+        self.toplevel_info_state
+            .info(toplevel)
+            .unwrap()
+            .title
+            .clone()
+    }
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(ToplevelInfoState);
+}
+
+impl ToplevelInfoHandler for AppData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        &mut self.toplevel_info_state
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.events.push(Event::New(self.title(toplevel)));
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.events.push(Event::Update(self.title(toplevel)));
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+        self.events.push(Event::Closed(self.title(toplevel)));
+    }
+
+    fn info_done(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>) {
+        self.events.push(Event::InfoDone);
+    }
+
+    fn finished(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>) {
+        self.events.push(Event::Finished);
+    }
+}
+
+sctk::delegate_registry!(AppData);
+cosmic_client_toolkit::delegate_toplevel_info!(AppData);
+
+fn connect(mock: &MockCompositor) -> (EventQueue<AppData>, AppData) {
+    let conn = mock.connect();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let mut app_data = AppData {
+        toplevel_info_state: ToplevelInfoState::new(&registry_state, &qh),
+        registry_state,
+        events: Vec::new(),
+    };
+    event_queue.roundtrip(&mut app_data).unwrap();
+    (event_queue, app_data)
+}
+
+// The client requests extension objects in response to events, so events sent in reply
+// need a second roundtrip
+fn roundtrip(event_queue: &mut EventQueue<AppData>, app_data: &mut AppData) {
+    event_queue.roundtrip(app_data).unwrap();
+    event_queue.roundtrip(app_data).unwrap();
+}
+
+#[test]
+fn new_toplevel_waits_for_cosmic_info() {
+    let mock = MockCompositor::new();
+    let (mut event_queue, mut app_data) = connect(&mock);
+
+    mock.with(|c| {
+        let toplevel = c.new_toplevel("org.example.App", "Example");
+        c.set_toplevel_state(toplevel, &[server_handle::State::Activated]);
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+
+    // The `done` sent before the cosmic handle existed is ignored
+    assert_eq!(app_data.events, [Event::New("Example".to_string())]);
+    let info = app_data.toplevel_info_state.toplevels().next().unwrap();
+    assert_eq!(info.app_id, "org.example.App");
+    assert!(info.cosmic_toplevel.is_some());
+    assert!(
+        info.state
+            .contains(&zcosmic_toplevel_handle_v1::State::Activated)
+    );
+}
+
+#[test]
+fn toplevel_changes_are_applied_on_done() {
+    let mock = MockCompositor::new();
+    let (mut event_queue, mut app_data) = connect(&mock);
+    let toplevel = mock.with(|c| c.new_toplevel("org.example.App", "Old"));
+    roundtrip(&mut event_queue, &mut app_data);
+    app_data.events.clear();
+
+    mock.with(|c| {
+        c.set_toplevel_title(toplevel, "New");
+        c.set_toplevel_state(toplevel, &[server_handle::State::Maximized]);
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    assert!(app_data.events.is_empty());
+    let info = app_data.toplevel_info_state.toplevels().next().unwrap();
+    assert_eq!(info.title, "Old");
+    assert!(info.state.is_empty());
+
+    mock.with(|c| {
+        c.toplevel_done(toplevel);
+        c.toplevel_info_done();
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(
+        app_data.events,
+        [Event::Update("New".to_string()), Event::InfoDone]
+    );
+    let info = app_data.toplevel_info_state.toplevels().next().unwrap();
+    assert_eq!(info.title, "New");
+    assert!(
+        info.state
+            .contains(&zcosmic_toplevel_handle_v1::State::Maximized)
+    );
+}
+
+#[test]
+fn closed_toplevel_is_removed() {
+    let mock = MockCompositor::new();
+    let (mut event_queue, mut app_data) = connect(&mock);
+    let toplevel = mock.with(|c| c.new_toplevel("org.example.App", "Example"));
+    roundtrip(&mut event_queue, &mut app_data);
+
+    mock.with(|c| c.close_toplevel(toplevel));
+    roundtrip(&mut event_queue, &mut app_data);
+
+    assert_eq!(
+        app_data.events,
+        [
+            Event::New("Example".to_string()),
+            Event::Closed("Example".to_string())
+        ]
+    );
+    assert_eq!(app_data.toplevel_info_state.toplevels().count(), 0);
+}
+
+#[test]
+fn existing_toplevels_are_sent_on_bind() {
+    let mock = MockCompositor::new();
+    mock.with(|c| {
+        c.new_toplevel("org.example.First", "First");
+        c.new_toplevel("org.example.Second", "Second");
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+    roundtrip(&mut event_queue, &mut app_data);
+
+    assert_eq!(
+        app_data.events,
+        [
+            Event::New("First".to_string()),
+            Event::New("Second".to_string())
+        ]
+    );
+}
+
+#[test]
+fn without_cosmic_toplevel_info() {
+    let mock = MockCompositor::with_globals([(Global::ForeignToplevelList, 1)]);
+    let (mut event_queue, mut app_data) = connect(&mock);
+    assert!(app_data.toplevel_info_state.cosmic_toplevel_info.is_none());
+
+    mock.with(|c| c.new_toplevel("org.example.App", "Example"));
+    event_queue.roundtrip(&mut app_data).unwrap();
+
+    assert_eq!(app_data.events, [Event::New("Example".to_string())]);
+    let info = app_data.toplevel_info_state.toplevels().next().unwrap();
+    assert!(info.cosmic_toplevel.is_none());
+}
+
+#[test]
+fn globals_added_and_removed() {
+    let mock = MockCompositor::with_globals([(Global::ToplevelInfo, 3)]);
+    mock.with(|c| c.new_toplevel("org.example.App", "Example"));
+    let (mut event_queue, mut app_data) = connect(&mock);
+    assert!(
+        app_data
+            .toplevel_info_state
+            .foreign_toplevel_list()
+            .is_none()
+    );
+
+    // The list is bound in response to the global event
+    mock.with(|c| c.create_global(Global::ForeignToplevelList, 1));
+    event_queue.roundtrip(&mut app_data).unwrap();
+    roundtrip(&mut event_queue, &mut app_data);
+    assert!(app_data.toplevel_info_state.cosmic_toplevel_info.is_some());
+    assert_eq!(app_data.events, [Event::New("Example".to_string())]);
+    app_data.events.clear();
+
+    mock.with(|c| c.remove_global(Global::ForeignToplevelList));
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(
+        app_data.events,
+        [Event::Closed("Example".to_string()), Event::Finished]
+    );
+    assert_eq!(app_data.toplevel_info_state.toplevels().count(), 0);
+    assert!(
+        app_data
+            .toplevel_info_state
+            .foreign_toplevel_list()
+            .is_none()
+    );
+}
+
+#[test]
+fn cosmic_toplevel_info_added_and_removed() {
+    let mock = MockCompositor::with_globals([(Global::ForeignToplevelList, 1)]);
+    mock.with(|c| {
+        let toplevel = c.new_toplevel("org.example.App", "Example");
+        c.set_toplevel_state(toplevel, &[server_handle::State::Activated]);
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(app_data.events, [Event::New("Example".to_string())]);
+    app_data.events.clear();
+
+    // Existing toplevels are extended without being closed and announced again
+    mock.with(|c| c.create_global(Global::ToplevelInfo, 3));
+    roundtrip(&mut event_queue, &mut app_data);
+    app_data.events.retain(|event| *event != Event::InfoDone);
+    assert_eq!(app_data.events, [Event::Update("Example".to_string())]);
+    app_data.events.clear();
+    let info = app_data.toplevel_info_state.toplevels().next().unwrap();
+    assert!(info.cosmic_toplevel.is_some());
+    assert!(
+        info.state
+            .contains(&zcosmic_toplevel_handle_v1::State::Activated)
+    );
+
+    mock.with(|c| c.remove_global(Global::ToplevelInfo));
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(app_data.events, [Event::Update("Example".to_string())]);
+    let info = app_data.toplevel_info_state.toplevels().next().unwrap();
+    assert!(info.cosmic_toplevel.is_none());
+    assert!(info.state.is_empty());
+}
@@ -0,0 +1,157 @@
+use cosmic_client_toolkit::{
+    cosmic_protocols::toplevel_management::v1::client::zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1 as Capability,
+    toplevel_info::{ToplevelInfoHandler, ToplevelInfoState},
+    toplevel_management::{ToplevelManagerHandler, ToplevelManagerState},
+};
+use cosmic_mock_compositor::{Global, MockCompositor, Request};
+use sctk::registry::{ProvidesRegistryState, RegistryState};
+use wayland_client::{
+    Connection, EventQueue, QueueHandle, WEnum, globals::registry_queue_init, protocol::wl_seat,
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1;
+
+struct AppData {
+    registry_state: RegistryState,
+    toplevel_info_state: ToplevelInfoState,
+    toplevel_manager_state: ToplevelManagerState,
+    seat: wl_seat::WlSeat,
+    capabilities: Option<Vec<WEnum<Capability>>>,
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(ToplevelManagerState);
+}
+
+impl ToplevelInfoHandler for AppData {
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState {
+        &mut self.toplevel_info_state
+    }
+
+    fn new_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn update_toplevel(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+
+    fn toplevel_closed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    ) {
+    }
+}
+
+impl ToplevelManagerHandler for AppData {
+    fn toplevel_manager_state(&mut self) -> &mut ToplevelManagerState {
+        &mut self.toplevel_manager_state
+    }
+
+    fn capabilities(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        capabilities: Vec<WEnum<Capability>>,
+    ) {
+        self.capabilities = Some(capabilities);
+    }
+}
+
+sctk::delegate_registry!(AppData);
+cosmic_client_toolkit::delegate_toplevel_info!(AppData);
+cosmic_client_toolkit::delegate_toplevel_manager!(AppData);
+wayland_client::delegate_noop!(AppData: ignore wl_seat::WlSeat);
+
+fn connect(mock: &MockCompositor) -> (EventQueue<AppData>, AppData) {
+    let conn = mock.connect();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let mut app_data = AppData {
+        toplevel_info_state: ToplevelInfoState::new(&registry_state, &qh),
+        toplevel_manager_state: ToplevelManagerState::new(&registry_state, &qh),
+        seat: globals.bind(&qh, 1..=1, ()).unwrap(),
+        registry_state,
+        capabilities: None,
+    };
+    event_queue.roundtrip(&mut app_data).unwrap();
+    event_queue.roundtrip(&mut app_data).unwrap();
+    (event_queue, app_data)
+}
+
+#[test]
+fn capabilities_of_version() {
+    let mock = MockCompositor::new();
+    let (_event_queue, app_data) = connect(&mock);
+    let capabilities = app_data.capabilities.unwrap();
+    assert_eq!(capabilities.len(), 8);
+    assert!(capabilities.contains(&WEnum::Value(Capability::MoveToExtWorkspace)));
+
+    let mock = MockCompositor::with_globals(Global::ALL.iter().map(|global| match global {
+        Global::ToplevelManager => (*global, 2),
+        _ => (*global, global.max_version()),
+    }));
+    let (_event_queue, app_data) = connect(&mock);
+    let capabilities = app_data.capabilities.unwrap();
+    assert_eq!(capabilities.len(), 6);
+    assert!(!capabilities.contains(&WEnum::Value(Capability::Sticky)));
+}
+
+#[test]
+fn toplevel_requests() {
+    let mock = MockCompositor::new();
+    let toplevel = mock.with(|c| c.new_toplevel("org.example.App", "Example"));
+    let (mut event_queue, mut app_data) = connect(&mock);
+
+    let info = app_data.toplevel_info_state.toplevels().next().unwrap();
+    let cosmic_toplevel = info.cosmic_toplevel.as_ref().unwrap();
+    let manager = app_data.toplevel_manager_state.manager().unwrap();
+    manager.activate(cosmic_toplevel, &app_data.seat);
+    manager.set_fullscreen(cosmic_toplevel, None);
+    manager.set_sticky(cosmic_toplevel);
+    manager.close(cosmic_toplevel);
+    event_queue.roundtrip(&mut app_data).unwrap();
+
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [
+            Request::ActivateToplevel(toplevel),
+            Request::SetFullscreen(toplevel, None),
+            Request::SetSticky(toplevel),
+            Request::CloseToplevel(toplevel),
+        ]
+    );
+}
+
+#[test]
+fn global_added_and_removed() {
+    let mock = MockCompositor::with_globals([(Global::Seat, 1)]);
+    let (mut event_queue, mut app_data) = connect(&mock);
+    assert!(app_data.toplevel_manager_state.manager().is_none());
+    assert!(app_data.capabilities.is_none());
+
+    // The manager is bound in response to the global event
+    mock.with(|c| c.create_global(Global::ToplevelManager, 4));
+    event_queue.roundtrip(&mut app_data).unwrap();
+    event_queue.roundtrip(&mut app_data).unwrap();
+    assert_eq!(app_data.capabilities.as_ref().unwrap().len(), 8);
+
+    mock.with(|c| c.remove_global(Global::ToplevelManager));
+    event_queue.roundtrip(&mut app_data).unwrap();
+    assert!(app_data.toplevel_manager_state.manager().is_none());
+    assert_eq!(app_data.capabilities, Some(Vec::new()));
+}
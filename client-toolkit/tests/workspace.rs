@@ -0,0 +1,264 @@
+use cosmic_client_toolkit::{
+    cosmic_protocols::workspace::v2::client::zcosmic_workspace_handle_v2,
+    wayland_protocols::ext::workspace::v1::client::{
+        ext_workspace_group_handle_v1, ext_workspace_handle_v1,
+    },
+    workspace::{WorkspaceHandler, WorkspaceState},
+};
+use cosmic_mock_compositor::{
+    Global, MockCompositor, Request,
+    wayland_protocols::ext::workspace::v1::server::{
+        ext_workspace_group_handle_v1::GroupCapabilities,
+        ext_workspace_handle_v1::State as ServerState,
+    },
+};
+use sctk::registry::{ProvidesRegistryState, RegistryState};
+use wayland_client::{EventQueue, globals::registry_queue_init};
+
+struct AppData {
+    registry_state: RegistryState,
+    workspace_state: WorkspaceState,
+    done_count: usize,
+}
+
+impl ProvidesRegistryState for AppData {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    sctk::registry_handlers!(WorkspaceState);
+}
+
+impl WorkspaceHandler for AppData {
+    fn workspace_state(&mut self) -> &mut WorkspaceState {
+        &mut self.workspace_state
+    }
+
+    fn done(&mut self) {
+        self.done_count += 1;
+    }
+}
+
+sctk::delegate_registry!(AppData);
+cosmic_client_toolkit::delegate_workspace!(AppData);
+
+fn connect(mock: &MockCompositor) -> (EventQueue<AppData>, AppData) {
+    let conn = mock.connect();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let registry_state = RegistryState::new(&globals);
+    let mut app_data = AppData {
+        workspace_state: WorkspaceState::new(&registry_state, &qh),
+        registry_state,
+        done_count: 0,
+    };
+    roundtrip(&mut event_queue, &mut app_data);
+    (event_queue, app_data)
+}
+
+// The client requests extension objects in response to events, so events sent in reply
+// need a second roundtrip
+fn roundtrip(event_queue: &mut EventQueue<AppData>, app_data: &mut AppData) {
+    event_queue.roundtrip(app_data).unwrap();
+    event_queue.roundtrip(app_data).unwrap();
+}
+
+#[test]
+fn done_waits_for_cosmic_info() {
+    let mock = MockCompositor::new();
+    mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::CreateWorkspace);
+        c.new_workspace(Some(group), "1");
+        c.new_workspace(Some(group), "2");
+    });
+    let (_event_queue, app_data) = connect(&mock);
+
+    // The `done` sent on bind is ignored, since cosmic capabilities haven't been received
+    assert_eq!(app_data.done_count, 1);
+    let group = app_data.workspace_state.workspace_groups().next().unwrap();
+    assert_eq!(
+        group.capabilities,
+        ext_workspace_group_handle_v1::GroupCapabilities::CreateWorkspace
+    );
+    assert_eq!(group.workspaces.len(), 2);
+    let names = app_data
+        .workspace_state
+        .workspaces()
+        .map(|w| w.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["1", "2"]);
+    for workspace in app_data.workspace_state.workspaces() {
+        assert!(workspace.cosmic_handle.is_some());
+        assert_eq!(
+            workspace.cosmic_capabilities,
+            zcosmic_workspace_handle_v2::WorkspaceCapabilities::all()
+        );
+        assert!(workspace.tiling.is_some());
+    }
+}
+
+#[test]
+fn workspace_changes_are_applied_on_done() {
+    let mock = MockCompositor::new();
+    let workspace = mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        c.new_workspace(Some(group), "1")
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+
+    mock.with(|c| {
+        c.set_workspace_name(workspace, "renamed");
+        c.set_workspace_state(workspace, ServerState::Active);
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(app_data.done_count, 1);
+    let info = app_data.workspace_state.workspaces().next().unwrap();
+    assert_eq!(info.name, "1");
+    assert!(info.state.is_empty());
+
+    mock.with(|c| c.workspace_done());
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(app_data.done_count, 2);
+    let info = app_data.workspace_state.workspaces().next().unwrap();
+    assert_eq!(info.name, "renamed");
+    assert_eq!(info.state, ext_workspace_handle_v1::State::Active);
+}
+
+#[test]
+fn removed_workspace() {
+    let mock = MockCompositor::new();
+    let (group, workspace) = mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        (group, c.new_workspace(Some(group), "1"))
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+    assert_eq!(app_data.workspace_state.workspaces().count(), 1);
+
+    mock.with(|c| {
+        c.remove_workspace(workspace);
+        c.workspace_done();
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(app_data.workspace_state.workspaces().count(), 0);
+    let group_info = app_data.workspace_state.workspace_groups().next().unwrap();
+    assert!(group_info.workspaces.is_empty());
+
+    mock.with(|c| {
+        c.remove_workspace_group(group);
+        c.workspace_done();
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(app_data.workspace_state.workspace_groups().count(), 0);
+}
+
+#[test]
+fn workspace_requests() {
+    let mock = MockCompositor::new();
+    let workspace = mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        c.new_workspace(Some(group), "1")
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+
+    let info = app_data.workspace_state.workspaces().next().unwrap();
+    info.handle.activate();
+    info.cosmic_handle
+        .as_ref()
+        .unwrap()
+        .rename("renamed".to_string());
+    app_data
+        .workspace_state
+        .workspace_manager()
+        .get()
+        .unwrap()
+        .commit();
+    event_queue.roundtrip(&mut app_data).unwrap();
+
+    assert_eq!(
+        mock.with(|c| c.take_requests()),
+        [
+            Request::ActivateWorkspace(workspace),
+            Request::RenameWorkspace {
+                workspace,
+                name: "renamed".to_string()
+            },
+            Request::CommitWorkspaces,
+        ]
+    );
+}
+
+#[test]
+fn without_cosmic_workspace_manager() {
+    let mock = MockCompositor::with_globals([(Global::WorkspaceManager, 1)]);
+    mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        c.new_workspace(Some(group), "1");
+    });
+    let (_event_queue, app_data) = connect(&mock);
+
+    assert_eq!(app_data.done_count, 1);
+    let info = app_data.workspace_state.workspaces().next().unwrap();
+    assert!(info.cosmic_handle.is_none());
+    assert!(info.tiling.is_none());
+}
+
+#[test]
+fn globals_added_and_removed() {
+    let mock = MockCompositor::with_globals([(Global::CosmicWorkspaceManager, 2)]);
+    mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        c.new_workspace(Some(group), "1");
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+    assert!(app_data.workspace_state.workspace_manager().get().is_err());
+    assert_eq!(app_data.done_count, 0);
+
+    // The manager is bound in response to the global event
+    mock.with(|c| c.create_global(Global::WorkspaceManager, 1));
+    event_queue.roundtrip(&mut app_data).unwrap();
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(app_data.done_count, 1);
+    let info = app_data.workspace_state.workspaces().next().unwrap();
+    assert_eq!(info.name, "1");
+    assert!(info.cosmic_handle.is_some());
+
+    mock.with(|c| c.remove_global(Global::WorkspaceManager));
+    roundtrip(&mut event_queue, &mut app_data);
+    assert_eq!(app_data.done_count, 2);
+    assert_eq!(app_data.workspace_state.workspaces().count(), 0);
+    assert_eq!(app_data.workspace_state.workspace_groups().count(), 0);
+}
+
+#[test]
+fn events_of_replaced_manager() {
+    let mock = MockCompositor::with_globals([(Global::WorkspaceManager, 1)]);
+    let group = mock.with(|c| {
+        let group = c.new_workspace_group(GroupCapabilities::empty());
+        c.new_workspace(Some(group), "1");
+        group
+    });
+    let (mut event_queue, mut app_data) = connect(&mock);
+    assert_eq!(app_data.done_count, 1);
+
+    // The manager is replaced before the events sent to it are dispatched
+    mock.with(|c| {
+        c.create_global(Global::CosmicWorkspaceManager, 2);
+        c.new_workspace(Some(group), "2");
+        c.workspace_done();
+    });
+    roundtrip(&mut event_queue, &mut app_data);
+    roundtrip(&mut event_queue, &mut app_data);
+    let names = app_data
+        .workspace_state
+        .workspaces()
+        .map(|info| info.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["1", "2"]);
+    assert_eq!(app_data.workspace_state.workspace_groups().count(), 1);
+    assert!(
+        app_data
+            .workspace_state
+            .workspaces()
+            .all(|info| info.cosmic_handle.is_some())
+    );
+}
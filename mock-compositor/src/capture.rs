@@ -0,0 +1,639 @@
+use cosmic_protocols::image_capture_source::v1::server::zcosmic_workspace_image_capture_source_manager_v1;
+use std::time::Duration;
+use wayland_protocols::ext::{
+    image_capture_source::v1::server::{
+        ext_foreign_toplevel_image_capture_source_manager_v1, ext_image_capture_source_v1,
+        ext_output_image_capture_source_manager_v1,
+    },
+    image_copy_capture::v1::server::{
+        ext_image_copy_capture_cursor_session_v1, ext_image_copy_capture_frame_v1,
+        ext_image_copy_capture_manager_v1, ext_image_copy_capture_session_v1,
+    },
+};
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+    backend::ClientId,
+    protocol::{wl_buffer, wl_output, wl_shm},
+};
+
+use crate::{Compositor, OutputId, ToplevelId, WorkspaceId, shm::ShmBuffer};
+
+/// Source an `ext_image_capture_source_v1` was created for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CaptureSource {
+    Output(OutputId),
+    Toplevel(ToplevelId),
+    Workspace(WorkspaceId),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SessionId(u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FrameId(u32);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Content and metadata of a frame completed with [`Compositor::ready_frame`]
+#[derive(Clone, Debug)]
+pub struct FrameContent {
+    /// RGBA color the buffer is filled with
+    pub color: [u8; 4],
+    pub transform: wl_output::Transform,
+    /// Damage sent with the frame; `None` to damage the whole buffer
+    pub damage: Option<Vec<Rect>>,
+    pub presentation_time: Option<Duration>,
+}
+
+impl Default for FrameContent {
+    fn default() -> Self {
+        Self {
+            color: [0, 0, 0, 255],
+            transform: wl_output::Transform::Normal,
+            damage: None,
+            presentation_time: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Session {
+    id: SessionId,
+    source: CaptureSource,
+    paint_cursors: bool,
+    buffer_size: (u32, u32),
+    formats: Vec<wl_shm::Format>,
+    resource: ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+    stopped: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct Frame {
+    id: FrameId,
+    session: SessionId,
+    resource: ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+    buffer: Option<wl_buffer::WlBuffer>,
+    damage: Vec<Rect>,
+    captured: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct CursorSessionData {
+    source: CaptureSource,
+}
+
+// Size of cursor capture sessions
+const CURSOR_SIZE: (i32, i32) = (24, 24);
+
+impl Compositor {
+    /// Shm formats advertised to new capture sessions
+    pub fn set_capture_formats(&mut self, formats: &[wl_shm::Format]) {
+        self.capture_formats = formats.to_vec();
+    }
+
+    /// Sessions that haven't been stopped or destroyed
+    pub fn sessions(&self) -> impl Iterator<Item = SessionId> + '_ {
+        self.sessions.iter().filter(|s| !s.stopped).map(|s| s.id)
+    }
+
+    pub fn session_source(&self, id: SessionId) -> Option<CaptureSource> {
+        Some(self.sessions.iter().find(|s| s.id == id)?.source)
+    }
+
+    /// Whether the session was created with the `paint_cursors` option
+    pub fn session_paints_cursors(&self, id: SessionId) -> bool {
+        self.sessions.iter().any(|s| s.id == id && s.paint_cursors)
+    }
+
+    /// Send new buffer constraints for a session, followed by `done`
+    pub fn send_buffer_constraints(
+        &mut self,
+        id: SessionId,
+        buffer_size: (u32, u32),
+        formats: &[wl_shm::Format],
+    ) {
+        let Some(session) = self.sessions.iter_mut().find(|s| s.id == id) else {
+            return;
+        };
+        session.buffer_size = buffer_size;
+        session.formats = formats.to_vec();
+        session.send_constraints();
+    }
+
+    /// Fail pending frames of the session, and send `stopped`
+    pub fn stop_session(&mut self, id: SessionId) {
+        let Some(session) = self.sessions.iter_mut().find(|s| s.id == id && !s.stopped) else {
+            return;
+        };
+        session.stopped = true;
+        for frame in self
+            .frames
+            .iter_mut()
+            .filter(|f| f.session == id && f.captured)
+        {
+            frame
+                .resource
+                .failed(ext_image_copy_capture_frame_v1::FailureReason::Stopped);
+            frame.captured = false;
+        }
+        session.resource.stopped();
+    }
+
+    pub(crate) fn stop_sessions_of(&mut self, source: CaptureSource) {
+        let ids = self
+            .sessions
+            .iter()
+            .filter(|s| s.source == source)
+            .map(|s| s.id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            self.stop_session(id);
+        }
+    }
+
+    /// Frames that were captured, and haven't been completed yet
+    pub fn pending_frames(&self) -> impl Iterator<Item = FrameId> + '_ {
+        self.frames.iter().filter(|f| f.captured).map(|f| f.id)
+    }
+
+    pub fn frame_session(&self, id: FrameId) -> Option<SessionId> {
+        Some(self.frames.iter().find(|f| f.id == id)?.session)
+    }
+
+    /// Buffer damage the client sent for the frame
+    pub fn frame_damage(&self, id: FrameId) -> &[Rect] {
+        self.frames
+            .iter()
+            .find(|f| f.id == id)
+            .map_or(&[], |f| &f.damage)
+    }
+
+    /// Fill the buffer of a captured frame, and send `ready`
+    pub fn ready_frame(&mut self, id: FrameId, content: &FrameContent) {
+        let frame = self.captured_frame(id);
+        frame.captured = false;
+        let buffer = frame.buffer.as_ref().unwrap().data::<ShmBuffer>().unwrap();
+        buffer.fill(content.color);
+        let (width, height) = buffer.size();
+
+        frame.resource.transform(content.transform);
+        let full_damage = [Rect {
+            x: 0,
+            y: 0,
+            width: width as i32,
+            height: height as i32,
+        }];
+        for rect in content.damage.as_deref().unwrap_or(&full_damage) {
+            frame
+                .resource
+                .damage(rect.x, rect.y, rect.width, rect.height);
+        }
+        if let Some(time) = content.presentation_time {
+            frame.resource.presentation_time(
+                (time.as_secs() >> 32) as u32,
+                time.as_secs() as u32,
+                time.subsec_nanos(),
+            );
+        }
+        frame.resource.ready();
+    }
+
+    pub fn fail_frame(
+        &mut self,
+        id: FrameId,
+        reason: ext_image_copy_capture_frame_v1::FailureReason,
+    ) {
+        let frame = self.captured_frame(id);
+        frame.captured = false;
+        frame.resource.failed(reason);
+    }
+
+    fn captured_frame(&mut self, id: FrameId) -> &mut Frame {
+        self.frames
+            .iter_mut()
+            .find(|f| f.id == id && f.captured)
+            .expect("No such captured frame")
+    }
+
+    fn source_size(&self, source: CaptureSource) -> Option<(i32, i32)> {
+        match source {
+            CaptureSource::Output(id) => self.output_mode_size(id),
+            CaptureSource::Toplevel(id) => self.toplevel_capture_size(id),
+            CaptureSource::Workspace(id) => self.workspace_capture_size(id),
+        }
+    }
+
+    fn new_session(
+        &mut self,
+        resource: ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+        id: SessionId,
+        source: CaptureSource,
+        paint_cursors: bool,
+        size: Option<(i32, i32)>,
+    ) {
+        let session = Session {
+            id,
+            source,
+            paint_cursors,
+            buffer_size: size.map_or((0, 0), |(w, h)| (w as u32, h as u32)),
+            formats: self.capture_formats.clone(),
+            resource,
+            stopped: size.is_none(),
+        };
+        if session.stopped {
+            // Source no longer exists
+            session.resource.stopped();
+        } else {
+            session.send_constraints();
+        }
+        self.sessions.push(session);
+    }
+}
+
+impl Session {
+    fn send_constraints(&self) {
+        self.resource
+            .buffer_size(self.buffer_size.0, self.buffer_size.1);
+        for format in &self.formats {
+            self.resource.shm_format(*format);
+        }
+        self.resource.done();
+    }
+}
+
+impl
+    GlobalDispatch<
+        ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+        (),
+    > for Compositor
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<
+            ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+        >,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1, ()>
+    for Compositor
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+        request: ext_output_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_output_image_capture_source_manager_v1::Request::CreateSource {
+                source,
+                output,
+            } => {
+                let id = *output.data::<OutputId>().unwrap();
+                data_init.init(source, CaptureSource::Output(id));
+            }
+            ext_output_image_capture_source_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl
+    GlobalDispatch<
+        ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+        (),
+    > for Compositor
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<
+            ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+        >,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl
+    Dispatch<
+        ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+        (),
+    > for Compositor
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+        request: ext_foreign_toplevel_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_foreign_toplevel_image_capture_source_manager_v1::Request::CreateSource {
+                source,
+                toplevel_handle,
+            } => {
+                let id = *toplevel_handle.data::<ToplevelId>().unwrap();
+                data_init.init(source, CaptureSource::Toplevel(id));
+            }
+            ext_foreign_toplevel_image_capture_source_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl
+    GlobalDispatch<
+        zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+        (),
+    > for Compositor
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<
+            zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+        >,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl
+    Dispatch<
+        zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+        (),
+    > for Compositor
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+        request: zcosmic_workspace_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zcosmic_workspace_image_capture_source_manager_v1::Request::CreateSource {
+                source,
+                output,
+            } => {
+                let id = *output.data::<WorkspaceId>().unwrap();
+                data_init.init(source, CaptureSource::Workspace(id));
+            }
+            zcosmic_workspace_image_capture_source_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<ext_image_capture_source_v1::ExtImageCaptureSourceV1, CaptureSource> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+        request: ext_image_capture_source_v1::Request,
+        _data: &CaptureSource,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_capture_source_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl GlobalDispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, ()>
+    for Compositor
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, ()> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1,
+        request: ext_image_copy_capture_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_copy_capture_manager_v1::Request::CreateSession {
+                session,
+                source,
+                options,
+            } => {
+                let source = *source.data::<CaptureSource>().unwrap();
+                let paint_cursors = match options {
+                    WEnum::Value(options) => {
+                        options.contains(ext_image_copy_capture_manager_v1::Options::PaintCursors)
+                    }
+                    WEnum::Unknown(_) => false,
+                };
+                let id = SessionId(state.next_id());
+                let resource = data_init.init(session, id);
+                let size = state.source_size(source);
+                state.new_session(resource, id, source, paint_cursors, size);
+            }
+            ext_image_copy_capture_manager_v1::Request::CreatePointerCursorSession {
+                session,
+                source,
+                pointer: _,
+            } => {
+                let source = *source.data::<CaptureSource>().unwrap();
+                data_init.init(session, CursorSessionData { source });
+            }
+            ext_image_copy_capture_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl
+    Dispatch<
+        ext_image_copy_capture_cursor_session_v1::ExtImageCopyCaptureCursorSessionV1,
+        CursorSessionData,
+    > for Compositor
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ext_image_copy_capture_cursor_session_v1::ExtImageCopyCaptureCursorSessionV1,
+        request: ext_image_copy_capture_cursor_session_v1::Request,
+        data: &CursorSessionData,
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_copy_capture_cursor_session_v1::Request::GetCaptureSession { session } => {
+                let id = SessionId(state.next_id());
+                let resource = data_init.init(session, id);
+                let size = state.source_size(data.source).map(|_| CURSOR_SIZE);
+                state.new_session(resource, id, data.source, false, size);
+            }
+            ext_image_copy_capture_cursor_session_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1, SessionId>
+    for Compositor
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+        request: ext_image_copy_capture_session_v1::Request,
+        session: &SessionId,
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_copy_capture_session_v1::Request::CreateFrame { frame } => {
+                let id = FrameId(state.next_id());
+                let resource = data_init.init(frame, id);
+                state.frames.push(Frame {
+                    id,
+                    session: *session,
+                    resource,
+                    buffer: None,
+                    damage: Vec::new(),
+                    captured: false,
+                });
+            }
+            ext_image_copy_capture_session_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        _resource: &ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+        session: &SessionId,
+    ) {
+        state.sessions.retain(|s| s.id != *session);
+    }
+}
+
+impl Dispatch<ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1, FrameId> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+        request: ext_image_copy_capture_frame_v1::Request,
+        id: &FrameId,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let Some(frame) = state.frames.iter_mut().find(|f| f.id == *id) else {
+            return;
+        };
+        match request {
+            ext_image_copy_capture_frame_v1::Request::AttachBuffer { buffer } => {
+                frame.buffer = Some(buffer);
+            }
+            ext_image_copy_capture_frame_v1::Request::DamageBuffer {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                if x < 0 || y < 0 || width <= 0 || height <= 0 {
+                    resource.post_error(
+                        ext_image_copy_capture_frame_v1::Error::InvalidBufferDamage,
+                        "invalid buffer damage".to_string(),
+                    );
+                    return;
+                }
+                frame.damage.push(Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+            ext_image_copy_capture_frame_v1::Request::Capture => {
+                if frame.captured {
+                    resource.post_error(
+                        ext_image_copy_capture_frame_v1::Error::AlreadyCaptured,
+                        "frame already captured".to_string(),
+                    );
+                    return;
+                }
+                let Some(buffer) = &frame.buffer else {
+                    resource.post_error(
+                        ext_image_copy_capture_frame_v1::Error::NoBuffer,
+                        "no buffer attached".to_string(),
+                    );
+                    return;
+                };
+                let Some(session) = state.sessions.iter().find(|s| s.id == frame.session) else {
+                    return;
+                };
+                if session.stopped {
+                    resource.failed(ext_image_copy_capture_frame_v1::FailureReason::Stopped);
+                    return;
+                }
+                let matches_constraints = buffer.data::<ShmBuffer>().is_some_and(|b| {
+                    b.size() == session.buffer_size
+                        && matches!(b.format(), WEnum::Value(f) if session.formats.contains(&f))
+                });
+                if !matches_constraints {
+                    resource
+                        .failed(ext_image_copy_capture_frame_v1::FailureReason::BufferConstraints);
+                    return;
+                }
+                frame.captured = true;
+            }
+            ext_image_copy_capture_frame_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        _resource: &ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+        id: &FrameId,
+    ) {
+        state.frames.retain(|f| f.id != *id);
+    }
+}
@@ -0,0 +1,511 @@
+//! In-process mock compositor, for testing clients of the COSMIC protocols without a running
+//! compositor.
+//!
+//! [`MockCompositor`] runs a [`wayland_server::Display`] on its own thread, so clients can use
+//! blocking roundtrips as usual. Clients are connected over a socketpair with
+//! [`MockCompositor::connect`].
+//!
+//! Nothing is sent unless a test asks for it: [`MockCompositor::with`] gives access to the
+//! [`Compositor`] state, whose methods create objects and send events. Properties are sent
+//! immediately, and `done` events only when requested, so tests control what is seen
+//! atomically by clients. Requests of interest are recorded, and can be inspected with
+//! [`Compositor::requests`] after a roundtrip.
+//!
+//! The one exception are the extension objects of `zcosmic_toplevel_info_v1` and
+//! `zcosmic_workspace_manager_v2`: like cosmic-comp, their initial state is sent as soon as the
+//! client requests them, followed by a `done`.
+//!
+//! A session recorded with `cosmic_client_toolkit::recording::Recorder` can be replayed with
+//! [`MockCompositor::replaying`], to turn event orderings seen with a real compositor into
+//! deterministic tests.
+
+use std::{
+    collections::HashMap,
+    io,
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread::{self, JoinHandle},
+};
+use wayland_client::Connection;
+use wayland_server::{
+    Display, DisplayHandle, Resource, WEnum,
+    backend::{ClientData, ClientId, GlobalId},
+};
+
+use cosmic_protocols::{
+    image_capture_source::v1::server::zcosmic_workspace_image_capture_source_manager_v1,
+    server::output_management::{OutputConfigurationRequest, OutputConfigurationState},
+    toplevel_info::v1::server::zcosmic_toplevel_info_v1,
+    toplevel_management::v1::server::zcosmic_toplevel_manager_v1,
+    workspace::v2::server::{zcosmic_workspace_handle_v2, zcosmic_workspace_manager_v2},
+};
+use wayland_protocols::{
+    ext::{
+        foreign_toplevel_list::v1::server::ext_foreign_toplevel_list_v1,
+        image_capture_source::v1::server::{
+            ext_foreign_toplevel_image_capture_source_manager_v1,
+            ext_output_image_capture_source_manager_v1,
+        },
+        image_copy_capture::v1::server::ext_image_copy_capture_manager_v1,
+        workspace::v1::server::ext_workspace_manager_v1,
+    },
+    xdg::xdg_output::zv1::server::zxdg_output_manager_v1,
+};
+use wayland_server::protocol::{wl_seat, wl_shm};
+
+mod capture;
+pub use capture::{CaptureSource, FrameContent, FrameId, Rect, SessionId};
+mod output;
+pub use output::OutputId;
+mod output_management;
+pub use output_management::OutputSnapshot;
+mod replay;
+pub use replay::{ParseRecordingError, Recording, ReplayStatus};
+mod shm;
+mod toplevel;
+pub use toplevel::ToplevelId;
+mod workspace;
+pub use workspace::{WorkspaceGroupId, WorkspaceId};
+
+// Server-side protocol types used in the API of the mock
+pub use cosmic_protocols;
+pub use wayland_protocols;
+pub use wayland_server;
+
+/// Globals the mock compositor can advertise.
+///
+/// Outputs aren't included, since there can be any number of them; see
+/// [`Compositor::add_output`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Global {
+    Shm,
+    Seat,
+    XdgOutputManager,
+    ForeignToplevelList,
+    ToplevelInfo,
+    ToplevelManager,
+    WorkspaceManager,
+    CosmicWorkspaceManager,
+    ImageCopyCaptureManager,
+    OutputImageCaptureSourceManager,
+    ForeignToplevelImageCaptureSourceManager,
+    WorkspaceImageCaptureSourceManager,
+    /// `zcosmic_output_manager_v1`, together with the `zwlr_output_manager_v1` it extends
+    OutputManager,
+}
+
+impl Global {
+    pub const ALL: [Self; 13] = [
+        Self::Shm,
+        Self::Seat,
+        Self::XdgOutputManager,
+        Self::ForeignToplevelList,
+        Self::ToplevelInfo,
+        Self::ToplevelManager,
+        Self::WorkspaceManager,
+        Self::CosmicWorkspaceManager,
+        Self::ImageCopyCaptureManager,
+        Self::OutputImageCaptureSourceManager,
+        Self::ForeignToplevelImageCaptureSourceManager,
+        Self::WorkspaceImageCaptureSourceManager,
+        Self::OutputManager,
+    ];
+
+    /// Highest version of the global supported by the mock compositor
+    pub fn max_version(self) -> u32 {
+        match self {
+            Self::Shm => 1,
+            Self::Seat => 5,
+            Self::XdgOutputManager => 3,
+            Self::ForeignToplevelList => 1,
+            Self::ToplevelInfo => 3,
+            Self::ToplevelManager => 4,
+            Self::WorkspaceManager => 1,
+            Self::CosmicWorkspaceManager => 2,
+            Self::ImageCopyCaptureManager => 1,
+            Self::OutputImageCaptureSourceManager => 1,
+            Self::ForeignToplevelImageCaptureSourceManager => 1,
+            Self::WorkspaceImageCaptureSourceManager => 1,
+            Self::OutputManager => 3,
+        }
+    }
+}
+
+/// Request sent by a client, recorded by the mock compositor
+#[derive(Clone, Debug, PartialEq)]
+pub enum Request {
+    // zcosmic_toplevel_manager_v1
+    CloseToplevel(ToplevelId),
+    ActivateToplevel(ToplevelId),
+    SetMaximized(ToplevelId),
+    UnsetMaximized(ToplevelId),
+    SetMinimized(ToplevelId),
+    UnsetMinimized(ToplevelId),
+    SetFullscreen(ToplevelId, Option<OutputId>),
+    UnsetFullscreen(ToplevelId),
+    SetRectangle {
+        toplevel: ToplevelId,
+        rect: Rect,
+    },
+    SetSticky(ToplevelId),
+    UnsetSticky(ToplevelId),
+    MoveToExtWorkspace {
+        toplevel: ToplevelId,
+        workspace: WorkspaceId,
+        output: OutputId,
+    },
+    // ext_workspace_manager_v1
+    CommitWorkspaces,
+    CreateWorkspace {
+        group: WorkspaceGroupId,
+        name: String,
+    },
+    ActivateWorkspace(WorkspaceId),
+    DeactivateWorkspace(WorkspaceId),
+    AssignWorkspace {
+        workspace: WorkspaceId,
+        group: WorkspaceGroupId,
+    },
+    RemoveWorkspace(WorkspaceId),
+    // zcosmic_workspace_handle_v2
+    RenameWorkspace {
+        workspace: WorkspaceId,
+        name: String,
+    },
+    SetTilingState {
+        workspace: WorkspaceId,
+        state: WEnum<zcosmic_workspace_handle_v2::TilingState>,
+    },
+    MoveWorkspaceBefore {
+        workspace: WorkspaceId,
+        other: WorkspaceId,
+        axis: u32,
+    },
+    MoveWorkspaceAfter {
+        workspace: WorkspaceId,
+        other: WorkspaceId,
+        axis: u32,
+    },
+    PinWorkspace(WorkspaceId),
+    UnpinWorkspace(WorkspaceId),
+    // zwlr_output_manager_v1 and zcosmic_output_manager_v1
+    ConfigureOutputs(OutputConfigurationRequest<OutputId>),
+    SetXwaylandPrimary(Option<OutputId>),
+}
+
+/// State of the mock compositor, and methods to send events to clients
+#[derive(Debug)]
+pub struct Compositor {
+    dh: DisplayHandle,
+    globals: HashMap<Global, GlobalId>,
+    next_id: u32,
+    requests: Vec<Request>,
+    outputs: Vec<output::Output>,
+    toplevel_lists: Vec<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1>,
+    toplevel_infos: Vec<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1>,
+    toplevel_managers: Vec<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1>,
+    toplevels: Vec<toplevel::Toplevel>,
+    workspace_managers: Vec<ext_workspace_manager_v1::ExtWorkspaceManagerV1>,
+    workspace_groups: Vec<workspace::WorkspaceGroup>,
+    workspaces: Vec<workspace::Workspace>,
+    capture_formats: Vec<wl_shm::Format>,
+    sessions: Vec<capture::Session>,
+    frames: Vec<capture::Frame>,
+    output_configuration: Option<OutputConfigurationState<Self>>,
+    output_heads: Vec<(OutputId, output_management::OutputSnapshot)>,
+    output_configuration_result: bool,
+    replay: replay::Replay,
+}
+
+impl Compositor {
+    fn new(dh: DisplayHandle) -> Self {
+        Self {
+            dh,
+            globals: HashMap::new(),
+            next_id: 1,
+            requests: Vec::new(),
+            outputs: Vec::new(),
+            toplevel_lists: Vec::new(),
+            toplevel_infos: Vec::new(),
+            toplevel_managers: Vec::new(),
+            toplevels: Vec::new(),
+            workspace_managers: Vec::new(),
+            workspace_groups: Vec::new(),
+            workspaces: Vec::new(),
+            capture_formats: vec![wl_shm::Format::Argb8888, wl_shm::Format::Xrgb8888],
+            sessions: Vec::new(),
+            frames: Vec::new(),
+            output_configuration: None,
+            output_heads: Vec::new(),
+            output_configuration_result: true,
+            replay: replay::Replay::default(),
+        }
+    }
+
+    fn next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Advertise `global`, replacing it if it is already advertised
+    pub fn create_global(&mut self, global: Global, version: u32) {
+        assert!(
+            version >= 1 && version <= global.max_version(),
+            "unsupported version {} of {:?}",
+            version,
+            global
+        );
+        let dh = &self.dh;
+        let id = match global {
+            Global::Shm => dh.create_global::<Self, wl_shm::WlShm, ()>(version, ()),
+            Global::Seat => dh.create_global::<Self, wl_seat::WlSeat, ()>(version, ()),
+            Global::XdgOutputManager => dh
+                .create_global::<Self, zxdg_output_manager_v1::ZxdgOutputManagerV1, ()>(
+                    version,
+                    (),
+                ),
+            Global::ForeignToplevelList => dh
+                .create_global::<Self, ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, ()>(
+                    version,
+                    (),
+                ),
+            Global::ToplevelInfo => dh
+                .create_global::<Self, zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, ()>(
+                    version,
+                    (),
+                ),
+            Global::ToplevelManager => dh
+                .create_global::<Self, zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, ()>(
+                    version,
+                    (),
+                ),
+            Global::WorkspaceManager => dh
+                .create_global::<Self, ext_workspace_manager_v1::ExtWorkspaceManagerV1, ()>(
+                    version,
+                    (),
+                ),
+            Global::CosmicWorkspaceManager => dh.create_global::<
+                Self,
+                zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2,
+                (),
+            >(version, ()),
+            Global::ImageCopyCaptureManager => dh.create_global::<
+                Self,
+                ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1,
+                (),
+            >(version, ()),
+            Global::OutputImageCaptureSourceManager => dh.create_global::<
+                Self,
+                ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+                (),
+            >(version, ()),
+            Global::ForeignToplevelImageCaptureSourceManager => dh.create_global::<
+                Self,
+                ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+                (),
+            >(version, ()),
+            Global::WorkspaceImageCaptureSourceManager => dh.create_global::<
+                Self,
+                zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+                (),
+            >(version, ()),
+            Global::OutputManager => self.create_output_manager(version),
+        };
+        if let Some(old) = self.globals.insert(global, id) {
+            self.dh.remove_global::<Self>(old);
+        }
+    }
+
+    /// Stop advertising `global`. Objects already bound from it are unaffected.
+    pub fn remove_global(&mut self, global: Global) {
+        if let Some(id) = self.globals.remove(&global) {
+            self.dh.remove_global::<Self>(id);
+            if global == Global::OutputManager {
+                self.remove_wlr_output_manager();
+            }
+        }
+    }
+
+    /// Requests recorded since the last [`take_requests`](Self::take_requests)
+    pub fn requests(&self) -> &[Request] {
+        &self.requests
+    }
+
+    pub fn take_requests(&mut self) -> Vec<Request> {
+        std::mem::take(&mut self.requests)
+    }
+}
+
+// Resource of the same client as `other`, from a list of resources with one per client
+fn for_client<'a, R: Resource>(resources: &'a [R], other: &impl Resource) -> Option<&'a R> {
+    resources
+        .iter()
+        .find(|resource| resource.id().same_client_as(&other.id()))
+}
+
+#[derive(Debug)]
+struct ClientState;
+
+impl ClientData for ClientState {}
+
+#[derive(Debug)]
+struct Inner {
+    display: Display<Compositor>,
+    compositor: Compositor,
+    clients: Vec<ClientId>,
+}
+
+/// Mock compositor running on its own thread.
+///
+/// Dropping it disconnects all clients.
+#[derive(Debug)]
+pub struct MockCompositor {
+    inner: Arc<Mutex<Inner>>,
+    // Closed to stop the compositor thread
+    stop: Option<UnixStream>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockCompositor {
+    /// Mock compositor advertising every [`Global`] at its highest version
+    pub fn new() -> Self {
+        Self::with_globals(Global::ALL.map(|global| (global, global.max_version())))
+    }
+
+    /// Mock compositor advertising only `globals`, at the given versions
+    pub fn with_globals(globals: impl IntoIterator<Item = (Global, u32)>) -> Self {
+        let mut display = Display::new().expect("Failed to create display");
+        let mut compositor = Compositor::new(display.handle());
+        for (global, version) in globals {
+            compositor.create_global(global, version);
+        }
+        let poll_fd = display
+            .backend()
+            .poll_fd()
+            .try_clone_to_owned()
+            .expect("Failed to duplicate display fd");
+        let (stop, stop_receiver) = UnixStream::pair().expect("Failed to create socketpair");
+
+        let inner = Arc::new(Mutex::new(Inner {
+            display,
+            compositor,
+            clients: Vec::new(),
+        }));
+        let thread = thread::spawn({
+            let inner = inner.clone();
+            move || run(inner, poll_fd, stop_receiver)
+        });
+        Self {
+            inner,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Connect a new client
+    pub fn connect(&self) -> Connection {
+        let (server, client) = UnixStream::pair().expect("Failed to create socketpair");
+        let mut inner = self.lock();
+        let client_id = inner
+            .compositor
+            .dh
+            .insert_client(server, Arc::new(ClientState))
+            .expect("Failed to insert client")
+            .id();
+        inner.clients.push(client_id);
+        Connection::from_socket(client).expect("Failed to connect to mock compositor")
+    }
+
+    /// Run `f` on the compositor state, then flush events it sent to clients.
+    ///
+    /// Requests are processed on the compositor thread, so a client has to do a roundtrip
+    /// before requests it sent can be observed here.
+    pub fn with<T>(&self, f: impl FnOnce(&mut Compositor) -> T) -> T {
+        let mut inner = self.lock();
+        let ret = f(&mut inner.compositor);
+        let _ = inner.display.flush_clients();
+        ret
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().expect("Mock compositor thread panicked")
+    }
+}
+
+impl Default for MockCompositor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for MockCompositor {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Disconnects all clients when the compositor thread exits, so they don't block forever if it
+// panicked
+struct DisconnectOnExit(Arc<Mutex<Inner>>);
+
+impl Drop for DisconnectOnExit {
+    fn drop(&mut self) {
+        let mut inner = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        // Clients are tracked here, since `with_all_clients` never terminates with the
+        // libwayland backend
+        let handle = inner.display.backend().handle();
+        for client in std::mem::take(&mut inner.clients) {
+            handle.kill_client(
+                client,
+                wayland_server::backend::DisconnectReason::ConnectionClosed,
+            );
+        }
+        let _ = inner.display.flush_clients();
+    }
+}
+
+fn run(inner: Arc<Mutex<Inner>>, poll_fd: OwnedFd, stop: UnixStream) {
+    let _guard = DisconnectOnExit(inner.clone());
+    let mut fds = [
+        libc::pollfd {
+            fd: poll_fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: stop.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+    loop {
+        // SAFETY: `fds` is a valid array of `pollfd` of the given length
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            panic!("Failed to poll display: {}", err);
+        }
+        if fds[1].revents != 0 {
+            break;
+        }
+        let mut inner = inner.lock().unwrap();
+        let Inner {
+            display,
+            compositor,
+            ..
+        } = &mut *inner;
+        display
+            .dispatch_clients(compositor)
+            .expect("Failed to dispatch clients");
+        let _ = display.flush_clients();
+    }
+}
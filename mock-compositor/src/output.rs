@@ -0,0 +1,325 @@
+use wayland_protocols::xdg::xdg_output::zv1::server::{zxdg_output_manager_v1, zxdg_output_v1};
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    backend::{ClientId, GlobalId},
+    protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_touch},
+};
+
+use crate::{Compositor, for_client};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OutputId(u32);
+
+#[derive(Debug)]
+pub(crate) struct Output {
+    pub id: OutputId,
+    name: String,
+    position: (i32, i32),
+    mode_size: (i32, i32),
+    scale: i32,
+    transform: wl_output::Transform,
+    global: GlobalId,
+    pub resources: Vec<wl_output::WlOutput>,
+}
+
+impl Output {
+    fn logical_size(&self) -> (i32, i32) {
+        let (width, height) = (self.mode_size.0 / self.scale, self.mode_size.1 / self.scale);
+        match self.transform {
+            wl_output::Transform::_90
+            | wl_output::Transform::_270
+            | wl_output::Transform::Flipped90
+            | wl_output::Transform::Flipped270 => (height, width),
+            _ => (width, height),
+        }
+    }
+}
+
+impl Compositor {
+    /// Advertise a `wl_output` with a mode of `mode_size`, at `position` in the global
+    /// logical coordinate space
+    pub fn add_output(
+        &mut self,
+        name: &str,
+        position: (i32, i32),
+        mode_size: (i32, i32),
+        scale: i32,
+    ) -> OutputId {
+        let id = OutputId(self.next_id());
+        let global = self
+            .dh
+            .create_global::<Self, wl_output::WlOutput, OutputId>(4, id);
+        self.outputs.push(Output {
+            id,
+            name: name.to_string(),
+            position,
+            mode_size,
+            scale,
+            transform: wl_output::Transform::Normal,
+            global,
+            resources: Vec::new(),
+        });
+        id
+    }
+
+    /// Set the transform of an output, rotating its logical size.
+    ///
+    /// Only sent to clients binding the output afterwards.
+    pub fn set_output_transform(&mut self, id: OutputId, transform: wl_output::Transform) {
+        if let Some(output) = self.outputs.iter_mut().find(|o| o.id == id) {
+            output.transform = transform;
+        }
+    }
+
+    /// Remove the global of an output, and stop capture sessions of it
+    pub fn remove_output(&mut self, id: OutputId) {
+        if let Some(idx) = self.outputs.iter().position(|o| o.id == id) {
+            let output = self.outputs.remove(idx);
+            self.dh.remove_global::<Self>(output.global);
+            self.stop_sessions_of(crate::CaptureSource::Output(id));
+        }
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = OutputId> + '_ {
+        self.outputs.iter().map(|o| o.id)
+    }
+
+    /// Size of the output's mode, in buffer pixels
+    pub(crate) fn output_mode_size(&self, id: OutputId) -> Option<(i32, i32)> {
+        Some(self.outputs.iter().find(|o| o.id == id)?.mode_size)
+    }
+
+    // `wl_output` of `client` for an output
+    pub(crate) fn output_resource(
+        &self,
+        id: OutputId,
+        client: &impl Resource,
+    ) -> Option<&wl_output::WlOutput> {
+        for_client(&self.outputs.iter().find(|o| o.id == id)?.resources, client)
+    }
+}
+
+impl GlobalDispatch<wl_output::WlOutput, OutputId> for Compositor {
+    fn bind(
+        state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<wl_output::WlOutput>,
+        id: &OutputId,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let resource = data_init.init(resource, *id);
+        let Some(output) = state.outputs.iter_mut().find(|o| o.id == *id) else {
+            return;
+        };
+        resource.geometry(
+            output.position.0,
+            output.position.1,
+            0,
+            0,
+            wl_output::Subpixel::Unknown,
+            "COSMIC".to_string(),
+            "Mock".to_string(),
+            output.transform,
+        );
+        resource.mode(
+            wl_output::Mode::Current | wl_output::Mode::Preferred,
+            output.mode_size.0,
+            output.mode_size.1,
+            60_000,
+        );
+        if resource.version() >= 2 {
+            resource.scale(output.scale);
+        }
+        if resource.version() >= 4 {
+            resource.name(output.name.clone());
+            resource.description(format!("Mock output {}", output.name));
+        }
+        if resource.version() >= 2 {
+            resource.done();
+        }
+        output.resources.push(resource);
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, OutputId> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &wl_output::WlOutput,
+        request: wl_output::Request,
+        _data: &OutputId,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_output::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        resource: &wl_output::WlOutput,
+        id: &OutputId,
+    ) {
+        if let Some(output) = state.outputs.iter_mut().find(|o| o.id == *id) {
+            output.resources.retain(|r| r != resource);
+        }
+    }
+}
+
+impl GlobalDispatch<zxdg_output_manager_v1::ZxdgOutputManagerV1, ()> for Compositor {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<zxdg_output_manager_v1::ZxdgOutputManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<zxdg_output_manager_v1::ZxdgOutputManagerV1, ()> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &zxdg_output_manager_v1::ZxdgOutputManagerV1,
+        request: zxdg_output_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zxdg_output_manager_v1::Request::GetXdgOutput { id, output } => {
+                let xdg_output = data_init.init(id, ());
+                let Some(output_data) = output
+                    .data::<OutputId>()
+                    .and_then(|id| state.outputs.iter().find(|o| o.id == *id))
+                else {
+                    return;
+                };
+                xdg_output.logical_position(output_data.position.0, output_data.position.1);
+                let (width, height) = output_data.logical_size();
+                xdg_output.logical_size(width, height);
+                if xdg_output.version() >= 2 {
+                    xdg_output.name(output_data.name.clone());
+                    xdg_output.description(format!("Mock output {}", output_data.name));
+                }
+                if xdg_output.version() >= 3 {
+                    if output.version() >= 2 {
+                        output.done();
+                    }
+                } else {
+                    xdg_output.done();
+                }
+            }
+            zxdg_output_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<zxdg_output_v1::ZxdgOutputV1, ()> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &zxdg_output_v1::ZxdgOutputV1,
+        request: zxdg_output_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zxdg_output_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+// The seat has no capabilities, and exists so requests taking a `wl_seat` can be made
+
+impl GlobalDispatch<wl_seat::WlSeat, ()> for Compositor {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<wl_seat::WlSeat>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let seat = data_init.init(resource, ());
+        seat.capabilities(wl_seat::Capability::empty());
+        if seat.version() >= 2 {
+            seat.name("seat0".to_string());
+        }
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &wl_seat::WlSeat,
+        request: wl_seat::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_seat::Request::GetPointer { id } => {
+                data_init.init(id, ());
+            }
+            wl_seat::Request::GetKeyboard { id } => {
+                data_init.init(id, ());
+            }
+            wl_seat::Request::GetTouch { id } => {
+                data_init.init(id, ());
+            }
+            wl_seat::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, ()> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &wl_pointer::WlPointer,
+        _request: wl_pointer::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &wl_keyboard::WlKeyboard,
+        _request: wl_keyboard::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_touch::WlTouch, ()> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &wl_touch::WlTouch,
+        _request: wl_touch::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
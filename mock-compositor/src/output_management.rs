@@ -0,0 +1,69 @@
+use cosmic_protocols::server::output_management::{
+    OutputConfigurationHandler, OutputConfigurationRequest, OutputConfigurationState,
+};
+use wayland_server::backend::GlobalId;
+
+pub use cosmic_protocols::server::output_management::OutputSnapshot;
+
+use crate::{Compositor, OutputId, Request};
+
+impl Compositor {
+    pub(crate) fn create_output_manager(&mut self, version: u32) -> GlobalId {
+        // The `zcosmic_output_manager_v1` global is replaced by `create_global`
+        self.remove_wlr_output_manager();
+        let mut state = OutputConfigurationState::new(&self.dh, version, |_| true);
+        state.set_outputs(self.output_heads.clone());
+        let id = state.cosmic_output_manager_global();
+        self.output_configuration = Some(state);
+        id
+    }
+
+    // Objects of the state stay usable, so it isn't dropped
+    pub(crate) fn remove_wlr_output_manager(&mut self) {
+        if let Some(state) = &self.output_configuration {
+            self.dh
+                .remove_global::<Self>(state.wlr_output_manager_global());
+        }
+    }
+
+    /// Advertise heads through output management, replacing previous ones, and send `done`
+    /// if anything changed.
+    ///
+    /// Heads aren't tied to the `wl_output` globals of outputs; they only share their ids.
+    pub fn set_output_heads(
+        &mut self,
+        heads: impl IntoIterator<Item = (OutputId, OutputSnapshot)>,
+    ) {
+        self.output_heads = heads.into_iter().collect();
+        if let Some(state) = &mut self.output_configuration {
+            state.set_outputs(self.output_heads.clone());
+        }
+    }
+
+    /// Whether configurations requested by clients succeed; `true` by default.
+    ///
+    /// Applied configurations are only recorded, not reflected in the heads.
+    pub fn set_output_configuration_result(&mut self, succeeded: bool) {
+        self.output_configuration_result = succeeded;
+    }
+}
+
+impl OutputConfigurationHandler for Compositor {
+    type Output = OutputId;
+
+    fn output_configuration_state(&mut self) -> &mut OutputConfigurationState<Self> {
+        // Only called for objects of the globals, which exist once created
+        self.output_configuration.as_mut().unwrap()
+    }
+
+    fn configure(&mut self, request: OutputConfigurationRequest<OutputId>) -> bool {
+        self.requests.push(Request::ConfigureOutputs(request));
+        self.output_configuration_result
+    }
+
+    fn set_xwayland_primary(&mut self, output: Option<OutputId>) {
+        self.requests.push(Request::SetXwaylandPrimary(output));
+    }
+}
+
+cosmic_protocols::delegate_cosmic_output_management!(Compositor);
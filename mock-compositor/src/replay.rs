@@ -0,0 +1,594 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    ffi::CString,
+    fmt,
+    fs::File,
+    os::fd::{AsRawFd, OwnedFd, RawFd},
+    str::FromStr,
+    sync::Arc,
+};
+use wayland_server::{
+    Resource,
+    backend::{
+        ClientId, GlobalHandler, GlobalId, Handle, ObjectData, ObjectId,
+        protocol::{Argument, ArgumentType, Interface, Message},
+    },
+    protocol::{wl_output, wl_seat, wl_shm},
+};
+
+use cosmic_protocols::{
+    image_capture_source::v1::server::zcosmic_workspace_image_capture_source_manager_v1,
+    toplevel_info::v1::server::zcosmic_toplevel_info_v1,
+    toplevel_management::v1::server::zcosmic_toplevel_manager_v1,
+    workspace::v2::server::zcosmic_workspace_manager_v2,
+};
+use wayland_protocols::{
+    ext::{
+        foreign_toplevel_list::v1::server::ext_foreign_toplevel_list_v1,
+        image_capture_source::v1::server::{
+            ext_foreign_toplevel_image_capture_source_manager_v1,
+            ext_output_image_capture_source_manager_v1,
+        },
+        image_copy_capture::v1::server::ext_image_copy_capture_manager_v1,
+        workspace::v1::server::ext_workspace_manager_v1,
+    },
+    xdg::xdg_output::zv1::server::zxdg_output_manager_v1,
+};
+
+use crate::{Compositor, MockCompositor};
+
+// Interfaces of every object reachable from the globals known to the mock compositor, by name
+fn interfaces() -> HashMap<&'static str, &'static Interface> {
+    let mut queue = VecDeque::from([
+        wl_shm::WlShm::interface(),
+        wl_seat::WlSeat::interface(),
+        wl_output::WlOutput::interface(),
+        zxdg_output_manager_v1::ZxdgOutputManagerV1::interface(),
+        ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1::interface(),
+        zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1::interface(),
+        zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1::interface(),
+        ext_workspace_manager_v1::ExtWorkspaceManagerV1::interface(),
+        zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2::interface(),
+        ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1::interface(),
+        ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1::interface(),
+        ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1::interface(),
+        zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1::interface(),
+    ]);
+    let mut interfaces = HashMap::new();
+    while let Some(interface) = queue.pop_front() {
+        if interfaces.insert(interface.name, interface).is_some() {
+            continue;
+        }
+        for message in interface.requests.iter().chain(interface.events) {
+            queue.extend(message.child_interface);
+            queue.extend(message.arg_interfaces);
+        }
+    }
+    interfaces
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Arg {
+    Int(i32),
+    Uint(u32),
+    Fixed(i32),
+    Str(Option<String>),
+    Object(Option<u32>),
+    NewId(u32),
+    Array(Vec<u8>),
+    Fd,
+}
+
+#[derive(Clone, Debug)]
+enum Entry {
+    Create {
+        id: u32,
+        interface: &'static Interface,
+    },
+    Event {
+        id: u32,
+        interface: &'static Interface,
+        opcode: u16,
+        args: Vec<Arg>,
+    },
+}
+
+/// Error parsing a [`Recording`]
+#[derive(Debug)]
+pub struct ParseRecordingError {
+    /// Line of the error, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseRecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseRecordingError {}
+
+/// Events received by a client, recorded with `cosmic_client_toolkit::recording::Recorder`.
+///
+/// Parsed with [`str::parse`], and replayed with [`MockCompositor::replaying`]. Globals of
+/// interfaces the mock compositor doesn't know are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    globals: Vec<(&'static Interface, u32)>,
+    // Entries, with their line number
+    entries: Vec<(usize, Entry)>,
+}
+
+impl FromStr for Recording {
+    type Err = ParseRecordingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let interfaces = interfaces();
+        let mut recording = Recording::default();
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| ParseRecordingError {
+                line: idx + 1,
+                message,
+            };
+            let tokens = tokenize(line).map_err(error)?;
+            let interface = |name: &str| {
+                interfaces
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| error(format!("unknown interface '{}'", name)))
+            };
+            match tokens.as_slice() {
+                [
+                    Token::Bare("global"),
+                    Token::Bare(name),
+                    Token::Bare(version),
+                ] => {
+                    let version = parse_number(version).map_err(error)?;
+                    if let Some(interface) = interfaces.get(name) {
+                        recording.globals.push((interface, version));
+                    }
+                }
+                [
+                    Token::Bare("create"),
+                    Token::Bare(id),
+                    Token::Bare(name),
+                    Token::Bare(_version),
+                ] => {
+                    let entry = Entry::Create {
+                        id: parse_number(id).map_err(error)?,
+                        interface: interface(name)?,
+                    };
+                    recording.entries.push((idx + 1, entry));
+                }
+                [
+                    Token::Bare("event"),
+                    Token::Bare(id),
+                    Token::Bare(name),
+                    args @ ..,
+                ] => {
+                    let (name, event) = name
+                        .split_once('.')
+                        .ok_or_else(|| error(format!("invalid event name '{}'", name)))?;
+                    let interface = interface(name)?;
+                    let opcode = interface
+                        .events
+                        .iter()
+                        .position(|desc| desc.name == event)
+                        .ok_or_else(|| error(format!("no event '{}' in '{}'", event, name)))?;
+                    let signature = interface.events[opcode].signature;
+                    if args.len() != signature.len() {
+                        return Err(error(format!(
+                            "expected {} arguments, found {}",
+                            signature.len(),
+                            args.len()
+                        )));
+                    }
+                    let args = args
+                        .iter()
+                        .zip(signature)
+                        .map(|(arg, ty)| parse_arg(arg, *ty))
+                        .collect::<Result<_, _>>()
+                        .map_err(error)?;
+                    let entry = Entry::Event {
+                        id: parse_number(id).map_err(error)?,
+                        interface,
+                        opcode: opcode as u16,
+                        args,
+                    };
+                    recording.entries.push((idx + 1, entry));
+                }
+                _ => return Err(error(format!("invalid entry '{}'", line))),
+            }
+        }
+        Ok(recording)
+    }
+}
+
+#[derive(Debug)]
+enum Token<'a> {
+    Bare(&'a str),
+    Quoted(String),
+}
+
+// Split at whitespace outside of quoted strings, unescaping quoted strings
+fn tokenize(line: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let mut string = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((idx, '"')) => break idx + 1,
+                    Some((_, '\\')) => string.push(unescape(&mut chars)?),
+                    Some((_, c)) => string.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            };
+            tokens.push(Token::Quoted(string));
+            rest = &quoted[end..];
+            if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+                return Err("missing space after string".to_string());
+            }
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            tokens.push(Token::Bare(&rest[..end]));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+// Character of an escape sequence written by `str`'s `Debug` implementation
+fn unescape(chars: &mut impl Iterator<Item = (usize, char)>) -> Result<char, String> {
+    Ok(match chars.next().map(|(_, c)| c) {
+        Some('n') => '\n',
+        Some('r') => '\r',
+        Some('t') => '\t',
+        Some('0') => '\0',
+        Some(c @ ('\\' | '"' | '\'')) => c,
+        Some('u') => {
+            let mut code = String::new();
+            if chars.next().map(|(_, c)| c) != Some('{') {
+                return Err("invalid unicode escape".to_string());
+            }
+            loop {
+                match chars.next().map(|(_, c)| c) {
+                    Some('}') => break,
+                    Some(c) => code.push(c),
+                    None => return Err("invalid unicode escape".to_string()),
+                }
+            }
+            u32::from_str_radix(&code, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| format!("invalid unicode escape '{}'", code))?
+        }
+        c => return Err(format!("invalid escape '\\{}'", c.unwrap_or(' '))),
+    })
+}
+
+fn parse_number<T: FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid number '{}'", s))
+}
+
+fn parse_arg(token: &Token<'_>, ty: ArgumentType) -> Result<Arg, String> {
+    let bare = match token {
+        Token::Quoted(s) if matches!(ty, ArgumentType::Str(_)) => {
+            if s.contains('\0') {
+                return Err("string contains a nul byte".to_string());
+            }
+            return Ok(Arg::Str(Some(s.clone())));
+        }
+        Token::Quoted(s) => return Err(format!("unexpected string {:?}", s)),
+        Token::Bare(s) => *s,
+    };
+    Ok(match ty {
+        ArgumentType::Int => Arg::Int(parse_number(bare)?),
+        ArgumentType::Uint => Arg::Uint(parse_number(bare)?),
+        ArgumentType::Fixed => Arg::Fixed(parse_number(bare)?),
+        ArgumentType::Str(_) if bare == "null" => Arg::Str(None),
+        ArgumentType::Object(_) if bare == "null" => Arg::Object(None),
+        ArgumentType::Object(_) => Arg::Object(Some(parse_number(
+            bare.strip_prefix('@')
+                .ok_or_else(|| format!("invalid object '{}'", bare))?,
+        )?)),
+        ArgumentType::NewId => Arg::NewId(parse_number(
+            bare.strip_prefix("new@")
+                .ok_or_else(|| format!("invalid new id '{}'", bare))?,
+        )?),
+        ArgumentType::Array => {
+            let hex = bare
+                .strip_prefix('[')
+                .and_then(|s| s.strip_suffix(']'))
+                .filter(|hex| hex.len() % 2 == 0)
+                .ok_or_else(|| format!("invalid array '{}'", bare))?;
+            (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<_, _>>()
+                .map(Arg::Array)
+                .map_err(|_| format!("invalid array '{}'", bare))?
+        }
+        ArgumentType::Fd if bare == "fd" => Arg::Fd,
+        _ => return Err(format!("invalid {:?} argument '{}'", ty, bare)),
+    })
+}
+
+/// Progress of [`Compositor::replay`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayStatus {
+    /// All events of the recording were sent
+    Done,
+    /// Waiting for the client to create an object needed by the entry at `line` of the
+    /// recording
+    Blocked { line: usize },
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Replay {
+    entries: Vec<(usize, Entry)>,
+    next: usize,
+    // The client events are replayed to, which is the first to bind a recorded global
+    client: Option<ClientId>,
+    // Objects created by the client that aren't matched with a recorded object yet, in the
+    // order they were created
+    unclaimed: Vec<ObjectId>,
+    // Live objects of recorded object ids
+    objects: HashMap<u32, ObjectId>,
+    // Sent in place of recorded file descriptors
+    fds: Vec<OwnedFd>,
+}
+
+enum Resolved {
+    Live(ObjectId),
+    Dead,
+    Missing,
+}
+
+impl Replay {
+    fn is_replay_client(&self, client_id: &ClientId) -> bool {
+        self.client.as_ref() == Some(client_id)
+    }
+
+    // Live object of a recorded object id, or an unclaimed object of the client to match with
+    // it, added to `claims`
+    fn resolve(
+        &self,
+        handle: &Handle,
+        id: u32,
+        interface: &Interface,
+        claims: &mut Vec<(u32, ObjectId)>,
+    ) -> Resolved {
+        if let Some(object) = self.objects.get(&id) {
+            return match handle.object_info(object.clone()) {
+                Ok(_) => Resolved::Live(object.clone()),
+                Err(_) => Resolved::Dead,
+            };
+        }
+        if let Some((_, object)) = claims.iter().find(|(claimed, _)| *claimed == id) {
+            return Resolved::Live(object.clone());
+        }
+        let unclaimed = self.unclaimed.iter().find(|object| {
+            !claims.iter().any(|(_, claimed)| claimed == *object)
+                && handle
+                    .object_info((*object).clone())
+                    .is_ok_and(|info| info.interface.name == interface.name)
+        });
+        match unclaimed {
+            Some(object) => {
+                claims.push((id, object.clone()));
+                Resolved::Live(object.clone())
+            }
+            None => Resolved::Missing,
+        }
+    }
+
+    // Replay an entry, returning `false` if it needs objects the client hasn't created yet
+    fn step(&mut self, handle: &Handle, client: ClientId, entry: &Entry) -> bool {
+        let mut claims = Vec::new();
+        match entry {
+            Entry::Create { id, interface } => {
+                match self.resolve(handle, *id, interface, &mut claims) {
+                    Resolved::Live(_) => {}
+                    // Object ids may be reused once destroyed
+                    Resolved::Dead => {
+                        self.objects.remove(id);
+                        return self.step(handle, client, entry);
+                    }
+                    Resolved::Missing => return false,
+                }
+            }
+            Entry::Event {
+                id,
+                interface,
+                opcode,
+                args,
+            } => {
+                let target = match self.resolve(handle, *id, interface, &mut claims) {
+                    Resolved::Live(object) => object,
+                    // Destroyed by the client, which is handled by protocols with a
+                    // destructor request
+                    Resolved::Dead => return true,
+                    Resolved::Missing => return false,
+                };
+                let desc = &interface.events[*opcode as usize];
+                let mut arg_interfaces = desc.arg_interfaces.iter();
+                let mut objects = Vec::new();
+                for arg in args {
+                    if let Arg::Object(Some(id)) = arg {
+                        let interface = arg_interfaces.next().unwrap();
+                        match self.resolve(handle, *id, interface, &mut claims) {
+                            Resolved::Live(object) => objects.push(object),
+                            Resolved::Dead => return true,
+                            Resolved::Missing => return false,
+                        }
+                    } else if let Arg::Object(None) = arg {
+                        arg_interfaces.next();
+                    }
+                }
+
+                let version = handle.object_info(target.clone()).unwrap().version;
+                let mut objects = objects.into_iter();
+                let mut message_args = Vec::new();
+                for arg in args {
+                    message_args.push(match arg {
+                        Arg::Int(value) => Argument::Int(*value),
+                        Arg::Uint(value) => Argument::Uint(*value),
+                        Arg::Fixed(value) => Argument::Fixed(*value),
+                        Arg::Str(value) => Argument::Str(
+                            value
+                                .as_ref()
+                                .map(|s| Box::new(CString::new(s.as_str()).unwrap())),
+                        ),
+                        Arg::Object(Some(_)) => Argument::Object(objects.next().unwrap()),
+                        Arg::Object(None) => Argument::Object(ObjectId::null()),
+                        Arg::NewId(id) => {
+                            let object = handle
+                                .create_object::<Compositor>(
+                                    client.clone(),
+                                    desc.child_interface.unwrap(),
+                                    version,
+                                    Arc::new(ReplayObject),
+                                )
+                                .unwrap();
+                            self.objects.insert(*id, object.clone());
+                            Argument::NewId(object)
+                        }
+                        Arg::Array(bytes) => Argument::Array(Box::new(bytes.clone())),
+                        Arg::Fd => Argument::Fd(self.dev_null()),
+                    });
+                }
+                let _ = handle.send_event(Message {
+                    sender_id: target,
+                    opcode: *opcode,
+                    args: message_args.into(),
+                });
+            }
+        }
+        for (id, object) in claims {
+            self.unclaimed.retain(|unclaimed| unclaimed != &object);
+            self.objects.insert(id, object);
+        }
+        true
+    }
+
+    fn dev_null(&mut self) -> RawFd {
+        let fd = OwnedFd::from(File::open("/dev/null").expect("Failed to open /dev/null"));
+        let raw_fd = fd.as_raw_fd();
+        self.fds.push(fd);
+        raw_fd
+    }
+}
+
+// Object data of objects created while replaying, which only keeps track of the objects
+// created by the client
+struct ReplayObject;
+
+impl ObjectData<Compositor> for ReplayObject {
+    fn request(
+        self: Arc<Self>,
+        _handle: &Handle,
+        compositor: &mut Compositor,
+        client_id: ClientId,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData<Compositor>>> {
+        let new_id = msg.args.iter().find_map(|arg| match arg {
+            Argument::NewId(id) => Some(id.clone()),
+            _ => None,
+        })?;
+        if compositor.replay.is_replay_client(&client_id) {
+            compositor.replay.unclaimed.push(new_id);
+        }
+        Some(self)
+    }
+
+    fn destroyed(
+        self: Arc<Self>,
+        _handle: &Handle,
+        compositor: &mut Compositor,
+        _client_id: ClientId,
+        object_id: ObjectId,
+    ) {
+        compositor
+            .replay
+            .unclaimed
+            .retain(|unclaimed| unclaimed != &object_id);
+    }
+}
+
+struct ReplayGlobal;
+
+impl GlobalHandler<Compositor> for ReplayGlobal {
+    fn bind(
+        self: Arc<Self>,
+        _handle: &Handle,
+        compositor: &mut Compositor,
+        client_id: ClientId,
+        _global_id: GlobalId,
+        object_id: ObjectId,
+    ) -> Arc<dyn ObjectData<Compositor>> {
+        let replay = &mut compositor.replay;
+        if replay.client.is_none() {
+            replay.client = Some(client_id.clone());
+        }
+        if replay.is_replay_client(&client_id) {
+            replay.unclaimed.push(object_id);
+        }
+        Arc::new(ReplayObject)
+    }
+}
+
+impl Compositor {
+    fn load_recording(&mut self, recording: Recording) {
+        let handle = self.dh.backend_handle();
+        for (interface, version) in recording.globals {
+            handle.create_global::<Self>(interface, version, Arc::new(ReplayGlobal));
+        }
+        self.replay = Replay {
+            entries: recording.entries,
+            ..Replay::default()
+        };
+    }
+
+    /// Send the events of the recording in order, until one needs an object the client
+    /// hasn't created yet.
+    ///
+    /// Objects the client creates are matched with recorded objects of the same interface in
+    /// the order they are created, and the order they are first used by the recording. The
+    /// client has to do a roundtrip before the objects it created are seen here.
+    pub fn replay(&mut self) -> ReplayStatus {
+        let handle = self.dh.backend_handle();
+        while let Some((line, entry)) = self.replay.entries.get(self.replay.next).cloned() {
+            let Some(client) = self.replay.client.clone() else {
+                return ReplayStatus::Blocked { line };
+            };
+            if !self.replay.step(&handle, client, &entry) {
+                return ReplayStatus::Blocked { line };
+            }
+            self.replay.next += 1;
+        }
+        ReplayStatus::Done
+    }
+}
+
+impl MockCompositor {
+    /// Mock compositor advertising the globals of `recording`, whose events are sent with
+    /// [`Compositor::replay`].
+    ///
+    /// Only the recorded events are sent, so objects of the replayed protocols aren't
+    /// available through the other methods of [`Compositor`].
+    pub fn replaying(recording: Recording) -> Self {
+        let mock = Self::with_globals([]);
+        mock.with(|c| c.load_recording(recording));
+        mock
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    os::fd::{AsRawFd, OwnedFd},
+    ptr,
+    sync::{Arc, Mutex},
+};
+use wayland_client::WEnum;
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    protocol::{wl_buffer, wl_shm, wl_shm_pool},
+};
+
+use crate::Compositor;
+
+#[derive(Debug)]
+pub(crate) struct ShmPool {
+    fd: OwnedFd,
+    size: Mutex<usize>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ShmBuffer {
+    pool: Arc<ShmPool>,
+    offset: usize,
+    width: usize,
+    height: usize,
+    stride: usize,
+    format: WEnum<wl_shm::Format>,
+}
+
+impl ShmBuffer {
+    pub fn size(&self) -> (u32, u32) {
+        (self.width as u32, self.height as u32)
+    }
+
+    pub fn format(&self) -> WEnum<wl_shm::Format> {
+        self.format
+    }
+
+    /// Fill the buffer with an RGBA color
+    pub fn fill(&self, [r, g, b, a]: [u8; 4]) {
+        let pixel = match self.format {
+            WEnum::Value(wl_shm::Format::Argb8888) => [b, g, r, a],
+            WEnum::Value(wl_shm::Format::Xrgb8888) => [b, g, r, 255],
+            WEnum::Value(wl_shm::Format::Abgr8888) => [r, g, b, a],
+            WEnum::Value(wl_shm::Format::Xbgr8888) => [r, g, b, 255],
+            format => panic!("Unsupported shm format {:?}", format),
+        };
+        let size = *self.pool.size.lock().unwrap();
+        if size == 0 {
+            return;
+        }
+        // SAFETY: Maps the pool fd sent by the client, which is at least `size` bytes
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                self.pool.fd.as_raw_fd(),
+                0,
+            )
+        };
+        assert_ne!(map, libc::MAP_FAILED, "Failed to map shm pool");
+        // SAFETY: `map` is a valid mapping of `size` bytes until unmapped below
+        let data = unsafe { std::slice::from_raw_parts_mut(map.cast::<u8>(), size) };
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = self.offset + y * self.stride + x * 4;
+                if let Some(dst) = data.get_mut(idx..idx + 4) {
+                    dst.copy_from_slice(&pixel);
+                }
+            }
+        }
+        // SAFETY: `map` was mapped above with the same size, and `data` isn't used after this
+        unsafe {
+            libc::munmap(map, size);
+        }
+    }
+}
+
+impl GlobalDispatch<wl_shm::WlShm, ()> for Compositor {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<wl_shm::WlShm>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let shm = data_init.init(resource, ());
+        for format in [
+            wl_shm::Format::Argb8888,
+            wl_shm::Format::Xrgb8888,
+            wl_shm::Format::Abgr8888,
+            wl_shm::Format::Xbgr8888,
+        ] {
+            shm.format(format);
+        }
+    }
+}
+
+impl Dispatch<wl_shm::WlShm, ()> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &wl_shm::WlShm,
+        request: wl_shm::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_shm::Request::CreatePool { id, fd, size } => {
+                data_init.init(
+                    id,
+                    Arc::new(ShmPool {
+                        fd,
+                        size: Mutex::new(size.max(0) as usize),
+                    }),
+                );
+            }
+            wl_shm::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<wl_shm_pool::WlShmPool, Arc<ShmPool>> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &wl_shm_pool::WlShmPool,
+        request: wl_shm_pool::Request,
+        pool: &Arc<ShmPool>,
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_shm_pool::Request::CreateBuffer {
+                id,
+                offset,
+                width,
+                height,
+                stride,
+                format,
+            } => {
+                let buffer = data_init.init(
+                    id,
+                    ShmBuffer {
+                        pool: pool.clone(),
+                        offset: offset.max(0) as usize,
+                        width: width.max(0) as usize,
+                        height: height.max(0) as usize,
+                        stride: stride.max(0) as usize,
+                        format,
+                    },
+                );
+                if offset < 0 || width <= 0 || height <= 0 || stride < width * 4 {
+                    buffer.post_error(
+                        wl_shm::Error::InvalidStride,
+                        "invalid buffer dimensions".to_string(),
+                    );
+                }
+            }
+            wl_shm_pool::Request::Resize { size } => {
+                *pool.size.lock().unwrap() = size.max(0) as usize;
+            }
+            wl_shm_pool::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<wl_buffer::WlBuffer, ShmBuffer> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &wl_buffer::WlBuffer,
+        request: wl_buffer::Request,
+        _data: &ShmBuffer,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_buffer::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,525 @@
+use cosmic_protocols::{
+    toplevel_info::v1::server::{zcosmic_toplevel_handle_v1, zcosmic_toplevel_info_v1},
+    toplevel_management::v1::server::zcosmic_toplevel_manager_v1,
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::server::{
+    ext_foreign_toplevel_handle_v1, ext_foreign_toplevel_list_v1,
+};
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, backend::ClientId,
+};
+
+use crate::{CaptureSource, Compositor, OutputId, Rect, Request, WorkspaceId};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ToplevelId(u32);
+
+#[derive(Debug)]
+pub(crate) struct Toplevel {
+    pub id: ToplevelId,
+    identifier: String,
+    title: String,
+    app_id: String,
+    states: Vec<zcosmic_toplevel_handle_v1::State>,
+    outputs: Vec<OutputId>,
+    workspaces: Vec<WorkspaceId>,
+    capture_size: (i32, i32),
+    handles: Vec<ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1>,
+    cosmic_handles: Vec<zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1>,
+}
+
+impl Compositor {
+    /// Announce a new toplevel to every `ext_foreign_toplevel_list_v1`, followed by a `done`
+    pub fn new_toplevel(&mut self, app_id: &str, title: &str) -> ToplevelId {
+        let id = ToplevelId(self.next_id());
+        self.toplevels.push(Toplevel {
+            id,
+            identifier: format!("mock-toplevel-{}", id.0),
+            title: title.to_string(),
+            app_id: app_id.to_string(),
+            states: Vec::new(),
+            outputs: Vec::new(),
+            workspaces: Vec::new(),
+            capture_size: (64, 48),
+            handles: Vec::new(),
+            cosmic_handles: Vec::new(),
+        });
+        for list in self.toplevel_lists.clone() {
+            self.send_toplevel(&list, id);
+        }
+        id
+    }
+
+    /// Send `closed`, and stop capture sessions of the toplevel
+    pub fn close_toplevel(&mut self, id: ToplevelId) {
+        let Some(idx) = self.toplevels.iter().position(|t| t.id == id) else {
+            return;
+        };
+        let toplevel = self.toplevels.remove(idx);
+        for handle in &toplevel.handles {
+            handle.closed();
+        }
+        self.stop_sessions_of(CaptureSource::Toplevel(id));
+    }
+
+    pub fn toplevels(&self) -> impl Iterator<Item = ToplevelId> + '_ {
+        self.toplevels.iter().map(|t| t.id)
+    }
+
+    /// Identifier sent in `ext_foreign_toplevel_handle_v1::identifier`
+    pub fn toplevel_identifier(&self, id: ToplevelId) -> Option<&str> {
+        Some(&self.toplevels.iter().find(|t| t.id == id)?.identifier)
+    }
+
+    pub fn set_toplevel_title(&mut self, id: ToplevelId, title: &str) {
+        let toplevel = self.toplevel_mut(id);
+        toplevel.title = title.to_string();
+        for handle in &toplevel.handles {
+            handle.title(title.to_string());
+        }
+    }
+
+    pub fn set_toplevel_app_id(&mut self, id: ToplevelId, app_id: &str) {
+        let toplevel = self.toplevel_mut(id);
+        toplevel.app_id = app_id.to_string();
+        for handle in &toplevel.handles {
+            handle.app_id(app_id.to_string());
+        }
+    }
+
+    pub fn set_toplevel_state(
+        &mut self,
+        id: ToplevelId,
+        states: &[zcosmic_toplevel_handle_v1::State],
+    ) {
+        let toplevel = self.toplevel_mut(id);
+        toplevel.states = states.to_vec();
+        for handle in &toplevel.cosmic_handles {
+            handle.state(state_array(states));
+        }
+    }
+
+    pub fn toplevel_enter_output(&mut self, id: ToplevelId, output: OutputId) {
+        self.toplevel_mut(id).outputs.push(output);
+        let toplevel = self.toplevel(id);
+        for handle in &toplevel.cosmic_handles {
+            if let Some(output) = self.output_resource(output, handle) {
+                handle.output_enter(output);
+            }
+        }
+    }
+
+    pub fn toplevel_leave_output(&mut self, id: ToplevelId, output: OutputId) {
+        self.toplevel_mut(id).outputs.retain(|o| *o != output);
+        let toplevel = self.toplevel(id);
+        for handle in &toplevel.cosmic_handles {
+            if let Some(output) = self.output_resource(output, handle) {
+                handle.output_leave(output);
+            }
+        }
+    }
+
+    /// Send `geometry` relative to `output`
+    pub fn set_toplevel_geometry(&mut self, id: ToplevelId, output: OutputId, geometry: Rect) {
+        let toplevel = self.toplevel(id);
+        for handle in &toplevel.cosmic_handles {
+            if handle.version() >= 2
+                && let Some(output) = self.output_resource(output, handle)
+            {
+                handle.geometry(
+                    output,
+                    geometry.x,
+                    geometry.y,
+                    geometry.width,
+                    geometry.height,
+                );
+            }
+        }
+    }
+
+    pub fn toplevel_enter_workspace(&mut self, id: ToplevelId, workspace: WorkspaceId) {
+        self.toplevel_mut(id).workspaces.push(workspace);
+        let toplevel = self.toplevel(id);
+        for handle in &toplevel.cosmic_handles {
+            if handle.version() >= 3
+                && let Some(workspace) = self.workspace_resource(workspace, handle)
+            {
+                handle.ext_workspace_enter(workspace);
+            }
+        }
+    }
+
+    pub fn toplevel_leave_workspace(&mut self, id: ToplevelId, workspace: WorkspaceId) {
+        self.toplevel_mut(id).workspaces.retain(|w| *w != workspace);
+        let toplevel = self.toplevel(id);
+        for handle in &toplevel.cosmic_handles {
+            if handle.version() >= 3
+                && let Some(workspace) = self.workspace_resource(workspace, handle)
+            {
+                handle.ext_workspace_leave(workspace);
+            }
+        }
+    }
+
+    /// Size of buffers for capture sessions of the toplevel
+    pub fn set_toplevel_capture_size(&mut self, id: ToplevelId, size: (i32, i32)) {
+        self.toplevel_mut(id).capture_size = size;
+    }
+
+    /// Send `ext_foreign_toplevel_handle_v1::done`
+    pub fn toplevel_done(&mut self, id: ToplevelId) {
+        for handle in &self.toplevel(id).handles {
+            handle.done();
+        }
+    }
+
+    /// Send `zcosmic_toplevel_info_v1::done`
+    pub fn toplevel_info_done(&mut self) {
+        for info in &self.toplevel_infos {
+            if info.version() >= 2 {
+                info.done();
+            }
+        }
+    }
+
+    /// Send `ext_foreign_toplevel_list_v1::finished`, after which lists get no more events
+    pub fn toplevel_list_finished(&mut self) {
+        for list in self.toplevel_lists.drain(..) {
+            list.finished();
+        }
+    }
+
+    pub(crate) fn toplevel_capture_size(&self, id: ToplevelId) -> Option<(i32, i32)> {
+        Some(self.toplevels.iter().find(|t| t.id == id)?.capture_size)
+    }
+
+    fn toplevel(&self, id: ToplevelId) -> &Toplevel {
+        self.toplevels
+            .iter()
+            .find(|t| t.id == id)
+            .expect("No such toplevel")
+    }
+
+    fn toplevel_mut(&mut self, id: ToplevelId) -> &mut Toplevel {
+        self.toplevels
+            .iter_mut()
+            .find(|t| t.id == id)
+            .expect("No such toplevel")
+    }
+
+    fn send_toplevel(
+        &mut self,
+        list: &ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1,
+        id: ToplevelId,
+    ) {
+        let Some(client) = list.client() else {
+            return;
+        };
+        let Ok(handle) = client
+            .create_resource::<ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1, _, Self>(
+                &self.dh,
+                list.version(),
+                id,
+            )
+        else {
+            return;
+        };
+        let toplevel = self.toplevel_mut(id);
+        list.toplevel(&handle);
+        handle.identifier(toplevel.identifier.clone());
+        handle.title(toplevel.title.clone());
+        handle.app_id(toplevel.app_id.clone());
+        handle.done();
+        toplevel.handles.push(handle);
+    }
+}
+
+fn state_array(states: &[zcosmic_toplevel_handle_v1::State]) -> Vec<u8> {
+    states
+        .iter()
+        .flat_map(|state| u32::from(*state).to_ne_bytes())
+        .collect()
+}
+
+impl GlobalDispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, ()> for Compositor {
+    fn bind(
+        state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let list = data_init.init(resource, ());
+        let ids = state.toplevels().collect::<Vec<_>>();
+        for id in ids {
+            state.send_toplevel(&list, id);
+        }
+        state.toplevel_lists.push(list);
+    }
+}
+
+impl Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, ()> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1,
+        request: ext_foreign_toplevel_list_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_foreign_toplevel_list_v1::Request::Stop => {
+                state.toplevel_lists.retain(|l| l != resource);
+                resource.finished();
+            }
+            ext_foreign_toplevel_list_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        resource: &ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1,
+        _data: &(),
+    ) {
+        state.toplevel_lists.retain(|l| l != resource);
+    }
+}
+
+impl Dispatch<ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1, ToplevelId>
+    for Compositor
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+        request: ext_foreign_toplevel_handle_v1::Request,
+        _data: &ToplevelId,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_foreign_toplevel_handle_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        resource: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+        id: &ToplevelId,
+    ) {
+        if let Some(toplevel) = state.toplevels.iter_mut().find(|t| t.id == *id) {
+            toplevel.handles.retain(|h| h != resource);
+        }
+    }
+}
+
+impl GlobalDispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, ()> for Compositor {
+    fn bind(
+        state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        // Version 1 isn't supported; no toplevels are sent to it
+        let info = data_init.init(resource, ());
+        state.toplevel_infos.push(info);
+    }
+}
+
+impl Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, ()> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1,
+        request: zcosmic_toplevel_info_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zcosmic_toplevel_info_v1::Request::GetCosmicToplevel {
+                cosmic_toplevel,
+                foreign_toplevel,
+            } => {
+                let id = *foreign_toplevel.data::<ToplevelId>().unwrap();
+                let handle = data_init.init(cosmic_toplevel, id);
+                // Toplevel may already be closed
+                let Some(toplevel) = state.toplevels.iter().find(|t| t.id == id) else {
+                    return;
+                };
+                handle.state(state_array(&toplevel.states));
+                for output in &toplevel.outputs {
+                    if let Some(output) = state.output_resource(*output, &handle) {
+                        handle.output_enter(output);
+                    }
+                }
+                if handle.version() >= 3 {
+                    for workspace in &toplevel.workspaces {
+                        if let Some(workspace) = state.workspace_resource(*workspace, &handle) {
+                            handle.ext_workspace_enter(workspace);
+                        }
+                    }
+                }
+                foreign_toplevel.done();
+                state.toplevel_mut(id).cosmic_handles.push(handle);
+            }
+            zcosmic_toplevel_info_v1::Request::Stop => {
+                state.toplevel_infos.retain(|i| i != resource);
+                resource.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        resource: &zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1,
+        _data: &(),
+    ) {
+        state.toplevel_infos.retain(|i| i != resource);
+    }
+}
+
+impl Dispatch<zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1, ToplevelId> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1,
+        request: zcosmic_toplevel_handle_v1::Request,
+        _data: &ToplevelId,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zcosmic_toplevel_handle_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        resource: &zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1,
+        id: &ToplevelId,
+    ) {
+        if let Some(toplevel) = state.toplevels.iter_mut().find(|t| t.id == *id) {
+            toplevel.cosmic_handles.retain(|h| h != resource);
+        }
+    }
+}
+
+impl GlobalDispatch<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, ()> for Compositor {
+    fn bind(
+        state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        use zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1 as Capability;
+
+        let manager = data_init.init(resource, ());
+        // Every capability supported by the bound version
+        let capabilities = [
+            (Capability::Close, 1),
+            (Capability::Activate, 1),
+            (Capability::Maximize, 1),
+            (Capability::Minimize, 1),
+            (Capability::Fullscreen, 1),
+            (Capability::MoveToWorkspace, 2),
+            (Capability::Sticky, 3),
+            (Capability::MoveToExtWorkspace, 4),
+        ]
+        .into_iter()
+        .filter(|(_, since)| manager.version() >= *since)
+        .flat_map(|(capability, _)| u32::from(capability).to_ne_bytes())
+        .collect();
+        manager.capabilities(capabilities);
+        state.toplevel_managers.push(manager);
+    }
+}
+
+impl Dispatch<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, ()> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1,
+        request: zcosmic_toplevel_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        use zcosmic_toplevel_manager_v1::Request as R;
+
+        fn toplevel_id(
+            toplevel: &zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1,
+        ) -> ToplevelId {
+            *toplevel.data::<ToplevelId>().unwrap()
+        }
+
+        let request = match request {
+            R::Close { toplevel } => Request::CloseToplevel(toplevel_id(&toplevel)),
+            R::Activate { toplevel, seat: _ } => Request::ActivateToplevel(toplevel_id(&toplevel)),
+            R::SetMaximized { toplevel } => Request::SetMaximized(toplevel_id(&toplevel)),
+            R::UnsetMaximized { toplevel } => Request::UnsetMaximized(toplevel_id(&toplevel)),
+            R::SetMinimized { toplevel } => Request::SetMinimized(toplevel_id(&toplevel)),
+            R::UnsetMinimized { toplevel } => Request::UnsetMinimized(toplevel_id(&toplevel)),
+            R::SetFullscreen { toplevel, output } => Request::SetFullscreen(
+                toplevel_id(&toplevel),
+                output.and_then(|o| o.data::<OutputId>().copied()),
+            ),
+            R::UnsetFullscreen { toplevel } => Request::UnsetFullscreen(toplevel_id(&toplevel)),
+            R::SetRectangle {
+                toplevel,
+                surface: _,
+                x,
+                y,
+                width,
+                height,
+            } => Request::SetRectangle {
+                toplevel: toplevel_id(&toplevel),
+                rect: Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                },
+            },
+            R::SetSticky { toplevel } => Request::SetSticky(toplevel_id(&toplevel)),
+            R::UnsetSticky { toplevel } => Request::UnsetSticky(toplevel_id(&toplevel)),
+            R::MoveToExtWorkspace {
+                toplevel,
+                workspace,
+                output,
+            } => Request::MoveToExtWorkspace {
+                toplevel: toplevel_id(&toplevel),
+                workspace: *workspace.data::<WorkspaceId>().unwrap(),
+                output: *output.data::<OutputId>().unwrap(),
+            },
+            R::Destroy => return,
+            // `zcosmic_workspace_handle_v1` isn't supported, so this can't be sent
+            R::MoveToWorkspace { .. } => return,
+            _ => unreachable!(),
+        };
+        state.requests.push(request);
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        resource: &zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1,
+        _data: &(),
+    ) {
+        state.toplevel_managers.retain(|m| m != resource);
+    }
+}
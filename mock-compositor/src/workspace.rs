@@ -0,0 +1,646 @@
+use cosmic_protocols::workspace::v2::server::{
+    zcosmic_workspace_handle_v2, zcosmic_workspace_manager_v2,
+};
+use wayland_protocols::ext::workspace::v1::server::{
+    ext_workspace_group_handle_v1, ext_workspace_handle_v1, ext_workspace_manager_v1,
+};
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, backend::ClientId,
+};
+
+use crate::{CaptureSource, Compositor, OutputId, Request, for_client};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WorkspaceGroupId(u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WorkspaceId(u32);
+
+#[derive(Debug)]
+pub(crate) struct WorkspaceGroup {
+    id: WorkspaceGroupId,
+    capabilities: ext_workspace_group_handle_v1::GroupCapabilities,
+    outputs: Vec<OutputId>,
+    handles: Vec<ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Workspace {
+    id: WorkspaceId,
+    group: Option<WorkspaceGroupId>,
+    name: String,
+    coordinates: Vec<u32>,
+    state: ext_workspace_handle_v1::State,
+    capabilities: ext_workspace_handle_v1::WorkspaceCapabilities,
+    cosmic_capabilities: zcosmic_workspace_handle_v2::WorkspaceCapabilities,
+    tiling_state: zcosmic_workspace_handle_v2::TilingState,
+    cosmic_state: zcosmic_workspace_handle_v2::State,
+    capture_size: (i32, i32),
+    handles: Vec<ext_workspace_handle_v1::ExtWorkspaceHandleV1>,
+    // Extension objects, with the handle they were created for
+    cosmic_handles: Vec<(
+        ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+        zcosmic_workspace_handle_v2::ZcosmicWorkspaceHandleV2,
+    )>,
+}
+
+impl Workspace {
+    fn send_cosmic_state(&self, handle: &zcosmic_workspace_handle_v2::ZcosmicWorkspaceHandleV2) {
+        handle.capabilities(cosmic_capabilities(
+            self.cosmic_capabilities,
+            handle.version(),
+        ));
+        handle.tiling_state(self.tiling_state);
+        if handle.version() >= 2 {
+            handle.state(self.cosmic_state);
+        }
+    }
+}
+
+// Capabilities supported by the version of the handle
+fn cosmic_capabilities(
+    capabilities: zcosmic_workspace_handle_v2::WorkspaceCapabilities,
+    version: u32,
+) -> zcosmic_workspace_handle_v2::WorkspaceCapabilities {
+    use zcosmic_workspace_handle_v2::WorkspaceCapabilities;
+
+    if version >= 2 {
+        capabilities
+    } else {
+        capabilities & (WorkspaceCapabilities::Rename | WorkspaceCapabilities::SetTilingState)
+    }
+}
+
+impl Compositor {
+    /// Announce a new workspace group to every `ext_workspace_manager_v1`
+    pub fn new_workspace_group(
+        &mut self,
+        capabilities: ext_workspace_group_handle_v1::GroupCapabilities,
+    ) -> WorkspaceGroupId {
+        let id = WorkspaceGroupId(self.next_id());
+        self.workspace_groups.push(WorkspaceGroup {
+            id,
+            capabilities,
+            outputs: Vec::new(),
+            handles: Vec::new(),
+        });
+        for manager in self.workspace_managers.clone() {
+            self.send_workspace_group(&manager, id);
+        }
+        id
+    }
+
+    pub fn workspace_group_enter_output(&mut self, id: WorkspaceGroupId, output: OutputId) {
+        self.workspace_group_mut(id).outputs.push(output);
+        let group = self.workspace_group(id);
+        for handle in &group.handles {
+            if let Some(output) = self.output_resource(output, handle) {
+                handle.output_enter(output);
+            }
+        }
+    }
+
+    pub fn workspace_group_leave_output(&mut self, id: WorkspaceGroupId, output: OutputId) {
+        self.workspace_group_mut(id)
+            .outputs
+            .retain(|o| *o != output);
+        let group = self.workspace_group(id);
+        for handle in &group.handles {
+            if let Some(output) = self.output_resource(output, handle) {
+                handle.output_leave(output);
+            }
+        }
+    }
+
+    /// Send `removed` for the group. Its workspaces are moved out of it first.
+    pub fn remove_workspace_group(&mut self, id: WorkspaceGroupId) {
+        let workspaces = self
+            .workspaces
+            .iter()
+            .filter(|w| w.group == Some(id))
+            .map(|w| w.id)
+            .collect::<Vec<_>>();
+        for workspace in workspaces {
+            self.assign_workspace(workspace, None);
+        }
+        let Some(idx) = self.workspace_groups.iter().position(|g| g.id == id) else {
+            return;
+        };
+        let group = self.workspace_groups.remove(idx);
+        for handle in &group.handles {
+            handle.removed();
+        }
+    }
+
+    /// Announce a new workspace to every `ext_workspace_manager_v1`, and add it to `group`
+    pub fn new_workspace(&mut self, group: Option<WorkspaceGroupId>, name: &str) -> WorkspaceId {
+        let id = WorkspaceId(self.next_id());
+        self.workspaces.push(Workspace {
+            id,
+            group: None,
+            name: name.to_string(),
+            coordinates: Vec::new(),
+            state: ext_workspace_handle_v1::State::empty(),
+            capabilities: ext_workspace_handle_v1::WorkspaceCapabilities::all(),
+            cosmic_capabilities: zcosmic_workspace_handle_v2::WorkspaceCapabilities::all(),
+            tiling_state: zcosmic_workspace_handle_v2::TilingState::FloatingOnly,
+            cosmic_state: zcosmic_workspace_handle_v2::State::empty(),
+            capture_size: (64, 48),
+            handles: Vec::new(),
+            cosmic_handles: Vec::new(),
+        });
+        for manager in self.workspace_managers.clone() {
+            self.send_workspace(&manager, id);
+        }
+        if group.is_some() {
+            self.assign_workspace(id, group);
+        }
+        id
+    }
+
+    /// Move a workspace to another group, sending `workspace_leave` and `workspace_enter`
+    pub fn assign_workspace(&mut self, id: WorkspaceId, group: Option<WorkspaceGroupId>) {
+        let old_group = std::mem::replace(&mut self.workspace_mut(id).group, group);
+        let workspace = self.workspace(id);
+        if let Some(old_group) =
+            old_group.and_then(|g| self.workspace_groups.iter().find(|x| x.id == g))
+        {
+            for group_handle in &old_group.handles {
+                if let Some(handle) = for_client(&workspace.handles, group_handle) {
+                    group_handle.workspace_leave(handle);
+                }
+            }
+        }
+        if let Some(group) = group.and_then(|g| self.workspace_groups.iter().find(|x| x.id == g)) {
+            for group_handle in &group.handles {
+                if let Some(handle) = for_client(&workspace.handles, group_handle) {
+                    group_handle.workspace_enter(handle);
+                }
+            }
+        }
+    }
+
+    /// Send `removed` for the workspace, after removing it from its group, and stop capture
+    /// sessions of it
+    pub fn remove_workspace(&mut self, id: WorkspaceId) {
+        self.assign_workspace(id, None);
+        let Some(idx) = self.workspaces.iter().position(|w| w.id == id) else {
+            return;
+        };
+        let workspace = self.workspaces.remove(idx);
+        for handle in &workspace.handles {
+            handle.removed();
+        }
+        self.stop_sessions_of(CaptureSource::Workspace(id));
+    }
+
+    pub fn workspace_groups(&self) -> impl Iterator<Item = WorkspaceGroupId> + '_ {
+        self.workspace_groups.iter().map(|g| g.id)
+    }
+
+    pub fn workspaces(&self) -> impl Iterator<Item = WorkspaceId> + '_ {
+        self.workspaces.iter().map(|w| w.id)
+    }
+
+    pub fn set_workspace_name(&mut self, id: WorkspaceId, name: &str) {
+        let workspace = self.workspace_mut(id);
+        workspace.name = name.to_string();
+        for handle in &workspace.handles {
+            handle.name(name.to_string());
+        }
+    }
+
+    pub fn set_workspace_coordinates(&mut self, id: WorkspaceId, coordinates: &[u32]) {
+        let workspace = self.workspace_mut(id);
+        workspace.coordinates = coordinates.to_vec();
+        for handle in &workspace.handles {
+            handle.coordinates(coordinate_array(coordinates));
+        }
+    }
+
+    pub fn set_workspace_state(&mut self, id: WorkspaceId, state: ext_workspace_handle_v1::State) {
+        let workspace = self.workspace_mut(id);
+        workspace.state = state;
+        for handle in &workspace.handles {
+            handle.state(state);
+        }
+    }
+
+    pub fn set_workspace_capabilities(
+        &mut self,
+        id: WorkspaceId,
+        capabilities: ext_workspace_handle_v1::WorkspaceCapabilities,
+    ) {
+        let workspace = self.workspace_mut(id);
+        workspace.capabilities = capabilities;
+        for handle in &workspace.handles {
+            handle.capabilities(capabilities);
+        }
+    }
+
+    pub fn set_workspace_cosmic_capabilities(
+        &mut self,
+        id: WorkspaceId,
+        capabilities: zcosmic_workspace_handle_v2::WorkspaceCapabilities,
+    ) {
+        let workspace = self.workspace_mut(id);
+        workspace.cosmic_capabilities = capabilities;
+        for (_, handle) in &workspace.cosmic_handles {
+            handle.capabilities(cosmic_capabilities(capabilities, handle.version()));
+        }
+    }
+
+    pub fn set_workspace_tiling_state(
+        &mut self,
+        id: WorkspaceId,
+        state: zcosmic_workspace_handle_v2::TilingState,
+    ) {
+        let workspace = self.workspace_mut(id);
+        workspace.tiling_state = state;
+        for (_, handle) in &workspace.cosmic_handles {
+            handle.tiling_state(state);
+        }
+    }
+
+    pub fn set_workspace_cosmic_state(
+        &mut self,
+        id: WorkspaceId,
+        state: zcosmic_workspace_handle_v2::State,
+    ) {
+        let workspace = self.workspace_mut(id);
+        workspace.cosmic_state = state;
+        for (_, handle) in &workspace.cosmic_handles {
+            if handle.version() >= 2 {
+                handle.state(state);
+            }
+        }
+    }
+
+    /// Size of buffers for capture sessions of the workspace
+    pub fn set_workspace_capture_size(&mut self, id: WorkspaceId, size: (i32, i32)) {
+        self.workspace_mut(id).capture_size = size;
+    }
+
+    /// Send `ext_workspace_manager_v1::done`
+    pub fn workspace_done(&mut self) {
+        for manager in &self.workspace_managers {
+            manager.done();
+        }
+    }
+
+    /// Send `ext_workspace_manager_v1::finished`, after which managers get no more events
+    pub fn workspace_finished(&mut self) {
+        for manager in self.workspace_managers.drain(..) {
+            manager.finished();
+        }
+    }
+
+    pub(crate) fn workspace_capture_size(&self, id: WorkspaceId) -> Option<(i32, i32)> {
+        Some(self.workspaces.iter().find(|w| w.id == id)?.capture_size)
+    }
+
+    // `ext_workspace_handle_v1` of `client` for a workspace
+    pub(crate) fn workspace_resource(
+        &self,
+        id: WorkspaceId,
+        client: &impl Resource,
+    ) -> Option<&ext_workspace_handle_v1::ExtWorkspaceHandleV1> {
+        for_client(
+            &self.workspaces.iter().find(|w| w.id == id)?.handles,
+            client,
+        )
+    }
+
+    fn workspace_group(&self, id: WorkspaceGroupId) -> &WorkspaceGroup {
+        self.workspace_groups
+            .iter()
+            .find(|g| g.id == id)
+            .expect("No such workspace group")
+    }
+
+    fn workspace_group_mut(&mut self, id: WorkspaceGroupId) -> &mut WorkspaceGroup {
+        self.workspace_groups
+            .iter_mut()
+            .find(|g| g.id == id)
+            .expect("No such workspace group")
+    }
+
+    fn workspace(&self, id: WorkspaceId) -> &Workspace {
+        self.workspaces
+            .iter()
+            .find(|w| w.id == id)
+            .expect("No such workspace")
+    }
+
+    fn workspace_mut(&mut self, id: WorkspaceId) -> &mut Workspace {
+        self.workspaces
+            .iter_mut()
+            .find(|w| w.id == id)
+            .expect("No such workspace")
+    }
+
+    fn send_workspace_group(
+        &mut self,
+        manager: &ext_workspace_manager_v1::ExtWorkspaceManagerV1,
+        id: WorkspaceGroupId,
+    ) {
+        let Some(client) = manager.client() else {
+            return;
+        };
+        let Ok(handle) = client
+            .create_resource::<ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1, _, Self>(
+                &self.dh,
+                manager.version(),
+                id,
+            )
+        else {
+            return;
+        };
+        manager.workspace_group(&handle);
+        let group = self.workspace_group(id);
+        handle.capabilities(group.capabilities);
+        for output in &group.outputs {
+            if let Some(output) = self.output_resource(*output, &handle) {
+                handle.output_enter(output);
+            }
+        }
+        self.workspace_group_mut(id).handles.push(handle);
+    }
+
+    fn send_workspace(
+        &mut self,
+        manager: &ext_workspace_manager_v1::ExtWorkspaceManagerV1,
+        id: WorkspaceId,
+    ) {
+        let Some(client) = manager.client() else {
+            return;
+        };
+        let Ok(handle) = client
+            .create_resource::<ext_workspace_handle_v1::ExtWorkspaceHandleV1, _, Self>(
+                &self.dh,
+                manager.version(),
+                id,
+            )
+        else {
+            return;
+        };
+        manager.workspace(&handle);
+        let workspace = self.workspace(id);
+        handle.id(format!("mock-workspace-{}", id.0));
+        handle.name(workspace.name.clone());
+        handle.coordinates(coordinate_array(&workspace.coordinates));
+        handle.state(workspace.state);
+        handle.capabilities(workspace.capabilities);
+        if let Some(group) = workspace
+            .group
+            .and_then(|g| self.workspace_groups.iter().find(|x| x.id == g))
+            && let Some(group_handle) = for_client(&group.handles, &handle)
+        {
+            group_handle.workspace_enter(&handle);
+        }
+        self.workspace_mut(id).handles.push(handle);
+    }
+}
+
+fn coordinate_array(coordinates: &[u32]) -> Vec<u8> {
+    coordinates
+        .iter()
+        .flat_map(|coordinate| coordinate.to_ne_bytes())
+        .collect()
+}
+
+impl GlobalDispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, ()> for Compositor {
+    fn bind(
+        state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ext_workspace_manager_v1::ExtWorkspaceManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let manager = data_init.init(resource, ());
+        for id in state.workspace_groups().collect::<Vec<_>>() {
+            state.send_workspace_group(&manager, id);
+        }
+        for id in state.workspaces().collect::<Vec<_>>() {
+            state.send_workspace(&manager, id);
+        }
+        manager.done();
+        state.workspace_managers.push(manager);
+    }
+}
+
+impl Dispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, ()> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &ext_workspace_manager_v1::ExtWorkspaceManagerV1,
+        request: ext_workspace_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_workspace_manager_v1::Request::Commit => {
+                state.requests.push(Request::CommitWorkspaces);
+            }
+            ext_workspace_manager_v1::Request::Stop => {
+                state.workspace_managers.retain(|m| m != resource);
+                resource.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        resource: &ext_workspace_manager_v1::ExtWorkspaceManagerV1,
+        _data: &(),
+    ) {
+        state.workspace_managers.retain(|m| m != resource);
+    }
+}
+
+impl Dispatch<ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1, WorkspaceGroupId>
+    for Compositor
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1,
+        request: ext_workspace_group_handle_v1::Request,
+        id: &WorkspaceGroupId,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_workspace_group_handle_v1::Request::CreateWorkspace { workspace } => {
+                state.requests.push(Request::CreateWorkspace {
+                    group: *id,
+                    name: workspace,
+                });
+            }
+            ext_workspace_group_handle_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        resource: &ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1,
+        id: &WorkspaceGroupId,
+    ) {
+        if let Some(group) = state.workspace_groups.iter_mut().find(|g| g.id == *id) {
+            group.handles.retain(|h| h != resource);
+        }
+    }
+}
+
+impl Dispatch<ext_workspace_handle_v1::ExtWorkspaceHandleV1, WorkspaceId> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+        request: ext_workspace_handle_v1::Request,
+        id: &WorkspaceId,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let request = match request {
+            ext_workspace_handle_v1::Request::Activate => Request::ActivateWorkspace(*id),
+            ext_workspace_handle_v1::Request::Deactivate => Request::DeactivateWorkspace(*id),
+            ext_workspace_handle_v1::Request::Assign { workspace_group } => {
+                Request::AssignWorkspace {
+                    workspace: *id,
+                    group: *workspace_group.data::<WorkspaceGroupId>().unwrap(),
+                }
+            }
+            ext_workspace_handle_v1::Request::Remove => Request::RemoveWorkspace(*id),
+            ext_workspace_handle_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+        state.requests.push(request);
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        resource: &ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+        id: &WorkspaceId,
+    ) {
+        if let Some(workspace) = state.workspaces.iter_mut().find(|w| w.id == *id) {
+            workspace.handles.retain(|h| h != resource);
+            workspace.cosmic_handles.retain(|(h, _)| h != resource);
+        }
+    }
+}
+
+impl GlobalDispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, ()> for Compositor {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2, ()> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2,
+        request: zcosmic_workspace_manager_v2::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zcosmic_workspace_manager_v2::Request::GetCosmicWorkspace {
+                cosmic_workspace,
+                workspace,
+            } => {
+                let id = *workspace.data::<WorkspaceId>().unwrap();
+                let cosmic_workspace = data_init.init(cosmic_workspace, id);
+                // Workspace may already be removed
+                let Some(data) = state.workspaces.iter_mut().find(|w| w.id == id) else {
+                    return;
+                };
+                if data.cosmic_handles.iter().any(|(h, _)| *h == workspace) {
+                    resource.post_error(
+                        zcosmic_workspace_manager_v2::Error::WorkspaceExists,
+                        "workspace already has a zcosmic_workspace_handle_v2".to_string(),
+                    );
+                    return;
+                }
+                data.send_cosmic_state(&cosmic_workspace);
+                data.cosmic_handles
+                    .push((workspace, cosmic_workspace.clone()));
+                if let Some(manager) = for_client(&state.workspace_managers, &cosmic_workspace) {
+                    manager.done();
+                }
+            }
+            zcosmic_workspace_manager_v2::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<zcosmic_workspace_handle_v2::ZcosmicWorkspaceHandleV2, WorkspaceId> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &zcosmic_workspace_handle_v2::ZcosmicWorkspaceHandleV2,
+        request: zcosmic_workspace_handle_v2::Request,
+        id: &WorkspaceId,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let workspace = *id;
+        let request = match request {
+            zcosmic_workspace_handle_v2::Request::Rename { name } => {
+                Request::RenameWorkspace { workspace, name }
+            }
+            zcosmic_workspace_handle_v2::Request::SetTilingState { state } => {
+                Request::SetTilingState { workspace, state }
+            }
+            zcosmic_workspace_handle_v2::Request::MoveBefore {
+                other_workspace,
+                axis,
+            } => Request::MoveWorkspaceBefore {
+                workspace,
+                other: *other_workspace.data::<WorkspaceId>().unwrap(),
+                axis,
+            },
+            zcosmic_workspace_handle_v2::Request::MoveAfter {
+                other_workspace,
+                axis,
+            } => Request::MoveWorkspaceAfter {
+                workspace,
+                other: *other_workspace.data::<WorkspaceId>().unwrap(),
+                axis,
+            },
+            zcosmic_workspace_handle_v2::Request::Pin => Request::PinWorkspace(workspace),
+            zcosmic_workspace_handle_v2::Request::Unpin => Request::UnpinWorkspace(workspace),
+            zcosmic_workspace_handle_v2::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+        state.requests.push(request);
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        resource: &zcosmic_workspace_handle_v2::ZcosmicWorkspaceHandleV2,
+        id: &WorkspaceId,
+    ) {
+        if let Some(workspace) = state.workspaces.iter_mut().find(|w| w.id == *id) {
+            workspace.cosmic_handles.retain(|(_, h)| h != resource);
+        }
+    }
+}
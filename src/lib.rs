@@ -133,3 +133,15 @@ pub mod workspace {
         );
     }
 }
+
+#[cfg(feature = "server")]
+pub mod server;
+
+/// Crates used by the `delegate_cosmic_*` macros
+#[cfg(feature = "delegate-macros")]
+#[doc(hidden)]
+pub mod reexports {
+    pub use wayland_protocols;
+    pub use wayland_protocols_wlr;
+    pub use wayland_server;
+}
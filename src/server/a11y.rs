@@ -0,0 +1,271 @@
+//! Helper for `cosmic_a11y_manager_v1`.
+//!
+//! The compositor sets its accessibility settings with [`A11yState::set_settings`], which
+//! broadcasts changes to every bound client, with the events of the version it bound. Requests
+//! to change them are passed to the [`A11yHandler`], including those of the deprecated
+//! `set_screen_filter`.
+//!
+//! ```ignore
+//! use cosmic_protocols::{
+//!     a11y::v1::server::cosmic_a11y_manager_v1,
+//!     server::{GlobalFilter, a11y::A11yState},
+//! };
+//!
+//! wayland_server::delegate_global_dispatch!(State: [cosmic_a11y_manager_v1::CosmicA11yManagerV1: GlobalFilter] => A11yState);
+//! wayland_server::delegate_dispatch!(State: [cosmic_a11y_manager_v1::CosmicA11yManagerV1: ()] => A11yState);
+//! ```
+
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+    backend::{ClientId, GlobalId},
+};
+
+use super::GlobalFilter;
+use crate::a11y::v1::server::cosmic_a11y_manager_v1::{
+    self, ActiveState, CosmicA11yManagerV1, Filter,
+};
+
+/// Accessibility settings of the compositor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct A11ySettings {
+    /// Whether the screen magnifier is enabled
+    pub magnifier: bool,
+    /// Whether screen colors are inverted
+    pub screen_inverted: bool,
+    /// Selected screen filter; `Filter::Disabled` is advertised as `Filter::Unknown`
+    pub screen_filter: Filter,
+    /// Whether the selected screen filter is active
+    pub screen_filter_active: bool,
+}
+
+impl Default for A11ySettings {
+    fn default() -> Self {
+        Self {
+            magnifier: false,
+            screen_inverted: false,
+            screen_filter: Filter::Unknown,
+            screen_filter_active: false,
+        }
+    }
+}
+
+/// Handler trait for [`A11yState`]
+///
+/// Clients only update their UI on the events broadcast after the compositor calls
+/// [`A11yState::set_settings`], so requests don't need to be honored.
+pub trait A11yHandler {
+    /// [`A11yState`] of the compositor
+    fn a11y_state(&mut self) -> &mut A11yState;
+
+    /// Client requested the screen magnifier to be enabled or disabled
+    fn set_magnifier(&mut self, enabled: bool);
+
+    /// Client requested a change of screen filtering.
+    ///
+    /// `filter` is `None` if the selected filter should be kept, and `active` is `None` if
+    /// whether the filter is active should be kept.
+    fn set_screen_filter(&mut self, inverted: bool, filter: Option<Filter>, active: Option<bool>);
+}
+
+/// State of the `cosmic_a11y_manager_v1` global
+#[derive(Debug)]
+pub struct A11yState {
+    global: GlobalId,
+    settings: A11ySettings,
+    managers: Vec<CosmicA11yManagerV1>,
+}
+
+impl A11yState {
+    /// Create the `cosmic_a11y_manager_v1` global with the given version, advertised to
+    /// clients for which `client_filter` returns `true`.
+    ///
+    /// # Panics
+    ///
+    /// If `version` isn't between 1 and 3.
+    pub fn new<D, F>(dh: &DisplayHandle, version: u32, client_filter: F) -> Self
+    where
+        D: GlobalDispatch<CosmicA11yManagerV1, GlobalFilter> + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        assert!(
+            (1..=3).contains(&version),
+            "Unsupported cosmic_a11y_manager_v1 version {version}"
+        );
+        let global = dh
+            .create_global::<D, CosmicA11yManagerV1, _>(version, GlobalFilter::new(client_filter));
+        Self {
+            global,
+            settings: A11ySettings::default(),
+            managers: Vec::new(),
+        }
+    }
+
+    /// Id of the `cosmic_a11y_manager_v1` global
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Current accessibility settings
+    pub fn settings(&self) -> A11ySettings {
+        self.settings
+    }
+
+    /// Change the accessibility settings, sending changes to every client
+    pub fn set_settings(&mut self, settings: A11ySettings) {
+        let old = std::mem::replace(&mut self.settings, settings);
+        for manager in &self.managers {
+            send_settings(manager, Some(&old), &settings);
+        }
+    }
+}
+
+fn active_state(active: bool) -> ActiveState {
+    if active {
+        ActiveState::Enabled
+    } else {
+        ActiveState::Disabled
+    }
+}
+
+// Send settings that differ between `old` and `new`, or every setting if `old` is `None`
+fn send_settings(manager: &CosmicA11yManagerV1, old: Option<&A11ySettings>, new: &A11ySettings) {
+    if old.is_none_or(|old| old.magnifier != new.magnifier) {
+        manager.magnifier(active_state(new.magnifier));
+    }
+    let version = manager.version();
+    if version >= cosmic_a11y_manager_v1::EVT_SCREEN_FILTER2_SINCE {
+        if old.is_none_or(|old| {
+            (
+                old.screen_inverted,
+                old.screen_filter,
+                old.screen_filter_active,
+            ) != (
+                new.screen_inverted,
+                new.screen_filter,
+                new.screen_filter_active,
+            )
+        }) {
+            let filter = match new.screen_filter {
+                Filter::Disabled => Filter::Unknown,
+                filter => filter,
+            };
+            manager.screen_filter2(
+                active_state(new.screen_inverted),
+                filter,
+                active_state(new.screen_filter_active),
+            );
+        }
+    } else if version >= cosmic_a11y_manager_v1::EVT_SCREEN_FILTER_SINCE {
+        // Before version 3, an inactive filter is sent as `disabled`
+        let filter = |settings: &A11ySettings| {
+            if settings.screen_filter_active {
+                settings.screen_filter
+            } else {
+                Filter::Disabled
+            }
+        };
+        if old.is_none_or(|old| {
+            (old.screen_inverted, filter(old)) != (new.screen_inverted, filter(new))
+        }) {
+            manager.screen_filter(active_state(new.screen_inverted), filter(new));
+        }
+    }
+}
+
+impl<D> GlobalDispatch<CosmicA11yManagerV1, GlobalFilter, D> for A11yState
+where
+    D: GlobalDispatch<CosmicA11yManagerV1, GlobalFilter>
+        + Dispatch<CosmicA11yManagerV1, ()>
+        + A11yHandler
+        + 'static,
+{
+    fn bind(
+        state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<CosmicA11yManagerV1>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+        let a11y_state = state.a11y_state();
+        send_settings(&manager, None, &a11y_state.settings);
+        a11y_state.managers.push(manager);
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<CosmicA11yManagerV1, (), D> for A11yState
+where
+    D: Dispatch<CosmicA11yManagerV1, ()> + A11yHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &CosmicA11yManagerV1,
+        request: cosmic_a11y_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            cosmic_a11y_manager_v1::Request::SetMagnifier {
+                active: WEnum::Value(active),
+            } => {
+                state.set_magnifier(active == ActiveState::Enabled);
+            }
+            cosmic_a11y_manager_v1::Request::SetScreenFilter {
+                inverted: WEnum::Value(inverted),
+                filter,
+            } => {
+                if resource.version() >= cosmic_a11y_manager_v1::REQ_SET_SCREEN_FILTER2_SINCE {
+                    resource.post_error(
+                        cosmic_a11y_manager_v1::Error::Deprecated,
+                        "set_screen_filter is deprecated, use set_screen_filter2",
+                    );
+                    return;
+                }
+                let (filter, active) = match filter {
+                    WEnum::Value(Filter::Disabled) => (None, Some(false)),
+                    WEnum::Value(Filter::Unknown) | WEnum::Unknown(_) => (None, None),
+                    WEnum::Value(filter) => (Some(filter), Some(true)),
+                };
+                state.set_screen_filter(inverted == ActiveState::Enabled, filter, active);
+            }
+            cosmic_a11y_manager_v1::Request::SetScreenFilter2 {
+                inverted: WEnum::Value(inverted),
+                filter,
+                filter_state: WEnum::Value(filter_state),
+            } => {
+                let filter = match filter {
+                    WEnum::Value(Filter::Disabled) => {
+                        resource.post_error(
+                            cosmic_a11y_manager_v1::Error::Deprecated,
+                            "the disabled filter is deprecated",
+                        );
+                        return;
+                    }
+                    WEnum::Value(Filter::Unknown) | WEnum::Unknown(_) => None,
+                    WEnum::Value(filter) => Some(filter),
+                };
+                state.set_screen_filter(
+                    inverted == ActiveState::Enabled,
+                    filter,
+                    Some(filter_state == ActiveState::Enabled),
+                );
+            }
+            // Unknown active states are ignored
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &CosmicA11yManagerV1, _data: &()) {
+        state
+            .a11y_state()
+            .managers
+            .retain(|manager| manager != resource);
+    }
+}
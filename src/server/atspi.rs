@@ -0,0 +1,221 @@
+//! Helper for `cosmic_atspi_manager_v1`.
+//!
+//! Grabs are tracked per client, and removed when the client destroys its manager or
+//! disconnects. The compositor checks key input against [`AtspiState::key_grabs`] and
+//! [`AtspiState::keyboard_grabbed`], and is told when they change with
+//! [`AtspiHandler::grabs_changed`].
+//!
+//! ```ignore
+//! use cosmic_protocols::{
+//!     atspi::v1::server::cosmic_atspi_manager_v1,
+//!     server::{GlobalFilter, atspi::AtspiState},
+//! };
+//!
+//! wayland_server::delegate_global_dispatch!(State: [cosmic_atspi_manager_v1::CosmicAtspiManagerV1: GlobalFilter] => AtspiState);
+//! wayland_server::delegate_dispatch!(State: [cosmic_atspi_manager_v1::CosmicAtspiManagerV1: ()] => AtspiState);
+//! ```
+
+use std::os::fd::{AsFd, OwnedFd};
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New,
+    backend::{ClientId, GlobalId},
+};
+
+use super::GlobalFilter;
+use crate::atspi::v1::server::cosmic_atspi_manager_v1::{self, CosmicAtspiManagerV1};
+
+/// Grabbed key combination
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyGrab {
+    /// Modifiers
+    pub mods: u32,
+    /// Keycodes of virtual modifiers
+    pub virtual_mods: Vec<u32>,
+    /// Keycode
+    pub key: u32,
+}
+
+/// Handler trait for [`AtspiState`]
+pub trait AtspiHandler {
+    /// [`AtspiState`] of the compositor
+    fn atspi_state(&mut self) -> &mut AtspiState;
+
+    /// File descriptor of an EIS socket for `client` to monitor keyboard input, sent when it
+    /// binds the global
+    fn key_events_eis(&mut self, client: &Client) -> Option<OwnedFd>;
+
+    /// Key grabs or keyboard grabs changed
+    fn grabs_changed(&mut self) {}
+}
+
+#[derive(Debug)]
+struct Manager {
+    manager: CosmicAtspiManagerV1,
+    key_grabs: Vec<KeyGrab>,
+    keyboard_grabbed: bool,
+}
+
+/// State of the `cosmic_atspi_manager_v1` global
+#[derive(Debug)]
+pub struct AtspiState {
+    global: GlobalId,
+    managers: Vec<Manager>,
+}
+
+impl AtspiState {
+    /// Create the `cosmic_atspi_manager_v1` global, advertised to clients for which
+    /// `client_filter` returns `true`.
+    pub fn new<D, F>(dh: &DisplayHandle, client_filter: F) -> Self
+    where
+        D: GlobalDispatch<CosmicAtspiManagerV1, GlobalFilter> + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        let global =
+            dh.create_global::<D, CosmicAtspiManagerV1, _>(1, GlobalFilter::new(client_filter));
+        Self {
+            global,
+            managers: Vec::new(),
+        }
+    }
+
+    /// Id of the `cosmic_atspi_manager_v1` global
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Key combinations grabbed by any client
+    pub fn key_grabs(&self) -> impl Iterator<Item = &KeyGrab> {
+        self.managers.iter().flat_map(|manager| &manager.key_grabs)
+    }
+
+    /// Whether any client grabbed the keyboard
+    pub fn keyboard_grabbed(&self) -> bool {
+        self.managers.iter().any(|manager| manager.keyboard_grabbed)
+    }
+
+    // Remove a manager, returning whether it had any grabs
+    fn remove_manager(&mut self, resource: &CosmicAtspiManagerV1) -> bool {
+        let Some(idx) = self
+            .managers
+            .iter()
+            .position(|manager| manager.manager == *resource)
+        else {
+            return false;
+        };
+        let manager = self.managers.remove(idx);
+        !manager.key_grabs.is_empty() || manager.keyboard_grabbed
+    }
+}
+
+impl<D> GlobalDispatch<CosmicAtspiManagerV1, GlobalFilter, D> for AtspiState
+where
+    D: GlobalDispatch<CosmicAtspiManagerV1, GlobalFilter>
+        + Dispatch<CosmicAtspiManagerV1, ()>
+        + AtspiHandler
+        + 'static,
+{
+    fn bind(
+        state: &mut D,
+        _dh: &DisplayHandle,
+        client: &Client,
+        resource: New<CosmicAtspiManagerV1>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+        if let Some(fd) = state.key_events_eis(client) {
+            manager.key_events_eis(fd.as_fd());
+        }
+        state.atspi_state().managers.push(Manager {
+            manager,
+            key_grabs: Vec::new(),
+            keyboard_grabbed: false,
+        });
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<CosmicAtspiManagerV1, (), D> for AtspiState
+where
+    D: Dispatch<CosmicAtspiManagerV1, ()> + AtspiHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &CosmicAtspiManagerV1,
+        request: cosmic_atspi_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let Some(manager) = state
+            .atspi_state()
+            .managers
+            .iter_mut()
+            .find(|manager| manager.manager == *resource)
+        else {
+            return;
+        };
+        let changed = match request {
+            cosmic_atspi_manager_v1::Request::AddKeyGrab {
+                mods,
+                virtual_mods,
+                key,
+            } => {
+                let grab = KeyGrab {
+                    mods,
+                    virtual_mods: keycodes(&virtual_mods),
+                    key,
+                };
+                if manager.key_grabs.contains(&grab) {
+                    false
+                } else {
+                    manager.key_grabs.push(grab);
+                    true
+                }
+            }
+            cosmic_atspi_manager_v1::Request::RemoveKeyGrab {
+                mods,
+                virtual_mods,
+                key,
+            } => {
+                let grab = KeyGrab {
+                    mods,
+                    virtual_mods: keycodes(&virtual_mods),
+                    key,
+                };
+                let len = manager.key_grabs.len();
+                manager.key_grabs.retain(|g| *g != grab);
+                manager.key_grabs.len() != len
+            }
+            cosmic_atspi_manager_v1::Request::GrabKeyboard => {
+                !std::mem::replace(&mut manager.keyboard_grabbed, true)
+            }
+            cosmic_atspi_manager_v1::Request::UngrabKeyboard => {
+                std::mem::replace(&mut manager.keyboard_grabbed, false)
+            }
+            // Grabs are removed when the object is destroyed
+            cosmic_atspi_manager_v1::Request::Destroy => false,
+        };
+        if changed {
+            state.grabs_changed();
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &CosmicAtspiManagerV1, _data: &()) {
+        if state.atspi_state().remove_manager(resource) {
+            state.grabs_changed();
+        }
+    }
+}
+
+// Keycodes of an array of 32-bit unsigned integers in native endianness
+fn keycodes(array: &[u8]) -> Vec<u32> {
+    array
+        .chunks_exact(4)
+        .map(|chunk| u32::from_ne_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
@@ -0,0 +1,284 @@
+//! Helper for `cosmic_corner_radius_manager_v1`.
+//!
+//! Corner radii are double-buffered: the compositor calls [`CornerRadiusState::commit`] when
+//! the `wl_surface` of a toplevel is committed, which applies the pending radius and checks it
+//! against the window geometry.
+//!
+//! ```ignore
+//! use cosmic_protocols::{
+//!     corner_radius::v1::server::{
+//!         cosmic_corner_radius_manager_v1, cosmic_corner_radius_toplevel_v1,
+//!     },
+//!     server::{GlobalFilter, corner_radius::CornerRadiusState},
+//! };
+//! use wayland_protocols::xdg::shell::server::xdg_toplevel;
+//!
+//! wayland_server::delegate_global_dispatch!(State: [cosmic_corner_radius_manager_v1::CosmicCornerRadiusManagerV1: GlobalFilter] => CornerRadiusState);
+//! wayland_server::delegate_dispatch!(State: [cosmic_corner_radius_manager_v1::CosmicCornerRadiusManagerV1: ()] => CornerRadiusState);
+//! wayland_server::delegate_dispatch!(State: [cosmic_corner_radius_toplevel_v1::CosmicCornerRadiusToplevelV1: xdg_toplevel::XdgToplevel] => CornerRadiusState);
+//! ```
+
+use wayland_protocols::xdg::shell::server::xdg_toplevel::XdgToplevel;
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    backend::{ClientId, GlobalId},
+};
+
+use super::GlobalFilter;
+use crate::corner_radius::v1::server::{
+    cosmic_corner_radius_manager_v1::{self, CosmicCornerRadiusManagerV1},
+    cosmic_corner_radius_toplevel_v1::{self, CosmicCornerRadiusToplevelV1},
+};
+
+/// Corner radius values of a toplevel, in logical coordinates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CornerRadius {
+    /// Radius of the top-left corner
+    pub top_left: u32,
+    /// Radius of the top-right corner
+    pub top_right: u32,
+    /// Radius of the bottom-right corner
+    pub bottom_right: u32,
+    /// Radius of the bottom-left corner
+    pub bottom_left: u32,
+}
+
+impl CornerRadius {
+    fn max(&self) -> u32 {
+        self.top_left
+            .max(self.top_right)
+            .max(self.bottom_right)
+            .max(self.bottom_left)
+    }
+}
+
+/// Handler trait for [`CornerRadiusState`]
+pub trait CornerRadiusHandler {
+    /// [`CornerRadiusState`] of the compositor
+    fn corner_radius_state(&mut self) -> &mut CornerRadiusState;
+}
+
+#[derive(Debug)]
+struct ToplevelRadius {
+    toplevel: XdgToplevel,
+    // `None` once destroyed, until the radius is unset on the next commit
+    object: Option<CosmicCornerRadiusToplevelV1>,
+    pending: Option<Option<CornerRadius>>,
+    current: Option<CornerRadius>,
+}
+
+/// State of the `cosmic_corner_radius_manager_v1` global
+#[derive(Debug)]
+pub struct CornerRadiusState {
+    global: GlobalId,
+    toplevels: Vec<ToplevelRadius>,
+}
+
+impl CornerRadiusState {
+    /// Create the `cosmic_corner_radius_manager_v1` global, advertised to clients for which
+    /// `client_filter` returns `true`.
+    pub fn new<D, F>(dh: &DisplayHandle, client_filter: F) -> Self
+    where
+        D: GlobalDispatch<CosmicCornerRadiusManagerV1, GlobalFilter> + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        let global = dh.create_global::<D, CosmicCornerRadiusManagerV1, _>(
+            1,
+            GlobalFilter::new(client_filter),
+        );
+        Self {
+            global,
+            toplevels: Vec::new(),
+        }
+    }
+
+    /// Id of the `cosmic_corner_radius_manager_v1` global
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Apply the pending corner radius of a toplevel, on commit of its surface, returning the
+    /// current corner radius.
+    ///
+    /// `geometry_size` is the size of the window geometry after the commit. If the radius
+    /// exceeds a quarter of either dimension, the `radius_too_large` protocol error is raised.
+    pub fn commit(
+        &mut self,
+        toplevel: &XdgToplevel,
+        geometry_size: (i32, i32),
+    ) -> Option<CornerRadius> {
+        self.toplevels.retain(|t| t.toplevel.is_alive());
+        let idx = self
+            .toplevels
+            .iter()
+            .position(|t| t.toplevel == *toplevel)?;
+        let entry = &mut self.toplevels[idx];
+        if let Some(pending) = entry.pending.take() {
+            entry.current = pending;
+        }
+        let Some(object) = &entry.object else {
+            self.toplevels.remove(idx);
+            return None;
+        };
+        let current = entry.current?;
+        let max = (geometry_size.0.min(geometry_size.1).max(0) / 4) as u32;
+        if current.max() > max {
+            object.post_error(
+                cosmic_corner_radius_toplevel_v1::Error::RadiusTooLarge,
+                format!(
+                    "corner radius {} exceeds a quarter of the window geometry {}x{}",
+                    current.max(),
+                    geometry_size.0,
+                    geometry_size.1
+                ),
+            );
+            entry.current = None;
+        }
+        entry.current
+    }
+
+    /// Current corner radius of a toplevel
+    pub fn corner_radius(&self, toplevel: &XdgToplevel) -> Option<CornerRadius> {
+        self.toplevels
+            .iter()
+            .find(|t| t.toplevel == *toplevel)?
+            .current
+    }
+}
+
+impl<D> GlobalDispatch<CosmicCornerRadiusManagerV1, GlobalFilter, D> for CornerRadiusState
+where
+    D: GlobalDispatch<CosmicCornerRadiusManagerV1, GlobalFilter>
+        + Dispatch<CosmicCornerRadiusManagerV1, ()>
+        + CornerRadiusHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<CosmicCornerRadiusManagerV1>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<CosmicCornerRadiusManagerV1, (), D> for CornerRadiusState
+where
+    D: Dispatch<CosmicCornerRadiusManagerV1, ()>
+        + Dispatch<CosmicCornerRadiusToplevelV1, XdgToplevel>
+        + CornerRadiusHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &CosmicCornerRadiusManagerV1,
+        request: cosmic_corner_radius_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            cosmic_corner_radius_manager_v1::Request::GetCornerRadius { id, toplevel } => {
+                let object = data_init.init(id, toplevel.clone());
+                let corner_radius_state = state.corner_radius_state();
+                corner_radius_state
+                    .toplevels
+                    .retain(|t| t.toplevel.is_alive());
+                match corner_radius_state
+                    .toplevels
+                    .iter_mut()
+                    .find(|t| t.toplevel == toplevel)
+                {
+                    Some(ToplevelRadius {
+                        object: Some(_), ..
+                    }) => {
+                        resource.post_error(
+                            cosmic_corner_radius_manager_v1::Error::CornerRadiusExists,
+                            "toplevel already has a corner radius object",
+                        );
+                    }
+                    // Destroyed object whose radius wasn't unset yet
+                    Some(entry) => entry.object = Some(object),
+                    None => corner_radius_state.toplevels.push(ToplevelRadius {
+                        toplevel,
+                        object: Some(object),
+                        pending: None,
+                        current: None,
+                    }),
+                }
+            }
+            cosmic_corner_radius_manager_v1::Request::Destroy => {}
+        }
+    }
+}
+
+impl<D> Dispatch<CosmicCornerRadiusToplevelV1, XdgToplevel, D> for CornerRadiusState
+where
+    D: Dispatch<CosmicCornerRadiusToplevelV1, XdgToplevel> + CornerRadiusHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &CosmicCornerRadiusToplevelV1,
+        request: cosmic_corner_radius_toplevel_v1::Request,
+        toplevel: &XdgToplevel,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let radius = match request {
+            cosmic_corner_radius_toplevel_v1::Request::SetRadius {
+                top_left,
+                top_right,
+                bottom_right,
+                bottom_left,
+            } => Some(CornerRadius {
+                top_left,
+                top_right,
+                bottom_right,
+                bottom_left,
+            }),
+            cosmic_corner_radius_toplevel_v1::Request::UnsetRadius => None,
+            cosmic_corner_radius_toplevel_v1::Request::Destroy => return,
+        };
+        if !toplevel.is_alive() {
+            resource.post_error(
+                cosmic_corner_radius_toplevel_v1::Error::ToplevelDestroyed,
+                "toplevel was destroyed",
+            );
+            return;
+        }
+        if let Some(entry) = state
+            .corner_radius_state()
+            .toplevels
+            .iter_mut()
+            .find(|t| t.object.as_ref() == Some(resource))
+        {
+            entry.pending = Some(radius);
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &CosmicCornerRadiusToplevelV1,
+        _toplevel: &XdgToplevel,
+    ) {
+        if let Some(entry) = state
+            .corner_radius_state()
+            .toplevels
+            .iter_mut()
+            .find(|t| t.object.as_ref() == Some(resource))
+        {
+            entry.object = None;
+            entry.pending = Some(None);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Macros delegating the interfaces of each helper to the compositor state, in the style of
+//! smithay's `delegate_*` macros.
+//!
+//! They only depend on `wayland-server`, through a hidden `reexports` module, so they work with the
+//! `wayland-server` re-exported by smithay as long as both use the same version.
+//!
+//! ```ignore
+//! cosmic_protocols::delegate_cosmic_foreign_toplevel_list!(State);
+//! cosmic_protocols::delegate_cosmic_toplevel_info!(State);
+//! cosmic_protocols::delegate_cosmic_toplevel_management!(State);
+//! cosmic_protocols::delegate_cosmic_workspace!(State);
+//! ```
+
+/// Delegate the `ext_foreign_toplevel_list_v1` interfaces of
+/// [`ToplevelInfoState`](crate::server::toplevel_info::ToplevelInfoState) to a type.
+///
+/// `ToplevelInfoState` creates the `ext_foreign_toplevel_list_v1` global itself and needs its
+/// own toplevel handles, so this is used instead of another implementation of that protocol,
+/// like smithay's `delegate_foreign_toplevel_list!`.
+#[macro_export]
+macro_rules! delegate_cosmic_foreign_toplevel_list {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server::ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1: $crate::server::GlobalFilter
+        ] => $crate::server::toplevel_info::ToplevelInfoState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server::ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1: ()
+        ] => $crate::server::toplevel_info::ToplevelInfoState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server::ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1: $crate::server::toplevel_info::ToplevelId
+        ] => $crate::server::toplevel_info::ToplevelInfoState<$ty>);
+    };
+}
+
+/// Delegate the `zcosmic_toplevel_info_v1` interfaces of
+/// [`ToplevelInfoState`](crate::server::toplevel_info::ToplevelInfoState) to a type.
+///
+/// Has to be combined with [`delegate_cosmic_foreign_toplevel_list!`] for the
+/// `ext_foreign_toplevel_list_v1` interfaces.
+#[macro_export]
+macro_rules! delegate_cosmic_toplevel_info {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::toplevel_info::v1::server::zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1: $crate::server::GlobalFilter
+        ] => $crate::server::toplevel_info::ToplevelInfoState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::toplevel_info::v1::server::zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1: ()
+        ] => $crate::server::toplevel_info::ToplevelInfoState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::toplevel_info::v1::server::zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1: $crate::server::toplevel_info::ToplevelId
+        ] => $crate::server::toplevel_info::ToplevelInfoState<$ty>);
+    };
+}
+
+/// Delegate the interfaces of
+/// [`ToplevelManagementState`](crate::server::toplevel_management::ToplevelManagementState) to
+/// a type.
+#[macro_export]
+macro_rules! delegate_cosmic_toplevel_management {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::toplevel_management::v1::server::zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1: $crate::server::GlobalFilter
+        ] => $crate::server::toplevel_management::ToplevelManagementState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::toplevel_management::v1::server::zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1: ()
+        ] => $crate::server::toplevel_management::ToplevelManagementState);
+    };
+}
+
+/// Delegate the interfaces of [`CosmicWorkspaceState`](crate::server::workspace::CosmicWorkspaceState)
+/// to a type.
+///
+/// The `ext_workspace_manager_v1` global it extends isn't included, and has to be implemented
+/// by the compositor itself.
+#[macro_export]
+macro_rules! delegate_cosmic_workspace {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::workspace::v2::server::zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2: $crate::server::GlobalFilter
+        ] => $crate::server::workspace::CosmicWorkspaceState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::workspace::v2::server::zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2: ()
+        ] => $crate::server::workspace::CosmicWorkspaceState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::workspace::v2::server::zcosmic_workspace_handle_v2::ZcosmicWorkspaceHandleV2: $crate::reexports::wayland_protocols::ext::workspace::v1::server::ext_workspace_handle_v1::ExtWorkspaceHandleV1
+        ] => $crate::server::workspace::CosmicWorkspaceState<$ty>);
+    };
+}
+
+/// Delegate the interfaces of
+/// [`OutputConfigurationState`](crate::server::output_management::OutputConfigurationState) to
+/// a type.
+///
+/// This includes `zwlr_output_manager_v1`, which the cosmic extension builds on.
+#[macro_export]
+macro_rules! delegate_cosmic_output_management {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: $crate::server::GlobalFilter
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::output_management::v1::server::zcosmic_output_manager_v1::ZcosmicOutputManagerV1: $crate::server::GlobalFilter
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: ()
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_head_v1::ZwlrOutputHeadV1: $crate::server::output_management::OutputId
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_mode_v1::ZwlrOutputModeV1: $crate::server::output_management::ModeData
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_v1::ZwlrOutputConfigurationV1: $crate::server::output_management::ConfigurationData
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1: $crate::server::output_management::ConfigurationHeadData
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::output_management::v1::server::zcosmic_output_manager_v1::ZcosmicOutputManagerV1: ()
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::output_management::v1::server::zcosmic_output_head_v1::ZcosmicOutputHeadV1: $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_head_v1::ZwlrOutputHeadV1
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::output_management::v1::server::zcosmic_output_configuration_v1::ZcosmicOutputConfigurationV1: $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_v1::ZwlrOutputConfigurationV1
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::output_management::v1::server::zcosmic_output_configuration_head_v1::ZcosmicOutputConfigurationHeadV1: $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1
+        ] => $crate::server::output_management::OutputConfigurationState<$ty>);
+    };
+}
+
+/// Delegate the interfaces of [`A11yState`](crate::server::a11y::A11yState) to a type.
+#[macro_export]
+macro_rules! delegate_cosmic_a11y {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::a11y::v1::server::cosmic_a11y_manager_v1::CosmicA11yManagerV1: $crate::server::GlobalFilter
+        ] => $crate::server::a11y::A11yState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::a11y::v1::server::cosmic_a11y_manager_v1::CosmicA11yManagerV1: ()
+        ] => $crate::server::a11y::A11yState);
+    };
+}
+
+/// Delegate the interfaces of [`AtspiState`](crate::server::atspi::AtspiState) to a type.
+#[macro_export]
+macro_rules! delegate_cosmic_atspi {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::atspi::v1::server::cosmic_atspi_manager_v1::CosmicAtspiManagerV1: $crate::server::GlobalFilter
+        ] => $crate::server::atspi::AtspiState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::atspi::v1::server::cosmic_atspi_manager_v1::CosmicAtspiManagerV1: ()
+        ] => $crate::server::atspi::AtspiState);
+    };
+}
+
+/// Delegate the interfaces of [`CornerRadiusState`](crate::server::corner_radius::CornerRadiusState)
+/// to a type.
+#[macro_export]
+macro_rules! delegate_cosmic_corner_radius {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::corner_radius::v1::server::cosmic_corner_radius_manager_v1::CosmicCornerRadiusManagerV1: $crate::server::GlobalFilter
+        ] => $crate::server::corner_radius::CornerRadiusState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::corner_radius::v1::server::cosmic_corner_radius_manager_v1::CosmicCornerRadiusManagerV1: ()
+        ] => $crate::server::corner_radius::CornerRadiusState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::corner_radius::v1::server::cosmic_corner_radius_toplevel_v1::CosmicCornerRadiusToplevelV1: $crate::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::XdgToplevel
+        ] => $crate::server::corner_radius::CornerRadiusState);
+    };
+}
+
+/// Delegate the interfaces of [`OverlapNotifyState`](crate::server::overlap_notify::OverlapNotifyState)
+/// to a type.
+#[macro_export]
+macro_rules! delegate_cosmic_overlap_notify {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::overlap_notify::v1::server::zcosmic_overlap_notify_v1::ZcosmicOverlapNotifyV1: $crate::server::GlobalFilter
+        ] => $crate::server::overlap_notify::OverlapNotifyState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::overlap_notify::v1::server::zcosmic_overlap_notify_v1::ZcosmicOverlapNotifyV1: ()
+        ] => $crate::server::overlap_notify::OverlapNotifyState<$ty>);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::overlap_notify::v1::server::zcosmic_overlap_notification_v1::ZcosmicOverlapNotificationV1: $crate::reexports::wayland_protocols_wlr::layer_shell::v1::server::zwlr_layer_surface_v1::ZwlrLayerSurfaceV1
+        ] => $crate::server::overlap_notify::OverlapNotifyState<$ty>);
+    };
+}
+
+/// Delegate the interfaces of
+/// [`WorkspaceImageCaptureSourceState`](crate::server::image_capture_source::WorkspaceImageCaptureSourceState)
+/// to a type.
+#[macro_export]
+macro_rules! delegate_cosmic_workspace_image_capture_source {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::image_capture_source::v1::server::zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1: $crate::server::GlobalFilter
+        ] => $crate::server::image_capture_source::WorkspaceImageCaptureSourceState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::image_capture_source::v1::server::zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1: ()
+        ] => $crate::server::image_capture_source::WorkspaceImageCaptureSourceState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_image_capture_source_v1::ExtImageCaptureSourceV1: $crate::server::image_capture_source::WorkspaceCaptureSource
+        ] => $crate::server::image_capture_source::WorkspaceImageCaptureSourceState);
+    };
+}
@@ -0,0 +1,198 @@
+//! Helper for `zcosmic_workspace_image_capture_source_manager_v1`.
+//!
+//! Sources created from workspaces are `ext_image_capture_source_v1` objects with
+//! [`WorkspaceCaptureSource`] user data. This helper only creates them; capturing is left to the
+//! compositor's `ext-image-copy-capture-v1` implementation.
+//!
+//! That implementation recognizes workspace sources by their [`WorkspaceCaptureSource`] user
+//! data, and renders frames of those with
+//! [`WorkspaceImageCaptureSourceState::render`], which calls
+//! [`WorkspaceImageCaptureSourceHandler::render_workspace`] for the workspace of the source.
+//!
+//! ```ignore
+//! use cosmic_protocols::{
+//!     image_capture_source::v1::server::zcosmic_workspace_image_capture_source_manager_v1,
+//!     server::{
+//!         GlobalFilter,
+//!         image_capture_source::{WorkspaceCaptureSource, WorkspaceImageCaptureSourceState},
+//!     },
+//! };
+//! use wayland_protocols::ext::image_capture_source::v1::server::ext_image_capture_source_v1;
+//!
+//! wayland_server::delegate_global_dispatch!(State: [zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1: GlobalFilter] => WorkspaceImageCaptureSourceState);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_workspace_image_capture_source_manager_v1::ZcosmicWorkspaceImageCaptureSourceManagerV1: ()] => WorkspaceImageCaptureSourceState);
+//! wayland_server::delegate_dispatch!(State: [ext_image_capture_source_v1::ExtImageCaptureSourceV1: WorkspaceCaptureSource] => WorkspaceImageCaptureSourceState);
+//! ```
+
+use wayland_protocols::ext::{
+    image_capture_source::v1::server::ext_image_capture_source_v1::{
+        self, ExtImageCaptureSourceV1,
+    },
+    image_copy_capture::v1::server::ext_image_copy_capture_frame_v1::FailureReason,
+    workspace::v1::server::ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+};
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, backend::GlobalId,
+    protocol::wl_buffer::WlBuffer,
+};
+
+use super::{GlobalFilter, workspace::CosmicWorkspaceHandler};
+use crate::image_capture_source::v1::server::zcosmic_workspace_image_capture_source_manager_v1::{
+    self, ZcosmicWorkspaceImageCaptureSourceManagerV1,
+};
+
+/// User data of `ext_image_capture_source_v1` objects created from a workspace
+#[derive(Debug)]
+pub struct WorkspaceCaptureSource {
+    handle: ExtWorkspaceHandleV1,
+}
+
+impl WorkspaceCaptureSource {
+    /// `ext_workspace_handle_v1` the source was created from
+    pub fn workspace_handle(&self) -> &ExtWorkspaceHandleV1 {
+        &self.handle
+    }
+}
+
+/// Handler trait for [`WorkspaceImageCaptureSourceState`]
+///
+/// Workspaces are resolved with [`CosmicWorkspaceHandler::workspace_for_handle`].
+pub trait WorkspaceImageCaptureSourceHandler: CosmicWorkspaceHandler {
+    /// [`WorkspaceImageCaptureSourceState`] of the compositor
+    fn workspace_image_capture_source_state(&mut self) -> &mut WorkspaceImageCaptureSourceState;
+
+    /// Render a frame of a workspace into `buffer`, with cursors if `paint_cursors` is set
+    fn render_workspace(
+        &mut self,
+        workspace: Self::Workspace,
+        buffer: &WlBuffer,
+        paint_cursors: bool,
+    ) -> Result<(), FailureReason>;
+}
+
+/// State of the `zcosmic_workspace_image_capture_source_manager_v1` global
+#[derive(Debug)]
+pub struct WorkspaceImageCaptureSourceState {
+    global: GlobalId,
+}
+
+impl WorkspaceImageCaptureSourceState {
+    /// Create the `zcosmic_workspace_image_capture_source_manager_v1` global, advertised to
+    /// clients for which `client_filter` returns `true`.
+    pub fn new<D, F>(dh: &DisplayHandle, client_filter: F) -> Self
+    where
+        D: GlobalDispatch<ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalFilter> + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        let global = dh.create_global::<D, ZcosmicWorkspaceImageCaptureSourceManagerV1, _>(
+            1,
+            GlobalFilter::new(client_filter),
+        );
+        Self { global }
+    }
+
+    /// Id of the `zcosmic_workspace_image_capture_source_manager_v1` global
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Workspace of a capture source, or `None` if it wasn't created from a workspace or the
+    /// workspace was removed
+    pub fn workspace_for_source<D: WorkspaceImageCaptureSourceHandler>(
+        state: &mut D,
+        source: &ExtImageCaptureSourceV1,
+    ) -> Option<D::Workspace> {
+        let data = source.data::<WorkspaceCaptureSource>()?;
+        if !data.handle.is_alive() {
+            return None;
+        }
+        state.workspace_for_handle(&data.handle)
+    }
+
+    /// Render a frame of the workspace of a capture source into `buffer`.
+    ///
+    /// Fails with [`FailureReason::Stopped`] if the source wasn't created from a workspace or
+    /// the workspace was removed, which ends the capture session.
+    pub fn render<D: WorkspaceImageCaptureSourceHandler>(
+        state: &mut D,
+        source: &ExtImageCaptureSourceV1,
+        buffer: &WlBuffer,
+        paint_cursors: bool,
+    ) -> Result<(), FailureReason> {
+        let workspace = Self::workspace_for_source(state, source).ok_or(FailureReason::Stopped)?;
+        state.render_workspace(workspace, buffer, paint_cursors)
+    }
+}
+
+impl<D> GlobalDispatch<ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalFilter, D>
+    for WorkspaceImageCaptureSourceState
+where
+    D: GlobalDispatch<ZcosmicWorkspaceImageCaptureSourceManagerV1, GlobalFilter>
+        + Dispatch<ZcosmicWorkspaceImageCaptureSourceManagerV1, ()>
+        + WorkspaceImageCaptureSourceHandler,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZcosmicWorkspaceImageCaptureSourceManagerV1>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<ZcosmicWorkspaceImageCaptureSourceManagerV1, (), D>
+    for WorkspaceImageCaptureSourceState
+where
+    D: Dispatch<ZcosmicWorkspaceImageCaptureSourceManagerV1, ()>
+        + Dispatch<ExtImageCaptureSourceV1, WorkspaceCaptureSource>
+        + WorkspaceImageCaptureSourceHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZcosmicWorkspaceImageCaptureSourceManagerV1,
+        request: zcosmic_workspace_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_workspace_image_capture_source_manager_v1::Request::CreateSource {
+                source,
+                output,
+            } => {
+                data_init.init(source, WorkspaceCaptureSource { handle: output });
+            }
+            zcosmic_workspace_image_capture_source_manager_v1::Request::Destroy => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ExtImageCaptureSourceV1, WorkspaceCaptureSource, D>
+    for WorkspaceImageCaptureSourceState
+where
+    D: Dispatch<ExtImageCaptureSourceV1, WorkspaceCaptureSource>
+        + WorkspaceImageCaptureSourceHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ExtImageCaptureSourceV1,
+        request: ext_image_capture_source_v1::Request,
+        _data: &WorkspaceCaptureSource,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        // `destroy` is the only request; the generated enum is just `#[non_exhaustive]`
+        if let ext_image_capture_source_v1::Request::Destroy = request {
+            // The user data is all there is to clean up, and is dropped with the object
+        }
+    }
+}
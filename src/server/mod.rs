@@ -0,0 +1,56 @@
+//! Helpers for implementing the COSMIC protocols in a compositor.
+//!
+//! Each module provides a state type implementing `GlobalDispatch` and `Dispatch` for the
+//! interfaces of a protocol, which the compositor state delegates to with
+//! `wayland_server::delegate_global_dispatch!` and `wayland_server::delegate_dispatch!`,
+//! and a handler trait for the compositor to implement.
+//!
+//! The `delegate-macros` feature adds exported `delegate_cosmic_*` macros doing this for each
+//! helper, like `delegate_cosmic_toplevel_info!`.
+
+use std::{fmt, sync::Arc};
+use wayland_server::{Client, Resource};
+
+pub mod a11y;
+pub mod atspi;
+pub mod corner_radius;
+#[cfg(feature = "delegate-macros")]
+mod delegate;
+pub mod image_capture_source;
+pub mod output_management;
+pub mod overlap_notify;
+pub mod toplevel_info;
+pub mod toplevel_management;
+pub mod workspace;
+
+/// Global data of the helpers' globals, deciding which clients they are advertised to
+#[derive(Clone)]
+pub struct GlobalFilter(Arc<dyn Fn(&Client) -> bool + Send + Sync>);
+
+impl GlobalFilter {
+    pub(crate) fn new<F>(client_filter: F) -> Self
+    where
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(client_filter))
+    }
+
+    pub(crate) fn can_view(&self, client: &Client) -> bool {
+        (self.0)(client)
+    }
+}
+
+impl fmt::Debug for GlobalFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("GlobalFilter").finish_non_exhaustive()
+    }
+}
+
+/// Object of the compositor that clients see through per-client resources, like an output
+/// and the `wl_output` objects bound by each client.
+///
+/// Helpers use this to find the resources to send in events to a particular client.
+pub trait ClientResources<I: Resource> {
+    /// Resources of `client` representing this object
+    fn client_resources(&self, client: &Client) -> Vec<I>;
+}
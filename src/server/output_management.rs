@@ -0,0 +1,1293 @@
+//! Helper for `zcosmic_output_manager_v1`, together with the `zwlr_output_manager_v1` it
+//! extends.
+//!
+//! The compositor describes its outputs with [`OutputSnapshot`]s passed to
+//! [`OutputConfigurationState::set_outputs`], which sends what changed on every
+//! `zwlr_output_head_v1` and its `zcosmic_output_head_v1` extension, followed by a `done`
+//! with a new serial.
+//!
+//! Configurations are passed to the compositor as a single [`OutputConfigurationRequest`],
+//! with the settings of `zcosmic_output_configuration_head_v1` and
+//! `zcosmic_output_configuration_v1::mirror_head` merged into those of the
+//! `zwlr_output_configuration_head_v1` they extend. Configurations created before the last
+//! change of outputs are cancelled without reaching the compositor.
+//!
+//! ```ignore
+//! use cosmic_protocols::{
+//!     output_management::v1::server::{
+//!         zcosmic_output_configuration_head_v1, zcosmic_output_configuration_v1,
+//!         zcosmic_output_head_v1, zcosmic_output_manager_v1,
+//!     },
+//!     server::{
+//!         GlobalFilter,
+//!         output_management::{
+//!             ConfigurationData, ConfigurationHeadData, ModeData, OutputConfigurationState,
+//!             OutputId,
+//!         },
+//!     },
+//! };
+//! use wayland_protocols_wlr::output_management::v1::server::{
+//!     zwlr_output_configuration_head_v1, zwlr_output_configuration_v1, zwlr_output_head_v1,
+//!     zwlr_output_manager_v1, zwlr_output_mode_v1,
+//! };
+//!
+//! wayland_server::delegate_global_dispatch!(State: [zwlr_output_manager_v1::ZwlrOutputManagerV1: GlobalFilter] => OutputConfigurationState<State>);
+//! wayland_server::delegate_global_dispatch!(State: [zcosmic_output_manager_v1::ZcosmicOutputManagerV1: GlobalFilter] => OutputConfigurationState<State>);
+//! wayland_server::delegate_dispatch!(State: [zwlr_output_manager_v1::ZwlrOutputManagerV1: ()] => OutputConfigurationState<State>);
+//! wayland_server::delegate_dispatch!(State: [zwlr_output_head_v1::ZwlrOutputHeadV1: OutputId] => OutputConfigurationState<State>);
+//! wayland_server::delegate_dispatch!(State: [zwlr_output_mode_v1::ZwlrOutputModeV1: ModeData] => OutputConfigurationState<State>);
+//! wayland_server::delegate_dispatch!(State: [zwlr_output_configuration_v1::ZwlrOutputConfigurationV1: ConfigurationData] => OutputConfigurationState<State>);
+//! wayland_server::delegate_dispatch!(State: [zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1: ConfigurationHeadData] => OutputConfigurationState<State>);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_output_manager_v1::ZcosmicOutputManagerV1: ()] => OutputConfigurationState<State>);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_output_head_v1::ZcosmicOutputHeadV1: zwlr_output_head_v1::ZwlrOutputHeadV1] => OutputConfigurationState<State>);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_output_configuration_v1::ZcosmicOutputConfigurationV1: zwlr_output_configuration_v1::ZwlrOutputConfigurationV1] => OutputConfigurationState<State>);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_output_configuration_head_v1::ZcosmicOutputConfigurationHeadV1: zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1] => OutputConfigurationState<State>);
+//! ```
+
+use std::sync::Mutex;
+use wayland_protocols_wlr::output_management::v1::server::{
+    zwlr_output_configuration_head_v1::{self, ZwlrOutputConfigurationHeadV1},
+    zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+    zwlr_output_head_v1::{self, ZwlrOutputHeadV1},
+    zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+    zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+};
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+    backend::{ClientId, GlobalId},
+    protocol::wl_output::Transform,
+};
+
+use super::GlobalFilter;
+use crate::output_management::v1::server::{
+    zcosmic_output_configuration_head_v1::{self, ZcosmicOutputConfigurationHeadV1},
+    zcosmic_output_configuration_v1::{self, ZcosmicOutputConfigurationV1},
+    zcosmic_output_head_v1::{
+        self, AdaptiveSyncAvailability, AdaptiveSyncStateExt, ZcosmicOutputHeadV1,
+    },
+    zcosmic_output_manager_v1::{self, ZcosmicOutputManagerV1},
+};
+
+/// Mode of an output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputMode {
+    /// Width in hardware units
+    pub width: i32,
+    /// Height in hardware units
+    pub height: i32,
+    /// Refresh rate in mHz, or zero if unknown
+    pub refresh: i32,
+    /// Whether this is the preferred mode of the output
+    pub preferred: bool,
+}
+
+/// Properties of an output, as advertised to clients
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputSnapshot {
+    /// Name of the output, which shouldn't change
+    pub name: String,
+    /// Description of the output
+    pub description: String,
+    /// Manufacturer of the output, if known
+    pub make: Option<String>,
+    /// Model of the output, if known
+    pub model: Option<String>,
+    /// Serial number of the output, if known
+    pub serial_number: Option<String>,
+    /// Physical size in millimeters, or zero if unknown
+    pub physical_size: (i32, i32),
+    /// Modes supported by the output
+    pub modes: Vec<OutputMode>,
+    /// Whether the output is enabled; other properties below are only sent if it is
+    pub enabled: bool,
+    /// Index of the current mode in `modes`, if any
+    pub current_mode: Option<usize>,
+    /// Position in the global compositor space
+    pub position: (i32, i32),
+    /// Transform of the output
+    pub transform: Transform,
+    /// Scale of the output
+    pub scale: f64,
+    /// Adaptive sync state
+    pub adaptive_sync: AdaptiveSyncStateExt,
+    /// Availability of adaptive sync
+    pub adaptive_sync_available: AdaptiveSyncAvailability,
+    /// Name of the output mirrored onto this one, if any
+    pub mirroring: Option<String>,
+    /// Whether the output is advertised as the primary output to Xwayland
+    pub xwayland_primary: bool,
+}
+
+impl Default for OutputSnapshot {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            description: String::new(),
+            make: None,
+            model: None,
+            serial_number: None,
+            physical_size: (0, 0),
+            modes: Vec::new(),
+            enabled: false,
+            current_mode: None,
+            position: (0, 0),
+            transform: Transform::Normal,
+            scale: 1.0,
+            adaptive_sync: AdaptiveSyncStateExt::Disabled,
+            adaptive_sync_available: AdaptiveSyncAvailability::Unsupported,
+            mirroring: None,
+            xwayland_primary: false,
+        }
+    }
+}
+
+/// Mode requested for an output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeConfiguration {
+    /// One of the modes advertised for the output
+    Mode(OutputMode),
+    /// Custom mode
+    Custom {
+        /// Width in hardware units
+        width: i32,
+        /// Height in hardware units
+        height: i32,
+        /// Refresh rate in mHz, or zero to let the compositor choose
+        refresh: i32,
+    },
+}
+
+/// Requested configuration of an enabled output.
+///
+/// Properties that weren't set by the client are `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeadConfiguration<O> {
+    /// Mode of the output
+    pub mode: Option<ModeConfiguration>,
+    /// Position in the global compositor space
+    pub position: Option<(i32, i32)>,
+    /// Transform of the output
+    pub transform: Option<Transform>,
+    /// Scale of the output, from either `set_scale` or `set_scale_1000`
+    pub scale: Option<f64>,
+    /// Adaptive sync state, from either `set_adaptive_sync` or `set_adaptive_sync_ext`
+    pub adaptive_sync: Option<AdaptiveSyncStateExt>,
+    /// Output to mirror onto this one
+    pub mirroring: Option<O>,
+}
+
+impl<O> Default for HeadConfiguration<O> {
+    fn default() -> Self {
+        Self {
+            mode: None,
+            position: None,
+            transform: None,
+            scale: None,
+            adaptive_sync: None,
+            mirroring: None,
+        }
+    }
+}
+
+/// Output configuration requested by a client
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputConfigurationRequest<O> {
+    /// Whether the configuration should only be tested, without applying it
+    pub test_only: bool,
+    /// Every output, with its configuration, or `None` if it should be disabled
+    pub heads: Vec<(O, Option<HeadConfiguration<O>>)>,
+}
+
+/// Handler trait for [`OutputConfigurationState`]
+pub trait OutputConfigurationHandler: Sized + 'static {
+    /// Compositor type of outputs
+    type Output: Clone + PartialEq;
+
+    /// [`OutputConfigurationState`] of the compositor
+    fn output_configuration_state(&mut self) -> &mut OutputConfigurationState<Self>;
+
+    /// Test or apply a configuration, returning whether it succeeded.
+    ///
+    /// After applying a configuration, the compositor should update its outputs with
+    /// [`OutputConfigurationState::set_outputs`].
+    fn configure(&mut self, request: OutputConfigurationRequest<Self::Output>) -> bool;
+
+    /// Client requested an enabled output to be advertised as the primary output to Xwayland,
+    /// or no output with `None`
+    fn set_xwayland_primary(&mut self, output: Option<Self::Output>) {
+        let _ = output;
+    }
+}
+
+/// Identifies an output of an [`OutputConfigurationState`]; user data of its heads
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OutputId(u64);
+
+/// User data of `zwlr_output_mode_v1`
+#[derive(Debug)]
+pub struct ModeData {
+    output: OutputId,
+    mode: OutputMode,
+}
+
+/// User data of `zwlr_output_configuration_v1`
+#[derive(Debug)]
+pub struct ConfigurationData {
+    serial: u32,
+    inner: Mutex<PendingConfiguration>,
+}
+
+#[derive(Debug, Default)]
+struct PendingConfiguration {
+    used: bool,
+    // Configured heads, with `None` for disabled heads
+    heads: Vec<(OutputId, Option<ZwlrOutputConfigurationHeadV1>)>,
+    // Heads used as the `mirroring` argument of `mirror_head`
+    mirrored: Vec<OutputId>,
+    extension: Option<ZcosmicOutputConfigurationV1>,
+}
+
+/// User data of `zwlr_output_configuration_head_v1`
+#[derive(Debug)]
+pub struct ConfigurationHeadData {
+    output: OutputId,
+    settings: Mutex<HeadSettings>,
+}
+
+#[derive(Debug, Default)]
+struct HeadSettings {
+    mode: Option<ModeConfiguration>,
+    position: Option<(i32, i32)>,
+    transform: Option<Transform>,
+    scale: Option<f64>,
+    adaptive_sync: Option<AdaptiveSyncStateExt>,
+    mirroring: Option<OutputId>,
+    extended: bool,
+}
+
+// A `zwlr_output_head_v1`, with its modes and extension object
+#[derive(Debug)]
+struct HeadInstance {
+    head: ZwlrOutputHeadV1,
+    manager: ZwlrOutputManagerV1,
+    modes: Vec<ZwlrOutputModeV1>,
+    cosmic: Option<ZcosmicOutputHeadV1>,
+}
+
+struct OutputData<O> {
+    id: OutputId,
+    output: O,
+    snapshot: OutputSnapshot,
+    instances: Vec<HeadInstance>,
+}
+
+/// State of the `zwlr_output_manager_v1` and `zcosmic_output_manager_v1` globals
+pub struct OutputConfigurationState<D: OutputConfigurationHandler> {
+    dh: DisplayHandle,
+    wlr_global: GlobalId,
+    cosmic_global: GlobalId,
+    managers: Vec<ZwlrOutputManagerV1>,
+    outputs: Vec<OutputData<D::Output>>,
+    serial: u32,
+    next_id: u64,
+}
+
+impl<D: OutputConfigurationHandler> std::fmt::Debug for OutputConfigurationState<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputConfigurationState")
+            .field("wlr_global", &self.wlr_global)
+            .field("cosmic_global", &self.cosmic_global)
+            .field("managers", &self.managers)
+            .field("serial", &self.serial)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: OutputConfigurationHandler> OutputConfigurationState<D> {
+    /// Create the `zwlr_output_manager_v1` global, and the `zcosmic_output_manager_v1` global
+    /// with the given version, advertised to clients for which `client_filter` returns `true`.
+    ///
+    /// # Panics
+    ///
+    /// If `version` isn't between 1 and 3.
+    pub fn new<F>(dh: &DisplayHandle, version: u32, client_filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwlrOutputManagerV1, GlobalFilter>
+            + GlobalDispatch<ZcosmicOutputManagerV1, GlobalFilter>,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        assert!(
+            (1..=3).contains(&version),
+            "Unsupported zcosmic_output_manager_v1 version {version}"
+        );
+        let filter = GlobalFilter::new(client_filter);
+        let wlr_global = dh.create_global::<D, ZwlrOutputManagerV1, _>(4, filter.clone());
+        let cosmic_global = dh.create_global::<D, ZcosmicOutputManagerV1, _>(version, filter);
+        Self {
+            dh: dh.clone(),
+            wlr_global,
+            cosmic_global,
+            managers: Vec::new(),
+            outputs: Vec::new(),
+            serial: 0,
+            next_id: 0,
+        }
+    }
+
+    /// Id of the `zwlr_output_manager_v1` global
+    pub fn wlr_output_manager_global(&self) -> GlobalId {
+        self.wlr_global.clone()
+    }
+
+    /// Id of the `zcosmic_output_manager_v1` global
+    pub fn cosmic_output_manager_global(&self) -> GlobalId {
+        self.cosmic_global.clone()
+    }
+
+    /// Serial of the current output configuration
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// Set the current outputs and their properties.
+    ///
+    /// New outputs are announced, missing ones are removed, and changes are sent to clients,
+    /// followed by `done` with a new serial if anything changed.
+    pub fn set_outputs(&mut self, outputs: impl IntoIterator<Item = (D::Output, OutputSnapshot)>)
+    where
+        D: Dispatch<ZwlrOutputHeadV1, OutputId> + Dispatch<ZwlrOutputModeV1, ModeData>,
+    {
+        let outputs = outputs.into_iter().collect::<Vec<_>>();
+        let mut changed = false;
+
+        self.outputs.retain(|data| {
+            if outputs.iter().any(|(output, _)| *output == data.output) {
+                return true;
+            }
+            for instance in &data.instances {
+                for mode in &instance.modes {
+                    mode.finished();
+                }
+                instance.head.finished();
+            }
+            changed = true;
+            false
+        });
+
+        for (output, snapshot) in outputs {
+            if let Some(data) = self.outputs.iter_mut().find(|data| data.output == output) {
+                if data.snapshot != snapshot {
+                    for instance in &mut data.instances {
+                        instance.send_state::<D>(
+                            &self.dh,
+                            data.id,
+                            Some(&data.snapshot),
+                            &snapshot,
+                        );
+                    }
+                    data.snapshot = snapshot;
+                    changed = true;
+                }
+            } else {
+                let id = OutputId(self.next_id);
+                self.next_id += 1;
+                let mut data = OutputData {
+                    id,
+                    output,
+                    snapshot,
+                    instances: Vec::new(),
+                };
+                for manager in &self.managers {
+                    data.instances
+                        .extend(data.send_to_manager::<D>(&self.dh, manager));
+                }
+                self.outputs.push(data);
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.serial = self.serial.wrapping_add(1);
+            for manager in &self.managers {
+                manager.done(self.serial);
+            }
+        }
+    }
+
+    /// Current properties of an output
+    pub fn snapshot(&self, output: &D::Output) -> Option<&OutputSnapshot> {
+        Some(
+            &self
+                .outputs
+                .iter()
+                .find(|data| data.output == *output)?
+                .snapshot,
+        )
+    }
+
+    /// Output of a `zwlr_output_head_v1`, if it hasn't been removed
+    pub fn output_for_head(&self, head: &ZwlrOutputHeadV1) -> Option<&D::Output> {
+        self.output_for_id(*head.data::<OutputId>()?)
+    }
+
+    fn output_for_id(&self, id: OutputId) -> Option<&D::Output> {
+        Some(&self.outputs.iter().find(|data| data.id == id)?.output)
+    }
+
+    fn data_for_id(&self, id: OutputId) -> Option<&OutputData<D::Output>> {
+        self.outputs.iter().find(|data| data.id == id)
+    }
+}
+
+impl<O> OutputData<O> {
+    // Create a head for the client of `manager`, and send its properties
+    fn send_to_manager<D>(
+        &self,
+        dh: &DisplayHandle,
+        manager: &ZwlrOutputManagerV1,
+    ) -> Option<HeadInstance>
+    where
+        D: Dispatch<ZwlrOutputHeadV1, OutputId> + Dispatch<ZwlrOutputModeV1, ModeData> + 'static,
+    {
+        let client = manager.client()?;
+        let head = client
+            .create_resource::<ZwlrOutputHeadV1, _, D>(dh, manager.version(), self.id)
+            .ok()?;
+        manager.head(&head);
+        head.name(self.snapshot.name.clone());
+        head.description(self.snapshot.description.clone());
+        let (width, height) = self.snapshot.physical_size;
+        if width > 0 && height > 0 {
+            head.physical_size(width, height);
+        }
+        if head.version() >= zwlr_output_head_v1::EVT_MAKE_SINCE {
+            if let Some(make) = &self.snapshot.make {
+                head.make(make.clone());
+            }
+            if let Some(model) = &self.snapshot.model {
+                head.model(model.clone());
+            }
+            if let Some(serial_number) = &self.snapshot.serial_number {
+                head.serial_number(serial_number.clone());
+            }
+        }
+        let mut instance = HeadInstance {
+            head,
+            manager: manager.clone(),
+            modes: Vec::new(),
+            cosmic: None,
+        };
+        instance.send_state::<D>(dh, self.id, None, &self.snapshot);
+        Some(instance)
+    }
+}
+
+impl HeadInstance {
+    // Send properties that differ between `old` and `new`, or every property if `old` is `None`
+    fn send_state<D>(
+        &mut self,
+        dh: &DisplayHandle,
+        id: OutputId,
+        old: Option<&OutputSnapshot>,
+        new: &OutputSnapshot,
+    ) where
+        D: Dispatch<ZwlrOutputModeV1, ModeData> + 'static,
+    {
+        let modes_changed = old.is_none_or(|old| old.modes != new.modes);
+        if modes_changed {
+            for mode in self.modes.drain(..) {
+                mode.finished();
+            }
+            if let Some(client) = self.head.client() {
+                for output_mode in &new.modes {
+                    let Ok(mode) = client.create_resource::<ZwlrOutputModeV1, _, D>(
+                        dh,
+                        self.head.version().min(3),
+                        ModeData {
+                            output: id,
+                            mode: *output_mode,
+                        },
+                    ) else {
+                        continue;
+                    };
+                    self.head.mode(&mode);
+                    mode.size(output_mode.width, output_mode.height);
+                    if output_mode.refresh > 0 {
+                        mode.refresh(output_mode.refresh);
+                    }
+                    if output_mode.preferred {
+                        mode.preferred();
+                    }
+                    self.modes.push(mode);
+                }
+            }
+        }
+
+        if old.is_none_or(|old| old.enabled != new.enabled) {
+            self.head.enabled(new.enabled as i32);
+        }
+        if new.enabled {
+            // Properties of disabled heads weren't sent
+            let old = old.filter(|old| old.enabled);
+            if (modes_changed || old.is_none_or(|old| old.current_mode != new.current_mode))
+                && let Some(mode) = new.current_mode.and_then(|i| self.modes.get(i))
+            {
+                self.head.current_mode(mode);
+            }
+            if old.is_none_or(|old| old.position != new.position) {
+                self.head.position(new.position.0, new.position.1);
+            }
+            if old.is_none_or(|old| old.transform != new.transform) {
+                self.head.transform(new.transform);
+            }
+            if old.is_none_or(|old| old.scale != new.scale) {
+                self.head.scale(new.scale);
+            }
+            if self.head.version() >= zwlr_output_head_v1::EVT_ADAPTIVE_SYNC_SINCE
+                && old.is_none_or(|old| {
+                    wlr_adaptive_sync(old.adaptive_sync) != wlr_adaptive_sync(new.adaptive_sync)
+                })
+            {
+                self.head
+                    .adaptive_sync(wlr_adaptive_sync(new.adaptive_sync));
+            }
+            if let Some(cosmic) = &self.cosmic {
+                send_cosmic_state(cosmic, old, new);
+            }
+        }
+    }
+}
+
+// Send properties of an enabled head that differ between `old` and `new`, or every property
+// if `old` is `None`
+fn send_cosmic_state(
+    cosmic: &ZcosmicOutputHeadV1,
+    old: Option<&OutputSnapshot>,
+    new: &OutputSnapshot,
+) {
+    if old.is_none_or(|old| old.scale != new.scale) {
+        cosmic.scale_1000((new.scale * 1000.0).round() as i32);
+    }
+    if old.is_none_or(|old| old.mirroring != new.mirroring) {
+        cosmic.mirroring(new.mirroring.clone());
+    }
+    if cosmic.version() >= zcosmic_output_head_v1::EVT_ADAPTIVE_SYNC_AVAILABLE_SINCE {
+        if old.is_none_or(|old| old.adaptive_sync_available != new.adaptive_sync_available) {
+            cosmic.adaptive_sync_available(new.adaptive_sync_available);
+        }
+        if old.is_none_or(|old| old.adaptive_sync != new.adaptive_sync) {
+            cosmic.adaptive_sync_ext(new.adaptive_sync);
+        }
+    }
+    if cosmic.version() >= zcosmic_output_head_v1::EVT_XWAYLAND_PRIMARY_SINCE
+        && old.is_none_or(|old| old.xwayland_primary != new.xwayland_primary)
+    {
+        cosmic.xwayland_primary(new.xwayland_primary as u32);
+    }
+}
+
+fn wlr_adaptive_sync(state: AdaptiveSyncStateExt) -> zwlr_output_head_v1::AdaptiveSyncState {
+    match state {
+        AdaptiveSyncStateExt::Disabled => zwlr_output_head_v1::AdaptiveSyncState::Disabled,
+        AdaptiveSyncStateExt::Automatic | AdaptiveSyncStateExt::Always => {
+            zwlr_output_head_v1::AdaptiveSyncState::Enabled
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrOutputManagerV1, GlobalFilter, D> for OutputConfigurationState<D>
+where
+    D: GlobalDispatch<ZwlrOutputManagerV1, GlobalFilter>
+        + Dispatch<ZwlrOutputManagerV1, ()>
+        + Dispatch<ZwlrOutputHeadV1, OutputId>
+        + Dispatch<ZwlrOutputModeV1, ModeData>
+        + OutputConfigurationHandler,
+{
+    fn bind(
+        state: &mut D,
+        dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrOutputManagerV1>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+        let output_configuration_state = state.output_configuration_state();
+        for data in &mut output_configuration_state.outputs {
+            if let Some(instance) = data.send_to_manager::<D>(dh, &manager) {
+                data.instances.push(instance);
+            }
+        }
+        manager.done(output_configuration_state.serial);
+        output_configuration_state.managers.push(manager);
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputManagerV1, (), D> for OutputConfigurationState<D>
+where
+    D: Dispatch<ZwlrOutputManagerV1, ()>
+        + Dispatch<ZwlrOutputConfigurationV1, ConfigurationData>
+        + OutputConfigurationHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputManagerV1,
+        request: zwlr_output_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_manager_v1::Request::CreateConfiguration { id, serial } => {
+                data_init.init(
+                    id,
+                    ConfigurationData {
+                        serial,
+                        inner: Mutex::new(PendingConfiguration::default()),
+                    },
+                );
+            }
+            zwlr_output_manager_v1::Request::Stop => {
+                let output_configuration_state = state.output_configuration_state();
+                output_configuration_state
+                    .managers
+                    .retain(|manager| manager != resource);
+                for data in &mut output_configuration_state.outputs {
+                    data.instances
+                        .retain(|instance| instance.manager != *resource);
+                }
+                resource.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwlrOutputManagerV1, _data: &()) {
+        state
+            .output_configuration_state()
+            .managers
+            .retain(|manager| manager != resource);
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputHeadV1, OutputId, D> for OutputConfigurationState<D>
+where
+    D: Dispatch<ZwlrOutputHeadV1, OutputId> + OutputConfigurationHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrOutputHeadV1,
+        request: zwlr_output_head_v1::Request,
+        _data: &OutputId,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_head_v1::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwlrOutputHeadV1, id: &OutputId) {
+        if let Some(data) = state
+            .output_configuration_state()
+            .outputs
+            .iter_mut()
+            .find(|data| data.id == *id)
+        {
+            data.instances.retain(|instance| instance.head != *resource);
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputModeV1, ModeData, D> for OutputConfigurationState<D>
+where
+    D: Dispatch<ZwlrOutputModeV1, ModeData> + OutputConfigurationHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrOutputModeV1,
+        request: zwlr_output_mode_v1::Request,
+        _data: &ModeData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_mode_v1::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputConfigurationV1, ConfigurationData, D> for OutputConfigurationState<D>
+where
+    D: Dispatch<ZwlrOutputConfigurationV1, ConfigurationData>
+        + Dispatch<ZwlrOutputConfigurationHeadV1, ConfigurationHeadData>
+        + OutputConfigurationHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputConfigurationV1,
+        request: zwlr_output_configuration_v1::Request,
+        data: &ConfigurationData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let mut pending = data.inner.lock().unwrap();
+        match request {
+            zwlr_output_configuration_v1::Request::EnableHead { id, head } => {
+                let output = *head.data::<OutputId>().unwrap();
+                let config_head = data_init.init(
+                    id,
+                    ConfigurationHeadData {
+                        output,
+                        settings: Mutex::new(HeadSettings::default()),
+                    },
+                );
+                if !check_unused(resource, &pending)
+                    || !check_unconfigured(resource, &pending, output)
+                {
+                    return;
+                }
+                pending.heads.push((output, Some(config_head)));
+            }
+            zwlr_output_configuration_v1::Request::DisableHead { head } => {
+                let output = *head.data::<OutputId>().unwrap();
+                if !check_unused(resource, &pending)
+                    || !check_unconfigured(resource, &pending, output)
+                {
+                    return;
+                }
+                if pending.mirrored.contains(&output)
+                    && let Some(extension) = &pending.extension
+                {
+                    extension.post_error(
+                        zcosmic_output_configuration_v1::Error::MirroredHeadBusy,
+                        "head is mirrored by another head of the configuration",
+                    );
+                    return;
+                }
+                pending.heads.push((output, None));
+            }
+            zwlr_output_configuration_v1::Request::Apply
+            | zwlr_output_configuration_v1::Request::Test => {
+                let test_only = matches!(request, zwlr_output_configuration_v1::Request::Test);
+                if !check_unused(resource, &pending) {
+                    return;
+                }
+                pending.used = true;
+                let extension = pending.extension.take();
+                let heads = std::mem::take(&mut pending.heads);
+                drop(pending);
+
+                let request = build_request(
+                    state.output_configuration_state(),
+                    resource,
+                    data.serial,
+                    heads,
+                    test_only,
+                );
+                match request.map(|request| state.configure(request)) {
+                    Some(true) => resource.succeeded(),
+                    Some(false) => resource.failed(),
+                    None => resource.cancelled(),
+                }
+                if let Some(extension) = extension {
+                    extension.finished();
+                }
+            }
+            zwlr_output_configuration_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+// Post `already_used` if the configuration was applied or tested
+fn check_unused(resource: &ZwlrOutputConfigurationV1, pending: &PendingConfiguration) -> bool {
+    if pending.used {
+        resource.post_error(
+            zwlr_output_configuration_v1::Error::AlreadyUsed,
+            "configuration was already applied or tested",
+        );
+    }
+    !pending.used
+}
+
+// Post `already_configured_head` if the head was already enabled or disabled
+fn check_unconfigured(
+    resource: &ZwlrOutputConfigurationV1,
+    pending: &PendingConfiguration,
+    output: OutputId,
+) -> bool {
+    let configured = pending.heads.iter().any(|(id, _)| *id == output);
+    if configured {
+        resource.post_error(
+            zwlr_output_configuration_v1::Error::AlreadyConfiguredHead,
+            "head was already configured",
+        );
+    }
+    !configured
+}
+
+// Merge configured heads into a request, or `None` if the configuration should be cancelled
+fn build_request<D: OutputConfigurationHandler>(
+    state: &OutputConfigurationState<D>,
+    resource: &ZwlrOutputConfigurationV1,
+    serial: u32,
+    heads: Vec<(OutputId, Option<ZwlrOutputConfigurationHeadV1>)>,
+    test_only: bool,
+) -> Option<OutputConfigurationRequest<D::Output>> {
+    if serial != state.serial {
+        return None;
+    }
+    if let Some(data) = state
+        .outputs
+        .iter()
+        .find(|data| !heads.iter().any(|(id, _)| *id == data.id))
+    {
+        resource.post_error(
+            zwlr_output_configuration_v1::Error::UnconfiguredHead,
+            format!("head {} wasn't configured", data.snapshot.name),
+        );
+        return None;
+    }
+
+    let mut request = OutputConfigurationRequest {
+        test_only,
+        heads: Vec::new(),
+    };
+    for (id, config_head) in heads {
+        let output = state.output_for_id(id)?.clone();
+        let configuration = match config_head {
+            Some(config_head) => {
+                let data = config_head.data::<ConfigurationHeadData>().unwrap();
+                let settings = data.settings.lock().unwrap();
+                Some(HeadConfiguration {
+                    mode: settings.mode,
+                    position: settings.position,
+                    transform: settings.transform,
+                    scale: settings.scale,
+                    adaptive_sync: settings.adaptive_sync,
+                    mirroring: match settings.mirroring {
+                        Some(id) => Some(state.output_for_id(id)?.clone()),
+                        None => None,
+                    },
+                })
+            }
+            None => None,
+        };
+        request.heads.push((output, configuration));
+    }
+    Some(request)
+}
+
+impl<D> Dispatch<ZwlrOutputConfigurationHeadV1, ConfigurationHeadData, D>
+    for OutputConfigurationState<D>
+where
+    D: Dispatch<ZwlrOutputConfigurationHeadV1, ConfigurationHeadData> + OutputConfigurationHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputConfigurationHeadV1,
+        request: zwlr_output_configuration_head_v1::Request,
+        data: &ConfigurationHeadData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        use zwlr_output_configuration_head_v1::Error;
+
+        let mut settings = data.settings.lock().unwrap();
+        match request {
+            zwlr_output_configuration_head_v1::Request::SetMode { mode } => {
+                if settings.mode.is_some() {
+                    return post_already_set(resource, "mode");
+                }
+                let mode_data = mode.data::<ModeData>().unwrap();
+                let belongs_to_head = mode_data.output == data.output
+                    && state
+                        .output_configuration_state()
+                        .data_for_id(data.output)
+                        .is_some_and(|output| output.snapshot.modes.contains(&mode_data.mode));
+                if !belongs_to_head {
+                    resource.post_error(Error::InvalidMode, "mode doesn't belong to head");
+                    return;
+                }
+                settings.mode = Some(ModeConfiguration::Mode(mode_data.mode));
+            }
+            zwlr_output_configuration_head_v1::Request::SetCustomMode {
+                width,
+                height,
+                refresh,
+            } => {
+                if settings.mode.is_some() {
+                    return post_already_set(resource, "mode");
+                }
+                if width <= 0 || height <= 0 || refresh < 0 {
+                    resource.post_error(
+                        Error::InvalidCustomMode,
+                        format!("invalid custom mode {width}x{height}@{refresh}"),
+                    );
+                    return;
+                }
+                settings.mode = Some(ModeConfiguration::Custom {
+                    width,
+                    height,
+                    refresh,
+                });
+            }
+            zwlr_output_configuration_head_v1::Request::SetPosition { x, y } => {
+                if settings.position.is_some() {
+                    return post_already_set(resource, "position");
+                }
+                settings.position = Some((x, y));
+            }
+            zwlr_output_configuration_head_v1::Request::SetTransform { transform } => {
+                if settings.transform.is_some() {
+                    return post_already_set(resource, "transform");
+                }
+                let WEnum::Value(transform) = transform else {
+                    resource.post_error(Error::InvalidTransform, "invalid transform");
+                    return;
+                };
+                settings.transform = Some(transform);
+            }
+            zwlr_output_configuration_head_v1::Request::SetScale { scale } => {
+                if settings.scale.is_some() {
+                    return post_already_set(resource, "scale");
+                }
+                if scale <= 0.0 {
+                    resource.post_error(Error::InvalidScale, format!("invalid scale {scale}"));
+                    return;
+                }
+                settings.scale = Some(scale);
+            }
+            zwlr_output_configuration_head_v1::Request::SetAdaptiveSync { state } => {
+                if settings.adaptive_sync.is_some() {
+                    return post_already_set(resource, "adaptive sync");
+                }
+                settings.adaptive_sync = Some(match state {
+                    WEnum::Value(zwlr_output_head_v1::AdaptiveSyncState::Disabled) => {
+                        AdaptiveSyncStateExt::Disabled
+                    }
+                    WEnum::Value(zwlr_output_head_v1::AdaptiveSyncState::Enabled) => {
+                        AdaptiveSyncStateExt::Automatic
+                    }
+                    _ => {
+                        resource.post_error(
+                            Error::InvalidAdaptiveSyncState,
+                            "invalid adaptive sync state",
+                        );
+                        return;
+                    }
+                });
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn post_already_set(resource: &ZwlrOutputConfigurationHeadV1, property: &str) {
+    resource.post_error(
+        zwlr_output_configuration_head_v1::Error::AlreadySet,
+        format!("{property} was already set"),
+    );
+}
+
+impl<D> GlobalDispatch<ZcosmicOutputManagerV1, GlobalFilter, D> for OutputConfigurationState<D>
+where
+    D: GlobalDispatch<ZcosmicOutputManagerV1, GlobalFilter>
+        + Dispatch<ZcosmicOutputManagerV1, ()>
+        + OutputConfigurationHandler,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZcosmicOutputManagerV1>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<ZcosmicOutputManagerV1, (), D> for OutputConfigurationState<D>
+where
+    D: Dispatch<ZcosmicOutputManagerV1, ()>
+        + Dispatch<ZcosmicOutputHeadV1, ZwlrOutputHeadV1>
+        + Dispatch<ZcosmicOutputConfigurationV1, ZwlrOutputConfigurationV1>
+        + Dispatch<ZcosmicOutputConfigurationHeadV1, ZwlrOutputConfigurationHeadV1>
+        + OutputConfigurationHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZcosmicOutputManagerV1,
+        request: zcosmic_output_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_output_manager_v1::Request::GetHead { extended, head } => {
+                let id = *head.data::<OutputId>().unwrap();
+                let extended = data_init.init(extended, head.clone());
+                let output_configuration_state = state.output_configuration_state();
+                let serial = output_configuration_state.serial;
+                // Head may already be removed
+                let Some(data) = output_configuration_state
+                    .outputs
+                    .iter_mut()
+                    .find(|data| data.id == id)
+                else {
+                    return;
+                };
+                let Some(instance) = data.instances.iter_mut().find(|i| i.head == head) else {
+                    return;
+                };
+                if instance.cosmic.is_some() {
+                    return post_already_extended(resource);
+                }
+                if data.snapshot.enabled {
+                    send_cosmic_state(&extended, None, &data.snapshot);
+                }
+                instance.cosmic = Some(extended);
+                instance.manager.done(serial);
+            }
+            zcosmic_output_manager_v1::Request::GetConfiguration { extended, config } => {
+                let extended = data_init.init(extended, config.clone());
+                let data = config.data::<ConfigurationData>().unwrap();
+                let mut pending = data.inner.lock().unwrap();
+                if pending.extension.is_some() {
+                    return post_already_extended(resource);
+                }
+                if pending.used {
+                    extended.finished();
+                } else {
+                    pending.extension = Some(extended);
+                }
+            }
+            zcosmic_output_manager_v1::Request::GetConfigurationHead {
+                extended,
+                config_head,
+            } => {
+                data_init.init(extended, config_head.clone());
+                let data = config_head.data::<ConfigurationHeadData>().unwrap();
+                let mut settings = data.settings.lock().unwrap();
+                if settings.extended {
+                    return post_already_extended(resource);
+                }
+                settings.extended = true;
+            }
+            zcosmic_output_manager_v1::Request::SetXwaylandPrimary { head } => {
+                let output = match head {
+                    Some(head) => {
+                        let output_configuration_state = state.output_configuration_state();
+                        let Some(data) = head
+                            .data::<ZwlrOutputHeadV1>()
+                            .and_then(|head| head.data::<OutputId>())
+                            .and_then(|id| output_configuration_state.data_for_id(*id))
+                        else {
+                            return;
+                        };
+                        // Disabled heads are ignored, to avoid races
+                        if !data.snapshot.enabled {
+                            return;
+                        }
+                        Some(data.output.clone())
+                    }
+                    None => None,
+                };
+                state.set_xwayland_primary(output);
+            }
+            zcosmic_output_manager_v1::Request::Release => {}
+        }
+    }
+}
+
+fn post_already_extended(resource: &ZcosmicOutputManagerV1) {
+    resource.post_error(
+        zcosmic_output_manager_v1::Error::AlreadyExtended,
+        "object was already extended",
+    );
+}
+
+impl<D> Dispatch<ZcosmicOutputHeadV1, ZwlrOutputHeadV1, D> for OutputConfigurationState<D>
+where
+    D: Dispatch<ZcosmicOutputHeadV1, ZwlrOutputHeadV1> + OutputConfigurationHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZcosmicOutputHeadV1,
+        request: zcosmic_output_head_v1::Request,
+        _head: &ZwlrOutputHeadV1,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_output_head_v1::Request::Release => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZcosmicOutputHeadV1,
+        head: &ZwlrOutputHeadV1,
+    ) {
+        let Some(id) = head.data::<OutputId>() else {
+            return;
+        };
+        if let Some(instance) = state
+            .output_configuration_state()
+            .outputs
+            .iter_mut()
+            .filter(|data| data.id == *id)
+            .flat_map(|data| &mut data.instances)
+            .find(|instance| instance.cosmic.as_ref() == Some(resource))
+        {
+            instance.cosmic = None;
+        }
+    }
+}
+
+impl<D> Dispatch<ZcosmicOutputConfigurationV1, ZwlrOutputConfigurationV1, D>
+    for OutputConfigurationState<D>
+where
+    D: Dispatch<ZcosmicOutputConfigurationV1, ZwlrOutputConfigurationV1>
+        + Dispatch<ZwlrOutputConfigurationHeadV1, ConfigurationHeadData>
+        + OutputConfigurationHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        resource: &ZcosmicOutputConfigurationV1,
+        request: zcosmic_output_configuration_v1::Request,
+        config: &ZwlrOutputConfigurationV1,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_output_configuration_v1::Request::MirrorHead {
+                id,
+                head,
+                mirroring,
+            } => {
+                let output = *head.data::<OutputId>().unwrap();
+                let mirrored = *mirroring.data::<OutputId>().unwrap();
+                let config_head = data_init.init(
+                    id,
+                    ConfigurationHeadData {
+                        output,
+                        settings: Mutex::new(HeadSettings {
+                            mirroring: Some(mirrored),
+                            ..HeadSettings::default()
+                        }),
+                    },
+                );
+                let data = config.data::<ConfigurationData>().unwrap();
+                let mut pending = data.inner.lock().unwrap();
+                if pending.used {
+                    resource.post_error(
+                        zcosmic_output_configuration_v1::Error::AlreadyFinished,
+                        "configuration was already applied or tested",
+                    );
+                    return;
+                }
+                if !check_unconfigured(config, &pending, output) {
+                    return;
+                }
+                // The mirrored head can't be disabled or mirroring itself
+                let busy =
+                    pending.mirrored.contains(&output)
+                        || pending.heads.iter().any(|(id, config_head)| {
+                            *id == mirrored
+                                && config_head.as_ref().is_none_or(|config_head| {
+                                    config_head.data::<ConfigurationHeadData>().is_some_and(
+                                        |data| data.settings.lock().unwrap().mirroring.is_some(),
+                                    )
+                                })
+                        });
+                if busy || output == mirrored {
+                    resource.post_error(
+                        zcosmic_output_configuration_v1::Error::MirroredHeadBusy,
+                        "mirrored head is disabled or mirroring",
+                    );
+                    return;
+                }
+                pending.heads.push((output, Some(config_head)));
+                pending.mirrored.push(mirrored);
+            }
+            zcosmic_output_configuration_v1::Request::Release => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZcosmicOutputConfigurationHeadV1, ZwlrOutputConfigurationHeadV1, D>
+    for OutputConfigurationState<D>
+where
+    D: Dispatch<ZcosmicOutputConfigurationHeadV1, ZwlrOutputConfigurationHeadV1>
+        + OutputConfigurationHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZcosmicOutputConfigurationHeadV1,
+        request: zcosmic_output_configuration_head_v1::Request,
+        config_head: &ZwlrOutputConfigurationHeadV1,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        // Inert once the extended object is destroyed
+        if !config_head.is_alive() {
+            return;
+        }
+        let Some(data) = config_head.data::<ConfigurationHeadData>() else {
+            return;
+        };
+        let mut settings = data.settings.lock().unwrap();
+        match request {
+            zcosmic_output_configuration_head_v1::Request::SetScale1000 { scale_1000 } => {
+                if settings.scale.is_some() {
+                    return post_already_set(config_head, "scale");
+                }
+                if scale_1000 <= 0 {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidScale,
+                        format!("invalid scale {scale_1000}/1000"),
+                    );
+                    return;
+                }
+                settings.scale = Some(scale_1000 as f64 / 1000.0);
+            }
+            zcosmic_output_configuration_head_v1::Request::SetAdaptiveSyncExt { state } => {
+                if settings.adaptive_sync.is_some() {
+                    return post_already_set(config_head, "adaptive sync");
+                }
+                let WEnum::Value(state) = state else {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidAdaptiveSyncState,
+                        "invalid adaptive sync state",
+                    );
+                    return;
+                };
+                settings.adaptive_sync = Some(state);
+            }
+            zcosmic_output_configuration_head_v1::Request::Release => {}
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! Helper for `zcosmic_overlap_notify_v1`.
+//!
+//! The compositor computes the toplevels and layer surfaces overlapping each layer surface
+//! returned by [`OverlapNotifyState::layer_surfaces`], and passes them to
+//! [`OverlapNotifyState::set_overlaps`], which sends `enter` events for new or changed overlaps
+//! and `leave` events for those that ended.
+//!
+//! Toplevels are sent as the `ext_foreign_toplevel_handle_v1` objects of the
+//! [`ToplevelInfoState`](super::toplevel_info::ToplevelInfoState) of the compositor.
+//!
+//! Notifications of destroyed layer surfaces are dropped the next time overlaps are set or a
+//! notification is created. Compositors can drop them right away by calling
+//! [`OverlapNotifyState::layer_surface_destroyed`].
+//!
+//! ```ignore
+//! use cosmic_protocols::{
+//!     overlap_notify::v1::server::{zcosmic_overlap_notification_v1, zcosmic_overlap_notify_v1},
+//!     server::{GlobalFilter, overlap_notify::OverlapNotifyState},
+//! };
+//! use wayland_protocols_wlr::layer_shell::v1::server::zwlr_layer_surface_v1;
+//!
+//! wayland_server::delegate_global_dispatch!(State: [zcosmic_overlap_notify_v1::ZcosmicOverlapNotifyV1: GlobalFilter] => OverlapNotifyState<State>);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_overlap_notify_v1::ZcosmicOverlapNotifyV1: ()] => OverlapNotifyState<State>);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_overlap_notification_v1::ZcosmicOverlapNotificationV1: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1] => OverlapNotifyState<State>);
+//! ```
+
+use wayland_protocols::ext::foreign_toplevel_list::v1::server::ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1;
+use wayland_protocols_wlr::layer_shell::v1::server::{
+    zwlr_layer_shell_v1::Layer, zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+};
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    backend::{ClientId, GlobalId},
+};
+
+use super::{
+    GlobalFilter,
+    toplevel_info::{ToplevelGeometry, ToplevelInfoHandler},
+};
+use crate::overlap_notify::v1::server::{
+    zcosmic_overlap_notification_v1::{self, ZcosmicOverlapNotificationV1},
+    zcosmic_overlap_notify_v1::{self, ZcosmicOverlapNotifyV1},
+};
+
+/// Layer surface overlapping another
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LayerOverlap {
+    /// Unique identifier of the overlapping layer surface
+    pub identifier: String,
+    /// Namespace of the overlapping layer surface
+    pub namespace: String,
+    /// Whether the overlapping layer surface requests an exclusive zone
+    pub exclusive: bool,
+    /// Layer of the overlapping layer surface
+    pub layer: Layer,
+    /// Overlapping area, relative to the overlapped layer surface
+    pub area: ToplevelGeometry,
+}
+
+/// Handler trait for [`OverlapNotifyState`]
+pub trait OverlapNotifyHandler: ToplevelInfoHandler {
+    /// [`OverlapNotifyState`] of the compositor
+    fn overlap_notify_state(&mut self) -> &mut OverlapNotifyState<Self>;
+}
+
+struct ToplevelOverlap<W> {
+    window: W,
+    area: ToplevelGeometry,
+    // Handles the toplevel was sent as
+    handles: Vec<ExtForeignToplevelHandleV1>,
+}
+
+struct Notification<W> {
+    layer_surface: ZwlrLayerSurfaceV1,
+    notification: ZcosmicOverlapNotificationV1,
+    toplevels: Vec<ToplevelOverlap<W>>,
+    layers: Vec<LayerOverlap>,
+}
+
+/// State of the `zcosmic_overlap_notify_v1` global
+pub struct OverlapNotifyState<D: ToplevelInfoHandler> {
+    global: GlobalId,
+    notifications: Vec<Notification<D::Window>>,
+}
+
+impl<D: ToplevelInfoHandler> std::fmt::Debug for OverlapNotifyState<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OverlapNotifyState")
+            .field("global", &self.global)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: OverlapNotifyHandler> OverlapNotifyState<D> {
+    /// Create the `zcosmic_overlap_notify_v1` global, advertised to clients for which
+    /// `client_filter` returns `true`.
+    pub fn new<F>(dh: &DisplayHandle, client_filter: F) -> Self
+    where
+        D: GlobalDispatch<ZcosmicOverlapNotifyV1, GlobalFilter>,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        let global =
+            dh.create_global::<D, ZcosmicOverlapNotifyV1, _>(1, GlobalFilter::new(client_filter));
+        Self {
+            global,
+            notifications: Vec::new(),
+        }
+    }
+
+    /// Id of the `zcosmic_overlap_notify_v1` global
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Layer surfaces with overlap notifications
+    pub fn layer_surfaces(&self) -> impl Iterator<Item = &ZwlrLayerSurfaceV1> {
+        let mut layer_surfaces = Vec::<&ZwlrLayerSurfaceV1>::new();
+        for notification in &self.notifications {
+            if notification.layer_surface.is_alive()
+                && !layer_surfaces.contains(&&notification.layer_surface)
+            {
+                layer_surfaces.push(&notification.layer_surface);
+            }
+        }
+        layer_surfaces.into_iter()
+    }
+
+    /// Drop the notifications of a destroyed layer surface
+    pub fn layer_surface_destroyed(&mut self, layer_surface: &ZwlrLayerSurfaceV1) {
+        self.notifications
+            .retain(|n| n.layer_surface != *layer_surface);
+    }
+
+    /// Set the toplevels and layer surfaces overlapping a layer surface, with the overlapping
+    /// areas relative to it, sending changes to its notification objects.
+    ///
+    /// This should also be called when toplevels are announced to the client, so they can be
+    /// sent with new `ext_foreign_toplevel_handle_v1` objects.
+    pub fn set_overlaps(
+        state: &mut D,
+        layer_surface: &ZwlrLayerSurfaceV1,
+        toplevels: Vec<(D::Window, ToplevelGeometry)>,
+        layers: Vec<LayerOverlap>,
+    ) {
+        state
+            .overlap_notify_state()
+            .notifications
+            .retain(|n| n.layer_surface.is_alive());
+        let Some(client) = layer_surface.client() else {
+            return;
+        };
+        // Current handles of each toplevel for the client
+        let toplevels = toplevels
+            .into_iter()
+            .map(|(window, area)| {
+                let handles = state
+                    .toplevel_info_state()
+                    .foreign_handles(&window, &client);
+                ToplevelOverlap {
+                    window,
+                    area,
+                    handles,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for notification in &mut state.overlap_notify_state().notifications {
+            if notification.layer_surface != *layer_surface {
+                continue;
+            }
+            let object = &notification.notification;
+
+            for old in &notification.toplevels {
+                let new = toplevels.iter().find(|t| t.window == old.window);
+                for handle in &old.handles {
+                    if new.is_none_or(|new| !new.handles.contains(handle)) {
+                        object.toplevel_leave(handle);
+                    }
+                }
+            }
+            for new in &toplevels {
+                let old = notification
+                    .toplevels
+                    .iter()
+                    .find(|t| t.window == new.window);
+                for handle in &new.handles {
+                    if old.is_none_or(|old| old.area != new.area || !old.handles.contains(handle)) {
+                        let ToplevelGeometry {
+                            x,
+                            y,
+                            width,
+                            height,
+                        } = new.area;
+                        object.toplevel_enter(handle, x, y, width, height);
+                    }
+                }
+            }
+            notification.toplevels = toplevels
+                .iter()
+                .map(|t| ToplevelOverlap {
+                    window: t.window.clone(),
+                    area: t.area,
+                    handles: t.handles.clone(),
+                })
+                .collect();
+
+            for old in &notification.layers {
+                if !layers.iter().any(|l| l.identifier == old.identifier) {
+                    object.layer_leave(old.identifier.clone());
+                }
+            }
+            for new in &layers {
+                if !notification.layers.contains(new) {
+                    let ToplevelGeometry {
+                        x,
+                        y,
+                        width,
+                        height,
+                    } = new.area;
+                    object.layer_enter(
+                        new.identifier.clone(),
+                        new.namespace.clone(),
+                        new.exclusive as u32,
+                        new.layer,
+                        x,
+                        y,
+                        width,
+                        height,
+                    );
+                }
+            }
+            notification.layers = layers.clone();
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ZcosmicOverlapNotifyV1, GlobalFilter, D> for OverlapNotifyState<D>
+where
+    D: GlobalDispatch<ZcosmicOverlapNotifyV1, GlobalFilter>
+        + Dispatch<ZcosmicOverlapNotifyV1, ()>
+        + OverlapNotifyHandler,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZcosmicOverlapNotifyV1>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<ZcosmicOverlapNotifyV1, (), D> for OverlapNotifyState<D>
+where
+    D: Dispatch<ZcosmicOverlapNotifyV1, ()>
+        + Dispatch<ZcosmicOverlapNotificationV1, ZwlrLayerSurfaceV1>
+        + OverlapNotifyHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ZcosmicOverlapNotifyV1,
+        request: zcosmic_overlap_notify_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_overlap_notify_v1::Request::NotifyOnOverlap {
+                overlap_notification,
+                layer_surface,
+            } => {
+                let notification = data_init.init(overlap_notification, layer_surface.clone());
+                let overlap_notify_state = state.overlap_notify_state();
+                overlap_notify_state
+                    .notifications
+                    .retain(|n| n.layer_surface.is_alive());
+                overlap_notify_state.notifications.push(Notification {
+                    layer_surface,
+                    notification,
+                    toplevels: Vec::new(),
+                    layers: Vec::new(),
+                });
+            }
+        }
+    }
+}
+
+impl<D> Dispatch<ZcosmicOverlapNotificationV1, ZwlrLayerSurfaceV1, D> for OverlapNotifyState<D>
+where
+    D: Dispatch<ZcosmicOverlapNotificationV1, ZwlrLayerSurfaceV1> + OverlapNotifyHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZcosmicOverlapNotificationV1,
+        request: zcosmic_overlap_notification_v1::Request,
+        _layer_surface: &ZwlrLayerSurfaceV1,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_overlap_notification_v1::Request::Destroy => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZcosmicOverlapNotificationV1,
+        _layer_surface: &ZwlrLayerSurfaceV1,
+    ) {
+        state
+            .overlap_notify_state()
+            .notifications
+            .retain(|n| n.notification != *resource);
+    }
+}
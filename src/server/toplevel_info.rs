@@ -0,0 +1,720 @@
+//! Helper for `zcosmic_toplevel_info_v1`, along with the `ext_foreign_toplevel_list_v1` it
+//! extends.
+//!
+//! The compositor describes each toplevel with a [`ToplevelSnapshot`], passed to
+//! [`ToplevelInfoState::new_toplevel`] and [`ToplevelInfoState::update_toplevel`]. Snapshots
+//! are compared with what was last sent to each handle, so only changed properties are sent,
+//! followed by one `done` per handle and one `zcosmic_toplevel_info_v1::done` per client.
+//!
+//! Version 1 of `zcosmic_toplevel_info_v1` isn't supported: clients binding it aren't sent any
+//! toplevels.
+//!
+//! ```ignore
+//! use cosmic_protocols::{
+//!     server::{
+//!         GlobalFilter,
+//!         toplevel_info::{ToplevelId, ToplevelInfoState},
+//!     },
+//!     toplevel_info::v1::server::{zcosmic_toplevel_handle_v1, zcosmic_toplevel_info_v1},
+//! };
+//! use wayland_protocols::ext::foreign_toplevel_list::v1::server::{
+//!     ext_foreign_toplevel_handle_v1, ext_foreign_toplevel_list_v1,
+//! };
+//!
+//! wayland_server::delegate_global_dispatch!(State: [ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1: GlobalFilter] => ToplevelInfoState<State>);
+//! wayland_server::delegate_global_dispatch!(State: [zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1: GlobalFilter] => ToplevelInfoState<State>);
+//! wayland_server::delegate_dispatch!(State: [ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1: ()] => ToplevelInfoState<State>);
+//! wayland_server::delegate_dispatch!(State: [ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1: ToplevelId] => ToplevelInfoState<State>);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1: ()] => ToplevelInfoState<State>);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1: ToplevelId] => ToplevelInfoState<State>);
+//! ```
+
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::server::{
+        ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+        ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+    },
+    workspace::v1::server::ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+};
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    backend::{ClientId, GlobalId},
+    protocol::wl_output::WlOutput,
+};
+
+use super::{ClientResources, GlobalFilter};
+use crate::toplevel_info::v1::server::{
+    zcosmic_toplevel_handle_v1::{self, ZcosmicToplevelHandleV1},
+    zcosmic_toplevel_info_v1::{self, ZcosmicToplevelInfoV1},
+};
+
+/// Geometry of a toplevel, relative to an output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ToplevelGeometry {
+    /// X coordinate of the upper-left corner
+    pub x: i32,
+    /// Y coordinate of the upper-left corner
+    pub y: i32,
+    /// Width of the toplevel
+    pub width: i32,
+    /// Height of the toplevel
+    pub height: i32,
+}
+
+/// Properties of a toplevel, as advertised to clients
+#[derive(Clone, Debug)]
+pub struct ToplevelSnapshot<O, W> {
+    /// Title of the toplevel
+    pub title: String,
+    /// App id of the toplevel
+    pub app_id: String,
+    /// States of the toplevel
+    pub states: Vec<zcosmic_toplevel_handle_v1::State>,
+    /// Outputs the toplevel is visible on, with its geometry relative to each
+    pub outputs: Vec<(O, ToplevelGeometry)>,
+    /// Workspaces the toplevel is on
+    pub workspaces: Vec<W>,
+}
+
+impl<O, W> Default for ToplevelSnapshot<O, W> {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            app_id: String::new(),
+            states: Vec::new(),
+            outputs: Vec::new(),
+            workspaces: Vec::new(),
+        }
+    }
+}
+
+/// Identifies a toplevel of a [`ToplevelInfoState`]; user data of its handles
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ToplevelId(u64);
+
+/// Handler trait for [`ToplevelInfoState`]
+pub trait ToplevelInfoHandler: Sized + 'static {
+    /// Compositor type of toplevels
+    type Window: Clone + PartialEq;
+    /// Compositor type of outputs, which clients see as `wl_output`
+    type Output: ClientResources<WlOutput>;
+    /// Compositor type of workspaces, which clients see as `ext_workspace_handle_v1`
+    type Workspace: ClientResources<ExtWorkspaceHandleV1>;
+
+    /// [`ToplevelInfoState`] of the compositor
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState<Self>;
+}
+
+// State of a `zcosmic_toplevel_handle_v1`, as last sent to the client
+#[derive(Debug)]
+struct CosmicInstance {
+    handle: ZcosmicToplevelHandleV1,
+    states: Option<Vec<zcosmic_toplevel_handle_v1::State>>,
+    outputs: Vec<(WlOutput, ToplevelGeometry)>,
+    workspaces: Vec<ExtWorkspaceHandleV1>,
+}
+
+// State of an `ext_foreign_toplevel_handle_v1`, as last sent to the client
+#[derive(Debug)]
+struct Instance {
+    handle: ExtForeignToplevelHandleV1,
+    title: String,
+    app_id: String,
+    cosmic: Vec<CosmicInstance>,
+}
+
+struct Toplevel<D: ToplevelInfoHandler> {
+    id: ToplevelId,
+    window: D::Window,
+    identifier: String,
+    snapshot: ToplevelSnapshot<D::Output, D::Workspace>,
+    instances: Vec<Instance>,
+}
+
+/// State of the `ext_foreign_toplevel_list_v1` and `zcosmic_toplevel_info_v1` globals
+pub struct ToplevelInfoState<D: ToplevelInfoHandler> {
+    dh: DisplayHandle,
+    list_global: GlobalId,
+    info_global: GlobalId,
+    lists: Vec<ExtForeignToplevelListV1>,
+    infos: Vec<ZcosmicToplevelInfoV1>,
+    toplevels: Vec<Toplevel<D>>,
+    next_id: u64,
+}
+
+impl<D: ToplevelInfoHandler> std::fmt::Debug for ToplevelInfoState<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToplevelInfoState")
+            .field("list_global", &self.list_global)
+            .field("info_global", &self.info_global)
+            .field("lists", &self.lists)
+            .field("infos", &self.infos)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: ToplevelInfoHandler> ToplevelInfoState<D> {
+    /// Create the `ext_foreign_toplevel_list_v1` global, and the `zcosmic_toplevel_info_v1`
+    /// global with the given version, advertised to clients for which `client_filter` returns
+    /// `true`.
+    ///
+    /// # Panics
+    ///
+    /// If `version` isn't 2 or 3.
+    pub fn new<F>(dh: &DisplayHandle, version: u32, client_filter: F) -> Self
+    where
+        D: GlobalDispatch<ExtForeignToplevelListV1, GlobalFilter>
+            + GlobalDispatch<ZcosmicToplevelInfoV1, GlobalFilter>,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        assert!(
+            (2..=3).contains(&version),
+            "Unsupported zcosmic_toplevel_info_v1 version {version}"
+        );
+        let filter = GlobalFilter::new(client_filter);
+        let list_global = dh.create_global::<D, ExtForeignToplevelListV1, _>(1, filter.clone());
+        let info_global = dh.create_global::<D, ZcosmicToplevelInfoV1, _>(version, filter);
+        Self {
+            dh: dh.clone(),
+            list_global,
+            info_global,
+            lists: Vec::new(),
+            infos: Vec::new(),
+            toplevels: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Id of the `ext_foreign_toplevel_list_v1` global
+    pub fn foreign_toplevel_list_global(&self) -> GlobalId {
+        self.list_global.clone()
+    }
+
+    /// Id of the `zcosmic_toplevel_info_v1` global
+    pub fn toplevel_info_global(&self) -> GlobalId {
+        self.info_global.clone()
+    }
+
+    /// Announce a new toplevel to clients
+    pub fn new_toplevel(
+        &mut self,
+        window: D::Window,
+        snapshot: ToplevelSnapshot<D::Output, D::Workspace>,
+    ) where
+        D: Dispatch<ExtForeignToplevelHandleV1, ToplevelId>,
+    {
+        let id = ToplevelId(self.next_id);
+        self.next_id += 1;
+        let mut toplevel = Toplevel {
+            id,
+            window,
+            identifier: format!("cosmic-toplevel-{}", id.0),
+            snapshot,
+            instances: Vec::new(),
+        };
+        for list in &self.lists {
+            if let Some(instance) = toplevel.send_to_list(&self.dh, list) {
+                toplevel.instances.push(instance);
+            }
+        }
+        self.toplevels.push(toplevel);
+    }
+
+    /// Update the properties of a toplevel, sending what changed to clients.
+    ///
+    /// Does nothing if `window` wasn't announced with [`new_toplevel`](Self::new_toplevel).
+    pub fn update_toplevel(
+        &mut self,
+        window: &D::Window,
+        snapshot: ToplevelSnapshot<D::Output, D::Workspace>,
+    ) {
+        let Some(toplevel) = self.toplevels.iter_mut().find(|t| t.window == *window) else {
+            return;
+        };
+        toplevel.snapshot = snapshot;
+        let changed_clients = toplevel.send_changes();
+        self.send_info_done(&changed_clients);
+    }
+
+    /// Send changes to clients for every toplevel, without changing their snapshots.
+    ///
+    /// This should be called when the resources of an output or workspace of a client may have
+    /// changed, e.g. when it binds a `wl_output`.
+    pub fn refresh(&mut self) {
+        let mut changed_clients = Vec::new();
+        for toplevel in &mut self.toplevels {
+            for client in toplevel.send_changes() {
+                if !changed_clients.contains(&client) {
+                    changed_clients.push(client);
+                }
+            }
+        }
+        self.send_info_done(&changed_clients);
+    }
+
+    /// Send `closed` for a toplevel, and forget about it
+    pub fn remove_toplevel(&mut self, window: &D::Window) {
+        let Some(idx) = self.toplevels.iter().position(|t| t.window == *window) else {
+            return;
+        };
+        let toplevel = self.toplevels.remove(idx);
+        for instance in &toplevel.instances {
+            instance.handle.closed();
+        }
+    }
+
+    /// Current snapshot of a toplevel
+    pub fn snapshot(
+        &self,
+        window: &D::Window,
+    ) -> Option<&ToplevelSnapshot<D::Output, D::Workspace>> {
+        Some(
+            &self
+                .toplevels
+                .iter()
+                .find(|t| t.window == *window)?
+                .snapshot,
+        )
+    }
+
+    /// Identifier sent to clients for a toplevel
+    pub fn identifier(&self, window: &D::Window) -> Option<&str> {
+        Some(
+            &self
+                .toplevels
+                .iter()
+                .find(|t| t.window == *window)?
+                .identifier,
+        )
+    }
+
+    /// Toplevels announced to clients
+    pub fn windows(&self) -> impl Iterator<Item = &D::Window> {
+        self.toplevels.iter().map(|t| &t.window)
+    }
+
+    /// `ext_foreign_toplevel_handle_v1` objects of `client` representing a toplevel
+    pub fn foreign_handles(
+        &self,
+        window: &D::Window,
+        client: &Client,
+    ) -> Vec<ExtForeignToplevelHandleV1> {
+        let Some(toplevel) = self.toplevels.iter().find(|t| t.window == *window) else {
+            return Vec::new();
+        };
+        toplevel
+            .instances
+            .iter()
+            .filter(|instance| {
+                instance
+                    .handle
+                    .client()
+                    .is_some_and(|c| c.id() == client.id())
+            })
+            .map(|instance| instance.handle.clone())
+            .collect()
+    }
+
+    /// Toplevel of an `ext_foreign_toplevel_handle_v1`, if it hasn't been removed
+    pub fn window_for_foreign_handle(
+        &self,
+        handle: &ExtForeignToplevelHandleV1,
+    ) -> Option<&D::Window> {
+        self.window_for_id(*handle.data::<ToplevelId>()?)
+    }
+
+    /// Toplevel of a `zcosmic_toplevel_handle_v1`, if it hasn't been removed
+    pub fn window_for_cosmic_handle(&self, handle: &ZcosmicToplevelHandleV1) -> Option<&D::Window> {
+        self.window_for_id(*handle.data::<ToplevelId>()?)
+    }
+
+    fn window_for_id(&self, id: ToplevelId) -> Option<&D::Window> {
+        Some(&self.toplevels.iter().find(|t| t.id == id)?.window)
+    }
+
+    fn send_info_done(&self, clients: &[ClientId]) {
+        for info in &self.infos {
+            if info
+                .client()
+                .is_some_and(|client| clients.contains(&client.id()))
+            {
+                info.done();
+            }
+        }
+    }
+}
+
+impl<D: ToplevelInfoHandler> Toplevel<D> {
+    // Create a handle for the client of `list`, and send initial properties
+    fn send_to_list(&self, dh: &DisplayHandle, list: &ExtForeignToplevelListV1) -> Option<Instance>
+    where
+        D: Dispatch<ExtForeignToplevelHandleV1, ToplevelId>,
+    {
+        let client = list.client()?;
+        let handle = client
+            .create_resource::<ExtForeignToplevelHandleV1, _, D>(dh, list.version(), self.id)
+            .ok()?;
+        list.toplevel(&handle);
+        handle.identifier(self.identifier.clone());
+        handle.title(self.snapshot.title.clone());
+        handle.app_id(self.snapshot.app_id.clone());
+        handle.done();
+        Some(Instance {
+            handle,
+            title: self.snapshot.title.clone(),
+            app_id: self.snapshot.app_id.clone(),
+            cosmic: Vec::new(),
+        })
+    }
+
+    // Send changed properties to every handle, returning clients that were sent changes
+    fn send_changes(&mut self) -> Vec<ClientId> {
+        let mut changed_clients = Vec::new();
+        for instance in &mut self.instances {
+            let mut changed = false;
+            if instance.title != self.snapshot.title {
+                instance.title = self.snapshot.title.clone();
+                instance.handle.title(instance.title.clone());
+                changed = true;
+            }
+            if instance.app_id != self.snapshot.app_id {
+                instance.app_id = self.snapshot.app_id.clone();
+                instance.handle.app_id(instance.app_id.clone());
+                changed = true;
+            }
+            for cosmic in &mut instance.cosmic {
+                changed |= cosmic.send_changes(&self.snapshot);
+            }
+            if changed {
+                instance.handle.done();
+                changed_clients.extend(instance.handle.client().map(|client| client.id()));
+            }
+        }
+        changed_clients
+    }
+}
+
+impl CosmicInstance {
+    fn new(handle: ZcosmicToplevelHandleV1) -> Self {
+        Self {
+            handle,
+            states: None,
+            outputs: Vec::new(),
+            workspaces: Vec::new(),
+        }
+    }
+
+    // Send properties that differ from `snapshot`, returning whether anything was sent
+    fn send_changes<O, W>(&mut self, snapshot: &ToplevelSnapshot<O, W>) -> bool
+    where
+        O: ClientResources<WlOutput>,
+        W: ClientResources<ExtWorkspaceHandleV1>,
+    {
+        let Some(client) = self.handle.client() else {
+            return false;
+        };
+        let version = self.handle.version();
+        let mut changed = false;
+
+        if self.states.as_ref() != Some(&snapshot.states) {
+            let states = snapshot
+                .states
+                .iter()
+                .flat_map(|state| u32::from(*state).to_ne_bytes())
+                .collect();
+            self.handle.state(states);
+            self.states = Some(snapshot.states.clone());
+            changed = true;
+        }
+
+        let outputs = snapshot
+            .outputs
+            .iter()
+            .flat_map(|(output, geometry)| {
+                output
+                    .client_resources(&client)
+                    .into_iter()
+                    .map(|wl_output| (wl_output, *geometry))
+            })
+            .collect::<Vec<_>>();
+        for (wl_output, _) in &self.outputs {
+            if !outputs.iter().any(|(o, _)| o == wl_output) {
+                self.handle.output_leave(wl_output);
+                changed = true;
+            }
+        }
+        for (wl_output, geometry) in &outputs {
+            let sent = self.outputs.iter().find(|(o, _)| o == wl_output);
+            if sent.is_none() {
+                self.handle.output_enter(wl_output);
+                changed = true;
+            }
+            if version >= zcosmic_toplevel_handle_v1::EVT_GEOMETRY_SINCE
+                && sent.is_none_or(|(_, g)| g != geometry)
+            {
+                self.handle.geometry(
+                    wl_output,
+                    geometry.x,
+                    geometry.y,
+                    geometry.width,
+                    geometry.height,
+                );
+                changed = true;
+            }
+        }
+        self.outputs = outputs;
+
+        if version >= zcosmic_toplevel_handle_v1::EVT_EXT_WORKSPACE_ENTER_SINCE {
+            let workspaces = snapshot
+                .workspaces
+                .iter()
+                .flat_map(|workspace| workspace.client_resources(&client))
+                .collect::<Vec<_>>();
+            for workspace in &self.workspaces {
+                if !workspaces.contains(workspace) {
+                    self.handle.ext_workspace_leave(workspace);
+                    changed = true;
+                }
+            }
+            for workspace in &workspaces {
+                if !self.workspaces.contains(workspace) {
+                    self.handle.ext_workspace_enter(workspace);
+                    changed = true;
+                }
+            }
+            self.workspaces = workspaces;
+        }
+
+        changed
+    }
+}
+
+impl<D> GlobalDispatch<ExtForeignToplevelListV1, GlobalFilter, D> for ToplevelInfoState<D>
+where
+    D: GlobalDispatch<ExtForeignToplevelListV1, GlobalFilter>
+        + Dispatch<ExtForeignToplevelListV1, ()>
+        + Dispatch<ExtForeignToplevelHandleV1, ToplevelId>
+        + ToplevelInfoHandler,
+{
+    fn bind(
+        state: &mut D,
+        dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtForeignToplevelListV1>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let list = data_init.init(resource, ());
+        let toplevel_info_state = state.toplevel_info_state();
+        for toplevel in &mut toplevel_info_state.toplevels {
+            if let Some(instance) = toplevel.send_to_list(dh, &list) {
+                toplevel.instances.push(instance);
+            }
+        }
+        toplevel_info_state.lists.push(list);
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<ExtForeignToplevelListV1, (), D> for ToplevelInfoState<D>
+where
+    D: Dispatch<ExtForeignToplevelListV1, ()> + ToplevelInfoHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ExtForeignToplevelListV1,
+        request: ext_foreign_toplevel_list_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_foreign_toplevel_list_v1::Request::Stop => {
+                state
+                    .toplevel_info_state()
+                    .lists
+                    .retain(|list| list != resource);
+                resource.finished();
+            }
+            ext_foreign_toplevel_list_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ExtForeignToplevelListV1,
+        _data: &(),
+    ) {
+        state
+            .toplevel_info_state()
+            .lists
+            .retain(|list| list != resource);
+    }
+}
+
+impl<D> Dispatch<ExtForeignToplevelHandleV1, ToplevelId, D> for ToplevelInfoState<D>
+where
+    D: Dispatch<ExtForeignToplevelHandleV1, ToplevelId> + ToplevelInfoHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ExtForeignToplevelHandleV1,
+        request: ext_foreign_toplevel_handle_v1::Request,
+        _data: &ToplevelId,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_foreign_toplevel_handle_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ExtForeignToplevelHandleV1,
+        id: &ToplevelId,
+    ) {
+        if let Some(toplevel) = state
+            .toplevel_info_state()
+            .toplevels
+            .iter_mut()
+            .find(|t| t.id == *id)
+        {
+            toplevel.instances.retain(|i| i.handle != *resource);
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ZcosmicToplevelInfoV1, GlobalFilter, D> for ToplevelInfoState<D>
+where
+    D: GlobalDispatch<ZcosmicToplevelInfoV1, GlobalFilter>
+        + Dispatch<ZcosmicToplevelInfoV1, ()>
+        + ToplevelInfoHandler,
+{
+    fn bind(
+        state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZcosmicToplevelInfoV1>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let info = data_init.init(resource, ());
+        state.toplevel_info_state().infos.push(info);
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<ZcosmicToplevelInfoV1, (), D> for ToplevelInfoState<D>
+where
+    D: Dispatch<ZcosmicToplevelInfoV1, ()>
+        + Dispatch<ZcosmicToplevelHandleV1, ToplevelId>
+        + ToplevelInfoHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZcosmicToplevelInfoV1,
+        request: zcosmic_toplevel_info_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_toplevel_info_v1::Request::GetCosmicToplevel {
+                cosmic_toplevel,
+                foreign_toplevel,
+            } => {
+                // Handles of removed toplevels keep their id, which no longer matches a toplevel
+                let id = foreign_toplevel
+                    .data::<ToplevelId>()
+                    .copied()
+                    .unwrap_or(ToplevelId(u64::MAX));
+                let handle = data_init.init(cosmic_toplevel, id);
+                let toplevel_info_state = state.toplevel_info_state();
+                let Some(toplevel) = toplevel_info_state
+                    .toplevels
+                    .iter_mut()
+                    .find(|t| t.id == id)
+                else {
+                    return;
+                };
+                let Some(instance) = toplevel
+                    .instances
+                    .iter_mut()
+                    .find(|i| i.handle == foreign_toplevel)
+                else {
+                    return;
+                };
+                let mut cosmic = CosmicInstance::new(handle);
+                cosmic.send_changes(&toplevel.snapshot);
+                instance.cosmic.push(cosmic);
+                instance.handle.done();
+                resource.done();
+            }
+            zcosmic_toplevel_info_v1::Request::Stop => {
+                state
+                    .toplevel_info_state()
+                    .infos
+                    .retain(|info| info != resource);
+                resource.finished();
+            }
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZcosmicToplevelInfoV1, _data: &()) {
+        state
+            .toplevel_info_state()
+            .infos
+            .retain(|info| info != resource);
+    }
+}
+
+impl<D> Dispatch<ZcosmicToplevelHandleV1, ToplevelId, D> for ToplevelInfoState<D>
+where
+    D: Dispatch<ZcosmicToplevelHandleV1, ToplevelId> + ToplevelInfoHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZcosmicToplevelHandleV1,
+        request: zcosmic_toplevel_handle_v1::Request,
+        _data: &ToplevelId,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_toplevel_handle_v1::Request::Destroy => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZcosmicToplevelHandleV1,
+        id: &ToplevelId,
+    ) {
+        if let Some(toplevel) = state
+            .toplevel_info_state()
+            .toplevels
+            .iter_mut()
+            .find(|t| t.id == *id)
+        {
+            for instance in &mut toplevel.instances {
+                instance.cosmic.retain(|c| c.handle != *resource);
+            }
+        }
+    }
+}
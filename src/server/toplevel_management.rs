@@ -0,0 +1,358 @@
+//! Helper for `zcosmic_toplevel_manager_v1`.
+//!
+//! Toplevel handles passed in requests are resolved to compositor windows through the
+//! [`ToplevelInfoState`](super::toplevel_info::ToplevelInfoState) of the compositor, so this
+//! is used together with [`toplevel_info`](super::toplevel_info). Requests for toplevels that
+//! were removed, or needing a capability that isn't advertised, are ignored.
+//!
+//! The deprecated `move_to_workspace` request takes a `zcosmic_workspace_handle_v1`, which is
+//! passed to the handler as is, since this crate has no helper for `zcosmic_workspace_manager_v1`.
+//!
+//! ```ignore
+//! use cosmic_protocols::{
+//!     server::{GlobalFilter, toplevel_management::ToplevelManagementState},
+//!     toplevel_management::v1::server::zcosmic_toplevel_manager_v1,
+//! };
+//!
+//! wayland_server::delegate_global_dispatch!(State: [zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1: GlobalFilter] => ToplevelManagementState);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1: ()] => ToplevelManagementState);
+//! ```
+
+use wayland_protocols::ext::workspace::v1::server::ext_workspace_handle_v1::ExtWorkspaceHandleV1;
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    backend::{ClientId, GlobalId},
+    protocol::{wl_output::WlOutput, wl_seat::WlSeat, wl_surface::WlSurface},
+};
+
+use super::{
+    GlobalFilter,
+    toplevel_info::{ToplevelGeometry, ToplevelInfoHandler},
+};
+use crate::{
+    toplevel_management::v1::server::zcosmic_toplevel_manager_v1::{
+        self, ZcosmicToplelevelManagementCapabilitiesV1 as Capability, ZcosmicToplevelManagerV1,
+    },
+    workspace::v1::server::zcosmic_workspace_handle_v1::ZcosmicWorkspaceHandleV1,
+};
+
+/// Handler trait for [`ToplevelManagementState`]
+///
+/// Each method is only called if the capability it belongs to is advertised, so the default
+/// implementations of unsupported requests don't need to be replaced.
+pub trait ToplevelManagementHandler: ToplevelInfoHandler {
+    /// [`ToplevelManagementState`] of the compositor
+    fn toplevel_management_state(&mut self) -> &mut ToplevelManagementState;
+
+    /// Client requested a toplevel to be closed
+    fn close(&mut self, window: Self::Window) {
+        let _ = window;
+    }
+
+    /// Client requested a toplevel to be activated
+    fn activate(&mut self, window: Self::Window, seat: WlSeat) {
+        let _ = (window, seat);
+    }
+
+    /// Client requested a toplevel to be maximized
+    fn set_maximized(&mut self, window: Self::Window) {
+        let _ = window;
+    }
+
+    /// Client requested a toplevel to be unmaximized
+    fn unset_maximized(&mut self, window: Self::Window) {
+        let _ = window;
+    }
+
+    /// Client requested a toplevel to be minimized
+    fn set_minimized(&mut self, window: Self::Window) {
+        let _ = window;
+    }
+
+    /// Client requested a toplevel to be unminimized
+    fn unset_minimized(&mut self, window: Self::Window) {
+        let _ = window;
+    }
+
+    /// Client requested a toplevel to be made fullscreen, optionally on a given output
+    fn set_fullscreen(&mut self, window: Self::Window, output: Option<WlOutput>) {
+        let _ = (window, output);
+    }
+
+    /// Client requested a toplevel to be unfullscreened
+    fn unset_fullscreen(&mut self, window: Self::Window) {
+        let _ = window;
+    }
+
+    /// Client set the rectangle of `surface` representing a toplevel, or removed it with
+    /// `None`. The rectangle is in surface-local coordinates.
+    fn set_rectangle(
+        &mut self,
+        window: Self::Window,
+        surface: WlSurface,
+        rectangle: Option<ToplevelGeometry>,
+    ) {
+        let _ = (window, surface, rectangle);
+    }
+
+    /// Client requested a toplevel to be moved to a `zcosmic_workspace_handle_v1` workspace, on
+    /// a given output
+    fn move_to_workspace(
+        &mut self,
+        window: Self::Window,
+        workspace: ZcosmicWorkspaceHandleV1,
+        output: WlOutput,
+    ) {
+        let _ = (window, workspace, output);
+    }
+
+    /// Client requested a toplevel to be moved to a workspace, on a given output
+    fn move_to_ext_workspace(
+        &mut self,
+        window: Self::Window,
+        workspace: ExtWorkspaceHandleV1,
+        output: WlOutput,
+    ) {
+        let _ = (window, workspace, output);
+    }
+
+    /// Client requested a toplevel to be made sticky
+    fn set_sticky(&mut self, window: Self::Window) {
+        let _ = window;
+    }
+
+    /// Client requested a toplevel to no longer be sticky
+    fn unset_sticky(&mut self, window: Self::Window) {
+        let _ = window;
+    }
+}
+
+/// State of the `zcosmic_toplevel_manager_v1` global
+#[derive(Debug)]
+pub struct ToplevelManagementState {
+    global: GlobalId,
+    capabilities: Vec<Capability>,
+    managers: Vec<ZcosmicToplevelManagerV1>,
+}
+
+impl ToplevelManagementState {
+    /// Create the `zcosmic_toplevel_manager_v1` global with the given version, advertised to
+    /// clients for which `client_filter` returns `true`.
+    ///
+    /// Of `capabilities`, each client is sent those that exist in the version it bound.
+    ///
+    /// # Panics
+    ///
+    /// If `version` isn't between 1 and 4.
+    pub fn new<D, F>(
+        dh: &DisplayHandle,
+        version: u32,
+        capabilities: Vec<Capability>,
+        client_filter: F,
+    ) -> Self
+    where
+        D: GlobalDispatch<ZcosmicToplevelManagerV1, GlobalFilter> + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        assert!(
+            (1..=4).contains(&version),
+            "Unsupported zcosmic_toplevel_manager_v1 version {version}"
+        );
+        let global = dh.create_global::<D, ZcosmicToplevelManagerV1, _>(
+            version,
+            GlobalFilter::new(client_filter),
+        );
+        Self {
+            global,
+            capabilities,
+            managers: Vec::new(),
+        }
+    }
+
+    /// Id of the `zcosmic_toplevel_manager_v1` global
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Capabilities supported by the compositor
+    pub fn capabilities(&self) -> &[Capability] {
+        &self.capabilities
+    }
+
+    /// Change the capabilities supported by the compositor, sending them to every client
+    pub fn set_capabilities(&mut self, capabilities: Vec<Capability>) {
+        self.capabilities = capabilities;
+        for manager in &self.managers {
+            self.send_capabilities(manager);
+        }
+    }
+
+    fn send_capabilities(&self, manager: &ZcosmicToplevelManagerV1) {
+        let capabilities = self
+            .capabilities_for_version(manager.version())
+            .flat_map(|capability| u32::from(capability).to_ne_bytes())
+            .collect();
+        manager.capabilities(capabilities);
+    }
+
+    // Advertised capabilities that exist in `version`
+    fn capabilities_for_version(&self, version: u32) -> impl Iterator<Item = Capability> + '_ {
+        self.capabilities
+            .iter()
+            .copied()
+            .filter(move |capability| match capability {
+                Capability::MoveToWorkspace => version >= 2,
+                Capability::Sticky => version >= 3,
+                Capability::MoveToExtWorkspace => version >= 4,
+                _ => true,
+            })
+    }
+
+    fn supports(&self, capability: Capability, version: u32) -> bool {
+        self.capabilities_for_version(version)
+            .any(|c| c == capability)
+    }
+}
+
+impl<D> GlobalDispatch<ZcosmicToplevelManagerV1, GlobalFilter, D> for ToplevelManagementState
+where
+    D: GlobalDispatch<ZcosmicToplevelManagerV1, GlobalFilter>
+        + Dispatch<ZcosmicToplevelManagerV1, ()>
+        + ToplevelManagementHandler,
+{
+    fn bind(
+        state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZcosmicToplevelManagerV1>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+        let toplevel_management_state = state.toplevel_management_state();
+        toplevel_management_state.send_capabilities(&manager);
+        toplevel_management_state.managers.push(manager);
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<ZcosmicToplevelManagerV1, (), D> for ToplevelManagementState
+where
+    D: Dispatch<ZcosmicToplevelManagerV1, ()> + ToplevelManagementHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZcosmicToplevelManagerV1,
+        request: zcosmic_toplevel_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        use zcosmic_toplevel_manager_v1::Request;
+
+        let (toplevel, capability) = match &request {
+            Request::Close { toplevel } => (toplevel, Some(Capability::Close)),
+            Request::Activate { toplevel, .. } => (toplevel, Some(Capability::Activate)),
+            Request::SetMaximized { toplevel } | Request::UnsetMaximized { toplevel } => {
+                (toplevel, Some(Capability::Maximize))
+            }
+            Request::SetMinimized { toplevel } | Request::UnsetMinimized { toplevel } => {
+                (toplevel, Some(Capability::Minimize))
+            }
+            Request::SetFullscreen { toplevel, .. } | Request::UnsetFullscreen { toplevel } => {
+                (toplevel, Some(Capability::Fullscreen))
+            }
+            Request::SetRectangle {
+                toplevel,
+                width,
+                height,
+                ..
+            } => {
+                if *width < 0 || *height < 0 {
+                    resource.post_error(
+                        zcosmic_toplevel_manager_v1::Error::InvalidRectangle,
+                        format!("invalid rectangle size {width}x{height}"),
+                    );
+                    return;
+                }
+                (toplevel, None)
+            }
+            Request::MoveToWorkspace { toplevel, .. } => {
+                (toplevel, Some(Capability::MoveToWorkspace))
+            }
+            Request::SetSticky { toplevel } | Request::UnsetSticky { toplevel } => {
+                (toplevel, Some(Capability::Sticky))
+            }
+            Request::MoveToExtWorkspace { toplevel, .. } => {
+                (toplevel, Some(Capability::MoveToExtWorkspace))
+            }
+            Request::Destroy => return,
+        };
+        if let Some(capability) = capability
+            && !state
+                .toplevel_management_state()
+                .supports(capability, resource.version())
+        {
+            return;
+        }
+        let Some(window) = state
+            .toplevel_info_state()
+            .window_for_cosmic_handle(toplevel)
+            .cloned()
+        else {
+            return;
+        };
+
+        match request {
+            Request::Close { .. } => state.close(window),
+            Request::Activate { seat, .. } => state.activate(window, seat),
+            Request::SetMaximized { .. } => state.set_maximized(window),
+            Request::UnsetMaximized { .. } => state.unset_maximized(window),
+            Request::SetMinimized { .. } => state.set_minimized(window),
+            Request::UnsetMinimized { .. } => state.unset_minimized(window),
+            Request::SetFullscreen { output, .. } => state.set_fullscreen(window, output),
+            Request::UnsetFullscreen { .. } => state.unset_fullscreen(window),
+            Request::SetRectangle {
+                surface,
+                x,
+                y,
+                width,
+                height,
+                ..
+            } => {
+                let rectangle = (width != 0 || height != 0).then_some(ToplevelGeometry {
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+                state.set_rectangle(window, surface, rectangle);
+            }
+            Request::SetSticky { .. } => state.set_sticky(window),
+            Request::UnsetSticky { .. } => state.unset_sticky(window),
+            Request::MoveToWorkspace {
+                workspace, output, ..
+            } => state.move_to_workspace(window, workspace, output),
+            Request::MoveToExtWorkspace {
+                workspace, output, ..
+            } => state.move_to_ext_workspace(window, workspace, output),
+            Request::Destroy => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZcosmicToplevelManagerV1,
+        _data: &(),
+    ) {
+        state
+            .toplevel_management_state()
+            .managers
+            .retain(|manager| manager != resource);
+    }
+}
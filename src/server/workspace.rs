@@ -0,0 +1,394 @@
+//! Helper for `zcosmic_workspace_manager_v2`, extending the `ext_workspace_manager_v1`
+//! implemented by the compositor.
+//!
+//! The compositor sets the COSMIC properties of each workspace with
+//! [`CosmicWorkspaceState::set_properties`], which sends changes to every extension object of the
+//! workspace. Like changes to `ext_workspace_handle_v1` properties, they are applied by clients
+//! on the next `ext_workspace_manager_v1::done`, which the compositor is responsible for.
+//!
+//! Requests on extension objects are passed to the [`CosmicWorkspaceHandler`], unless the
+//! capability they need isn't advertised for the workspace.
+//!
+//! ```ignore
+//! use cosmic_protocols::{
+//!     server::{GlobalFilter, workspace::CosmicWorkspaceState},
+//!     workspace::v2::server::{zcosmic_workspace_handle_v2, zcosmic_workspace_manager_v2},
+//! };
+//! use wayland_protocols::ext::workspace::v1::server::ext_workspace_handle_v1;
+//!
+//! wayland_server::delegate_global_dispatch!(State: [zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2: GlobalFilter] => CosmicWorkspaceState<State>);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_workspace_manager_v2::ZcosmicWorkspaceManagerV2: ()] => CosmicWorkspaceState<State>);
+//! wayland_server::delegate_dispatch!(State: [zcosmic_workspace_handle_v2::ZcosmicWorkspaceHandleV2: ext_workspace_handle_v1::ExtWorkspaceHandleV1] => CosmicWorkspaceState<State>);
+//! ```
+
+use wayland_protocols::ext::workspace::v1::server::ext_workspace_handle_v1::ExtWorkspaceHandleV1;
+use wayland_server::{
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+    backend::{ClientId, GlobalId},
+};
+
+use super::GlobalFilter;
+use crate::workspace::v2::server::{
+    zcosmic_workspace_handle_v2::{
+        self, TilingState, WorkspaceCapabilities, ZcosmicWorkspaceHandleV2,
+    },
+    zcosmic_workspace_manager_v2::{self, ZcosmicWorkspaceManagerV2},
+};
+
+/// COSMIC properties of a workspace
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CosmicWorkspaceProperties {
+    /// Requests supported for the workspace
+    ///
+    /// `Pin` has the same bits as `Rename | SetTilingState`, so advertising both of those also
+    /// allows pin requests.
+    pub capabilities: WorkspaceCapabilities,
+    /// Tiling state of the workspace
+    pub tiling_state: TilingState,
+    /// State of the workspace, extending the ext workspace state
+    pub state: zcosmic_workspace_handle_v2::State,
+}
+
+impl Default for CosmicWorkspaceProperties {
+    fn default() -> Self {
+        Self {
+            capabilities: WorkspaceCapabilities::empty(),
+            tiling_state: TilingState::FloatingOnly,
+            state: zcosmic_workspace_handle_v2::State::empty(),
+        }
+    }
+}
+
+/// Handler trait for [`CosmicWorkspaceState`]
+///
+/// Requests are passed on immediately; compositors applying ext workspace requests on
+/// `ext_workspace_manager_v1::commit` may want to queue them until then.
+pub trait CosmicWorkspaceHandler: Sized + 'static {
+    /// Compositor type of workspaces
+    type Workspace: Clone + PartialEq;
+
+    /// [`CosmicWorkspaceState`] of the compositor
+    fn cosmic_workspace_state(&mut self) -> &mut CosmicWorkspaceState<Self>;
+
+    /// Workspace of an `ext_workspace_handle_v1`, if it hasn't been removed
+    fn workspace_for_handle(&mut self, handle: &ExtWorkspaceHandleV1) -> Option<Self::Workspace>;
+
+    /// Send `ext_workspace_manager_v1::done` to `client`, after the initial state of a new
+    /// extension object was sent
+    fn ext_workspace_done(&mut self, client: &Client);
+
+    /// Client requested a workspace to be renamed
+    fn rename_workspace(&mut self, workspace: Self::Workspace, name: String);
+
+    /// Client requested the tiling state of a workspace to be changed
+    fn set_tiling_state(&mut self, workspace: Self::Workspace, state: TilingState);
+
+    /// Client requested a workspace to be moved before `other_workspace` along `axis`
+    fn move_workspace_before(
+        &mut self,
+        workspace: Self::Workspace,
+        other_workspace: Self::Workspace,
+        axis: u32,
+    );
+
+    /// Client requested a workspace to be moved after `other_workspace` along `axis`
+    fn move_workspace_after(
+        &mut self,
+        workspace: Self::Workspace,
+        other_workspace: Self::Workspace,
+        axis: u32,
+    );
+
+    /// Client requested a workspace to be pinned
+    fn pin_workspace(&mut self, workspace: Self::Workspace);
+
+    /// Client requested a workspace to be unpinned
+    fn unpin_workspace(&mut self, workspace: Self::Workspace);
+}
+
+struct WorkspaceData<W> {
+    workspace: W,
+    properties: CosmicWorkspaceProperties,
+    // Extension objects, with the handle they were created for
+    handles: Vec<(ExtWorkspaceHandleV1, ZcosmicWorkspaceHandleV2)>,
+}
+
+/// State of the `zcosmic_workspace_manager_v2` global
+pub struct CosmicWorkspaceState<D: CosmicWorkspaceHandler> {
+    global: GlobalId,
+    workspaces: Vec<WorkspaceData<D::Workspace>>,
+}
+
+impl<D: CosmicWorkspaceHandler> std::fmt::Debug for CosmicWorkspaceState<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CosmicWorkspaceState")
+            .field("global", &self.global)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: CosmicWorkspaceHandler> CosmicWorkspaceState<D> {
+    /// Create the `zcosmic_workspace_manager_v2` global with version 2, advertised to clients
+    /// for which `client_filter` returns `true`.
+    pub fn new<F>(dh: &DisplayHandle, client_filter: F) -> Self
+    where
+        D: GlobalDispatch<ZcosmicWorkspaceManagerV2, GlobalFilter>,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        let global = dh
+            .create_global::<D, ZcosmicWorkspaceManagerV2, _>(2, GlobalFilter::new(client_filter));
+        Self {
+            global,
+            workspaces: Vec::new(),
+        }
+    }
+
+    /// Id of the `zcosmic_workspace_manager_v2` global
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Set the COSMIC properties of a workspace, sending changes to its extension objects.
+    ///
+    /// Workspaces without properties set have the default properties.
+    pub fn set_properties(
+        &mut self,
+        workspace: &D::Workspace,
+        properties: CosmicWorkspaceProperties,
+    ) {
+        let data = match self
+            .workspaces
+            .iter_mut()
+            .find(|w| w.workspace == *workspace)
+        {
+            Some(data) => data,
+            None => {
+                self.workspaces.push(WorkspaceData {
+                    workspace: workspace.clone(),
+                    properties: CosmicWorkspaceProperties::default(),
+                    handles: Vec::new(),
+                });
+                self.workspaces.last_mut().unwrap()
+            }
+        };
+        let old = std::mem::replace(&mut data.properties, properties);
+        for (_, handle) in &data.handles {
+            let version = handle.version();
+            let capabilities = capabilities_for_version(properties.capabilities, version);
+            if capabilities != capabilities_for_version(old.capabilities, version) {
+                handle.capabilities(capabilities);
+            }
+            if properties.tiling_state != old.tiling_state {
+                handle.tiling_state(properties.tiling_state);
+            }
+            if version >= zcosmic_workspace_handle_v2::EVT_STATE_SINCE
+                && properties.state != old.state
+            {
+                handle.state(properties.state);
+            }
+        }
+    }
+
+    /// COSMIC properties of a workspace
+    pub fn properties(&self, workspace: &D::Workspace) -> CosmicWorkspaceProperties {
+        self.workspaces
+            .iter()
+            .find(|w| w.workspace == *workspace)
+            .map(|w| w.properties)
+            .unwrap_or_default()
+    }
+
+    /// Forget about a removed workspace; its extension objects become inert
+    pub fn remove_workspace(&mut self, workspace: &D::Workspace) {
+        self.workspaces.retain(|w| w.workspace != *workspace);
+    }
+}
+
+// Capabilities that exist in the version of the handle
+fn capabilities_for_version(
+    capabilities: WorkspaceCapabilities,
+    version: u32,
+) -> WorkspaceCapabilities {
+    if version >= 2 {
+        capabilities
+    } else {
+        capabilities & (WorkspaceCapabilities::Rename | WorkspaceCapabilities::SetTilingState)
+    }
+}
+
+impl<D> GlobalDispatch<ZcosmicWorkspaceManagerV2, GlobalFilter, D> for CosmicWorkspaceState<D>
+where
+    D: GlobalDispatch<ZcosmicWorkspaceManagerV2, GlobalFilter>
+        + Dispatch<ZcosmicWorkspaceManagerV2, ()>
+        + CosmicWorkspaceHandler,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZcosmicWorkspaceManagerV2>,
+        _global_data: &GlobalFilter,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GlobalFilter) -> bool {
+        global_data.can_view(&client)
+    }
+}
+
+impl<D> Dispatch<ZcosmicWorkspaceManagerV2, (), D> for CosmicWorkspaceState<D>
+where
+    D: Dispatch<ZcosmicWorkspaceManagerV2, ()>
+        + Dispatch<ZcosmicWorkspaceHandleV2, ExtWorkspaceHandleV1>
+        + CosmicWorkspaceHandler,
+{
+    fn request(
+        state: &mut D,
+        client: &Client,
+        resource: &ZcosmicWorkspaceManagerV2,
+        request: zcosmic_workspace_manager_v2::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zcosmic_workspace_manager_v2::Request::GetCosmicWorkspace {
+                cosmic_workspace,
+                workspace: handle,
+            } => {
+                let cosmic_workspace = data_init.init(cosmic_workspace, handle.clone());
+                // Workspace may already be removed
+                let Some(workspace) = state.workspace_for_handle(&handle) else {
+                    return;
+                };
+                let cosmic_workspace_state = state.cosmic_workspace_state();
+                if cosmic_workspace_state
+                    .workspaces
+                    .iter()
+                    .any(|w| w.handles.iter().any(|(h, _)| *h == handle))
+                {
+                    resource.post_error(
+                        zcosmic_workspace_manager_v2::Error::WorkspaceExists,
+                        "workspace already has a zcosmic_workspace_handle_v2",
+                    );
+                    return;
+                }
+                let properties = cosmic_workspace_state.properties(&workspace);
+                let data = match cosmic_workspace_state
+                    .workspaces
+                    .iter_mut()
+                    .find(|w| w.workspace == workspace)
+                {
+                    Some(data) => data,
+                    None => {
+                        cosmic_workspace_state.workspaces.push(WorkspaceData {
+                            workspace,
+                            properties,
+                            handles: Vec::new(),
+                        });
+                        cosmic_workspace_state.workspaces.last_mut().unwrap()
+                    }
+                };
+                let version = cosmic_workspace.version();
+                cosmic_workspace
+                    .capabilities(capabilities_for_version(properties.capabilities, version));
+                cosmic_workspace.tiling_state(properties.tiling_state);
+                if version >= zcosmic_workspace_handle_v2::EVT_STATE_SINCE {
+                    cosmic_workspace.state(properties.state);
+                }
+                data.handles.push((handle, cosmic_workspace));
+                state.ext_workspace_done(client);
+            }
+            zcosmic_workspace_manager_v2::Request::Destroy => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZcosmicWorkspaceHandleV2, ExtWorkspaceHandleV1, D> for CosmicWorkspaceState<D>
+where
+    D: Dispatch<ZcosmicWorkspaceHandleV2, ExtWorkspaceHandleV1> + CosmicWorkspaceHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZcosmicWorkspaceHandleV2,
+        request: zcosmic_workspace_handle_v2::Request,
+        handle: &ExtWorkspaceHandleV1,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        if let zcosmic_workspace_handle_v2::Request::Destroy = request {
+            return;
+        }
+        let Some(workspace) = state.workspace_for_handle(handle) else {
+            return;
+        };
+        let capabilities = state
+            .cosmic_workspace_state()
+            .properties(&workspace)
+            .capabilities;
+        // `Pin` has the bits of `Rename | SetTilingState`, so it can't be masked out for
+        // version 1 like in `capabilities_for_version`
+        let since_v2 = |capability| resource.version() >= 2 && capabilities.contains(capability);
+        match request {
+            zcosmic_workspace_handle_v2::Request::Rename { name } => {
+                if capabilities.contains(WorkspaceCapabilities::Rename) {
+                    state.rename_workspace(workspace, name);
+                }
+            }
+            zcosmic_workspace_handle_v2::Request::SetTilingState {
+                state: WEnum::Value(tiling_state),
+            } => {
+                if capabilities.contains(WorkspaceCapabilities::SetTilingState) {
+                    state.set_tiling_state(workspace, tiling_state);
+                }
+            }
+            zcosmic_workspace_handle_v2::Request::MoveBefore {
+                other_workspace,
+                axis,
+            } => {
+                if since_v2(WorkspaceCapabilities::Move)
+                    && let Some(other_workspace) = state.workspace_for_handle(&other_workspace)
+                {
+                    state.move_workspace_before(workspace, other_workspace, axis);
+                }
+            }
+            zcosmic_workspace_handle_v2::Request::MoveAfter {
+                other_workspace,
+                axis,
+            } => {
+                if since_v2(WorkspaceCapabilities::Move)
+                    && let Some(other_workspace) = state.workspace_for_handle(&other_workspace)
+                {
+                    state.move_workspace_after(workspace, other_workspace, axis);
+                }
+            }
+            zcosmic_workspace_handle_v2::Request::Pin => {
+                if since_v2(WorkspaceCapabilities::Pin) {
+                    state.pin_workspace(workspace);
+                }
+            }
+            zcosmic_workspace_handle_v2::Request::Unpin => {
+                if since_v2(WorkspaceCapabilities::Pin) {
+                    state.unpin_workspace(workspace);
+                }
+            }
+            // Unknown tiling states are ignored
+            zcosmic_workspace_handle_v2::Request::SetTilingState { .. }
+            | zcosmic_workspace_handle_v2::Request::Destroy => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZcosmicWorkspaceHandleV2,
+        _handle: &ExtWorkspaceHandleV1,
+    ) {
+        for data in &mut state.cosmic_workspace_state().workspaces {
+            data.handles.retain(|(_, h)| h != resource);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Plumbing shared by the conformance and server harnesses: a generated client connected over a
+//! socketpair to a `wayland_server::Display`, recording every event it receives.
+//!
+//! Each harness wraps [`Connection`] with the server state it tests against.
+
+// Each test crate uses a different part of this module
+#![allow(dead_code)]
+
+use std::{
+    os::{fd::OwnedFd, unix::net::UnixStream},
+    sync::Arc,
+};
+use wayland_backend::protocol::{Interface, MessageDesc};
+use wayland_client::{self as wc, Proxy, protocol::wl_registry};
+use wayland_server::{self as ws, Resource};
+
+/// Message received by one side
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    /// Interface of the object the message was sent to
+    pub interface: &'static str,
+    /// Name of the request or event
+    pub name: &'static str,
+    /// `Debug` output of the parsed message
+    pub args: String,
+}
+
+impl Message {
+    pub fn new(
+        interface: &'static Interface,
+        messages: &'static [MessageDesc],
+        opcode: u16,
+        args: String,
+    ) -> Self {
+        Self {
+            interface: interface.name,
+            name: messages[opcode as usize].name,
+            args,
+        }
+    }
+}
+
+/// `interface.name` of each message, for comparisons
+pub fn names(messages: &[Message]) -> Vec<String> {
+    messages
+        .iter()
+        .map(|message| format!("{}.{}", message.interface, message.name))
+        .collect()
+}
+
+/// Client state
+#[derive(Debug, Default)]
+pub struct Client {
+    events: Vec<Message>,
+    objects: Vec<wc::backend::ObjectId>,
+    globals: Vec<(u32, String)>,
+    fds: Vec<OwnedFd>,
+}
+
+impl Client {
+    pub fn record<I: Proxy>(&mut self, _proxy: &I, opcode: u16, args: String) {
+        let interface = I::interface();
+        self.events
+            .push(Message::new(interface, interface.events, opcode, args));
+    }
+
+    pub fn add_object(&mut self, proxy: &impl Proxy) {
+        self.objects.push(proxy.id());
+    }
+
+    pub fn add_fd(&mut self, fd: OwnedFd) {
+        self.fds.push(fd);
+    }
+}
+
+impl wc::Dispatch<wl_registry::WlRegistry, ()> for Client {
+    fn event(
+        state: &mut Self,
+        _proxy: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _data: &(),
+        _conn: &wc::Connection,
+        _qh: &wc::QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name, interface, ..
+            } => state.globals.push((name, interface)),
+            wl_registry::Event::GlobalRemove { name } => {
+                state.globals.retain(|(global, _)| *global != name)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Record events of an interface, keeping objects created by the listed events
+macro_rules! client_dispatch {
+    ($module:ident::$interface:ident $(, $event:ident($new:ident): $opcode:ident => $child:ident::$child_interface:ident)* $(,)?) => {
+        impl wayland_client::Dispatch<$module::$interface, ()> for $crate::common::Client {
+            #[allow(unreachable_patterns, clippy::match_single_binding)]
+            fn event(
+                state: &mut Self,
+                proxy: &$module::$interface,
+                event: $module::Event,
+                _data: &(),
+                _conn: &wayland_client::Connection,
+                _qh: &wayland_client::QueueHandle<Self>,
+            ) {
+                state.record(proxy, event.opcode(), format!("{event:?}"));
+                match event {
+                    $(
+                        $module::Event::$event { $new, .. } => state.add_object(&$new),
+                    )*
+                    _ => {}
+                }
+            }
+
+            wayland_client::event_created_child!($crate::common::Client, $module::$interface, [
+                $($module::$opcode => ($child::$child_interface, ()),)*
+            ]);
+        }
+    };
+}
+pub(crate) use client_dispatch;
+
+/// Client connected to a display
+pub struct Connection<S: 'static> {
+    pub display: ws::Display<S>,
+    pub server_client: ws::Client,
+    conn: wc::Connection,
+    queue: wc::EventQueue<Client>,
+    pub qh: wc::QueueHandle<Client>,
+    registry: wl_registry::WlRegistry,
+    pub client: Client,
+}
+
+impl<S: 'static> Connection<S> {
+    /// Connect a client to `display`, without dispatching anything yet
+    pub fn new(display: ws::Display<S>) -> Self {
+        let (server_stream, client_stream) =
+            UnixStream::pair().expect("Failed to create socketpair");
+        let server_client = display
+            .handle()
+            .insert_client(server_stream, Arc::new(ClientData))
+            .expect("Failed to insert client");
+        let conn = wc::Connection::from_socket(client_stream).expect("Failed to connect");
+        let queue = conn.new_event_queue();
+        let qh = queue.handle();
+        let registry = conn.display().get_registry(&qh, ());
+        Self {
+            display,
+            server_client,
+            conn,
+            queue,
+            qh,
+            registry,
+            client: Client::default(),
+        }
+    }
+
+    /// Exchange messages until both sides are idle, or the client got a protocol error
+    pub fn roundtrip(&mut self, state: &mut S) {
+        for _ in 0..4 {
+            self.conn.flush().expect("Failed to flush client");
+            self.display
+                .dispatch_clients(state)
+                .expect("Failed to dispatch clients");
+            self.display
+                .flush_clients()
+                .expect("Failed to flush server");
+            // Reading and dispatching only fail once the client got a protocol error
+            if let Some(guard) = self.queue.prepare_read()
+                && guard.read().is_err()
+            {
+                return;
+            }
+            if self.queue.dispatch_pending(&mut self.client).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Protocol error the client was killed with
+    pub fn protocol_error(&self) -> Option<wayland_backend::protocol::ProtocolError> {
+        self.conn.protocol_error()
+    }
+
+    /// Bind the latest global advertised for the interface of `C` at `version`
+    pub fn bind<C>(&mut self, version: u32) -> C
+    where
+        C: Proxy + 'static,
+        Client: wc::Dispatch<C, ()>,
+    {
+        let interface = C::interface().name;
+        let name = self
+            .client
+            .globals
+            .iter()
+            .rev()
+            .find(|(_, global)| global == interface)
+            .map(|(name, _)| *name)
+            .unwrap_or_else(|| panic!("No {interface} global"));
+        self.registry.bind::<C, _, _>(name, version, &self.qh, ())
+    }
+
+    /// Create an object on the server, to be sent to the client in an event
+    pub fn create_resource<I>(&mut self, version: u32) -> I
+    where
+        I: Resource + 'static,
+        S: ws::Dispatch<I, ()>,
+    {
+        self.server_client
+            .create_resource::<I, (), S>(&self.display.handle(), version, ())
+            .expect("Failed to create resource")
+    }
+
+    /// Latest client object of an interface created by an event
+    pub fn proxy<C: Proxy + 'static>(&self) -> C {
+        self.client
+            .objects
+            .iter()
+            .rev()
+            .filter(|id| id.interface().name == C::interface().name)
+            .find_map(|id| C::from_id(&self.conn, id.clone()).ok())
+            .unwrap_or_else(|| panic!("No {} on the client", C::interface().name))
+    }
+
+    /// Events received by the client since the last call
+    pub fn take_events(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.client.events)
+    }
+
+    /// File descriptors received by the client since the last call
+    pub fn take_fds(&mut self) -> Vec<OwnedFd> {
+        std::mem::take(&mut self.client.fds)
+    }
+}
+
+struct ClientData;
+
+impl ws::backend::ClientData for ClientData {}
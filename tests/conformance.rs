@@ -0,0 +1,31 @@
+//! Runs the generated client against the generated server of each protocol, exercising every
+//! request and event at every version they exist in, so changes to the XML that break existing
+//! clients or compositors are caught.
+
+#![cfg(all(feature = "client", feature = "server"))]
+
+#[path = "common/mod.rs"]
+mod common;
+#[path = "conformance/harness.rs"]
+mod harness;
+
+#[path = "conformance/a11y.rs"]
+mod a11y;
+#[path = "conformance/atspi.rs"]
+mod atspi;
+#[path = "conformance/corner_radius.rs"]
+mod corner_radius;
+#[path = "conformance/image_capture_source.rs"]
+mod image_capture_source;
+#[path = "conformance/output_management.rs"]
+mod output_management;
+#[path = "conformance/overlap_notify.rs"]
+mod overlap_notify;
+#[path = "conformance/toplevel_info.rs"]
+mod toplevel_info;
+#[path = "conformance/toplevel_management.rs"]
+mod toplevel_management;
+#[path = "conformance/workspace_v1.rs"]
+mod workspace_v1;
+#[path = "conformance/workspace_v2.rs"]
+mod workspace_v2;
@@ -0,0 +1,123 @@
+use cosmic_protocols::a11y::v1::{
+    client::cosmic_a11y_manager_v1 as c_a11y, server::cosmic_a11y_manager_v1 as s_a11y,
+};
+use wayland_server::{Resource, WEnum};
+
+use crate::harness::{Harness, assert_interface, client_dispatch, names, server_dispatch};
+
+server_dispatch!(s_a11y::CosmicA11yManagerV1);
+client_dispatch!(c_a11y::CosmicA11yManagerV1);
+
+#[test]
+fn interfaces() {
+    assert_interface(
+        s_a11y::CosmicA11yManagerV1::interface(),
+        3,
+        &[
+            ("set_magnifier", 1),
+            ("set_screen_filter", 2),
+            ("set_screen_filter2", 3),
+        ],
+        &[
+            ("magnifier", 1),
+            ("screen_filter", 2),
+            ("screen_filter2", 3),
+        ],
+    );
+}
+
+#[test]
+fn messages() {
+    for version in 1..=3 {
+        let mut h = Harness::new();
+        let (manager, s_manager) =
+            h.bind::<c_a11y::CosmicA11yManagerV1, s_a11y::CosmicA11yManagerV1>(version);
+        assert_eq!(s_manager.version(), version);
+
+        manager.set_magnifier(c_a11y::ActiveState::Enabled);
+        if version >= 2 {
+            // Deprecated, but still has to reach the compositor
+            manager.set_screen_filter(c_a11y::ActiveState::Enabled, c_a11y::Filter::Greyscale);
+        }
+        if version >= 3 {
+            manager.set_screen_filter2(
+                c_a11y::ActiveState::Disabled,
+                c_a11y::Filter::DaltonizeTritanopia,
+                c_a11y::ActiveState::Enabled,
+            );
+        }
+        h.roundtrip();
+        let requests = h.take_requests();
+        let mut expected = vec![format!(
+            "{:?}",
+            s_a11y::Request::SetMagnifier {
+                active: WEnum::Value(s_a11y::ActiveState::Enabled)
+            }
+        )];
+        if version >= 2 {
+            expected.push(format!(
+                "{:?}",
+                s_a11y::Request::SetScreenFilter {
+                    inverted: WEnum::Value(s_a11y::ActiveState::Enabled),
+                    filter: WEnum::Value(s_a11y::Filter::Greyscale),
+                }
+            ));
+        }
+        if version >= 3 {
+            expected.push(format!(
+                "{:?}",
+                s_a11y::Request::SetScreenFilter2 {
+                    inverted: WEnum::Value(s_a11y::ActiveState::Disabled),
+                    filter: WEnum::Value(s_a11y::Filter::DaltonizeTritanopia),
+                    filter_state: WEnum::Value(s_a11y::ActiveState::Enabled),
+                }
+            ));
+        }
+        assert_eq!(
+            requests.iter().map(|r| &r.args).collect::<Vec<_>>(),
+            expected.iter().collect::<Vec<_>>()
+        );
+
+        s_manager.magnifier(s_a11y::ActiveState::Disabled);
+        if version >= 2 {
+            s_manager.screen_filter(s_a11y::ActiveState::Disabled, s_a11y::Filter::Unknown);
+        }
+        if version >= 3 {
+            s_manager.screen_filter2(
+                s_a11y::ActiveState::Enabled,
+                s_a11y::Filter::DaltonizeProtanopia,
+                s_a11y::ActiveState::Disabled,
+            );
+        }
+        h.roundtrip();
+        let events = h.take_events();
+        let expected = [
+            "cosmic_a11y_manager_v1.magnifier",
+            "cosmic_a11y_manager_v1.screen_filter",
+            "cosmic_a11y_manager_v1.screen_filter2",
+        ];
+        assert_eq!(names(&events), &expected[..version as usize]);
+        assert_eq!(
+            events[0].args,
+            format!(
+                "{:?}",
+                c_a11y::Event::Magnifier {
+                    active: WEnum::Value(c_a11y::ActiveState::Disabled)
+                }
+            )
+        );
+        if version >= 3 {
+            assert_eq!(
+                events[2].args,
+                format!(
+                    "{:?}",
+                    c_a11y::Event::ScreenFilter2 {
+                        inverted: WEnum::Value(c_a11y::ActiveState::Enabled),
+                        filter: WEnum::Value(c_a11y::Filter::DaltonizeProtanopia),
+                        filter_state: WEnum::Value(c_a11y::ActiveState::Disabled),
+                    }
+                )
+            );
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    io::{Read, Write},
+    os::{fd::AsFd, unix::net::UnixStream},
+};
+
+use cosmic_protocols::atspi::v1::{
+    client::cosmic_atspi_manager_v1 as c_atspi, server::cosmic_atspi_manager_v1 as s_atspi,
+};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle};
+use wayland_server::Resource;
+
+use crate::harness::{
+    Client, Harness, assert_destructor, assert_interface, names, server_dispatch,
+};
+
+server_dispatch!(s_atspi::CosmicAtspiManagerV1);
+
+impl Dispatch<c_atspi::CosmicAtspiManagerV1, ()> for Client {
+    fn event(
+        state: &mut Self,
+        proxy: &c_atspi::CosmicAtspiManagerV1,
+        event: c_atspi::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        state.record(proxy, event.opcode(), format!("{event:?}"));
+        if let c_atspi::Event::KeyEventsEis { fd } = event {
+            state.add_fd(fd);
+        }
+    }
+}
+
+#[test]
+fn interfaces() {
+    assert_interface(
+        s_atspi::CosmicAtspiManagerV1::interface(),
+        1,
+        &[
+            ("destroy", 1),
+            ("add_key_grab", 1),
+            ("remove_key_grab", 1),
+            ("grab_keyboard", 1),
+            ("ungrab_keyboard", 1),
+        ],
+        &[("key_events_eis", 1)],
+    );
+    assert_destructor(s_atspi::CosmicAtspiManagerV1::interface(), "destroy");
+}
+
+#[test]
+fn messages() {
+    let mut h = Harness::new();
+    let (manager, s_manager) =
+        h.bind::<c_atspi::CosmicAtspiManagerV1, s_atspi::CosmicAtspiManagerV1>(1);
+
+    manager.add_key_grab(1, vec![2, 0, 0, 0], 30);
+    manager.remove_key_grab(1, vec![2, 0, 0, 0], 30);
+    manager.grab_keyboard();
+    manager.ungrab_keyboard();
+    h.roundtrip();
+    let requests = h.take_requests();
+    assert_eq!(
+        names(&requests),
+        [
+            "cosmic_atspi_manager_v1.add_key_grab",
+            "cosmic_atspi_manager_v1.remove_key_grab",
+            "cosmic_atspi_manager_v1.grab_keyboard",
+            "cosmic_atspi_manager_v1.ungrab_keyboard",
+        ]
+    );
+    assert_eq!(
+        requests[0].args,
+        format!(
+            "{:?}",
+            s_atspi::Request::AddKeyGrab {
+                mods: 1,
+                virtual_mods: vec![2, 0, 0, 0],
+                key: 30
+            }
+        )
+    );
+
+    // The fd has to arrive as the other end of the socket
+    let (mut ours, theirs) = UnixStream::pair().unwrap();
+    s_manager.key_events_eis(theirs.as_fd());
+    drop(theirs);
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_events()),
+        ["cosmic_atspi_manager_v1.key_events_eis"]
+    );
+    let mut received = UnixStream::from(h.take_fds().pop().unwrap());
+    ours.write_all(b"eis").unwrap();
+    let mut buf = [0; 3];
+    received.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"eis");
+
+    manager.destroy();
+    assert!(!manager.is_alive());
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_requests()),
+        ["cosmic_atspi_manager_v1.destroy"]
+    );
+    assert!(h.destroyed(&s_manager));
+    assert!(!s_manager.is_alive());
+}
@@ -0,0 +1,102 @@
+use cosmic_protocols::corner_radius::v1::{
+    client::{
+        cosmic_corner_radius_manager_v1 as c_manager,
+        cosmic_corner_radius_toplevel_v1 as c_toplevel,
+    },
+    server::{
+        cosmic_corner_radius_manager_v1 as s_manager,
+        cosmic_corner_radius_toplevel_v1 as s_toplevel,
+    },
+};
+use wayland_client::Proxy;
+use wayland_protocols::xdg::shell::{client::xdg_toplevel as c_xdg, server::xdg_toplevel as s_xdg};
+use wayland_server::Resource;
+
+use crate::harness::{
+    Harness, assert_destructor, assert_interface, client_dispatch, names, server_dispatch,
+};
+
+server_dispatch!(s_manager::CosmicCornerRadiusManagerV1, GetCornerRadius(id));
+server_dispatch!(s_toplevel::CosmicCornerRadiusToplevelV1);
+client_dispatch!(c_manager::CosmicCornerRadiusManagerV1);
+client_dispatch!(c_toplevel::CosmicCornerRadiusToplevelV1);
+
+#[test]
+fn interfaces() {
+    assert_interface(
+        s_manager::CosmicCornerRadiusManagerV1::interface(),
+        1,
+        &[("destroy", 1), ("get_corner_radius", 1)],
+        &[],
+    );
+    assert_interface(
+        s_toplevel::CosmicCornerRadiusToplevelV1::interface(),
+        1,
+        &[("destroy", 1), ("set_radius", 1), ("unset_radius", 1)],
+        &[],
+    );
+    assert_destructor(
+        s_manager::CosmicCornerRadiusManagerV1::interface(),
+        "destroy",
+    );
+    assert_destructor(
+        s_toplevel::CosmicCornerRadiusToplevelV1::interface(),
+        "destroy",
+    );
+}
+
+#[test]
+fn messages() {
+    let mut h = Harness::new();
+    let (manager, s_manager) =
+        h.bind::<c_manager::CosmicCornerRadiusManagerV1, s_manager::CosmicCornerRadiusManagerV1>(1);
+    let (xdg_toplevel, s_xdg_toplevel) = h.bind::<c_xdg::XdgToplevel, s_xdg::XdgToplevel>(1);
+
+    let toplevel = manager.get_corner_radius(&xdg_toplevel, &h.qh, ());
+    h.roundtrip();
+    let requests = h.take_requests();
+    assert_eq!(
+        names(&requests),
+        ["cosmic_corner_radius_manager_v1.get_corner_radius"]
+    );
+    let s_toplevel = h.resource::<s_toplevel::CosmicCornerRadiusToplevelV1>();
+    assert_eq!(s_toplevel.version(), 1);
+    assert_eq!(s_toplevel.id().protocol_id(), toplevel.id().protocol_id());
+
+    // Destroying the manager leaves the objects created from it alive
+    manager.destroy();
+    h.roundtrip();
+    assert!(h.destroyed(&s_manager));
+    assert!(s_toplevel.is_alive());
+
+    toplevel.set_radius(1, 2, 3, 4);
+    toplevel.unset_radius();
+    toplevel.destroy();
+    assert!(!toplevel.is_alive());
+    h.roundtrip();
+    let requests = h.take_requests();
+    assert_eq!(
+        names(&requests),
+        [
+            "cosmic_corner_radius_manager_v1.destroy",
+            "cosmic_corner_radius_toplevel_v1.set_radius",
+            "cosmic_corner_radius_toplevel_v1.unset_radius",
+            "cosmic_corner_radius_toplevel_v1.destroy",
+        ]
+    );
+    assert_eq!(
+        requests[1].args,
+        format!(
+            "{:?}",
+            s_toplevel::Request::SetRadius {
+                top_left: 1,
+                top_right: 2,
+                bottom_right: 3,
+                bottom_left: 4,
+            }
+        )
+    );
+    assert!(h.destroyed(&s_toplevel));
+    assert!(!s_toplevel.is_alive());
+    assert!(s_xdg_toplevel.is_alive());
+}
@@ -0,0 +1,291 @@
+//! Generated client and server connected over a socketpair, in a single thread.
+//!
+//! Both sides record every message they receive, along with the `Debug` output of its
+//! arguments, so tests can check what went over the wire. Objects of interfaces normally created
+//! by protocols outside of this crate, like `xdg_toplevel`, are bound as stand-in globals, since
+//! only their identity matters as arguments.
+
+use std::ops::{Deref, DerefMut};
+use wayland_backend::protocol::{Interface, MessageDesc};
+use wayland_client::{self as wc, Proxy};
+use wayland_server::{self as ws, Resource};
+
+use crate::common::Connection;
+pub(crate) use crate::common::client_dispatch;
+pub use crate::common::{Client, Message, names};
+
+/// Assert the version of an interface, and the name and `since` version of its requests and
+/// events, in opcode order
+pub fn assert_interface(
+    interface: &Interface,
+    version: u32,
+    requests: &[(&str, u32)],
+    events: &[(&str, u32)],
+) {
+    let messages = |descs: &[MessageDesc]| {
+        descs
+            .iter()
+            .map(|desc| (desc.name, desc.since))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(interface.version, version, "version of {}", interface.name);
+    assert_eq!(
+        messages(interface.requests),
+        requests,
+        "requests of {}",
+        interface.name
+    );
+    assert_eq!(
+        messages(interface.events),
+        events,
+        "events of {}",
+        interface.name
+    );
+}
+
+/// Assert that a message is a destructor of its interface
+pub fn assert_destructor(interface: &Interface, name: &str) {
+    let desc = interface
+        .requests
+        .iter()
+        .find(|desc| desc.name == name)
+        .unwrap_or_else(|| panic!("no request {} in {}", name, interface.name));
+    assert!(
+        desc.is_destructor,
+        "{}.{} isn't a destructor",
+        interface.name, name
+    );
+}
+
+/// Server state
+#[derive(Debug, Default)]
+pub struct Server {
+    requests: Vec<Message>,
+    objects: Vec<ws::backend::ObjectId>,
+    destroyed: Vec<ws::backend::ObjectId>,
+}
+
+impl Server {
+    pub fn record<I: Resource>(&mut self, _resource: &I, opcode: u16, args: String) {
+        let interface = I::interface();
+        self.requests
+            .push(Message::new(interface, interface.requests, opcode, args));
+    }
+
+    pub fn add_object(&mut self, resource: &impl Resource) {
+        self.objects.push(resource.id());
+    }
+
+    pub fn add_destroyed(&mut self, resource: &impl Resource) {
+        self.destroyed.push(resource.id());
+    }
+}
+
+impl<I> ws::GlobalDispatch<I, ()> for Server
+where
+    I: Resource + 'static,
+    Server: ws::Dispatch<I, ()>,
+{
+    fn bind(
+        state: &mut Self,
+        _dh: &ws::DisplayHandle,
+        _client: &ws::Client,
+        resource: ws::New<I>,
+        _global_data: &(),
+        data_init: &mut ws::DataInit<'_, Self>,
+    ) {
+        let resource = data_init.init(resource, ());
+        state.add_object(&resource);
+    }
+}
+
+/// Record requests to an interface, initializing objects created by the listed requests
+macro_rules! server_dispatch {
+    ($module:ident::$interface:ident $(, $request:ident($new:ident))* $(,)?) => {
+        impl wayland_server::Dispatch<$module::$interface, ()> for $crate::harness::Server {
+            #[allow(unused_variables, unreachable_patterns, clippy::match_single_binding)]
+            fn request(
+                state: &mut Self,
+                _client: &wayland_server::Client,
+                resource: &$module::$interface,
+                request: $module::Request,
+                _data: &(),
+                _dh: &wayland_server::DisplayHandle,
+                data_init: &mut wayland_server::DataInit<'_, Self>,
+            ) {
+                state.record(resource, request.opcode(), format!("{request:?}"));
+                match request {
+                    $(
+                        $module::Request::$request { $new, .. } => {
+                            let object = data_init.init($new, ());
+                            state.add_object(&object);
+                        }
+                    )*
+                    _ => {}
+                }
+            }
+
+            fn destroyed(
+                state: &mut Self,
+                _client: wayland_server::backend::ClientId,
+                resource: &$module::$interface,
+                _data: &(),
+            ) {
+                state.add_destroyed(resource);
+            }
+        }
+    };
+}
+pub(crate) use server_dispatch;
+
+// Stand-ins for objects of other protocols
+use wayland_client::protocol::{
+    wl_output as c_wl_output, wl_seat as c_wl_seat, wl_surface as c_wl_surface,
+};
+use wayland_protocols::{
+    ext::{
+        foreign_toplevel_list::v1::{
+            client::ext_foreign_toplevel_handle_v1 as c_ext_toplevel,
+            server::ext_foreign_toplevel_handle_v1 as s_ext_toplevel,
+        },
+        workspace::v1::{
+            client::ext_workspace_handle_v1 as c_ext_workspace,
+            server::ext_workspace_handle_v1 as s_ext_workspace,
+        },
+    },
+    xdg::shell::{client::xdg_toplevel as c_xdg_toplevel, server::xdg_toplevel as s_xdg_toplevel},
+};
+use wayland_protocols_wlr::{
+    layer_shell::v1::{
+        client::zwlr_layer_surface_v1 as c_layer_surface,
+        server::zwlr_layer_surface_v1 as s_layer_surface,
+    },
+    output_management::v1::{
+        client::{
+            zwlr_output_configuration_head_v1 as c_wlr_config_head,
+            zwlr_output_configuration_v1 as c_wlr_config, zwlr_output_head_v1 as c_wlr_head,
+        },
+        server::{
+            zwlr_output_configuration_head_v1 as s_wlr_config_head,
+            zwlr_output_configuration_v1 as s_wlr_config, zwlr_output_head_v1 as s_wlr_head,
+        },
+    },
+};
+use wayland_server::protocol::{
+    wl_output as s_wl_output, wl_seat as s_wl_seat, wl_surface as s_wl_surface,
+};
+
+server_dispatch!(s_wl_output::WlOutput);
+server_dispatch!(s_wl_seat::WlSeat);
+server_dispatch!(s_wl_surface::WlSurface);
+server_dispatch!(s_xdg_toplevel::XdgToplevel);
+server_dispatch!(s_layer_surface::ZwlrLayerSurfaceV1);
+server_dispatch!(s_ext_toplevel::ExtForeignToplevelHandleV1);
+server_dispatch!(s_ext_workspace::ExtWorkspaceHandleV1);
+server_dispatch!(s_wlr_head::ZwlrOutputHeadV1);
+server_dispatch!(s_wlr_config::ZwlrOutputConfigurationV1);
+server_dispatch!(s_wlr_config_head::ZwlrOutputConfigurationHeadV1);
+
+client_dispatch!(c_wl_output::WlOutput);
+client_dispatch!(c_wl_seat::WlSeat);
+client_dispatch!(c_wl_surface::WlSurface);
+client_dispatch!(c_xdg_toplevel::XdgToplevel);
+client_dispatch!(c_layer_surface::ZwlrLayerSurfaceV1);
+client_dispatch!(c_ext_toplevel::ExtForeignToplevelHandleV1);
+client_dispatch!(c_ext_workspace::ExtWorkspaceHandleV1);
+client_dispatch!(c_wlr_head::ZwlrOutputHeadV1);
+client_dispatch!(c_wlr_config::ZwlrOutputConfigurationV1);
+client_dispatch!(c_wlr_config_head::ZwlrOutputConfigurationHeadV1);
+
+/// Connected client and server
+pub struct Harness {
+    pub server: Server,
+    connection: Connection<Server>,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        let display = ws::Display::new().expect("Failed to create display");
+        let mut harness = Self {
+            server: Server::default(),
+            connection: Connection::new(display),
+        };
+        harness.roundtrip();
+        harness
+    }
+
+    /// Exchange messages until both sides are idle
+    pub fn roundtrip(&mut self) {
+        self.connection.roundtrip(&mut self.server);
+        if let Some(error) = self.connection.protocol_error() {
+            panic!("Protocol error: {error:?}");
+        }
+    }
+
+    /// Advertise a global at the highest version of its interface, and bind it at `version`,
+    /// returning the client and server objects
+    pub fn bind<C, S>(&mut self, version: u32) -> (C, S)
+    where
+        C: Proxy + 'static,
+        S: Resource + 'static,
+        Client: wc::Dispatch<C, ()>,
+        Server: ws::Dispatch<S, ()>,
+    {
+        let interface = S::interface();
+        assert_eq!(C::interface().name, interface.name);
+        self.display
+            .handle()
+            .create_global::<Server, S, ()>(interface.version, ());
+        self.roundtrip();
+        let proxy = self.connection.bind::<C>(version);
+        self.roundtrip();
+        (proxy, self.resource::<S>())
+    }
+
+    /// Create an object on the server, to be sent to the client in an event
+    pub fn create_resource<S>(&mut self, version: u32) -> S
+    where
+        S: Resource + 'static,
+        Server: ws::Dispatch<S, ()>,
+    {
+        let resource = self.connection.create_resource::<S>(version);
+        self.server.add_object(&resource);
+        resource
+    }
+
+    /// Latest server object of an interface
+    pub fn resource<S: Resource + 'static>(&self) -> S {
+        let dh = self.display.handle();
+        self.server
+            .objects
+            .iter()
+            .rev()
+            .filter(|id| id.interface().name == S::interface().name)
+            .find_map(|id| S::from_id(&dh, id.clone()).ok())
+            .unwrap_or_else(|| panic!("No {} on the server", S::interface().name))
+    }
+
+    /// Requests received by the server since the last call
+    pub fn take_requests(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.server.requests)
+    }
+
+    /// Whether the server got the destruction of an object
+    pub fn destroyed(&self, resource: &impl Resource) -> bool {
+        self.server.destroyed.contains(&resource.id())
+    }
+}
+
+impl Deref for Harness {
+    type Target = Connection<Server>;
+
+    fn deref(&self) -> &Connection<Server> {
+        &self.connection
+    }
+}
+
+impl DerefMut for Harness {
+    fn deref_mut(&mut self) -> &mut Connection<Server> {
+        &mut self.connection
+    }
+}
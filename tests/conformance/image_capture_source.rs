@@ -0,0 +1,81 @@
+use cosmic_protocols::image_capture_source::v1::{
+    client::zcosmic_workspace_image_capture_source_manager_v1 as c_manager,
+    server::zcosmic_workspace_image_capture_source_manager_v1 as s_manager,
+};
+use wayland_client::Proxy;
+use wayland_protocols::ext::{
+    image_capture_source::v1::{
+        client::ext_image_capture_source_v1 as c_source,
+        server::ext_image_capture_source_v1 as s_source,
+    },
+    workspace::v1::{
+        client::ext_workspace_handle_v1 as c_workspace,
+        server::ext_workspace_handle_v1 as s_workspace,
+    },
+};
+use wayland_server::Resource;
+
+use crate::harness::{
+    Harness, assert_destructor, assert_interface, client_dispatch, names, server_dispatch,
+};
+
+server_dispatch!(
+    s_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+    CreateSource(source)
+);
+server_dispatch!(s_source::ExtImageCaptureSourceV1);
+client_dispatch!(c_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1);
+client_dispatch!(c_source::ExtImageCaptureSourceV1);
+
+#[test]
+fn interfaces() {
+    assert_interface(
+        s_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1::interface(),
+        1,
+        &[("create_source", 1), ("destroy", 1)],
+        &[],
+    );
+    assert_destructor(
+        s_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1::interface(),
+        "destroy",
+    );
+}
+
+#[test]
+fn messages() {
+    let mut h = Harness::new();
+    let (manager, s_manager) = h.bind::<
+        c_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+        s_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+    >(1);
+    let (workspace, s_workspace) =
+        h.bind::<c_workspace::ExtWorkspaceHandleV1, s_workspace::ExtWorkspaceHandleV1>(1);
+
+    let source = manager.create_source(&workspace, &h.qh, ());
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_requests()),
+        ["zcosmic_workspace_image_capture_source_manager_v1.create_source"]
+    );
+    let s_source = h.resource::<s_source::ExtImageCaptureSourceV1>();
+    assert_eq!(s_source.id().protocol_id(), source.id().protocol_id());
+
+    // Sources outlive the manager
+    manager.destroy();
+    assert!(!manager.is_alive());
+    h.roundtrip();
+    assert!(h.destroyed(&s_manager));
+    assert!(s_source.is_alive());
+
+    source.destroy();
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_requests()),
+        [
+            "zcosmic_workspace_image_capture_source_manager_v1.destroy",
+            "ext_image_capture_source_v1.destroy",
+        ]
+    );
+    assert!(h.destroyed(&s_source));
+    assert!(s_workspace.is_alive());
+}
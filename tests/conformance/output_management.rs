@@ -0,0 +1,254 @@
+use cosmic_protocols::output_management::v1::{
+    client::{
+        zcosmic_output_configuration_head_v1 as c_config_head,
+        zcosmic_output_configuration_v1 as c_config, zcosmic_output_head_v1 as c_head,
+        zcosmic_output_manager_v1 as c_manager,
+    },
+    server::{
+        zcosmic_output_configuration_head_v1 as s_config_head,
+        zcosmic_output_configuration_v1 as s_config, zcosmic_output_head_v1 as s_head,
+        zcosmic_output_manager_v1 as s_manager,
+    },
+};
+use wayland_client::Proxy;
+use wayland_protocols_wlr::output_management::v1::{
+    client::{
+        zwlr_output_configuration_head_v1 as c_wlr_config_head,
+        zwlr_output_configuration_v1 as c_wlr_config, zwlr_output_head_v1 as c_wlr_head,
+    },
+    server::{
+        zwlr_output_configuration_head_v1 as s_wlr_config_head,
+        zwlr_output_configuration_v1 as s_wlr_config, zwlr_output_head_v1 as s_wlr_head,
+    },
+};
+use wayland_server::{Resource, WEnum};
+
+use crate::harness::{
+    Harness, assert_destructor, assert_interface, client_dispatch, names, server_dispatch,
+};
+
+server_dispatch!(
+    s_manager::ZcosmicOutputManagerV1,
+    GetHead(extended),
+    GetConfiguration(extended),
+    GetConfigurationHead(extended),
+);
+server_dispatch!(s_head::ZcosmicOutputHeadV1);
+server_dispatch!(s_config::ZcosmicOutputConfigurationV1, MirrorHead(id));
+server_dispatch!(s_config_head::ZcosmicOutputConfigurationHeadV1);
+client_dispatch!(c_manager::ZcosmicOutputManagerV1);
+client_dispatch!(c_head::ZcosmicOutputHeadV1);
+client_dispatch!(c_config::ZcosmicOutputConfigurationV1);
+client_dispatch!(c_config_head::ZcosmicOutputConfigurationHeadV1);
+
+#[test]
+fn interfaces() {
+    assert_interface(
+        s_manager::ZcosmicOutputManagerV1::interface(),
+        3,
+        &[
+            ("get_head", 1),
+            ("get_configuration", 1),
+            ("get_configuration_head", 1),
+            ("release", 1),
+            ("set_xwayland_primary", 3),
+        ],
+        &[],
+    );
+    assert_interface(
+        s_head::ZcosmicOutputHeadV1::interface(),
+        3,
+        &[("release", 1)],
+        &[
+            ("scale_1000", 1),
+            ("mirroring", 1),
+            ("adaptive_sync_available", 2),
+            ("adaptive_sync_ext", 2),
+            ("xwayland_primary", 3),
+        ],
+    );
+    assert_interface(
+        s_config::ZcosmicOutputConfigurationV1::interface(),
+        1,
+        &[("mirror_head", 1), ("release", 1)],
+        &[("finished", 1)],
+    );
+    assert_interface(
+        s_config_head::ZcosmicOutputConfigurationHeadV1::interface(),
+        2,
+        &[
+            ("set_scale_1000", 1),
+            ("release", 1),
+            ("set_adaptive_sync_ext", 2),
+        ],
+        &[],
+    );
+    assert_destructor(s_manager::ZcosmicOutputManagerV1::interface(), "release");
+    assert_destructor(s_head::ZcosmicOutputHeadV1::interface(), "release");
+    assert_destructor(
+        s_config::ZcosmicOutputConfigurationV1::interface(),
+        "release",
+    );
+    assert_destructor(
+        s_config_head::ZcosmicOutputConfigurationHeadV1::interface(),
+        "release",
+    );
+}
+
+#[test]
+fn messages() {
+    for version in 1..=3 {
+        let mut h = Harness::new();
+        let (manager, s_manager) =
+            h.bind::<c_manager::ZcosmicOutputManagerV1, s_manager::ZcosmicOutputManagerV1>(version);
+        let (wlr_head, _) = h.bind::<c_wlr_head::ZwlrOutputHeadV1, s_wlr_head::ZwlrOutputHeadV1>(4);
+        let (wlr_mirrored, _) =
+            h.bind::<c_wlr_head::ZwlrOutputHeadV1, s_wlr_head::ZwlrOutputHeadV1>(4);
+        let (wlr_config, _) = h.bind::<
+            c_wlr_config::ZwlrOutputConfigurationV1,
+            s_wlr_config::ZwlrOutputConfigurationV1,
+        >(4);
+        let (wlr_config_head, _) = h.bind::<
+            c_wlr_config_head::ZwlrOutputConfigurationHeadV1,
+            s_wlr_config_head::ZwlrOutputConfigurationHeadV1,
+        >(4);
+
+        // Extension objects
+        let head = manager.get_head(&wlr_head, &h.qh, ());
+        let config = manager.get_configuration(&wlr_config, &h.qh, ());
+        let config_head = manager.get_configuration_head(&wlr_config_head, &h.qh, ());
+        if version >= 3 {
+            manager.set_xwayland_primary(Some(&head));
+            manager.set_xwayland_primary(None);
+        }
+        h.roundtrip();
+        let mut expected = vec![
+            "zcosmic_output_manager_v1.get_head",
+            "zcosmic_output_manager_v1.get_configuration",
+            "zcosmic_output_manager_v1.get_configuration_head",
+        ];
+        if version >= 3 {
+            expected.extend([
+                "zcosmic_output_manager_v1.set_xwayland_primary",
+                "zcosmic_output_manager_v1.set_xwayland_primary",
+            ]);
+        }
+        assert_eq!(names(&h.take_requests()), expected);
+        let s_head = h.resource::<s_head::ZcosmicOutputHeadV1>();
+        let s_config = h.resource::<s_config::ZcosmicOutputConfigurationV1>();
+        let s_config_head = h.resource::<s_config_head::ZcosmicOutputConfigurationHeadV1>();
+        // Objects created by requests have the version of the manager
+        assert_eq!(s_head.version(), version);
+        assert_eq!(s_config.version(), version);
+        assert_eq!(s_config_head.version(), version);
+
+        // Head
+        s_head.scale_1000(1250);
+        s_head.mirroring(Some("DP-1".into()));
+        s_head.mirroring(None);
+        if version >= 2 {
+            s_head.adaptive_sync_available(s_head::AdaptiveSyncAvailability::RequiresModeset);
+            s_head.adaptive_sync_ext(s_head::AdaptiveSyncStateExt::Automatic);
+        }
+        if version >= 3 {
+            s_head.xwayland_primary(1);
+        }
+        h.roundtrip();
+        let events = h.take_events();
+        let mut expected = vec![
+            "zcosmic_output_head_v1.scale_1000",
+            "zcosmic_output_head_v1.mirroring",
+            "zcosmic_output_head_v1.mirroring",
+        ];
+        if version >= 2 {
+            expected.extend([
+                "zcosmic_output_head_v1.adaptive_sync_available",
+                "zcosmic_output_head_v1.adaptive_sync_ext",
+            ]);
+        }
+        if version >= 3 {
+            expected.push("zcosmic_output_head_v1.xwayland_primary");
+        }
+        assert_eq!(names(&events), expected);
+        assert_eq!(
+            events[2].args,
+            format!("{:?}", c_head::Event::Mirroring { name: None })
+        );
+        if version >= 2 {
+            assert_eq!(
+                events[4].args,
+                format!(
+                    "{:?}",
+                    c_head::Event::AdaptiveSyncExt {
+                        state: WEnum::Value(c_head::AdaptiveSyncStateExt::Automatic)
+                    }
+                )
+            );
+        }
+
+        // Configuration
+        let mirror_head = config.mirror_head(&wlr_head, &wlr_mirrored, &h.qh, ());
+        config_head.set_scale_1000(2000);
+        if version >= 2 {
+            config_head.set_adaptive_sync_ext(c_head::AdaptiveSyncStateExt::Always);
+        }
+        h.roundtrip();
+        let requests = h.take_requests();
+        let mut expected = vec![
+            "zcosmic_output_configuration_v1.mirror_head",
+            "zcosmic_output_configuration_head_v1.set_scale_1000",
+        ];
+        if version >= 2 {
+            expected.push("zcosmic_output_configuration_head_v1.set_adaptive_sync_ext");
+        }
+        assert_eq!(names(&requests), expected);
+        if version >= 2 {
+            assert_eq!(
+                requests[2].args,
+                format!(
+                    "{:?}",
+                    s_config_head::Request::SetAdaptiveSyncExt {
+                        state: WEnum::Value(s_head::AdaptiveSyncStateExt::Always)
+                    }
+                )
+            );
+        }
+        let s_mirror_head = h.resource::<s_wlr_config_head::ZwlrOutputConfigurationHeadV1>();
+        assert_eq!(
+            s_mirror_head.id().protocol_id(),
+            mirror_head.id().protocol_id()
+        );
+
+        s_config.finished();
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_events()),
+            ["zcosmic_output_configuration_v1.finished"]
+        );
+
+        // Releasing the manager leaves the extension objects alive
+        manager.release();
+        h.roundtrip();
+        assert!(h.destroyed(&s_manager));
+        assert!(s_head.is_alive() && s_config.is_alive() && s_config_head.is_alive());
+
+        config_head.release();
+        config.release();
+        head.release();
+        assert!(!head.is_alive());
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_requests()),
+            [
+                "zcosmic_output_manager_v1.release",
+                "zcosmic_output_configuration_head_v1.release",
+                "zcosmic_output_configuration_v1.release",
+                "zcosmic_output_head_v1.release",
+            ]
+        );
+        assert!(h.destroyed(&s_head) && h.destroyed(&s_config) && h.destroyed(&s_config_head));
+        // The wlr objects are untouched
+        assert!(s_mirror_head.is_alive());
+        assert!(wlr_head.is_alive() && wlr_config.is_alive() && wlr_config_head.is_alive());
+    }
+}
@@ -0,0 +1,147 @@
+use cosmic_protocols::overlap_notify::v1::{
+    client::{
+        zcosmic_overlap_notification_v1 as c_notification, zcosmic_overlap_notify_v1 as c_notify,
+    },
+    server::{
+        zcosmic_overlap_notification_v1 as s_notification, zcosmic_overlap_notify_v1 as s_notify,
+    },
+};
+use wayland_client::Proxy;
+use wayland_protocols::ext::foreign_toplevel_list::v1::{
+    client::ext_foreign_toplevel_handle_v1 as c_ext_toplevel,
+    server::ext_foreign_toplevel_handle_v1 as s_ext_toplevel,
+};
+use wayland_protocols_wlr::layer_shell::v1::{
+    client::{zwlr_layer_shell_v1 as c_layer_shell, zwlr_layer_surface_v1 as c_layer_surface},
+    server::{zwlr_layer_shell_v1 as s_layer_shell, zwlr_layer_surface_v1 as s_layer_surface},
+};
+use wayland_server::{Resource, WEnum};
+
+use crate::harness::{
+    Harness, assert_destructor, assert_interface, client_dispatch, names, server_dispatch,
+};
+
+server_dispatch!(
+    s_notify::ZcosmicOverlapNotifyV1,
+    NotifyOnOverlap(overlap_notification)
+);
+server_dispatch!(s_notification::ZcosmicOverlapNotificationV1);
+client_dispatch!(c_notify::ZcosmicOverlapNotifyV1);
+client_dispatch!(c_notification::ZcosmicOverlapNotificationV1);
+
+#[test]
+fn interfaces() {
+    assert_interface(
+        s_notify::ZcosmicOverlapNotifyV1::interface(),
+        1,
+        &[("notify_on_overlap", 1)],
+        &[],
+    );
+    assert_interface(
+        s_notification::ZcosmicOverlapNotificationV1::interface(),
+        1,
+        &[("destroy", 1)],
+        &[
+            ("toplevel_enter", 1),
+            ("toplevel_leave", 1),
+            ("layer_enter", 1),
+            ("layer_leave", 1),
+        ],
+    );
+    assert_destructor(
+        s_notification::ZcosmicOverlapNotificationV1::interface(),
+        "destroy",
+    );
+}
+
+#[test]
+fn messages() {
+    let mut h = Harness::new();
+    let (notify, _) =
+        h.bind::<c_notify::ZcosmicOverlapNotifyV1, s_notify::ZcosmicOverlapNotifyV1>(1);
+    let (layer_surface, s_layer_surface) =
+        h.bind::<c_layer_surface::ZwlrLayerSurfaceV1, s_layer_surface::ZwlrLayerSurfaceV1>(5);
+    let (toplevel, s_toplevel) = h.bind::<
+        c_ext_toplevel::ExtForeignToplevelHandleV1,
+        s_ext_toplevel::ExtForeignToplevelHandleV1,
+    >(1);
+
+    let notification = notify.notify_on_overlap(&layer_surface, &h.qh, ());
+    h.roundtrip();
+    let requests = h.take_requests();
+    assert_eq!(
+        names(&requests),
+        ["zcosmic_overlap_notify_v1.notify_on_overlap"]
+    );
+    let s_notification = h.resource::<s_notification::ZcosmicOverlapNotificationV1>();
+    assert!(
+        requests[0]
+            .args
+            .contains(&format!("{:?}", s_layer_surface.id()))
+    );
+
+    s_notification.toplevel_enter(&s_toplevel, 1, 2, 3, 4);
+    s_notification.layer_enter(
+        "panel".into(),
+        "cosmic-panel".into(),
+        1,
+        s_layer_shell::Layer::Top,
+        5,
+        6,
+        7,
+        8,
+    );
+    s_notification.toplevel_leave(&s_toplevel);
+    s_notification.layer_leave("panel".into());
+    h.roundtrip();
+    let events = h.take_events();
+    assert_eq!(
+        names(&events),
+        [
+            "zcosmic_overlap_notification_v1.toplevel_enter",
+            "zcosmic_overlap_notification_v1.layer_enter",
+            "zcosmic_overlap_notification_v1.toplevel_leave",
+            "zcosmic_overlap_notification_v1.layer_leave",
+        ]
+    );
+    assert_eq!(
+        events[0].args,
+        format!(
+            "{:?}",
+            c_notification::Event::ToplevelEnter {
+                toplevel: toplevel.clone(),
+                x: 1,
+                y: 2,
+                width: 3,
+                height: 4,
+            }
+        )
+    );
+    assert_eq!(
+        events[1].args,
+        format!(
+            "{:?}",
+            c_notification::Event::LayerEnter {
+                identifier: "panel".into(),
+                namespace: "cosmic-panel".into(),
+                exclusive: 1,
+                layer: WEnum::Value(c_layer_shell::Layer::Top),
+                x: 5,
+                y: 6,
+                width: 7,
+                height: 8,
+            }
+        )
+    );
+
+    notification.destroy();
+    assert!(!notification.is_alive());
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_requests()),
+        ["zcosmic_overlap_notification_v1.destroy"]
+    );
+    assert!(h.destroyed(&s_notification));
+    // The layer surface isn't affected
+    assert!(s_layer_surface.is_alive());
+}
@@ -0,0 +1,221 @@
+use cosmic_protocols::{
+    toplevel_info::v1::{
+        client::{zcosmic_toplevel_handle_v1 as c_handle, zcosmic_toplevel_info_v1 as c_info},
+        server::{zcosmic_toplevel_handle_v1 as s_handle, zcosmic_toplevel_info_v1 as s_info},
+    },
+    workspace::v1::{
+        client::zcosmic_workspace_handle_v1 as c_workspace,
+        server::zcosmic_workspace_handle_v1 as s_workspace,
+    },
+};
+use wayland_client::{
+    Connection, Dispatch, Proxy, QueueHandle, event_created_child,
+    protocol::wl_output as c_wl_output,
+};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::{
+        client::ext_foreign_toplevel_handle_v1 as c_ext_toplevel,
+        server::ext_foreign_toplevel_handle_v1 as s_ext_toplevel,
+    },
+    workspace::v1::{
+        client::ext_workspace_handle_v1 as c_ext_workspace,
+        server::ext_workspace_handle_v1 as s_ext_workspace,
+    },
+};
+use wayland_server::{Resource, protocol::wl_output as s_wl_output};
+
+use crate::harness::{
+    Client, Harness, assert_destructor, assert_interface, client_dispatch, names, server_dispatch,
+};
+
+server_dispatch!(
+    s_info::ZcosmicToplevelInfoV1,
+    GetCosmicToplevel(cosmic_toplevel)
+);
+server_dispatch!(s_handle::ZcosmicToplevelHandleV1);
+client_dispatch!(c_handle::ZcosmicToplevelHandleV1);
+
+impl Dispatch<c_info::ZcosmicToplevelInfoV1, ()> for Client {
+    fn event(
+        state: &mut Self,
+        proxy: &c_info::ZcosmicToplevelInfoV1,
+        event: c_info::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        state.record(proxy, event.opcode(), format!("{event:?}"));
+        if let c_info::Event::Toplevel { toplevel } = event {
+            state.add_object(&toplevel);
+        }
+    }
+
+    event_created_child!(Client, c_info::ZcosmicToplevelInfoV1, [
+        c_info::EVT_TOPLEVEL_OPCODE => (c_handle::ZcosmicToplevelHandleV1, ()),
+    ]);
+}
+
+#[test]
+fn interfaces() {
+    assert_interface(
+        s_info::ZcosmicToplevelInfoV1::interface(),
+        3,
+        &[("stop", 1), ("get_cosmic_toplevel", 2)],
+        &[("toplevel", 1), ("finished", 1), ("done", 2)],
+    );
+    assert_interface(
+        s_handle::ZcosmicToplevelHandleV1::interface(),
+        3,
+        &[("destroy", 1)],
+        &[
+            ("closed", 1),
+            ("done", 1),
+            ("title", 1),
+            ("app_id", 1),
+            ("output_enter", 1),
+            ("output_leave", 1),
+            ("workspace_enter", 1),
+            ("workspace_leave", 1),
+            ("state", 1),
+            ("geometry", 2),
+            ("ext_workspace_enter", 3),
+            ("ext_workspace_leave", 3),
+        ],
+    );
+    assert_destructor(s_handle::ZcosmicToplevelHandleV1::interface(), "destroy");
+}
+
+#[test]
+fn messages() {
+    for version in 1..=3 {
+        let mut h = Harness::new();
+        let (info, s_info) =
+            h.bind::<c_info::ZcosmicToplevelInfoV1, s_info::ZcosmicToplevelInfoV1>(version);
+        let (_, s_output) = h.bind::<c_wl_output::WlOutput, s_wl_output::WlOutput>(4);
+        let (_, s_workspace) = h
+            .bind::<c_workspace::ZcosmicWorkspaceHandleV1, s_workspace::ZcosmicWorkspaceHandleV1>(
+                2,
+            );
+        let (_, s_ext_workspace) = h
+            .bind::<c_ext_workspace::ExtWorkspaceHandleV1, s_ext_workspace::ExtWorkspaceHandleV1>(
+                1,
+            );
+        let (ext_toplevel, _) = h.bind::<
+            c_ext_toplevel::ExtForeignToplevelHandleV1,
+            s_ext_toplevel::ExtForeignToplevelHandleV1,
+        >(1);
+
+        // Deprecated creation of handles by the compositor
+        let s_handle = h.create_resource::<s_handle::ZcosmicToplevelHandleV1>(version);
+        s_info.toplevel(&s_handle);
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_events()),
+            ["zcosmic_toplevel_info_v1.toplevel"]
+        );
+        let handle = h.proxy::<c_handle::ZcosmicToplevelHandleV1>();
+        assert_eq!(handle.id().protocol_id(), s_handle.id().protocol_id());
+        assert_eq!(handle.version(), version);
+
+        let mut expected = vec![
+            "zcosmic_toplevel_handle_v1.title",
+            "zcosmic_toplevel_handle_v1.app_id",
+            "zcosmic_toplevel_handle_v1.output_enter",
+            "zcosmic_toplevel_handle_v1.workspace_enter",
+            "zcosmic_toplevel_handle_v1.state",
+        ];
+        s_handle.title("Terminal".into());
+        s_handle.app_id("com.system76.CosmicTerm".into());
+        s_handle.output_enter(&s_output);
+        s_handle.workspace_enter(&s_workspace);
+        s_handle.state(2u32.to_ne_bytes().to_vec());
+        if version >= 2 {
+            s_handle.geometry(&s_output, 1, 2, 3, 4);
+            expected.push("zcosmic_toplevel_handle_v1.geometry");
+        }
+        if version >= 3 {
+            s_handle.ext_workspace_enter(&s_ext_workspace);
+            s_handle.ext_workspace_leave(&s_ext_workspace);
+            expected.extend([
+                "zcosmic_toplevel_handle_v1.ext_workspace_enter",
+                "zcosmic_toplevel_handle_v1.ext_workspace_leave",
+            ]);
+        }
+        s_handle.workspace_leave(&s_workspace);
+        s_handle.output_leave(&s_output);
+        s_handle.done();
+        expected.extend([
+            "zcosmic_toplevel_handle_v1.workspace_leave",
+            "zcosmic_toplevel_handle_v1.output_leave",
+            "zcosmic_toplevel_handle_v1.done",
+        ]);
+        if version >= 2 {
+            s_info.done();
+            expected.push("zcosmic_toplevel_info_v1.done");
+        }
+        h.roundtrip();
+        let events = h.take_events();
+        assert_eq!(names(&events), expected);
+        assert_eq!(
+            events[0].args,
+            format!(
+                "{:?}",
+                c_handle::Event::Title {
+                    title: "Terminal".into()
+                }
+            )
+        );
+        assert_eq!(
+            events[4].args,
+            format!(
+                "{:?}",
+                c_handle::Event::State {
+                    state: 2u32.to_ne_bytes().to_vec()
+                }
+            )
+        );
+
+        // Handles for `ext_foreign_toplevel_handle_v1`
+        if version >= 2 {
+            let cosmic_toplevel = info.get_cosmic_toplevel(&ext_toplevel, &h.qh, ());
+            h.roundtrip();
+            assert_eq!(
+                names(&h.take_requests()),
+                ["zcosmic_toplevel_info_v1.get_cosmic_toplevel"]
+            );
+            let s_cosmic_toplevel = h.resource::<s_handle::ZcosmicToplevelHandleV1>();
+            assert_eq!(s_cosmic_toplevel.version(), version);
+            cosmic_toplevel.destroy();
+            h.roundtrip();
+            assert!(h.destroyed(&s_cosmic_toplevel));
+            h.take_requests();
+        }
+
+        s_handle.closed();
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_events()),
+            ["zcosmic_toplevel_handle_v1.closed"]
+        );
+        handle.destroy();
+        assert!(!handle.is_alive());
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_requests()),
+            ["zcosmic_toplevel_handle_v1.destroy"]
+        );
+        assert!(h.destroyed(&s_handle));
+        assert!(!s_handle.is_alive());
+
+        // Deprecated stop
+        info.stop();
+        h.roundtrip();
+        assert_eq!(names(&h.take_requests()), ["zcosmic_toplevel_info_v1.stop"]);
+        s_info.finished();
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_events()),
+            ["zcosmic_toplevel_info_v1.finished"]
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use cosmic_protocols::{
+    toplevel_info::v1::{
+        client::zcosmic_toplevel_handle_v1 as c_handle,
+        server::zcosmic_toplevel_handle_v1 as s_handle,
+    },
+    toplevel_management::v1::{
+        client::zcosmic_toplevel_manager_v1 as c_manager,
+        server::zcosmic_toplevel_manager_v1 as s_manager,
+    },
+    workspace::v1::{
+        client::zcosmic_workspace_handle_v1 as c_workspace,
+        server::zcosmic_workspace_handle_v1 as s_workspace,
+    },
+};
+use wayland_client::{
+    Proxy,
+    protocol::{wl_output as c_wl_output, wl_seat as c_wl_seat, wl_surface as c_wl_surface},
+};
+use wayland_protocols::ext::workspace::v1::{
+    client::ext_workspace_handle_v1 as c_ext_workspace,
+    server::ext_workspace_handle_v1 as s_ext_workspace,
+};
+use wayland_server::{
+    Resource,
+    protocol::{wl_output as s_wl_output, wl_seat as s_wl_seat, wl_surface as s_wl_surface},
+};
+
+use crate::harness::{
+    Harness, assert_destructor, assert_interface, client_dispatch, names, server_dispatch,
+};
+
+server_dispatch!(s_manager::ZcosmicToplevelManagerV1);
+client_dispatch!(c_manager::ZcosmicToplevelManagerV1);
+
+#[test]
+fn interfaces() {
+    assert_interface(
+        s_manager::ZcosmicToplevelManagerV1::interface(),
+        4,
+        &[
+            ("destroy", 1),
+            ("close", 1),
+            ("activate", 1),
+            ("set_maximized", 1),
+            ("unset_maximized", 1),
+            ("set_minimized", 1),
+            ("unset_minimized", 1),
+            ("set_fullscreen", 1),
+            ("unset_fullscreen", 1),
+            ("set_rectangle", 1),
+            ("move_to_workspace", 2),
+            ("set_sticky", 3),
+            ("unset_sticky", 3),
+            ("move_to_ext_workspace", 4),
+        ],
+        &[("capabilities", 1)],
+    );
+    assert_destructor(s_manager::ZcosmicToplevelManagerV1::interface(), "destroy");
+}
+
+#[test]
+fn messages() {
+    for version in 1..=4 {
+        let mut h = Harness::new();
+        let (manager, s_manager) = h
+            .bind::<c_manager::ZcosmicToplevelManagerV1, s_manager::ZcosmicToplevelManagerV1>(
+                version,
+            );
+        let (toplevel, s_toplevel) =
+            h.bind::<c_handle::ZcosmicToplevelHandleV1, s_handle::ZcosmicToplevelHandleV1>(3);
+        let (seat, _) = h.bind::<c_wl_seat::WlSeat, s_wl_seat::WlSeat>(1);
+        let (surface, _) = h.bind::<c_wl_surface::WlSurface, s_wl_surface::WlSurface>(1);
+        let (output, _) = h.bind::<c_wl_output::WlOutput, s_wl_output::WlOutput>(4);
+        let (workspace, _) = h
+            .bind::<c_workspace::ZcosmicWorkspaceHandleV1, s_workspace::ZcosmicWorkspaceHandleV1>(
+                2,
+            );
+        let (ext_workspace, _) = h
+            .bind::<c_ext_workspace::ExtWorkspaceHandleV1, s_ext_workspace::ExtWorkspaceHandleV1>(
+                1,
+            );
+
+        s_manager.capabilities([1u32, 2, 7].iter().flat_map(|c| c.to_ne_bytes()).collect());
+        h.roundtrip();
+        let events = h.take_events();
+        assert_eq!(names(&events), ["zcosmic_toplevel_manager_v1.capabilities"]);
+
+        manager.close(&toplevel);
+        manager.activate(&toplevel, &seat);
+        manager.set_maximized(&toplevel);
+        manager.unset_maximized(&toplevel);
+        manager.set_minimized(&toplevel);
+        manager.unset_minimized(&toplevel);
+        manager.set_fullscreen(&toplevel, Some(&output));
+        manager.set_fullscreen(&toplevel, None);
+        manager.unset_fullscreen(&toplevel);
+        manager.set_rectangle(&toplevel, &surface, 1, 2, 3, 4);
+        let mut expected = vec![
+            "zcosmic_toplevel_manager_v1.close",
+            "zcosmic_toplevel_manager_v1.activate",
+            "zcosmic_toplevel_manager_v1.set_maximized",
+            "zcosmic_toplevel_manager_v1.unset_maximized",
+            "zcosmic_toplevel_manager_v1.set_minimized",
+            "zcosmic_toplevel_manager_v1.unset_minimized",
+            "zcosmic_toplevel_manager_v1.set_fullscreen",
+            "zcosmic_toplevel_manager_v1.set_fullscreen",
+            "zcosmic_toplevel_manager_v1.unset_fullscreen",
+            "zcosmic_toplevel_manager_v1.set_rectangle",
+        ];
+        if version >= 2 {
+            // Deprecated in favor of `move_to_ext_workspace`
+            manager.move_to_workspace(&toplevel, &workspace, &output);
+            expected.push("zcosmic_toplevel_manager_v1.move_to_workspace");
+        }
+        if version >= 3 {
+            manager.set_sticky(&toplevel);
+            manager.unset_sticky(&toplevel);
+            expected.extend([
+                "zcosmic_toplevel_manager_v1.set_sticky",
+                "zcosmic_toplevel_manager_v1.unset_sticky",
+            ]);
+        }
+        if version >= 4 {
+            manager.move_to_ext_workspace(&toplevel, &ext_workspace, &output);
+            expected.push("zcosmic_toplevel_manager_v1.move_to_ext_workspace");
+        }
+        h.roundtrip();
+        let requests = h.take_requests();
+        assert_eq!(names(&requests), expected);
+        assert!(requests[7].args.contains("output: None"));
+        assert!(
+            requests
+                .iter()
+                .all(|r| r.args.contains(&format!("{:?}", s_toplevel.id())))
+        );
+
+        manager.destroy();
+        assert!(!manager.is_alive());
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_requests()),
+            ["zcosmic_toplevel_manager_v1.destroy"]
+        );
+        assert!(h.destroyed(&s_manager));
+        // Toplevels belong to `zcosmic_toplevel_info_v1`
+        assert!(s_toplevel.is_alive());
+    }
+}
@@ -0,0 +1,255 @@
+use cosmic_protocols::workspace::v1::{
+    client::{
+        zcosmic_workspace_group_handle_v1 as c_group, zcosmic_workspace_handle_v1 as c_workspace,
+        zcosmic_workspace_manager_v1 as c_manager,
+    },
+    server::{
+        zcosmic_workspace_group_handle_v1 as s_group, zcosmic_workspace_handle_v1 as s_workspace,
+        zcosmic_workspace_manager_v1 as s_manager,
+    },
+};
+use wayland_client::{
+    Connection, Dispatch, Proxy, QueueHandle, event_created_child,
+    protocol::wl_output as c_wl_output,
+};
+use wayland_server::{Resource, WEnum, protocol::wl_output as s_wl_output};
+
+use crate::harness::{
+    Client, Harness, assert_destructor, assert_interface, client_dispatch, names, server_dispatch,
+};
+
+server_dispatch!(s_manager::ZcosmicWorkspaceManagerV1);
+server_dispatch!(s_group::ZcosmicWorkspaceGroupHandleV1);
+server_dispatch!(s_workspace::ZcosmicWorkspaceHandleV1);
+client_dispatch!(c_workspace::ZcosmicWorkspaceHandleV1);
+
+impl Dispatch<c_manager::ZcosmicWorkspaceManagerV1, ()> for Client {
+    fn event(
+        state: &mut Self,
+        proxy: &c_manager::ZcosmicWorkspaceManagerV1,
+        event: c_manager::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        state.record(proxy, event.opcode(), format!("{event:?}"));
+        if let c_manager::Event::WorkspaceGroup { workspace_group } = event {
+            state.add_object(&workspace_group);
+        }
+    }
+
+    event_created_child!(Client, c_manager::ZcosmicWorkspaceManagerV1, [
+        c_manager::EVT_WORKSPACE_GROUP_OPCODE => (c_group::ZcosmicWorkspaceGroupHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<c_group::ZcosmicWorkspaceGroupHandleV1, ()> for Client {
+    fn event(
+        state: &mut Self,
+        proxy: &c_group::ZcosmicWorkspaceGroupHandleV1,
+        event: c_group::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        state.record(proxy, event.opcode(), format!("{event:?}"));
+        if let c_group::Event::Workspace { workspace } = event {
+            state.add_object(&workspace);
+        }
+    }
+
+    event_created_child!(Client, c_group::ZcosmicWorkspaceGroupHandleV1, [
+        c_group::EVT_WORKSPACE_OPCODE => (c_workspace::ZcosmicWorkspaceHandleV1, ()),
+    ]);
+}
+
+#[test]
+fn interfaces() {
+    assert_interface(
+        s_manager::ZcosmicWorkspaceManagerV1::interface(),
+        2,
+        &[("commit", 1), ("stop", 1)],
+        &[("workspace_group", 1), ("done", 1), ("finished", 1)],
+    );
+    assert_interface(
+        s_group::ZcosmicWorkspaceGroupHandleV1::interface(),
+        2,
+        &[("create_workspace", 1), ("destroy", 1)],
+        &[
+            ("capabilities", 1),
+            ("output_enter", 1),
+            ("output_leave", 1),
+            ("workspace", 1),
+            ("remove", 1),
+        ],
+    );
+    assert_interface(
+        s_workspace::ZcosmicWorkspaceHandleV1::interface(),
+        2,
+        &[
+            ("destroy", 1),
+            ("activate", 1),
+            ("deactivate", 1),
+            ("remove", 1),
+            ("rename", 2),
+            ("set_tiling_state", 2),
+        ],
+        &[
+            ("name", 1),
+            ("coordinates", 1),
+            ("state", 1),
+            ("capabilities", 1),
+            ("remove", 1),
+            ("tiling_state", 2),
+        ],
+    );
+    assert_destructor(
+        s_group::ZcosmicWorkspaceGroupHandleV1::interface(),
+        "destroy",
+    );
+    assert_destructor(
+        s_workspace::ZcosmicWorkspaceHandleV1::interface(),
+        "destroy",
+    );
+}
+
+#[test]
+fn messages() {
+    // The whole protocol is deprecated in favor of `ext-workspace-v1` and
+    // `zcosmic_workspace_manager_v2`, but still has to work for older clients
+    for version in 1..=2 {
+        let mut h = Harness::new();
+        let (manager, s_manager) = h
+            .bind::<c_manager::ZcosmicWorkspaceManagerV1, s_manager::ZcosmicWorkspaceManagerV1>(
+                version,
+            );
+        let (_, s_output) = h.bind::<c_wl_output::WlOutput, s_wl_output::WlOutput>(4);
+
+        // Group
+        let s_group = h.create_resource::<s_group::ZcosmicWorkspaceGroupHandleV1>(version);
+        s_manager.workspace_group(&s_group);
+        s_group.capabilities(1u32.to_ne_bytes().to_vec());
+        s_group.output_enter(&s_output);
+        h.roundtrip();
+        let group = h.proxy::<c_group::ZcosmicWorkspaceGroupHandleV1>();
+        assert_eq!(group.version(), version);
+
+        // Workspace
+        let s_workspace = h.create_resource::<s_workspace::ZcosmicWorkspaceHandleV1>(version);
+        s_group.workspace(&s_workspace);
+        s_workspace.name("1".into());
+        s_workspace.coordinates(vec![0; 4]);
+        s_workspace.state(0u32.to_ne_bytes().to_vec());
+        s_workspace.capabilities(
+            [1u32, 2, 3, 4, 5]
+                .iter()
+                .flat_map(|c| c.to_ne_bytes())
+                .collect(),
+        );
+        let mut expected = vec![
+            "zcosmic_workspace_manager_v1.workspace_group",
+            "zcosmic_workspace_group_handle_v1.capabilities",
+            "zcosmic_workspace_group_handle_v1.output_enter",
+            "zcosmic_workspace_group_handle_v1.workspace",
+            "zcosmic_workspace_handle_v1.name",
+            "zcosmic_workspace_handle_v1.coordinates",
+            "zcosmic_workspace_handle_v1.state",
+            "zcosmic_workspace_handle_v1.capabilities",
+        ];
+        if version >= 2 {
+            s_workspace.tiling_state(s_workspace::TilingState::TilingEnabled);
+            expected.push("zcosmic_workspace_handle_v1.tiling_state");
+        }
+        s_manager.done();
+        expected.push("zcosmic_workspace_manager_v1.done");
+        h.roundtrip();
+        let events = h.take_events();
+        assert_eq!(names(&events), expected);
+        let workspace = h.proxy::<c_workspace::ZcosmicWorkspaceHandleV1>();
+        assert_eq!(workspace.version(), version);
+        if version >= 2 {
+            assert_eq!(
+                events[8].args,
+                format!(
+                    "{:?}",
+                    c_workspace::Event::TilingState {
+                        state: WEnum::Value(c_workspace::TilingState::TilingEnabled)
+                    }
+                )
+            );
+        }
+
+        group.create_workspace("2".into());
+        workspace.activate();
+        workspace.deactivate();
+        workspace.remove();
+        let mut expected = vec![
+            "zcosmic_workspace_group_handle_v1.create_workspace",
+            "zcosmic_workspace_handle_v1.activate",
+            "zcosmic_workspace_handle_v1.deactivate",
+            "zcosmic_workspace_handle_v1.remove",
+        ];
+        if version >= 2 {
+            workspace.rename("Web".into());
+            workspace.set_tiling_state(c_workspace::TilingState::FloatingOnly);
+            expected.extend([
+                "zcosmic_workspace_handle_v1.rename",
+                "zcosmic_workspace_handle_v1.set_tiling_state",
+            ]);
+        }
+        manager.commit();
+        expected.push("zcosmic_workspace_manager_v1.commit");
+        h.roundtrip();
+        let requests = h.take_requests();
+        assert_eq!(names(&requests), expected);
+        assert_eq!(
+            requests[0].args,
+            format!(
+                "{:?}",
+                s_group::Request::CreateWorkspace {
+                    workspace: "2".into()
+                }
+            )
+        );
+
+        // Removal by the compositor, then destruction by the client
+        s_workspace.remove();
+        s_group.output_leave(&s_output);
+        s_group.remove();
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_events()),
+            [
+                "zcosmic_workspace_handle_v1.remove",
+                "zcosmic_workspace_group_handle_v1.output_leave",
+                "zcosmic_workspace_group_handle_v1.remove",
+            ]
+        );
+        workspace.destroy();
+        group.destroy();
+        assert!(!workspace.is_alive() && !group.is_alive());
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_requests()),
+            [
+                "zcosmic_workspace_handle_v1.destroy",
+                "zcosmic_workspace_group_handle_v1.destroy",
+            ]
+        );
+        assert!(h.destroyed(&s_workspace) && h.destroyed(&s_group));
+
+        manager.stop();
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_requests()),
+            ["zcosmic_workspace_manager_v1.stop"]
+        );
+        s_manager.finished();
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_events()),
+            ["zcosmic_workspace_manager_v1.finished"]
+        );
+        assert!(s_manager.is_alive());
+    }
+}
@@ -0,0 +1,171 @@
+use cosmic_protocols::workspace::v2::{
+    client::{
+        zcosmic_workspace_handle_v2 as c_workspace, zcosmic_workspace_manager_v2 as c_manager,
+    },
+    server::{
+        zcosmic_workspace_handle_v2 as s_workspace, zcosmic_workspace_manager_v2 as s_manager,
+    },
+};
+use wayland_client::Proxy;
+use wayland_protocols::ext::workspace::v1::{
+    client::ext_workspace_handle_v1 as c_ext_workspace,
+    server::ext_workspace_handle_v1 as s_ext_workspace,
+};
+use wayland_server::{Resource, WEnum};
+
+use crate::harness::{
+    Harness, assert_destructor, assert_interface, client_dispatch, names, server_dispatch,
+};
+
+server_dispatch!(
+    s_manager::ZcosmicWorkspaceManagerV2,
+    GetCosmicWorkspace(cosmic_workspace)
+);
+server_dispatch!(s_workspace::ZcosmicWorkspaceHandleV2);
+client_dispatch!(c_manager::ZcosmicWorkspaceManagerV2);
+client_dispatch!(c_workspace::ZcosmicWorkspaceHandleV2);
+
+#[test]
+fn interfaces() {
+    assert_interface(
+        s_manager::ZcosmicWorkspaceManagerV2::interface(),
+        2,
+        &[("get_cosmic_workspace", 2), ("destroy", 1)],
+        &[],
+    );
+    assert_interface(
+        s_workspace::ZcosmicWorkspaceHandleV2::interface(),
+        2,
+        &[
+            ("destroy", 1),
+            ("rename", 1),
+            ("set_tiling_state", 1),
+            ("move_before", 2),
+            ("move_after", 2),
+            ("pin", 2),
+            ("unpin", 2),
+        ],
+        &[("capabilities", 1), ("tiling_state", 1), ("state", 2)],
+    );
+    assert_destructor(s_manager::ZcosmicWorkspaceManagerV2::interface(), "destroy");
+    assert_destructor(
+        s_workspace::ZcosmicWorkspaceHandleV2::interface(),
+        "destroy",
+    );
+}
+
+#[test]
+fn messages() {
+    for version in 1..=2 {
+        let mut h = Harness::new();
+        let (manager, s_manager) = h
+            .bind::<c_manager::ZcosmicWorkspaceManagerV2, s_manager::ZcosmicWorkspaceManagerV2>(
+                version,
+            );
+        let (ext_workspace, _) = h
+            .bind::<c_ext_workspace::ExtWorkspaceHandleV1, s_ext_workspace::ExtWorkspaceHandleV1>(
+                1,
+            );
+        let (other_workspace, s_other_workspace) = h
+            .bind::<c_ext_workspace::ExtWorkspaceHandleV1, s_ext_workspace::ExtWorkspaceHandleV1>(
+                1,
+            );
+
+        // `get_cosmic_workspace` only exists since version 2
+        if version < 2 {
+            manager.destroy();
+            h.roundtrip();
+            assert_eq!(
+                names(&h.take_requests()),
+                ["zcosmic_workspace_manager_v2.destroy"]
+            );
+            assert!(h.destroyed(&s_manager));
+            continue;
+        }
+
+        let workspace = manager.get_cosmic_workspace(&ext_workspace, &h.qh, ());
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_requests()),
+            ["zcosmic_workspace_manager_v2.get_cosmic_workspace"]
+        );
+        let s_workspace = h.resource::<s_workspace::ZcosmicWorkspaceHandleV2>();
+        assert_eq!(s_workspace.version(), version);
+
+        s_workspace.capabilities(
+            s_workspace::WorkspaceCapabilities::Rename | s_workspace::WorkspaceCapabilities::Pin,
+        );
+        s_workspace.tiling_state(s_workspace::TilingState::FloatingOnly);
+        s_workspace.state(s_workspace::State::Pinned);
+        h.roundtrip();
+        let events = h.take_events();
+        assert_eq!(
+            names(&events),
+            [
+                "zcosmic_workspace_handle_v2.capabilities",
+                "zcosmic_workspace_handle_v2.tiling_state",
+                "zcosmic_workspace_handle_v2.state",
+            ]
+        );
+        assert_eq!(
+            events[0].args,
+            format!(
+                "{:?}",
+                c_workspace::Event::Capabilities {
+                    capabilities: WEnum::Value(
+                        c_workspace::WorkspaceCapabilities::Rename
+                            | c_workspace::WorkspaceCapabilities::Pin
+                    )
+                }
+            )
+        );
+
+        workspace.rename("Web".into());
+        workspace.set_tiling_state(c_workspace::TilingState::TilingEnabled);
+        workspace.move_before(&other_workspace, 0);
+        workspace.move_after(&other_workspace, 1);
+        workspace.pin();
+        workspace.unpin();
+        h.roundtrip();
+        let requests = h.take_requests();
+        assert_eq!(
+            names(&requests),
+            [
+                "zcosmic_workspace_handle_v2.rename",
+                "zcosmic_workspace_handle_v2.set_tiling_state",
+                "zcosmic_workspace_handle_v2.move_before",
+                "zcosmic_workspace_handle_v2.move_after",
+                "zcosmic_workspace_handle_v2.pin",
+                "zcosmic_workspace_handle_v2.unpin",
+            ]
+        );
+        assert_eq!(
+            requests[3].args,
+            format!(
+                "{:?}",
+                s_workspace::Request::MoveAfter {
+                    other_workspace: s_other_workspace.clone(),
+                    axis: 1,
+                }
+            )
+        );
+
+        // Destroying the manager leaves the handles alive
+        manager.destroy();
+        h.roundtrip();
+        assert!(h.destroyed(&s_manager));
+        assert!(s_workspace.is_alive());
+        workspace.destroy();
+        assert!(!workspace.is_alive());
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_requests()),
+            [
+                "zcosmic_workspace_manager_v2.destroy",
+                "zcosmic_workspace_handle_v2.destroy",
+            ]
+        );
+        assert!(h.destroyed(&s_workspace));
+        assert!(ext_workspace.is_alive());
+    }
+}
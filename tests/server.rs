@@ -0,0 +1,28 @@
+//! Runs the generated client against compositors built on the helpers of
+//! `cosmic_protocols::server`, checking the events they send and how they handle requests.
+
+#![cfg(all(feature = "client", feature = "server"))]
+
+#[path = "common/mod.rs"]
+mod common;
+#[path = "server/harness.rs"]
+mod harness;
+
+#[path = "server/a11y.rs"]
+mod a11y;
+#[path = "server/atspi.rs"]
+mod atspi;
+#[path = "server/corner_radius.rs"]
+mod corner_radius;
+#[path = "server/image_capture_source.rs"]
+mod image_capture_source;
+#[path = "server/output_management.rs"]
+mod output_management;
+#[path = "server/overlap_notify.rs"]
+mod overlap_notify;
+#[path = "server/toplevel_info.rs"]
+mod toplevel_info;
+#[path = "server/toplevel_management.rs"]
+mod toplevel_management;
+#[path = "server/workspace.rs"]
+mod workspace;
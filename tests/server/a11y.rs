@@ -0,0 +1,158 @@
+use cosmic_protocols::{
+    a11y::v1::{
+        client::cosmic_a11y_manager_v1 as c_manager,
+        server::cosmic_a11y_manager_v1::{self as s_manager, Filter},
+    },
+    server::{
+        GlobalFilter,
+        a11y::{A11yHandler, A11ySettings, A11yState},
+    },
+};
+use wayland_client::WEnum;
+use wayland_server::DisplayHandle;
+
+use crate::harness::{Harness, client_dispatch, names};
+
+struct State {
+    a11y: A11yState,
+    calls: Vec<String>,
+}
+
+impl A11yHandler for State {
+    fn a11y_state(&mut self) -> &mut A11yState {
+        &mut self.a11y
+    }
+
+    fn set_magnifier(&mut self, enabled: bool) {
+        self.calls.push(format!("set_magnifier {enabled}"));
+    }
+
+    fn set_screen_filter(&mut self, inverted: bool, filter: Option<Filter>, active: Option<bool>) {
+        self.calls.push(format!(
+            "set_screen_filter {inverted} {filter:?} {active:?}"
+        ));
+    }
+}
+
+wayland_server::delegate_global_dispatch!(State: [s_manager::CosmicA11yManagerV1: GlobalFilter] => A11yState);
+wayland_server::delegate_dispatch!(State: [s_manager::CosmicA11yManagerV1: ()] => A11yState);
+
+client_dispatch!(c_manager::CosmicA11yManagerV1);
+
+fn harness() -> Harness<State> {
+    Harness::new(|dh: &DisplayHandle| State {
+        a11y: A11yState::new::<State, _>(dh, 3, |_| true),
+        calls: Vec::new(),
+    })
+}
+
+#[test]
+fn settings() {
+    for version in 1..=3 {
+        let mut h = harness();
+        let mut settings = A11ySettings {
+            magnifier: true,
+            screen_filter: Filter::Greyscale,
+            screen_filter_active: true,
+            ..Default::default()
+        };
+        h.state.a11y.set_settings(settings);
+        h.bind::<c_manager::CosmicA11yManagerV1>(version);
+        let filter_event = match version {
+            1 => None,
+            2 => Some("cosmic_a11y_manager_v1.screen_filter"),
+            _ => Some("cosmic_a11y_manager_v1.screen_filter2"),
+        };
+        let mut expected = vec!["cosmic_a11y_manager_v1.magnifier"];
+        expected.extend(filter_event);
+        assert_eq!(names(&h.take_events()), expected);
+
+        // Only changes are sent
+        settings.magnifier = false;
+        h.state.a11y.set_settings(settings);
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_events()),
+            ["cosmic_a11y_manager_v1.magnifier"]
+        );
+
+        // Before version 3, the filter is sent as `disabled` once inactive
+        settings.screen_filter_active = false;
+        h.state.a11y.set_settings(settings);
+        h.roundtrip();
+        let events = h.take_events();
+        assert_eq!(names(&events), Vec::from_iter(filter_event));
+        if version == 2 {
+            assert_eq!(
+                events[0].args,
+                format!(
+                    "{:?}",
+                    c_manager::Event::ScreenFilter {
+                        inverted: WEnum::Value(c_manager::ActiveState::Disabled),
+                        filter: WEnum::Value(c_manager::Filter::Disabled),
+                    }
+                )
+            );
+        }
+
+        h.state.a11y.set_settings(settings);
+        h.roundtrip();
+        assert_eq!(h.take_events(), []);
+    }
+}
+
+#[test]
+fn requests() {
+    use c_manager::{ActiveState, Filter};
+
+    let mut h = harness();
+    let manager = h.bind::<c_manager::CosmicA11yManagerV1>(2);
+    manager.set_magnifier(ActiveState::Enabled);
+    manager.set_screen_filter(ActiveState::Disabled, Filter::Disabled);
+    manager.set_screen_filter(ActiveState::Enabled, Filter::Greyscale);
+    manager.set_screen_filter(ActiveState::Enabled, Filter::Unknown);
+    h.roundtrip();
+    assert_eq!(
+        h.state.calls,
+        [
+            "set_magnifier true",
+            "set_screen_filter false None Some(false)",
+            "set_screen_filter true Some(Greyscale) Some(true)",
+            "set_screen_filter true None None",
+        ]
+    );
+
+    let mut h = harness();
+    let manager = h.bind::<c_manager::CosmicA11yManagerV1>(3);
+    manager.set_screen_filter2(
+        ActiveState::Enabled,
+        Filter::DaltonizeTritanopia,
+        ActiveState::Disabled,
+    );
+    h.roundtrip();
+    assert_eq!(
+        h.state.calls,
+        ["set_screen_filter true Some(DaltonizeTritanopia) Some(false)"]
+    );
+    manager.set_screen_filter(ActiveState::Enabled, Filter::Greyscale);
+    h.roundtrip();
+    let error = h.protocol_error().expect("No protocol error");
+    assert_eq!(error.code, s_manager::Error::Deprecated as u32);
+}
+
+#[test]
+fn disabled_filter() {
+    use c_manager::{ActiveState, Filter};
+
+    let mut h = harness();
+    let manager = h.bind::<c_manager::CosmicA11yManagerV1>(3);
+    manager.set_screen_filter2(
+        ActiveState::Disabled,
+        Filter::Disabled,
+        ActiveState::Enabled,
+    );
+    h.roundtrip();
+    let error = h.protocol_error().expect("No protocol error");
+    assert_eq!(error.code, s_manager::Error::Deprecated as u32);
+    assert_eq!(h.state.calls, [] as [String; 0]);
+}
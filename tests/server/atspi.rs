@@ -0,0 +1,93 @@
+use cosmic_protocols::{
+    atspi::v1::{
+        client::cosmic_atspi_manager_v1 as c_manager, server::cosmic_atspi_manager_v1 as s_manager,
+    },
+    server::{
+        GlobalFilter,
+        atspi::{AtspiHandler, AtspiState, KeyGrab},
+    },
+};
+use std::os::{fd::OwnedFd, unix::net::UnixStream};
+use wayland_server::{Client, DisplayHandle};
+
+use crate::harness::{Harness, client_dispatch, names};
+
+struct State {
+    atspi: AtspiState,
+    grabs_changed: usize,
+}
+
+impl AtspiHandler for State {
+    fn atspi_state(&mut self) -> &mut AtspiState {
+        &mut self.atspi
+    }
+
+    fn key_events_eis(&mut self, _client: &Client) -> Option<OwnedFd> {
+        let (fd, _) = UnixStream::pair().unwrap();
+        Some(fd.into())
+    }
+
+    fn grabs_changed(&mut self) {
+        self.grabs_changed += 1;
+    }
+}
+
+wayland_server::delegate_global_dispatch!(State: [s_manager::CosmicAtspiManagerV1: GlobalFilter] => AtspiState);
+wayland_server::delegate_dispatch!(State: [s_manager::CosmicAtspiManagerV1: ()] => AtspiState);
+
+client_dispatch!(c_manager::CosmicAtspiManagerV1);
+
+fn virtual_mods(keycodes: &[u32]) -> Vec<u8> {
+    keycodes.iter().flat_map(|key| key.to_ne_bytes()).collect()
+}
+
+#[test]
+fn grabs() {
+    let mut h = Harness::new(|dh: &DisplayHandle| State {
+        atspi: AtspiState::new::<State, _>(dh, |_| true),
+        grabs_changed: 0,
+    });
+    let manager = h.bind::<c_manager::CosmicAtspiManagerV1>(1);
+    assert_eq!(
+        names(&h.take_events()),
+        ["cosmic_atspi_manager_v1.key_events_eis"]
+    );
+
+    manager.add_key_grab(4, virtual_mods(&[66]), 30);
+    // Grabbing the same key combination again changes nothing
+    manager.add_key_grab(4, virtual_mods(&[66]), 30);
+    manager.add_key_grab(0, Vec::new(), 31);
+    h.roundtrip();
+    assert_eq!(h.state.grabs_changed, 2);
+    assert_eq!(
+        h.state.atspi.key_grabs().collect::<Vec<_>>(),
+        [
+            &KeyGrab {
+                mods: 4,
+                virtual_mods: vec![66],
+                key: 30,
+            },
+            &KeyGrab {
+                mods: 0,
+                virtual_mods: Vec::new(),
+                key: 31,
+            },
+        ]
+    );
+
+    manager.remove_key_grab(0, Vec::new(), 31);
+    manager.remove_key_grab(0, Vec::new(), 31);
+    manager.grab_keyboard();
+    manager.grab_keyboard();
+    h.roundtrip();
+    assert_eq!(h.state.grabs_changed, 4);
+    assert_eq!(h.state.atspi.key_grabs().count(), 1);
+    assert!(h.state.atspi.keyboard_grabbed());
+
+    // Grabs are removed with the manager
+    manager.destroy();
+    h.roundtrip();
+    assert_eq!(h.state.grabs_changed, 5);
+    assert_eq!(h.state.atspi.key_grabs().count(), 0);
+    assert!(!h.state.atspi.keyboard_grabbed());
+}
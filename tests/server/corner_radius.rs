@@ -0,0 +1,142 @@
+use cosmic_protocols::{
+    corner_radius::v1::{
+        client::{
+            cosmic_corner_radius_manager_v1 as c_manager,
+            cosmic_corner_radius_toplevel_v1 as c_radius,
+        },
+        server::{
+            cosmic_corner_radius_manager_v1 as s_manager,
+            cosmic_corner_radius_toplevel_v1 as s_radius,
+        },
+    },
+    server::{
+        GlobalFilter,
+        corner_radius::{CornerRadius, CornerRadiusHandler, CornerRadiusState},
+    },
+};
+use wayland_protocols::xdg::shell::{
+    client::xdg_toplevel as c_xdg_toplevel, server::xdg_toplevel as s_xdg_toplevel,
+};
+use wayland_server::DisplayHandle;
+
+use crate::harness::{Harness, client_dispatch, stand_in};
+
+struct State {
+    corner_radius: CornerRadiusState,
+}
+
+impl CornerRadiusHandler for State {
+    fn corner_radius_state(&mut self) -> &mut CornerRadiusState {
+        &mut self.corner_radius
+    }
+}
+
+wayland_server::delegate_global_dispatch!(State: [s_manager::CosmicCornerRadiusManagerV1: GlobalFilter] => CornerRadiusState);
+wayland_server::delegate_dispatch!(State: [s_manager::CosmicCornerRadiusManagerV1: ()] => CornerRadiusState);
+wayland_server::delegate_dispatch!(State: [s_radius::CosmicCornerRadiusToplevelV1: s_xdg_toplevel::XdgToplevel] => CornerRadiusState);
+stand_in!(State: s_xdg_toplevel::XdgToplevel);
+
+client_dispatch!(c_xdg_toplevel::XdgToplevel);
+client_dispatch!(c_manager::CosmicCornerRadiusManagerV1);
+client_dispatch!(c_radius::CosmicCornerRadiusToplevelV1);
+
+const RADIUS: CornerRadius = CornerRadius {
+    top_left: 1,
+    top_right: 2,
+    bottom_right: 3,
+    bottom_left: 4,
+};
+
+fn harness() -> (
+    Harness<State>,
+    c_manager::CosmicCornerRadiusManagerV1,
+    c_xdg_toplevel::XdgToplevel,
+    s_xdg_toplevel::XdgToplevel,
+) {
+    let mut h = Harness::new(|dh: &DisplayHandle| {
+        dh.create_global::<State, s_xdg_toplevel::XdgToplevel, _>(7, ());
+        State {
+            corner_radius: CornerRadiusState::new::<State, _>(dh, |_| true),
+        }
+    });
+    let manager = h.bind::<c_manager::CosmicCornerRadiusManagerV1>(1);
+    let toplevel = h.bind::<c_xdg_toplevel::XdgToplevel>(7);
+    let s_toplevel = h.resource::<s_xdg_toplevel::XdgToplevel>(&toplevel);
+    (h, manager, toplevel, s_toplevel)
+}
+
+#[test]
+fn double_buffered() {
+    let (mut h, manager, toplevel, s_toplevel) = harness();
+    let radius = manager.get_corner_radius(&toplevel, &h.qh, ());
+    radius.set_radius(1, 2, 3, 4);
+    h.roundtrip();
+    assert_eq!(h.state.corner_radius.corner_radius(&s_toplevel), None);
+    assert_eq!(
+        h.state.corner_radius.commit(&s_toplevel, (100, 100)),
+        Some(RADIUS)
+    );
+    assert_eq!(
+        h.state.corner_radius.corner_radius(&s_toplevel),
+        Some(RADIUS)
+    );
+
+    radius.unset_radius();
+    h.roundtrip();
+    assert_eq!(
+        h.state.corner_radius.corner_radius(&s_toplevel),
+        Some(RADIUS)
+    );
+    assert_eq!(h.state.corner_radius.commit(&s_toplevel, (100, 100)), None);
+
+    // Destroying the object unsets the radius on the next commit
+    radius.set_radius(1, 2, 3, 4);
+    h.roundtrip();
+    h.state.corner_radius.commit(&s_toplevel, (100, 100));
+    radius.destroy();
+    h.roundtrip();
+    assert_eq!(
+        h.state.corner_radius.corner_radius(&s_toplevel),
+        Some(RADIUS)
+    );
+    assert_eq!(h.state.corner_radius.commit(&s_toplevel, (100, 100)), None);
+}
+
+#[test]
+fn corner_radius_exists() {
+    let (mut h, manager, toplevel, _) = harness();
+    // A destroyed object can be replaced before its radius is unset
+    manager.get_corner_radius(&toplevel, &h.qh, ()).destroy();
+    manager.get_corner_radius(&toplevel, &h.qh, ());
+    h.roundtrip();
+    assert!(h.protocol_error().is_none());
+
+    manager.get_corner_radius(&toplevel, &h.qh, ());
+    h.roundtrip();
+    let error = h.protocol_error().expect("No protocol error");
+    assert_eq!(error.object_interface, "cosmic_corner_radius_manager_v1");
+    assert_eq!(error.code, s_manager::Error::CornerRadiusExists as u32);
+}
+
+#[test]
+fn radius_too_large() {
+    let (mut h, manager, toplevel, s_toplevel) = harness();
+    let radius = manager.get_corner_radius(&toplevel, &h.qh, ());
+    radius.set_radius(10, 0, 0, 0);
+    h.roundtrip();
+    assert_eq!(
+        h.state.corner_radius.commit(&s_toplevel, (40, 100)),
+        Some(CornerRadius {
+            top_left: 10,
+            ..Default::default()
+        })
+    );
+    assert!(h.protocol_error().is_none());
+
+    // The radius may not exceed a quarter of the smallest dimension
+    assert_eq!(h.state.corner_radius.commit(&s_toplevel, (100, 39)), None);
+    h.roundtrip();
+    let error = h.protocol_error().expect("No protocol error");
+    assert_eq!(error.object_interface, "cosmic_corner_radius_toplevel_v1");
+    assert_eq!(error.code, s_radius::Error::RadiusTooLarge as u32);
+}
@@ -0,0 +1,162 @@
+//! Generated client connected over a socketpair to a compositor state using the helpers of
+//! `cosmic_protocols::server`, in a single thread.
+//!
+//! The client records every event it receives, like in the conformance tests. Objects of
+//! protocols the helpers only refer to, like `wl_output`, are served by [`stand_in!`], and
+//! compositor objects seen by clients through such resources are represented by [`Object`].
+
+use cosmic_protocols::server::ClientResources;
+use std::ops::{Deref, DerefMut};
+use wayland_client::{self as wc, Proxy};
+use wayland_server::{self as ws, Resource};
+
+use crate::common::Connection;
+pub(crate) use crate::common::client_dispatch;
+pub use crate::common::{Client, names};
+
+/// Compositor object seen by clients through the resources it holds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object<I>(pub Vec<I>);
+
+impl<I: Resource> ClientResources<I> for Object<I> {
+    fn client_resources(&self, client: &ws::Client) -> Vec<I> {
+        self.0
+            .iter()
+            .filter(|resource| resource.client().is_some_and(|c| c.id() == client.id()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Serve globals and objects of interfaces with `()` user data, ignoring their requests
+macro_rules! stand_in {
+    ($state:ty: $($module:ident::$interface:ident),* $(,)?) => {
+        $(
+            impl wayland_server::GlobalDispatch<$module::$interface, ()> for $state {
+                fn bind(
+                    _state: &mut Self,
+                    _dh: &wayland_server::DisplayHandle,
+                    _client: &wayland_server::Client,
+                    resource: wayland_server::New<$module::$interface>,
+                    _global_data: &(),
+                    data_init: &mut wayland_server::DataInit<'_, Self>,
+                ) {
+                    data_init.init(resource, ());
+                }
+            }
+
+            impl wayland_server::Dispatch<$module::$interface, ()> for $state {
+                fn request(
+                    _state: &mut Self,
+                    _client: &wayland_server::Client,
+                    _resource: &$module::$interface,
+                    _request: $module::Request,
+                    _data: &(),
+                    _dh: &wayland_server::DisplayHandle,
+                    _data_init: &mut wayland_server::DataInit<'_, Self>,
+                ) {
+                }
+            }
+        )*
+    };
+}
+pub(crate) use stand_in;
+
+use cosmic_protocols::{
+    toplevel_info::v1::client::{
+        zcosmic_toplevel_handle_v1 as c_handle, zcosmic_toplevel_info_v1 as c_info,
+    },
+    workspace::v1::client::zcosmic_workspace_handle_v1 as c_workspace,
+};
+use wayland_client::protocol::{
+    wl_output as c_wl_output, wl_seat as c_wl_seat, wl_surface as c_wl_surface,
+};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::client::{
+        ext_foreign_toplevel_handle_v1 as c_ext_toplevel,
+        ext_foreign_toplevel_list_v1 as c_ext_list,
+    },
+    workspace::v1::client::{
+        ext_workspace_group_handle_v1 as c_ext_group, ext_workspace_handle_v1 as c_ext_workspace,
+        ext_workspace_manager_v1 as c_ext_manager,
+    },
+};
+
+client_dispatch!(c_wl_output::WlOutput);
+client_dispatch!(c_wl_seat::WlSeat);
+client_dispatch!(c_wl_surface::WlSurface);
+client_dispatch!(c_workspace::ZcosmicWorkspaceHandleV1);
+client_dispatch!(
+    c_info::ZcosmicToplevelInfoV1,
+    Toplevel(toplevel): EVT_TOPLEVEL_OPCODE => c_handle::ZcosmicToplevelHandleV1,
+);
+client_dispatch!(c_handle::ZcosmicToplevelHandleV1);
+client_dispatch!(
+    c_ext_list::ExtForeignToplevelListV1,
+    Toplevel(toplevel): EVT_TOPLEVEL_OPCODE => c_ext_toplevel::ExtForeignToplevelHandleV1,
+);
+client_dispatch!(c_ext_toplevel::ExtForeignToplevelHandleV1);
+client_dispatch!(
+    c_ext_manager::ExtWorkspaceManagerV1,
+    WorkspaceGroup(workspace_group): EVT_WORKSPACE_GROUP_OPCODE => c_ext_group::ExtWorkspaceGroupHandleV1,
+    Workspace(workspace): EVT_WORKSPACE_OPCODE => c_ext_workspace::ExtWorkspaceHandleV1,
+);
+client_dispatch!(c_ext_group::ExtWorkspaceGroupHandleV1);
+client_dispatch!(c_ext_workspace::ExtWorkspaceHandleV1);
+
+/// Client connected to a compositor state
+pub struct Harness<S: 'static> {
+    pub state: S,
+    connection: Connection<S>,
+}
+
+impl<S: 'static> Harness<S> {
+    /// Create the compositor state with `init`, which creates its globals, and connect a client
+    pub fn new(init: impl FnOnce(&ws::DisplayHandle) -> S) -> Self {
+        let display = ws::Display::new().expect("Failed to create display");
+        let state = init(&display.handle());
+        let mut harness = Self {
+            state,
+            connection: Connection::new(display),
+        };
+        harness.roundtrip();
+        harness
+    }
+
+    /// Exchange messages until both sides are idle, or the client got a protocol error
+    pub fn roundtrip(&mut self) {
+        self.connection.roundtrip(&mut self.state);
+    }
+
+    /// Bind the latest global advertised for an interface at `version`
+    pub fn bind<C>(&mut self, version: u32) -> C
+    where
+        C: Proxy + 'static,
+        Client: wc::Dispatch<C, ()>,
+    {
+        let proxy = self.connection.bind(version);
+        self.roundtrip();
+        proxy
+    }
+
+    /// Server object of a client object
+    pub fn resource<I: Resource + 'static>(&self, proxy: &impl Proxy) -> I {
+        self.server_client
+            .object_from_protocol_id::<I>(&self.display.handle(), proxy.id().protocol_id())
+            .unwrap_or_else(|_| panic!("No {} on the server", I::interface().name))
+    }
+}
+
+impl<S> Deref for Harness<S> {
+    type Target = Connection<S>;
+
+    fn deref(&self) -> &Connection<S> {
+        &self.connection
+    }
+}
+
+impl<S> DerefMut for Harness<S> {
+    fn deref_mut(&mut self) -> &mut Connection<S> {
+        &mut self.connection
+    }
+}
@@ -0,0 +1,164 @@
+use cosmic_protocols::{
+    image_capture_source::v1::{
+        client::zcosmic_workspace_image_capture_source_manager_v1 as c_manager,
+        server::zcosmic_workspace_image_capture_source_manager_v1 as s_manager,
+    },
+    server::{
+        GlobalFilter,
+        image_capture_source::{
+            WorkspaceCaptureSource, WorkspaceImageCaptureSourceHandler,
+            WorkspaceImageCaptureSourceState,
+        },
+        workspace::{CosmicWorkspaceHandler, CosmicWorkspaceState},
+    },
+    workspace::v2::server::{
+        zcosmic_workspace_handle_v2 as s_workspace,
+        zcosmic_workspace_manager_v2 as s_workspace_manager,
+    },
+};
+use wayland_protocols::ext::{
+    image_capture_source::v1::{
+        client::ext_image_capture_source_v1 as c_source,
+        server::ext_image_capture_source_v1 as s_source,
+    },
+    image_copy_capture::v1::server::ext_image_copy_capture_frame_v1::FailureReason,
+    workspace::v1::{
+        client::{
+            ext_workspace_handle_v1 as c_ext_workspace, ext_workspace_manager_v1 as c_ext_manager,
+        },
+        server::{
+            ext_workspace_handle_v1 as s_ext_workspace, ext_workspace_manager_v1 as s_ext_manager,
+        },
+    },
+};
+use wayland_server::{Client, DisplayHandle, Resource, protocol::wl_buffer as s_wl_buffer};
+
+use crate::harness::{Harness, client_dispatch, stand_in};
+
+struct State {
+    cosmic_workspace: CosmicWorkspaceState<State>,
+    image_capture_source: WorkspaceImageCaptureSourceState,
+    workspaces: Vec<(s_ext_workspace::ExtWorkspaceHandleV1, u32)>,
+    calls: Vec<String>,
+}
+
+impl CosmicWorkspaceHandler for State {
+    type Workspace = u32;
+
+    fn cosmic_workspace_state(&mut self) -> &mut CosmicWorkspaceState<Self> {
+        &mut self.cosmic_workspace
+    }
+
+    fn workspace_for_handle(
+        &mut self,
+        handle: &s_ext_workspace::ExtWorkspaceHandleV1,
+    ) -> Option<u32> {
+        self.workspaces
+            .iter()
+            .find(|(h, _)| h == handle)
+            .map(|(_, workspace)| *workspace)
+    }
+
+    fn ext_workspace_done(&mut self, _client: &Client) {}
+
+    fn rename_workspace(&mut self, _workspace: u32, _name: String) {}
+
+    fn set_tiling_state(&mut self, _workspace: u32, _state: s_workspace::TilingState) {}
+
+    fn move_workspace_before(&mut self, _workspace: u32, _other_workspace: u32, _axis: u32) {}
+
+    fn move_workspace_after(&mut self, _workspace: u32, _other_workspace: u32, _axis: u32) {}
+
+    fn pin_workspace(&mut self, _workspace: u32) {}
+
+    fn unpin_workspace(&mut self, _workspace: u32) {}
+}
+
+impl WorkspaceImageCaptureSourceHandler for State {
+    fn workspace_image_capture_source_state(&mut self) -> &mut WorkspaceImageCaptureSourceState {
+        &mut self.image_capture_source
+    }
+
+    fn render_workspace(
+        &mut self,
+        workspace: u32,
+        _buffer: &s_wl_buffer::WlBuffer,
+        paint_cursors: bool,
+    ) -> Result<(), FailureReason> {
+        self.calls
+            .push(format!("render_workspace {workspace} {paint_cursors}"));
+        Ok(())
+    }
+}
+
+wayland_server::delegate_global_dispatch!(State: [s_workspace_manager::ZcosmicWorkspaceManagerV2: GlobalFilter] => CosmicWorkspaceState<State>);
+wayland_server::delegate_dispatch!(State: [s_workspace_manager::ZcosmicWorkspaceManagerV2: ()] => CosmicWorkspaceState<State>);
+wayland_server::delegate_dispatch!(State: [s_workspace::ZcosmicWorkspaceHandleV2: s_ext_workspace::ExtWorkspaceHandleV1] => CosmicWorkspaceState<State>);
+wayland_server::delegate_global_dispatch!(State: [s_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1: GlobalFilter] => WorkspaceImageCaptureSourceState);
+wayland_server::delegate_dispatch!(State: [s_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1: ()] => WorkspaceImageCaptureSourceState);
+wayland_server::delegate_dispatch!(State: [s_source::ExtImageCaptureSourceV1: WorkspaceCaptureSource] => WorkspaceImageCaptureSourceState);
+stand_in!(
+    State: s_ext_manager::ExtWorkspaceManagerV1,
+    s_ext_workspace::ExtWorkspaceHandleV1,
+    s_wl_buffer::WlBuffer,
+);
+
+client_dispatch!(c_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1);
+client_dispatch!(c_source::ExtImageCaptureSourceV1);
+
+// Announce workspace 1 to the client, returning its handle
+fn harness() -> (
+    Harness<State>,
+    c_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1,
+    c_ext_workspace::ExtWorkspaceHandleV1,
+) {
+    let mut h = Harness::new(|dh: &DisplayHandle| {
+        dh.create_global::<State, s_ext_manager::ExtWorkspaceManagerV1, _>(1, ());
+        State {
+            cosmic_workspace: CosmicWorkspaceState::new(dh, |_| true),
+            image_capture_source: WorkspaceImageCaptureSourceState::new::<State, _>(dh, |_| true),
+            workspaces: Vec::new(),
+            calls: Vec::new(),
+        }
+    });
+    let ext_manager = h.bind::<c_ext_manager::ExtWorkspaceManagerV1>(1);
+    let ext_manager = h.resource::<s_ext_manager::ExtWorkspaceManagerV1>(&ext_manager);
+    let handle = h.create_resource::<s_ext_workspace::ExtWorkspaceHandleV1>(1);
+    ext_manager.workspace(&handle);
+    h.state.workspaces.push((handle, 1));
+    h.roundtrip();
+    let handle = h.proxy::<c_ext_workspace::ExtWorkspaceHandleV1>();
+    let manager = h.bind::<c_manager::ZcosmicWorkspaceImageCaptureSourceManagerV1>(1);
+    h.take_events();
+    (h, manager, handle)
+}
+
+#[test]
+fn render() {
+    let (mut h, manager, handle) = harness();
+    let source = manager.create_source(&handle, &h.qh, ());
+    h.roundtrip();
+    let source = h.resource::<s_source::ExtImageCaptureSourceV1>(&source);
+    let s_handle = h.resource::<s_ext_workspace::ExtWorkspaceHandleV1>(&handle);
+    assert_eq!(
+        source
+            .data::<WorkspaceCaptureSource>()
+            .map(WorkspaceCaptureSource::workspace_handle),
+        Some(&s_handle)
+    );
+    let buffer = h.create_resource::<s_wl_buffer::WlBuffer>(1);
+
+    assert_eq!(
+        WorkspaceImageCaptureSourceState::render(&mut h.state, &source, &buffer, true),
+        Ok(())
+    );
+    assert_eq!(h.state.calls, ["render_workspace 1 true"]);
+
+    // Capture stops once the workspace is removed
+    h.state.workspaces.clear();
+    assert_eq!(
+        WorkspaceImageCaptureSourceState::render(&mut h.state, &source, &buffer, false),
+        Err(FailureReason::Stopped)
+    );
+    assert_eq!(h.state.calls, ["render_workspace 1 true"]);
+}
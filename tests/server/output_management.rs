@@ -0,0 +1,158 @@
+use cosmic_protocols::{
+    output_management::v1::{
+        client::{
+            zcosmic_output_configuration_head_v1 as c_config_head,
+            zcosmic_output_configuration_v1 as c_config, zcosmic_output_head_v1 as c_head,
+            zcosmic_output_manager_v1 as c_manager,
+        },
+        server::{
+            zcosmic_output_configuration_head_v1 as s_config_head,
+            zcosmic_output_configuration_v1 as s_config, zcosmic_output_head_v1 as s_head,
+            zcosmic_output_manager_v1 as s_manager,
+        },
+    },
+    server::{
+        GlobalFilter,
+        output_management::{
+            ConfigurationData, ConfigurationHeadData, ModeData, OutputConfigurationHandler,
+            OutputConfigurationRequest, OutputConfigurationState, OutputId, OutputMode,
+            OutputSnapshot,
+        },
+    },
+};
+use wayland_protocols_wlr::output_management::v1::{
+    client::{
+        zwlr_output_configuration_head_v1 as c_wlr_config_head,
+        zwlr_output_configuration_v1 as c_wlr_config, zwlr_output_head_v1 as c_wlr_head,
+        zwlr_output_manager_v1 as c_wlr_manager, zwlr_output_mode_v1 as c_wlr_mode,
+    },
+    server::{
+        zwlr_output_configuration_head_v1 as s_wlr_config_head,
+        zwlr_output_configuration_v1 as s_wlr_config, zwlr_output_head_v1 as s_wlr_head,
+        zwlr_output_manager_v1 as s_wlr_manager, zwlr_output_mode_v1 as s_wlr_mode,
+    },
+};
+use wayland_server::DisplayHandle;
+
+use crate::harness::{Harness, client_dispatch};
+
+struct State {
+    output_configuration: OutputConfigurationState<State>,
+}
+
+impl OutputConfigurationHandler for State {
+    type Output = u32;
+
+    fn output_configuration_state(&mut self) -> &mut OutputConfigurationState<Self> {
+        &mut self.output_configuration
+    }
+
+    fn configure(&mut self, _request: OutputConfigurationRequest<Self::Output>) -> bool {
+        true
+    }
+}
+
+wayland_server::delegate_global_dispatch!(State: [s_wlr_manager::ZwlrOutputManagerV1: GlobalFilter] => OutputConfigurationState<State>);
+wayland_server::delegate_global_dispatch!(State: [s_manager::ZcosmicOutputManagerV1: GlobalFilter] => OutputConfigurationState<State>);
+wayland_server::delegate_dispatch!(State: [s_wlr_manager::ZwlrOutputManagerV1: ()] => OutputConfigurationState<State>);
+wayland_server::delegate_dispatch!(State: [s_wlr_head::ZwlrOutputHeadV1: OutputId] => OutputConfigurationState<State>);
+wayland_server::delegate_dispatch!(State: [s_wlr_mode::ZwlrOutputModeV1: ModeData] => OutputConfigurationState<State>);
+wayland_server::delegate_dispatch!(State: [s_wlr_config::ZwlrOutputConfigurationV1: ConfigurationData] => OutputConfigurationState<State>);
+wayland_server::delegate_dispatch!(State: [s_wlr_config_head::ZwlrOutputConfigurationHeadV1: ConfigurationHeadData] => OutputConfigurationState<State>);
+wayland_server::delegate_dispatch!(State: [s_manager::ZcosmicOutputManagerV1: ()] => OutputConfigurationState<State>);
+wayland_server::delegate_dispatch!(State: [s_head::ZcosmicOutputHeadV1: s_wlr_head::ZwlrOutputHeadV1] => OutputConfigurationState<State>);
+wayland_server::delegate_dispatch!(State: [s_config::ZcosmicOutputConfigurationV1: s_wlr_config::ZwlrOutputConfigurationV1] => OutputConfigurationState<State>);
+wayland_server::delegate_dispatch!(State: [s_config_head::ZcosmicOutputConfigurationHeadV1: s_wlr_config_head::ZwlrOutputConfigurationHeadV1] => OutputConfigurationState<State>);
+
+client_dispatch!(
+    c_wlr_manager::ZwlrOutputManagerV1,
+    Head(head): EVT_HEAD_OPCODE => c_wlr_head::ZwlrOutputHeadV1,
+);
+client_dispatch!(
+    c_wlr_head::ZwlrOutputHeadV1,
+    Mode(mode): EVT_MODE_OPCODE => c_wlr_mode::ZwlrOutputModeV1,
+);
+client_dispatch!(c_wlr_mode::ZwlrOutputModeV1);
+client_dispatch!(c_wlr_config::ZwlrOutputConfigurationV1);
+client_dispatch!(c_wlr_config_head::ZwlrOutputConfigurationHeadV1);
+client_dispatch!(c_manager::ZcosmicOutputManagerV1);
+client_dispatch!(c_head::ZcosmicOutputHeadV1);
+client_dispatch!(c_config::ZcosmicOutputConfigurationV1);
+client_dispatch!(c_config_head::ZcosmicOutputConfigurationHeadV1);
+
+// Client bound to both managers, with a single enabled output
+fn harness() -> (
+    Harness<State>,
+    c_wlr_manager::ZwlrOutputManagerV1,
+    c_manager::ZcosmicOutputManagerV1,
+    c_wlr_head::ZwlrOutputHeadV1,
+) {
+    let mut h = Harness::new(|dh: &DisplayHandle| State {
+        output_configuration: OutputConfigurationState::new(dh, 3, |_| true),
+    });
+    h.state.output_configuration.set_outputs([(
+        1,
+        OutputSnapshot {
+            name: "DP-1".into(),
+            modes: vec![OutputMode {
+                width: 1920,
+                height: 1080,
+                refresh: 60000,
+                preferred: true,
+            }],
+            enabled: true,
+            current_mode: Some(0),
+            ..Default::default()
+        },
+    )]);
+    let wlr_manager = h.bind::<c_wlr_manager::ZwlrOutputManagerV1>(4);
+    let manager = h.bind::<c_manager::ZcosmicOutputManagerV1>(3);
+    let head = h.proxy::<c_wlr_head::ZwlrOutputHeadV1>();
+    h.take_events();
+    (h, wlr_manager, manager, head)
+}
+
+fn assert_already_extended(h: &mut Harness<State>) {
+    h.roundtrip();
+    let error = h.protocol_error().expect("No protocol error");
+    assert_eq!(error.object_interface, "zcosmic_output_manager_v1");
+    assert_eq!(error.code, s_manager::Error::AlreadyExtended as u32);
+}
+
+#[test]
+fn head_already_extended() {
+    let (mut h, _, manager, head) = harness();
+    manager.get_head(&head, &h.qh, ());
+    h.roundtrip();
+    assert!(h.protocol_error().is_none());
+
+    manager.get_head(&head, &h.qh, ());
+    assert_already_extended(&mut h);
+}
+
+#[test]
+fn configuration_already_extended() {
+    let (mut h, wlr_manager, manager, _) = harness();
+    let serial = h.state.output_configuration.serial();
+    let config = wlr_manager.create_configuration(serial, &h.qh, ());
+    manager.get_configuration(&config, &h.qh, ());
+    h.roundtrip();
+    assert!(h.protocol_error().is_none());
+
+    manager.get_configuration(&config, &h.qh, ());
+    assert_already_extended(&mut h);
+}
+
+#[test]
+fn configuration_head_already_extended() {
+    let (mut h, wlr_manager, manager, head) = harness();
+    let serial = h.state.output_configuration.serial();
+    let config = wlr_manager.create_configuration(serial, &h.qh, ());
+    let config_head = config.enable_head(&head, &h.qh, ());
+    manager.get_configuration_head(&config_head, &h.qh, ());
+    h.roundtrip();
+    assert!(h.protocol_error().is_none());
+
+    manager.get_configuration_head(&config_head, &h.qh, ());
+    assert_already_extended(&mut h);
+}
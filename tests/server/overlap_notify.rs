@@ -0,0 +1,223 @@
+use cosmic_protocols::{
+    overlap_notify::v1::{
+        client::{
+            zcosmic_overlap_notification_v1 as c_notification,
+            zcosmic_overlap_notify_v1 as c_notify,
+        },
+        server::{
+            zcosmic_overlap_notification_v1 as s_notification,
+            zcosmic_overlap_notify_v1 as s_notify,
+        },
+    },
+    server::{
+        GlobalFilter,
+        overlap_notify::{LayerOverlap, OverlapNotifyHandler, OverlapNotifyState},
+        toplevel_info::{
+            ToplevelGeometry, ToplevelId, ToplevelInfoHandler, ToplevelInfoState, ToplevelSnapshot,
+        },
+    },
+    toplevel_info::v1::server::{
+        zcosmic_toplevel_handle_v1 as s_handle, zcosmic_toplevel_info_v1 as s_info,
+    },
+};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::{
+        client::ext_foreign_toplevel_list_v1 as c_ext_list,
+        server::{
+            ext_foreign_toplevel_handle_v1 as s_ext_toplevel,
+            ext_foreign_toplevel_list_v1 as s_ext_list,
+        },
+    },
+    workspace::v1::server::ext_workspace_handle_v1 as s_ext_workspace,
+};
+use wayland_protocols_wlr::layer_shell::v1::{
+    client::zwlr_layer_surface_v1 as c_layer_surface,
+    server::{zwlr_layer_shell_v1::Layer, zwlr_layer_surface_v1 as s_layer_surface},
+};
+use wayland_server::{DisplayHandle, protocol::wl_output as s_wl_output};
+
+use crate::harness::{Harness, Object, client_dispatch, names, stand_in};
+
+struct State {
+    toplevel_info: ToplevelInfoState<State>,
+    overlap_notify: OverlapNotifyState<State>,
+}
+
+impl ToplevelInfoHandler for State {
+    type Window = u32;
+    type Output = Object<s_wl_output::WlOutput>;
+    type Workspace = Object<s_ext_workspace::ExtWorkspaceHandleV1>;
+
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState<Self> {
+        &mut self.toplevel_info
+    }
+}
+
+impl OverlapNotifyHandler for State {
+    fn overlap_notify_state(&mut self) -> &mut OverlapNotifyState<Self> {
+        &mut self.overlap_notify
+    }
+}
+
+wayland_server::delegate_global_dispatch!(State: [s_ext_list::ExtForeignToplevelListV1: GlobalFilter] => ToplevelInfoState<State>);
+wayland_server::delegate_global_dispatch!(State: [s_info::ZcosmicToplevelInfoV1: GlobalFilter] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_ext_list::ExtForeignToplevelListV1: ()] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_ext_toplevel::ExtForeignToplevelHandleV1: ToplevelId] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_info::ZcosmicToplevelInfoV1: ()] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_handle::ZcosmicToplevelHandleV1: ToplevelId] => ToplevelInfoState<State>);
+wayland_server::delegate_global_dispatch!(State: [s_notify::ZcosmicOverlapNotifyV1: GlobalFilter] => OverlapNotifyState<State>);
+wayland_server::delegate_dispatch!(State: [s_notify::ZcosmicOverlapNotifyV1: ()] => OverlapNotifyState<State>);
+wayland_server::delegate_dispatch!(State: [s_notification::ZcosmicOverlapNotificationV1: s_layer_surface::ZwlrLayerSurfaceV1] => OverlapNotifyState<State>);
+stand_in!(State: s_layer_surface::ZwlrLayerSurfaceV1);
+
+client_dispatch!(c_layer_surface::ZwlrLayerSurfaceV1);
+client_dispatch!(c_notify::ZcosmicOverlapNotifyV1);
+client_dispatch!(c_notification::ZcosmicOverlapNotificationV1);
+
+fn harness() -> (Harness<State>, c_notify::ZcosmicOverlapNotifyV1) {
+    let mut h = Harness::new(|dh: &DisplayHandle| {
+        dh.create_global::<State, s_layer_surface::ZwlrLayerSurfaceV1, _>(1, ());
+        State {
+            toplevel_info: ToplevelInfoState::new(dh, 3, |_| true),
+            overlap_notify: OverlapNotifyState::new(dh, |_| true),
+        }
+    });
+    h.bind::<c_ext_list::ExtForeignToplevelListV1>(1);
+    let notify = h.bind::<c_notify::ZcosmicOverlapNotifyV1>(1);
+    h.state
+        .toplevel_info
+        .new_toplevel(1, ToplevelSnapshot::default());
+    h.roundtrip();
+    h.take_events();
+    (h, notify)
+}
+
+// Create a layer surface with an overlap notification
+fn layer_surface(
+    h: &mut Harness<State>,
+    notify: &c_notify::ZcosmicOverlapNotifyV1,
+) -> (
+    c_layer_surface::ZwlrLayerSurfaceV1,
+    s_layer_surface::ZwlrLayerSurfaceV1,
+) {
+    let layer_surface = h.bind::<c_layer_surface::ZwlrLayerSurfaceV1>(1);
+    notify.notify_on_overlap(&layer_surface, &h.qh, ());
+    h.roundtrip();
+    let s_layer_surface = h.resource::<s_layer_surface::ZwlrLayerSurfaceV1>(&layer_surface);
+    (layer_surface, s_layer_surface)
+}
+
+fn area(width: i32) -> ToplevelGeometry {
+    ToplevelGeometry {
+        x: 0,
+        y: 0,
+        width,
+        height: 10,
+    }
+}
+
+fn layer(width: i32) -> LayerOverlap {
+    LayerOverlap {
+        identifier: "panel".into(),
+        namespace: "com.system76.CosmicPanel".into(),
+        exclusive: true,
+        layer: Layer::Top,
+        area: area(width),
+    }
+}
+
+#[test]
+fn overlaps() {
+    let (mut h, notify) = harness();
+    let (_, s_layer_surface) = layer_surface(&mut h, &notify);
+
+    OverlapNotifyState::set_overlaps(
+        &mut h.state,
+        &s_layer_surface,
+        vec![(1, area(10))],
+        vec![layer(10)],
+    );
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_events()),
+        [
+            "zcosmic_overlap_notification_v1.toplevel_enter",
+            "zcosmic_overlap_notification_v1.layer_enter",
+        ]
+    );
+
+    OverlapNotifyState::set_overlaps(
+        &mut h.state,
+        &s_layer_surface,
+        vec![(1, area(10))],
+        vec![layer(10)],
+    );
+    h.roundtrip();
+    assert_eq!(h.take_events(), []);
+
+    // Changed areas are sent with a new `enter`
+    OverlapNotifyState::set_overlaps(
+        &mut h.state,
+        &s_layer_surface,
+        vec![(1, area(20))],
+        vec![layer(20)],
+    );
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_events()),
+        [
+            "zcosmic_overlap_notification_v1.toplevel_enter",
+            "zcosmic_overlap_notification_v1.layer_enter",
+        ]
+    );
+
+    OverlapNotifyState::set_overlaps(&mut h.state, &s_layer_surface, Vec::new(), Vec::new());
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_events()),
+        [
+            "zcosmic_overlap_notification_v1.toplevel_leave",
+            "zcosmic_overlap_notification_v1.layer_leave",
+        ]
+    );
+}
+
+#[test]
+fn destroyed_layer_surface() {
+    let (mut h, notify) = harness();
+    let (layer_surface, s_layer_surface) = layer_surface(&mut h, &notify);
+    let (_, s_other_layer_surface) = self::layer_surface(&mut h, &notify);
+    assert_eq!(
+        h.state.overlap_notify.layer_surfaces().collect::<Vec<_>>(),
+        [&s_layer_surface, &s_other_layer_surface]
+    );
+
+    layer_surface.destroy();
+    h.roundtrip();
+    assert_eq!(
+        h.state.overlap_notify.layer_surfaces().collect::<Vec<_>>(),
+        [&s_other_layer_surface]
+    );
+    OverlapNotifyState::set_overlaps(
+        &mut h.state,
+        &s_layer_surface,
+        vec![(1, area(10))],
+        Vec::new(),
+    );
+    h.roundtrip();
+    assert_eq!(h.take_events(), []);
+
+    // Dropped right away by the compositor
+    h.state
+        .overlap_notify
+        .layer_surface_destroyed(&s_other_layer_surface);
+    assert_eq!(h.state.overlap_notify.layer_surfaces().count(), 0);
+    OverlapNotifyState::set_overlaps(
+        &mut h.state,
+        &s_other_layer_surface,
+        vec![(1, area(10))],
+        Vec::new(),
+    );
+    h.roundtrip();
+    assert_eq!(h.take_events(), []);
+}
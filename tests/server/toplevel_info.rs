@@ -0,0 +1,254 @@
+use cosmic_protocols::{
+    server::{
+        GlobalFilter,
+        toplevel_info::{
+            ToplevelGeometry, ToplevelId, ToplevelInfoHandler, ToplevelInfoState, ToplevelSnapshot,
+        },
+    },
+    toplevel_info::v1::{
+        client::{zcosmic_toplevel_handle_v1 as c_handle, zcosmic_toplevel_info_v1 as c_info},
+        server::{zcosmic_toplevel_handle_v1 as s_handle, zcosmic_toplevel_info_v1 as s_info},
+    },
+};
+use wayland_client::protocol::wl_output as c_wl_output;
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::{
+        client::{
+            ext_foreign_toplevel_handle_v1 as c_ext_toplevel,
+            ext_foreign_toplevel_list_v1 as c_ext_list,
+        },
+        server::{
+            ext_foreign_toplevel_handle_v1 as s_ext_toplevel,
+            ext_foreign_toplevel_list_v1 as s_ext_list,
+        },
+    },
+    workspace::v1::{
+        client::ext_workspace_manager_v1 as c_ext_manager,
+        server::{
+            ext_workspace_handle_v1 as s_ext_workspace, ext_workspace_manager_v1 as s_ext_manager,
+        },
+    },
+};
+use wayland_server::{DisplayHandle, protocol::wl_output as s_wl_output};
+
+use crate::harness::{Harness, Object, names, stand_in};
+
+struct State {
+    toplevel_info: ToplevelInfoState<State>,
+}
+
+impl ToplevelInfoHandler for State {
+    type Window = u32;
+    type Output = Object<s_wl_output::WlOutput>;
+    type Workspace = Object<s_ext_workspace::ExtWorkspaceHandleV1>;
+
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState<Self> {
+        &mut self.toplevel_info
+    }
+}
+
+wayland_server::delegate_global_dispatch!(State: [s_ext_list::ExtForeignToplevelListV1: GlobalFilter] => ToplevelInfoState<State>);
+wayland_server::delegate_global_dispatch!(State: [s_info::ZcosmicToplevelInfoV1: GlobalFilter] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_ext_list::ExtForeignToplevelListV1: ()] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_ext_toplevel::ExtForeignToplevelHandleV1: ToplevelId] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_info::ZcosmicToplevelInfoV1: ()] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_handle::ZcosmicToplevelHandleV1: ToplevelId] => ToplevelInfoState<State>);
+stand_in!(
+    State: s_wl_output::WlOutput,
+    s_ext_manager::ExtWorkspaceManagerV1,
+    s_ext_workspace::ExtWorkspaceHandleV1,
+);
+
+fn harness() -> Harness<State> {
+    Harness::new(|dh: &DisplayHandle| {
+        dh.create_global::<State, s_wl_output::WlOutput, _>(4, ());
+        dh.create_global::<State, s_ext_manager::ExtWorkspaceManagerV1, _>(1, ());
+        State {
+            toplevel_info: ToplevelInfoState::new(dh, 3, |_| true),
+        }
+    })
+}
+
+fn geometry(width: i32, height: i32) -> ToplevelGeometry {
+    ToplevelGeometry {
+        x: 10,
+        y: 20,
+        width,
+        height,
+    }
+}
+
+#[test]
+fn diffed_events() {
+    for version in 2..=3 {
+        let mut h = harness();
+        let wl_output = h.bind::<c_wl_output::WlOutput>(4);
+        let output = Object(vec![h.resource::<s_wl_output::WlOutput>(&wl_output)]);
+        let manager = h.bind::<c_ext_manager::ExtWorkspaceManagerV1>(1);
+        let workspace = h.create_resource::<s_ext_workspace::ExtWorkspaceHandleV1>(1);
+        h.resource::<s_ext_manager::ExtWorkspaceManagerV1>(&manager)
+            .workspace(&workspace);
+        let workspace = Object(vec![workspace]);
+        let list = h.bind::<c_ext_list::ExtForeignToplevelListV1>(1);
+        let info = h.bind::<c_info::ZcosmicToplevelInfoV1>(version);
+        h.take_events();
+
+        let mut snapshot = ToplevelSnapshot {
+            title: "Terminal".into(),
+            app_id: "com.system76.CosmicTerm".into(),
+            states: vec![s_handle::State::Activated],
+            outputs: vec![(output.clone(), geometry(800, 600))],
+            workspaces: vec![workspace.clone()],
+        };
+        h.state.toplevel_info.new_toplevel(1, snapshot.clone());
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_events()),
+            [
+                "ext_foreign_toplevel_list_v1.toplevel",
+                "ext_foreign_toplevel_handle_v1.identifier",
+                "ext_foreign_toplevel_handle_v1.title",
+                "ext_foreign_toplevel_handle_v1.app_id",
+                "ext_foreign_toplevel_handle_v1.done",
+            ]
+        );
+
+        // Every property is sent to a new handle
+        let foreign = h.proxy::<c_ext_toplevel::ExtForeignToplevelHandleV1>();
+        let cosmic = info.get_cosmic_toplevel(&foreign, &h.qh, ());
+        h.roundtrip();
+        let mut expected = vec![
+            "zcosmic_toplevel_handle_v1.state",
+            "zcosmic_toplevel_handle_v1.output_enter",
+            "zcosmic_toplevel_handle_v1.geometry",
+        ];
+        if version >= 3 {
+            expected.push("zcosmic_toplevel_handle_v1.ext_workspace_enter");
+        }
+        expected.extend([
+            "ext_foreign_toplevel_handle_v1.done",
+            "zcosmic_toplevel_info_v1.done",
+        ]);
+        let events = h.take_events();
+        assert_eq!(names(&events), expected);
+        assert_eq!(
+            events[0].args,
+            format!(
+                "{:?}",
+                c_handle::Event::State {
+                    state: 2u32.to_ne_bytes().to_vec()
+                }
+            )
+        );
+
+        // Nothing is sent if nothing changed
+        h.state.toplevel_info.update_toplevel(&1, snapshot.clone());
+        h.roundtrip();
+        assert_eq!(h.take_events(), []);
+
+        // Only changed properties are sent
+        snapshot.title = "Renamed".into();
+        snapshot.outputs[0].1 = geometry(1024, 768);
+        h.state.toplevel_info.update_toplevel(&1, snapshot.clone());
+        h.roundtrip();
+        let events = h.take_events();
+        assert_eq!(
+            names(&events),
+            [
+                "ext_foreign_toplevel_handle_v1.title",
+                "zcosmic_toplevel_handle_v1.geometry",
+                "ext_foreign_toplevel_handle_v1.done",
+                "zcosmic_toplevel_info_v1.done",
+            ]
+        );
+        assert_eq!(
+            events[1].args,
+            format!(
+                "{:?}",
+                c_handle::Event::Geometry {
+                    output: wl_output.clone(),
+                    x: 10,
+                    y: 20,
+                    width: 1024,
+                    height: 768,
+                }
+            )
+        );
+
+        snapshot.states.clear();
+        snapshot.outputs.clear();
+        snapshot.workspaces.clear();
+        h.state.toplevel_info.update_toplevel(&1, snapshot);
+        h.roundtrip();
+        let mut expected = vec![
+            "zcosmic_toplevel_handle_v1.state",
+            "zcosmic_toplevel_handle_v1.output_leave",
+        ];
+        if version >= 3 {
+            expected.push("zcosmic_toplevel_handle_v1.ext_workspace_leave");
+        }
+        expected.extend([
+            "ext_foreign_toplevel_handle_v1.done",
+            "zcosmic_toplevel_info_v1.done",
+        ]);
+        assert_eq!(names(&h.take_events()), expected);
+
+        h.state.toplevel_info.remove_toplevel(&1);
+        h.roundtrip();
+        assert_eq!(
+            names(&h.take_events()),
+            ["ext_foreign_toplevel_handle_v1.closed"]
+        );
+        cosmic.destroy();
+        foreign.destroy();
+        list.destroy();
+    }
+}
+
+#[test]
+fn stop() {
+    let mut h = harness();
+    let list = h.bind::<c_ext_list::ExtForeignToplevelListV1>(1);
+    let info = h.bind::<c_info::ZcosmicToplevelInfoV1>(3);
+    let mut snapshot = ToplevelSnapshot {
+        title: "Terminal".into(),
+        ..Default::default()
+    };
+    h.state.toplevel_info.new_toplevel(1, snapshot.clone());
+    h.roundtrip();
+    let foreign = h.proxy::<c_ext_toplevel::ExtForeignToplevelHandleV1>();
+    info.get_cosmic_toplevel(&foreign, &h.qh, ());
+    h.roundtrip();
+    h.take_events();
+
+    info.stop();
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_events()),
+        ["zcosmic_toplevel_info_v1.finished"]
+    );
+
+    // Existing handles are still updated, without `zcosmic_toplevel_info_v1.done`
+    snapshot.title = "Renamed".into();
+    h.state.toplevel_info.update_toplevel(&1, snapshot);
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_events()),
+        [
+            "ext_foreign_toplevel_handle_v1.title",
+            "ext_foreign_toplevel_handle_v1.done",
+        ]
+    );
+
+    list.stop();
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_events()),
+        ["ext_foreign_toplevel_list_v1.finished"]
+    );
+    h.state
+        .toplevel_info
+        .new_toplevel(2, ToplevelSnapshot::default());
+    h.roundtrip();
+    assert_eq!(h.take_events(), []);
+}
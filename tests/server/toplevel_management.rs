@@ -0,0 +1,353 @@
+use cosmic_protocols::{
+    server::{
+        GlobalFilter,
+        toplevel_info::{
+            ToplevelGeometry, ToplevelId, ToplevelInfoHandler, ToplevelInfoState, ToplevelSnapshot,
+        },
+        toplevel_management::{ToplevelManagementHandler, ToplevelManagementState},
+    },
+    toplevel_info::v1::{
+        client::{zcosmic_toplevel_handle_v1 as c_handle, zcosmic_toplevel_info_v1 as c_info},
+        server::{zcosmic_toplevel_handle_v1 as s_handle, zcosmic_toplevel_info_v1 as s_info},
+    },
+    toplevel_management::v1::{
+        client::zcosmic_toplevel_manager_v1 as c_manager,
+        server::zcosmic_toplevel_manager_v1::{
+            self as s_manager, ZcosmicToplelevelManagementCapabilitiesV1 as Capability,
+        },
+    },
+    workspace::v1::{
+        client::zcosmic_workspace_handle_v1 as c_workspace,
+        server::zcosmic_workspace_handle_v1 as s_workspace,
+    },
+};
+use wayland_client::protocol::{
+    wl_output as c_wl_output, wl_seat as c_wl_seat, wl_surface as c_wl_surface,
+};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::{
+        client::{
+            ext_foreign_toplevel_handle_v1 as c_ext_toplevel,
+            ext_foreign_toplevel_list_v1 as c_ext_list,
+        },
+        server::{
+            ext_foreign_toplevel_handle_v1 as s_ext_toplevel,
+            ext_foreign_toplevel_list_v1 as s_ext_list,
+        },
+    },
+    workspace::v1::{
+        client::ext_workspace_handle_v1 as c_ext_workspace,
+        server::ext_workspace_handle_v1 as s_ext_workspace,
+    },
+};
+use wayland_server::{
+    DisplayHandle,
+    protocol::{wl_output as s_wl_output, wl_seat as s_wl_seat, wl_surface as s_wl_surface},
+};
+
+use crate::harness::{Harness, Object, client_dispatch, names, stand_in};
+
+struct State {
+    toplevel_info: ToplevelInfoState<State>,
+    toplevel_management: ToplevelManagementState,
+    calls: Vec<String>,
+}
+
+impl ToplevelInfoHandler for State {
+    type Window = u32;
+    type Output = Object<s_wl_output::WlOutput>;
+    type Workspace = Object<s_ext_workspace::ExtWorkspaceHandleV1>;
+
+    fn toplevel_info_state(&mut self) -> &mut ToplevelInfoState<Self> {
+        &mut self.toplevel_info
+    }
+}
+
+impl ToplevelManagementHandler for State {
+    fn toplevel_management_state(&mut self) -> &mut ToplevelManagementState {
+        &mut self.toplevel_management
+    }
+
+    fn close(&mut self, window: u32) {
+        self.calls.push(format!("close {window}"));
+    }
+
+    fn activate(&mut self, window: u32, _seat: s_wl_seat::WlSeat) {
+        self.calls.push(format!("activate {window}"));
+    }
+
+    fn set_maximized(&mut self, window: u32) {
+        self.calls.push(format!("set_maximized {window}"));
+    }
+
+    fn unset_maximized(&mut self, window: u32) {
+        self.calls.push(format!("unset_maximized {window}"));
+    }
+
+    fn set_minimized(&mut self, window: u32) {
+        self.calls.push(format!("set_minimized {window}"));
+    }
+
+    fn unset_minimized(&mut self, window: u32) {
+        self.calls.push(format!("unset_minimized {window}"));
+    }
+
+    fn set_fullscreen(&mut self, window: u32, output: Option<s_wl_output::WlOutput>) {
+        self.calls
+            .push(format!("set_fullscreen {window} {}", output.is_some()));
+    }
+
+    fn unset_fullscreen(&mut self, window: u32) {
+        self.calls.push(format!("unset_fullscreen {window}"));
+    }
+
+    fn set_rectangle(
+        &mut self,
+        window: u32,
+        _surface: s_wl_surface::WlSurface,
+        rectangle: Option<ToplevelGeometry>,
+    ) {
+        let rectangle = rectangle.map(|r| (r.x, r.y, r.width, r.height));
+        self.calls
+            .push(format!("set_rectangle {window} {rectangle:?}"));
+    }
+
+    fn move_to_workspace(
+        &mut self,
+        window: u32,
+        _workspace: s_workspace::ZcosmicWorkspaceHandleV1,
+        _output: s_wl_output::WlOutput,
+    ) {
+        self.calls.push(format!("move_to_workspace {window}"));
+    }
+
+    fn move_to_ext_workspace(
+        &mut self,
+        window: u32,
+        _workspace: s_ext_workspace::ExtWorkspaceHandleV1,
+        _output: s_wl_output::WlOutput,
+    ) {
+        self.calls.push(format!("move_to_ext_workspace {window}"));
+    }
+
+    fn set_sticky(&mut self, window: u32) {
+        self.calls.push(format!("set_sticky {window}"));
+    }
+
+    fn unset_sticky(&mut self, window: u32) {
+        self.calls.push(format!("unset_sticky {window}"));
+    }
+}
+
+wayland_server::delegate_global_dispatch!(State: [s_ext_list::ExtForeignToplevelListV1: GlobalFilter] => ToplevelInfoState<State>);
+wayland_server::delegate_global_dispatch!(State: [s_info::ZcosmicToplevelInfoV1: GlobalFilter] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_ext_list::ExtForeignToplevelListV1: ()] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_ext_toplevel::ExtForeignToplevelHandleV1: ToplevelId] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_info::ZcosmicToplevelInfoV1: ()] => ToplevelInfoState<State>);
+wayland_server::delegate_dispatch!(State: [s_handle::ZcosmicToplevelHandleV1: ToplevelId] => ToplevelInfoState<State>);
+wayland_server::delegate_global_dispatch!(State: [s_manager::ZcosmicToplevelManagerV1: GlobalFilter] => ToplevelManagementState);
+wayland_server::delegate_dispatch!(State: [s_manager::ZcosmicToplevelManagerV1: ()] => ToplevelManagementState);
+stand_in!(
+    State: s_wl_output::WlOutput,
+    s_wl_seat::WlSeat,
+    s_wl_surface::WlSurface,
+    s_workspace::ZcosmicWorkspaceHandleV1,
+    s_ext_workspace::ExtWorkspaceHandleV1,
+);
+
+client_dispatch!(c_manager::ZcosmicToplevelManagerV1);
+
+const ALL: [Capability; 8] = [
+    Capability::Close,
+    Capability::Activate,
+    Capability::Maximize,
+    Capability::Minimize,
+    Capability::Fullscreen,
+    Capability::MoveToWorkspace,
+    Capability::Sticky,
+    Capability::MoveToExtWorkspace,
+];
+
+// Bind the manager at `version` and announce toplevel 1, returning its handle
+fn harness(
+    version: u32,
+    capabilities: &[Capability],
+) -> (
+    Harness<State>,
+    c_manager::ZcosmicToplevelManagerV1,
+    c_handle::ZcosmicToplevelHandleV1,
+) {
+    let capabilities = capabilities.to_vec();
+    let mut h = Harness::new(|dh: &DisplayHandle| {
+        dh.create_global::<State, s_wl_output::WlOutput, _>(4, ());
+        dh.create_global::<State, s_wl_seat::WlSeat, _>(1, ());
+        dh.create_global::<State, s_wl_surface::WlSurface, _>(1, ());
+        dh.create_global::<State, s_workspace::ZcosmicWorkspaceHandleV1, _>(2, ());
+        dh.create_global::<State, s_ext_workspace::ExtWorkspaceHandleV1, _>(1, ());
+        State {
+            toplevel_info: ToplevelInfoState::new(dh, 3, |_| true),
+            toplevel_management: ToplevelManagementState::new::<State, _>(
+                dh,
+                4,
+                capabilities,
+                |_| true,
+            ),
+            calls: Vec::new(),
+        }
+    });
+    h.bind::<c_ext_list::ExtForeignToplevelListV1>(1);
+    let info = h.bind::<c_info::ZcosmicToplevelInfoV1>(3);
+    let manager = h.bind::<c_manager::ZcosmicToplevelManagerV1>(version);
+    h.state
+        .toplevel_info
+        .new_toplevel(1, ToplevelSnapshot::default());
+    h.roundtrip();
+    let foreign = h.proxy::<c_ext_toplevel::ExtForeignToplevelHandleV1>();
+    let toplevel = info.get_cosmic_toplevel(&foreign, &h.qh, ());
+    h.roundtrip();
+    (h, manager, toplevel)
+}
+
+fn capabilities_event(
+    capabilities: &[c_manager::ZcosmicToplelevelManagementCapabilitiesV1],
+) -> String {
+    format!(
+        "{:?}",
+        c_manager::Event::Capabilities {
+            capabilities: capabilities
+                .iter()
+                .flat_map(|capability| u32::from(*capability).to_ne_bytes())
+                .collect(),
+        }
+    )
+}
+
+#[test]
+fn capabilities() {
+    use c_manager::ZcosmicToplelevelManagementCapabilitiesV1 as C;
+
+    for (version, expected) in [
+        (
+            1,
+            &[
+                C::Close,
+                C::Activate,
+                C::Maximize,
+                C::Minimize,
+                C::Fullscreen,
+            ][..],
+        ),
+        (
+            2,
+            &[
+                C::Close,
+                C::Activate,
+                C::Maximize,
+                C::Minimize,
+                C::Fullscreen,
+                C::MoveToWorkspace,
+            ],
+        ),
+        (
+            4,
+            &[
+                C::Close,
+                C::Activate,
+                C::Maximize,
+                C::Minimize,
+                C::Fullscreen,
+                C::MoveToWorkspace,
+                C::Sticky,
+                C::MoveToExtWorkspace,
+            ],
+        ),
+    ] {
+        let (mut h, _, _) = harness(version, &ALL);
+        let events = h
+            .take_events()
+            .into_iter()
+            .filter(|event| event.interface == "zcosmic_toplevel_manager_v1")
+            .collect::<Vec<_>>();
+        assert_eq!(names(&events), ["zcosmic_toplevel_manager_v1.capabilities"]);
+        assert_eq!(events[0].args, capabilities_event(expected));
+    }
+}
+
+#[test]
+fn requests() {
+    let (mut h, manager, toplevel) = harness(4, &ALL);
+    let seat = h.bind::<c_wl_seat::WlSeat>(1);
+    let surface = h.bind::<c_wl_surface::WlSurface>(1);
+    let output = h.bind::<c_wl_output::WlOutput>(4);
+    let workspace = h.bind::<c_workspace::ZcosmicWorkspaceHandleV1>(2);
+    let ext_workspace = h.bind::<c_ext_workspace::ExtWorkspaceHandleV1>(1);
+
+    manager.close(&toplevel);
+    manager.activate(&toplevel, &seat);
+    manager.set_maximized(&toplevel);
+    manager.unset_maximized(&toplevel);
+    manager.set_minimized(&toplevel);
+    manager.unset_minimized(&toplevel);
+    manager.set_fullscreen(&toplevel, Some(&output));
+    manager.set_fullscreen(&toplevel, None);
+    manager.unset_fullscreen(&toplevel);
+    manager.set_rectangle(&toplevel, &surface, 1, 2, 3, 4);
+    manager.set_rectangle(&toplevel, &surface, 0, 0, 0, 0);
+    manager.move_to_workspace(&toplevel, &workspace, &output);
+    manager.set_sticky(&toplevel);
+    manager.unset_sticky(&toplevel);
+    manager.move_to_ext_workspace(&toplevel, &ext_workspace, &output);
+    h.roundtrip();
+    assert_eq!(
+        h.state.calls,
+        [
+            "close 1",
+            "activate 1",
+            "set_maximized 1",
+            "unset_maximized 1",
+            "set_minimized 1",
+            "unset_minimized 1",
+            "set_fullscreen 1 true",
+            "set_fullscreen 1 false",
+            "unset_fullscreen 1",
+            "set_rectangle 1 Some((1, 2, 3, 4))",
+            "set_rectangle 1 None",
+            "move_to_workspace 1",
+            "set_sticky 1",
+            "unset_sticky 1",
+            "move_to_ext_workspace 1",
+        ]
+    );
+
+    // Requests needing capabilities that aren't advertised, or for removed toplevels, are
+    // ignored
+    h.state.calls.clear();
+    h.state
+        .toplevel_management
+        .set_capabilities(vec![Capability::Close]);
+    manager.set_maximized(&toplevel);
+    manager.move_to_workspace(&toplevel, &workspace, &output);
+    manager.close(&toplevel);
+    h.roundtrip();
+    assert_eq!(h.state.calls, ["close 1"]);
+
+    h.state.calls.clear();
+    h.state.toplevel_info.remove_toplevel(&1);
+    manager.close(&toplevel);
+    h.roundtrip();
+    assert_eq!(h.state.calls, [] as [String; 0]);
+}
+
+#[test]
+fn invalid_rectangle() {
+    for (width, height) in [(-1, 10), (10, -1)] {
+        let (mut h, manager, toplevel) = harness(4, &ALL);
+        let surface = h.bind::<c_wl_surface::WlSurface>(1);
+        manager.set_rectangle(&toplevel, &surface, 0, 0, width, height);
+        h.roundtrip();
+        let error = h.protocol_error().expect("No protocol error");
+        assert_eq!(error.object_interface, "zcosmic_toplevel_manager_v1");
+        assert_eq!(error.code, s_manager::Error::InvalidRectangle as u32);
+        assert_eq!(h.state.calls, [] as [String; 0]);
+    }
+}
@@ -0,0 +1,230 @@
+use cosmic_protocols::{
+    server::{
+        GlobalFilter,
+        workspace::{CosmicWorkspaceHandler, CosmicWorkspaceProperties, CosmicWorkspaceState},
+    },
+    workspace::v2::{
+        client::{
+            zcosmic_workspace_handle_v2 as c_workspace, zcosmic_workspace_manager_v2 as c_manager,
+        },
+        server::{
+            zcosmic_workspace_handle_v2 as s_workspace, zcosmic_workspace_manager_v2 as s_manager,
+        },
+    },
+};
+use wayland_protocols::ext::workspace::v1::{
+    client::{
+        ext_workspace_handle_v1 as c_ext_workspace, ext_workspace_manager_v1 as c_ext_manager,
+    },
+    server::{
+        ext_workspace_handle_v1 as s_ext_workspace, ext_workspace_manager_v1 as s_ext_manager,
+    },
+};
+use wayland_server::{Client, DisplayHandle};
+
+use crate::harness::{Harness, client_dispatch, names, stand_in};
+
+struct State {
+    cosmic_workspace: CosmicWorkspaceState<State>,
+    workspaces: Vec<(s_ext_workspace::ExtWorkspaceHandleV1, u32)>,
+    calls: Vec<String>,
+}
+
+impl CosmicWorkspaceHandler for State {
+    type Workspace = u32;
+
+    fn cosmic_workspace_state(&mut self) -> &mut CosmicWorkspaceState<Self> {
+        &mut self.cosmic_workspace
+    }
+
+    fn workspace_for_handle(
+        &mut self,
+        handle: &s_ext_workspace::ExtWorkspaceHandleV1,
+    ) -> Option<u32> {
+        self.workspaces
+            .iter()
+            .find(|(h, _)| h == handle)
+            .map(|(_, workspace)| *workspace)
+    }
+
+    fn ext_workspace_done(&mut self, _client: &Client) {
+        self.calls.push("ext_workspace_done".into());
+    }
+
+    fn rename_workspace(&mut self, workspace: u32, name: String) {
+        self.calls
+            .push(format!("rename_workspace {workspace} {name}"));
+    }
+
+    fn set_tiling_state(&mut self, workspace: u32, state: s_workspace::TilingState) {
+        self.calls
+            .push(format!("set_tiling_state {workspace} {state:?}"));
+    }
+
+    fn move_workspace_before(&mut self, workspace: u32, other_workspace: u32, axis: u32) {
+        self.calls.push(format!(
+            "move_workspace_before {workspace} {other_workspace} {axis}"
+        ));
+    }
+
+    fn move_workspace_after(&mut self, workspace: u32, other_workspace: u32, axis: u32) {
+        self.calls.push(format!(
+            "move_workspace_after {workspace} {other_workspace} {axis}"
+        ));
+    }
+
+    fn pin_workspace(&mut self, workspace: u32) {
+        self.calls.push(format!("pin_workspace {workspace}"));
+    }
+
+    fn unpin_workspace(&mut self, workspace: u32) {
+        self.calls.push(format!("unpin_workspace {workspace}"));
+    }
+}
+
+wayland_server::delegate_global_dispatch!(State: [s_manager::ZcosmicWorkspaceManagerV2: GlobalFilter] => CosmicWorkspaceState<State>);
+wayland_server::delegate_dispatch!(State: [s_manager::ZcosmicWorkspaceManagerV2: ()] => CosmicWorkspaceState<State>);
+wayland_server::delegate_dispatch!(State: [s_workspace::ZcosmicWorkspaceHandleV2: s_ext_workspace::ExtWorkspaceHandleV1] => CosmicWorkspaceState<State>);
+stand_in!(
+    State: s_ext_manager::ExtWorkspaceManagerV1,
+    s_ext_workspace::ExtWorkspaceHandleV1,
+);
+
+client_dispatch!(c_manager::ZcosmicWorkspaceManagerV2);
+client_dispatch!(c_workspace::ZcosmicWorkspaceHandleV2);
+
+// Announce workspaces 1 and 2 to the client, returning their handles
+fn harness() -> (
+    Harness<State>,
+    c_manager::ZcosmicWorkspaceManagerV2,
+    [c_ext_workspace::ExtWorkspaceHandleV1; 2],
+) {
+    let mut h = Harness::new(|dh: &DisplayHandle| {
+        dh.create_global::<State, s_ext_manager::ExtWorkspaceManagerV1, _>(1, ());
+        State {
+            cosmic_workspace: CosmicWorkspaceState::new(dh, |_| true),
+            workspaces: Vec::new(),
+            calls: Vec::new(),
+        }
+    });
+    let ext_manager = h.bind::<c_ext_manager::ExtWorkspaceManagerV1>(1);
+    let ext_manager = h.resource::<s_ext_manager::ExtWorkspaceManagerV1>(&ext_manager);
+    let handles = [1, 2].map(|workspace| {
+        let handle = h.create_resource::<s_ext_workspace::ExtWorkspaceHandleV1>(1);
+        ext_manager.workspace(&handle);
+        h.state.workspaces.push((handle, workspace));
+        h.roundtrip();
+        h.proxy::<c_ext_workspace::ExtWorkspaceHandleV1>()
+    });
+    let manager = h.bind::<c_manager::ZcosmicWorkspaceManagerV2>(2);
+    h.take_events();
+    (h, manager, handles)
+}
+
+#[test]
+fn workspace_exists() {
+    let (mut h, manager, [handle, _]) = harness();
+    manager.get_cosmic_workspace(&handle, &h.qh, ());
+    h.roundtrip();
+    assert_eq!(h.state.calls, ["ext_workspace_done"]);
+    assert_eq!(
+        names(&h.take_events()),
+        [
+            "zcosmic_workspace_handle_v2.capabilities",
+            "zcosmic_workspace_handle_v2.tiling_state",
+            "zcosmic_workspace_handle_v2.state",
+        ]
+    );
+
+    manager.get_cosmic_workspace(&handle, &h.qh, ());
+    h.roundtrip();
+    let error = h.protocol_error().expect("No protocol error");
+    assert_eq!(error.object_interface, "zcosmic_workspace_manager_v2");
+    assert_eq!(error.code, s_manager::Error::WorkspaceExists as u32);
+}
+
+#[test]
+fn removed_workspace() {
+    let (mut h, manager, [handle, _]) = harness();
+    h.state.workspaces.clear();
+
+    // Extension objects of removed workspaces are inert, however many there are
+    manager.get_cosmic_workspace(&handle, &h.qh, ());
+    manager.get_cosmic_workspace(&handle, &h.qh, ());
+    h.roundtrip();
+    assert!(h.protocol_error().is_none());
+    assert_eq!(h.state.calls, [] as [String; 0]);
+    assert_eq!(h.take_events(), []);
+}
+
+#[test]
+fn capabilities() {
+    let (mut h, manager, [handle, other_handle]) = harness();
+    h.state.cosmic_workspace.set_properties(
+        &1,
+        CosmicWorkspaceProperties {
+            capabilities: s_workspace::WorkspaceCapabilities::Rename,
+            ..Default::default()
+        },
+    );
+    let workspace = manager.get_cosmic_workspace(&handle, &h.qh, ());
+    h.roundtrip();
+    h.state.calls.clear();
+    h.take_events();
+
+    let requests = |workspace: &c_workspace::ZcosmicWorkspaceHandleV2| {
+        workspace.rename("Renamed".into());
+        workspace.set_tiling_state(c_workspace::TilingState::TilingEnabled);
+        workspace.move_before(&other_handle, 0);
+        workspace.move_after(&other_handle, 1);
+        workspace.pin();
+        workspace.unpin();
+    };
+    requests(&workspace);
+    h.roundtrip();
+    assert_eq!(h.state.calls, ["rename_workspace 1 Renamed"]);
+
+    h.state.calls.clear();
+    h.state.cosmic_workspace.set_properties(
+        &1,
+        CosmicWorkspaceProperties {
+            capabilities: s_workspace::WorkspaceCapabilities::Pin
+                | s_workspace::WorkspaceCapabilities::Move,
+            ..Default::default()
+        },
+    );
+    h.roundtrip();
+    assert_eq!(
+        names(&h.take_events()),
+        ["zcosmic_workspace_handle_v2.capabilities"]
+    );
+    requests(&workspace);
+    h.roundtrip();
+    // `Pin` includes the bits of `Rename` and `SetTilingState`
+    assert_eq!(
+        h.state.calls,
+        [
+            "rename_workspace 1 Renamed",
+            "set_tiling_state 1 TilingEnabled",
+            "move_workspace_before 1 2 0",
+            "move_workspace_after 1 2 1",
+            "pin_workspace 1",
+            "unpin_workspace 1",
+        ]
+    );
+
+    h.state.calls.clear();
+    h.state.cosmic_workspace.set_properties(
+        &1,
+        CosmicWorkspaceProperties {
+            capabilities: s_workspace::WorkspaceCapabilities::Move,
+            ..Default::default()
+        },
+    );
+    requests(&workspace);
+    h.roundtrip();
+    assert_eq!(
+        h.state.calls,
+        ["move_workspace_before 1 2 0", "move_workspace_after 1 2 1"]
+    );
+}